@@ -0,0 +1,93 @@
+use crate::pgn;
+use crate::stockfish::{SearchResult, StockfishEngine};
+use anyhow::Result;
+use chess::Board;
+use std::io::Write;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::oneshot;
+
+/// Moves of the principal variation shown in the readout
+const READOUT_MOVES: usize = 6;
+
+enum Request {
+    Analyze(Board),
+    Stop(oneshot::Sender<()>),
+}
+
+/// A second engine that analyzes the player's position for as long as they
+/// think, keeping a one-line eval/PV readout above the input prompt
+pub struct AutoAnalyzer {
+    sender: UnboundedSender<Request>,
+}
+
+impl AutoAnalyzer {
+    pub async fn spawn(stockfish_path: &str) -> Result<Self> {
+        let mut engine = StockfishEngine::new(stockfish_path).await?;
+
+        let (sender, mut receiver) = unbounded_channel::<Request>();
+        tokio::spawn(async move {
+            let mut pending = None;
+            loop {
+                let request = match pending.take() {
+                    Some(request) => request,
+                    None => match receiver.recv().await {
+                        Some(request) => request,
+                        None => break,
+                    },
+                };
+                let board = match request {
+                    Request::Analyze(board) => board,
+                    Request::Stop(done) => {
+                        let _ = done.send(());
+                        continue;
+                    }
+                };
+
+                let interrupted = engine
+                    .analyze_until(&board, receiver.recv(), |result| {
+                        print_readout(&board, result)
+                    })
+                    .await;
+                match interrupted {
+                    Ok(Some(request)) => pending = Some(request),
+                    Ok(None) => break,
+                    Err(e) => {
+                        println!("\n[analysis] Engine stopped: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(AutoAnalyzer { sender })
+    }
+
+    /// Start analyzing a position; the readout goes on the line above the
+    /// cursor, so the caller leaves an empty line before its prompt
+    pub fn start(&self, board: Board) {
+        let _ = self.sender.send(Request::Analyze(board));
+    }
+
+    /// Stop the current analysis and wait until the engine has quieted down
+    pub async fn stop(&self) {
+        let (done, stopped) = oneshot::channel();
+        if self.sender.send(Request::Stop(done)).is_ok() {
+            let _ = stopped.await;
+        }
+    }
+}
+
+fn print_readout(board: &Board, result: &SearchResult) {
+    let Some(score) = result.score else {
+        return;
+    };
+    let shown = &result.pv[..result.pv.len().min(READOUT_MOVES)];
+    // Save the cursor, rewrite the line above it, and jump back to the prompt
+    print!(
+        "\x1b7\x1b[1A\r\x1b[K[analysis] depth {}  {}  {}\x1b8",
+        result.depth,
+        score,
+        pgn::line_to_san(board, shown)
+    );
+    let _ = std::io::stdout().flush();
+}
@@ -0,0 +1,448 @@
+use crate::fen;
+use crate::pgn;
+use crate::stockfish::StockfishEngine;
+use crate::ui::{self, display_board_for_player};
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, Color};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Depth of `analyze` when none is given
+const DEFAULT_DEPTH: u8 = 18;
+
+/// One position of the variation tree
+struct Node {
+    board: Board,
+    /// Move that led here; None for the root
+    chess_move: Option<ChessMove>,
+    parent: Option<usize>,
+    /// Continuations, the main line first
+    children: Vec<usize>,
+    /// Moves from the root
+    ply: usize,
+    comment: Option<String>,
+}
+
+/// Positions reached by free moves for both sides, with every alternative
+/// tried kept as a variation
+struct Tree {
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+impl Tree {
+    fn new(start: Board) -> Self {
+        Tree {
+            nodes: vec![Node {
+                board: start,
+                chess_move: None,
+                parent: None,
+                children: Vec::new(),
+                ply: 0,
+                comment: None,
+            }],
+            current: 0,
+        }
+    }
+
+    fn board(&self) -> Board {
+        self.nodes[self.current].board
+    }
+
+    /// Go to the child reached by `chess_move`, adding it as a new variation
+    /// when it hasn't been played here before
+    fn play(&mut self, chess_move: ChessMove) {
+        let existing = self.nodes[self.current]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].chess_move == Some(chess_move));
+        self.current = existing.unwrap_or_else(|| {
+            let node = Node {
+                board: self.board().make_move_new(chess_move),
+                chess_move: Some(chess_move),
+                parent: Some(self.current),
+                children: Vec::new(),
+                ply: self.nodes[self.current].ply + 1,
+                comment: None,
+            };
+            self.nodes.push(node);
+            let index = self.nodes.len() - 1;
+            self.nodes[self.current].children.push(index);
+            index
+        });
+    }
+
+    fn back(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Follow continuation `choice` (0 for the main line)
+    fn forward(&mut self, choice: usize) -> bool {
+        match self.nodes[self.current].children.get(choice) {
+            Some(&child) => {
+                self.current = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Make the line leading to the current position the main line at every
+    /// branch on the way
+    fn promote(&mut self) {
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            let children = &mut self.nodes[parent].children;
+            if let Some(position) = children.iter().position(|&child| child == node) {
+                let child = children.remove(position);
+                children.insert(0, child);
+            }
+            node = parent;
+        }
+    }
+
+    /// Remove the current position and everything after it
+    fn delete(&mut self) -> bool {
+        let Some(parent) = self.nodes[self.current].parent else {
+            return false;
+        };
+        let removed = self.current;
+        self.nodes[parent]
+            .children
+            .retain(|&child| child != removed);
+        // Unreachable nodes stay in the arena; only the tree's links matter
+        self.current = parent;
+        true
+    }
+
+    /// SAN of the move into `node`, numbered when it starts a line or is
+    /// White's
+    fn move_tokens(&self, node: usize, force_number: bool, tokens: &mut Vec<String>) {
+        let node = &self.nodes[node];
+        let (Some(parent), Some(chess_move)) = (node.parent, node.chess_move) else {
+            return;
+        };
+        let before = &self.nodes[parent];
+        let number = move_number(&self.nodes[0].board, before.ply);
+        if before.board.side_to_move() == Color::White {
+            tokens.push(format!("{}.", number));
+        } else if force_number {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(pgn::move_to_san(&before.board, chess_move));
+        if let Some(comment) = &node.comment {
+            tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+        }
+    }
+
+    /// Movetext from `node` on: the main line with the alternatives to each
+    /// move in brackets right after it
+    fn line_tokens(&self, node: usize, mut force_number: bool, tokens: &mut Vec<String>) {
+        let mut node = node;
+        while let Some((&main, alternatives)) = self.nodes[node].children.split_first() {
+            self.move_tokens(main, force_number, tokens);
+            for &alternative in alternatives {
+                tokens.push("(".to_string());
+                self.move_tokens(alternative, true, tokens);
+                self.line_tokens(alternative, false, tokens);
+                tokens.push(")".to_string());
+            }
+            force_number = !alternatives.is_empty();
+            node = main;
+        }
+    }
+
+    fn movetext(&self) -> Vec<String> {
+        let mut tokens = Vec::new();
+        self.line_tokens(0, true, &mut tokens);
+        tokens
+    }
+
+    /// Moves from the root to the current position, in SAN
+    fn path(&self) -> String {
+        let mut moves = Vec::new();
+        let mut node = self.current;
+        while let (Some(parent), Some(chess_move)) =
+            (self.nodes[node].parent, self.nodes[node].chess_move)
+        {
+            moves.push(chess_move);
+            node = parent;
+        }
+        moves.reverse();
+        pgn::line_to_san(&self.nodes[0].board, &moves)
+    }
+
+    fn to_pgn(&self) -> String {
+        let start = self.nodes[0].board;
+        let mut headers = vec![
+            ("Event".to_string(), "Analysis".to_string()),
+            (
+                "Date".to_string(),
+                chrono::Local::now().format("%Y.%m.%d").to_string(),
+            ),
+            ("White".to_string(), "?".to_string()),
+            ("Black".to_string(), "?".to_string()),
+            ("Result".to_string(), "*".to_string()),
+        ];
+        if start != Board::default() {
+            headers.push(("SetUp".to_string(), "1".to_string()));
+            headers.push(("FEN".to_string(), start.to_string()));
+        }
+        let mut tokens = self.movetext();
+        tokens.push("*".to_string());
+        pgn::write_tokens(&headers, tokens)
+    }
+}
+
+/// Full-move number of the move played after `ply` moves from `start`
+fn move_number(start: &Board, ply: usize) -> usize {
+    (ply + (start.side_to_move() == Color::Black) as usize) / 2 + 1
+}
+
+/// A board with no opponent: moves for both sides are typed freely, and
+/// trying a different move somewhere keeps the old one as a variation.
+/// The engine is only started when analysis is asked for.
+pub async fn run(stockfish_path: &str, start: Board, moves: &[ChessMove]) -> Result<()> {
+    let mut tree = Tree::new(start);
+    for &chess_move in moves {
+        tree.play(chess_move);
+    }
+    let mut engine: Option<StockfishEngine> = None;
+    // Position of the last analysis and the engine's line from it
+    let mut last_line: Option<(Board, Vec<ChessMove>)> = None;
+    let mut perspective = Color::White;
+
+    println!("\n=== Analysis Board ===");
+    println!("Type moves for either side, 'h' for help, 'q' to quit.");
+    show(&tree, perspective);
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        let Some(input) = ui::next_input(&mut stdin).await? else {
+            break;
+        };
+        let typed = input.trim().to_string();
+        let lower = typed.to_lowercase();
+        let (command, arg) = lower.split_once(' ').unwrap_or((lower.as_str(), ""));
+        // File names and comments keep the case they were typed in
+        let typed_arg = typed.split_once(' ').map_or("", |(_, rest)| rest.trim());
+        match command {
+            "q" | "quit" => return Ok(()),
+            "" | "n" | "next" => {
+                let choice = arg
+                    .trim()
+                    .parse::<usize>()
+                    .map_or(0, |n| n.saturating_sub(1));
+                if !tree.forward(choice) {
+                    println!("No such continuation.");
+                    continue;
+                }
+            }
+            "b" | "back" | "p" | "prev" => {
+                if !tree.back() {
+                    println!("Already at the start.");
+                    continue;
+                }
+            }
+            "first" | "start" => while tree.back() {},
+            "end" => while tree.forward(0) {},
+            "vars" | "variations" => {
+                list_continuations(&tree);
+                continue;
+            }
+            "promote" => {
+                tree.promote();
+                println!("This line is now the main line.");
+                continue;
+            }
+            "delete" => {
+                if !tree.delete() {
+                    println!("Nothing to delete at the start.");
+                    continue;
+                }
+            }
+            "comment" => {
+                tree.nodes[tree.current].comment =
+                    (!typed_arg.is_empty()).then(|| typed_arg.to_string());
+                continue;
+            }
+            "tree" | "moves" => {
+                let text = tree.movetext().join(" ");
+                println!("{}", if text.is_empty() { "(no moves)" } else { &text });
+                continue;
+            }
+            "analyze" | "analyse" | "a" => {
+                let depth = match arg.trim() {
+                    "" => DEFAULT_DEPTH,
+                    depth => match depth.parse::<u8>() {
+                        Ok(depth @ 1..) => depth,
+                        _ => {
+                            println!("Usage: analyze [depth]");
+                            continue;
+                        }
+                    },
+                };
+                let board = tree.board();
+                if board.status() != chess::BoardStatus::Ongoing {
+                    println!("The game is over in this position.");
+                    continue;
+                }
+                if engine.is_none() {
+                    println!("Starting the engine...");
+                    let mut started = StockfishEngine::new(stockfish_path).await?;
+                    started.show_progress(true);
+                    engine = Some(started);
+                }
+                if let Some(engine) = &mut engine {
+                    let result = engine
+                        .analyze(&board, &format!("go depth {}", depth))
+                        .await?;
+                    if let Some(score) = result.score {
+                        println!(
+                            "Depth {}: {} (White's view)",
+                            result.depth,
+                            score.for_white(board.side_to_move())
+                        );
+                    }
+                    println!("Line: {}", pgn::line_to_san(&board, &result.pv));
+                    last_line = Some((board, result.pv));
+                }
+                continue;
+            }
+            "addline" => {
+                let line = match &last_line {
+                    Some((board, line)) if *board == tree.board() && !line.is_empty() => line,
+                    _ => {
+                        println!("Run 'analyze' here first.");
+                        continue;
+                    }
+                };
+                let from = tree.current;
+                for &chess_move in line {
+                    if !tree.board().legal(chess_move) {
+                        break;
+                    }
+                    tree.play(chess_move);
+                }
+                tree.current = from;
+                println!("Engine line added as a variation.");
+                continue;
+            }
+            "flip" => perspective = !perspective,
+            "fen" => {
+                println!("{}", tree.board());
+                continue;
+            }
+            "save" => {
+                if typed_arg.is_empty() {
+                    println!("Usage: save <file.pgn>");
+                } else {
+                    match std::fs::write(typed_arg, tree.to_pgn()) {
+                        Ok(()) => println!("Analysis written to {}", typed_arg),
+                        Err(e) => println!("Could not write {}: {}", typed_arg, e),
+                    }
+                }
+                continue;
+            }
+            "pgn" => {
+                print!("{}", tree.to_pgn());
+                continue;
+            }
+            "h" | "help" => {
+                print_help();
+                continue;
+            }
+            _ => match pgn::parse_move(&tree.board(), &typed) {
+                Ok(chess_move) => tree.play(chess_move),
+                Err(_) => {
+                    println!("Not a legal move or command: '{}'. 'h' for help.", typed);
+                    continue;
+                }
+            },
+        }
+        show(&tree, perspective);
+    }
+    Ok(())
+}
+
+/// Start the board from a FEN, or from the main line of a PGN game
+pub fn load_start(
+    fen_text: Option<&str>,
+    pgn_path: Option<&str>,
+    game_number: usize,
+) -> Result<(Board, Vec<ChessMove>)> {
+    if let Some(path) = pgn_path {
+        let games = pgn::load_pgn_file(path)?;
+        let game = games
+            .into_iter()
+            .nth(game_number.saturating_sub(1))
+            .ok_or_else(|| anyhow!("{} has no game {}", path, game_number))?;
+        return Ok((game.start, game.moves));
+    }
+    match fen_text {
+        Some(text) => Ok((fen::parse_fen(text)?, Vec::new())),
+        None => Ok((Board::default(), Vec::new())),
+    }
+}
+
+fn show(tree: &Tree, perspective: Color) {
+    display_board_for_player(&tree.board(), perspective);
+    let path = tree.path();
+    println!(
+        "{}",
+        if path.is_empty() {
+            "Start position"
+        } else {
+            &path
+        }
+    );
+    let continuations = tree.nodes[tree.current].children.len();
+    if continuations > 1 {
+        println!("{} continuations here ('vars' to list them)", continuations);
+    }
+}
+
+fn list_continuations(tree: &Tree) {
+    let node = &tree.nodes[tree.current];
+    if node.children.is_empty() {
+        println!("No moves played from here yet.");
+        return;
+    }
+    for (i, &child) in node.children.iter().enumerate() {
+        let mut tokens = Vec::new();
+        tree.move_tokens(child, true, &mut tokens);
+        tree.line_tokens(child, false, &mut tokens);
+        println!(
+            "  {}. {}{}",
+            i + 1,
+            tokens.join(" "),
+            if i == 0 { "  (main line)" } else { "" }
+        );
+    }
+}
+
+fn print_help() {
+    println!("\n=== Analysis Board Help ===");
+    println!("  • A move (e2e4 or Nf3) - Play it; a new move adds a variation");
+    println!("  • Enter, 'n' or 'next [k]' - Follow the main line, or continuation k");
+    println!("  • 'b' or 'back' - Go back one move");
+    println!("  • 'first' / 'end' - Jump to the start or the end of the line");
+    println!("  • 'vars' - List the continuations from here");
+    println!("  • 'promote' - Make the current line the main line");
+    println!("  • 'delete' - Remove this move and everything after it");
+    println!("  • 'comment <text>' - Comment the last move");
+    println!("  • 'analyze [depth]' - Engine evaluation and best line (depth 18 by default)");
+    println!("  • 'addline' - Add the engine's last line as a variation");
+    println!("  • 'tree' - Show all moves with variations");
+    println!("  • 'pgn' / 'save <file>' - Print or save the analysis as PGN");
+    println!("  • 'fen' - Show the position's FEN");
+    println!("  • 'flip' - Turn the board around");
+    println!("  • 'q' or 'quit' - Leave the board");
+    println!("===========================\n");
+}
@@ -0,0 +1,233 @@
+use crate::motifs;
+use crate::pgn::{self, PgnGame};
+use crate::stockfish::{Score, StockfishEngine};
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus, ChessMove};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::unbounded_channel;
+
+/// Centipawns lost for a move to be marked ?!, ? and ??
+const INACCURACY: i32 = 50;
+const MISTAKE: i32 = 100;
+const BLUNDER: i32 = 300;
+
+/// The engine's view of one position
+#[derive(Clone)]
+pub struct PositionEval {
+    /// Relative to the side to move
+    pub score: Score,
+    pub best_move: Option<ChessMove>,
+    /// The engine's main line, starting with `best_move`
+    pub pv: Vec<ChessMove>,
+}
+
+/// Number of engines to run when none is given: one per core
+pub fn default_engine_count() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Analyze positions with `engines` engine processes working through a shared
+/// queue, so a batch takes roughly 1/N of the time a single engine needs.
+/// Results come back in the order of `positions`.
+pub async fn analyze_positions(
+    stockfish_path: &str,
+    positions: &[Board],
+    depth: u8,
+    engines: usize,
+) -> Result<Vec<PositionEval>> {
+    let mut results: Vec<Option<PositionEval>> = vec![None; positions.len()];
+
+    // Finished games need no engine
+    let mut queue = VecDeque::new();
+    for (index, board) in positions.iter().enumerate() {
+        match board.status() {
+            BoardStatus::Checkmate => {
+                results[index] = Some(PositionEval {
+                    score: Score::Mate(0),
+                    best_move: None,
+                    pv: Vec::new(),
+                })
+            }
+            BoardStatus::Stalemate => {
+                results[index] = Some(PositionEval {
+                    score: Score::Centipawns(0),
+                    best_move: None,
+                    pv: Vec::new(),
+                })
+            }
+            BoardStatus::Ongoing => queue.push_back((index, *board)),
+        }
+    }
+    let total = queue.len();
+    let queue = Arc::new(Mutex::new(queue));
+    let (sender, mut receiver) = unbounded_channel();
+
+    let workers = engines.clamp(1, total.max(1));
+    for _ in 0..workers {
+        let mut engine = StockfishEngine::new(stockfish_path).await?;
+        engine.prepare_for_analysis().await?;
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            loop {
+                let Some((index, board)) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+                let result = engine
+                    .search(&board, &format!("go depth {}", depth))
+                    .await
+                    .and_then(|result| {
+                        let score = result
+                            .score
+                            .ok_or_else(|| anyhow!("Engine did not report a score"))?;
+                        Ok(PositionEval {
+                            score,
+                            best_move: result.best_move,
+                            pv: result.pv,
+                        })
+                    });
+                let failed = result.is_err();
+                if sender.send((index, result)).is_err() || failed {
+                    break;
+                }
+            }
+        });
+    }
+    // Only the workers hold senders now, so the channel closes when they finish
+    drop(sender);
+
+    let mut done = 0;
+    while let Some((index, result)) = receiver.recv().await {
+        results[index] = Some(result?);
+        done += 1;
+        eprint!(
+            "\rAnalyzed {}/{} positions with {} engine(s)",
+            done, total, workers
+        );
+        std::io::stderr().flush()?;
+    }
+    eprintln!();
+
+    results
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| anyhow!("Analysis stopped before every position was done"))
+}
+
+/// Annotate every game of a PGN file with evaluations and move marks
+pub async fn run(
+    stockfish_path: &str,
+    pgn_path: &str,
+    out: Option<&str>,
+    depth: u8,
+    engines: usize,
+) -> Result<()> {
+    let games = pgn::load_pgn_file(pgn_path)?;
+    if games.is_empty() {
+        return Err(anyhow!("No games found in {}", pgn_path));
+    }
+
+    // One batch for all games keeps every engine busy until the very end
+    let mut positions = Vec::new();
+    for game in &games {
+        let mut board = game.start;
+        positions.push(board);
+        for &chess_move in &game.moves {
+            board = board.make_move_new(chess_move);
+            positions.push(board);
+        }
+    }
+    // Progress goes to stderr so the annotated PGN can be piped
+    eprintln!(
+        "Annotating {} game(s), {} positions at depth {}...",
+        games.len(),
+        positions.len(),
+        depth
+    );
+    let evals = analyze_positions(stockfish_path, &positions, depth, engines).await?;
+
+    let mut text = String::new();
+    let mut offset = 0;
+    for game in &games {
+        let plies = game.moves.len() + 1;
+        text.push_str(&annotated_pgn(game, &evals[offset..offset + plies]));
+        text.push('\n');
+        offset += plies;
+    }
+
+    match out {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|e| anyhow!("Failed to write {}: {}", path, e))?;
+            println!("Annotated games written to {}", path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// `?!`, `?` or `??` for a move that lost this many centipawns, or nothing
+pub fn move_mark(loss: i32) -> &'static str {
+    match loss {
+        l if l >= BLUNDER => "??",
+        l if l >= MISTAKE => "?",
+        l if l >= INACCURACY => "?!",
+        _ => "",
+    }
+}
+
+/// Whether a move that lost this many centipawns is a mistake or worse
+pub fn is_mistake(loss: i32) -> bool {
+    loss >= MISTAKE
+}
+
+/// Whether a move that lost this many centipawns is a blunder
+pub fn is_blunder(loss: i32) -> bool {
+    loss >= BLUNDER
+}
+
+/// The game as PGN with a {White-relative eval} after every move, and the
+/// engine's choice after inaccuracies, mistakes and blunders, naming the
+/// tactic that refutes each blunder
+fn annotated_pgn(game: &PgnGame, evals: &[PositionEval]) -> String {
+    let mut headers = game.headers.clone();
+    headers.push(("Annotator".to_string(), "minichess".to_string()));
+
+    let mut board = game.start;
+    let mut moves = Vec::new();
+    for (ply, &chess_move) in game.moves.iter().enumerate() {
+        let mover = board.side_to_move();
+        let before = &evals[ply];
+        let after = &evals[ply + 1];
+        // The score after the move is the opponent's, so flip it back to the mover
+        let loss = before.score.as_centipawns() + after.score.as_centipawns();
+        let mark = move_mark(loss);
+        let san = format!("{}{}", pgn::move_to_san(&board, chess_move), mark);
+
+        let next = board.make_move_new(chess_move);
+        // Mate and stalemate speak for themselves
+        let comment = (next.status() == BoardStatus::Ongoing).then(|| {
+            let mut comment = after.score.for_white(!mover).to_string();
+            if !mark.is_empty()
+                && let Some(best) = before.best_move.filter(|&best| best != chess_move)
+            {
+                comment.push_str(&format!(", best was {}", pgn::move_to_san(&board, best)));
+            }
+            if is_blunder(loss) {
+                let motif = motifs::classify(&next, &after.pv);
+                comment.push_str(&format!(", loses to a {}", motif.name()));
+            }
+            comment
+        });
+        moves.push((san, comment));
+        board = next;
+    }
+
+    pgn::write_game(
+        &headers,
+        &game.start,
+        &moves,
+        game.header("Result").unwrap_or("*"),
+    )
+}
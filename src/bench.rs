@@ -0,0 +1,113 @@
+use crate::stockfish::StockfishEngine;
+use anyhow::{Result, anyhow};
+use chess::Board;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Positions searched by `bench`: the opening, sharp middlegames and
+/// endgames, so one figure covers the kinds of positions games go through
+const POSITIONS: [(&str, &str); 8] = [
+    (
+        "Start position",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    ),
+    (
+        "Kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+    (
+        "Italian game",
+        "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQK2R b KQkq - 0 5",
+    ),
+    (
+        "Queen's Gambit Declined",
+        "rnbqkb1r/ppp2ppp/4pn2/3p2B1/2PP4/2N5/PP2PPPP/R2QKBNR b KQkq - 1 4",
+    ),
+    (
+        "Sicilian Najdorf",
+        "rnbqkb1r/1p2pppp/p2p1n2/8/3NP3/2N5/PPP2PPP/R1BQKB1R w KQkq - 0 6",
+    ),
+    (
+        "Middlegame attack",
+        "r1b2rk1/2q1bppp/p2ppn2/1p6/3BPP2/2NB4/PPPQ2PP/2KR3R w - - 0 13",
+    ),
+    ("Rook endgame", "8/5pk1/6p1/R7/5P2/r5PK/7P/8 w - - 0 45"),
+    ("Pawn endgame", "8/8/1p3k2/p1p5/P1P2K2/1P6/8/8 w - - 0 50"),
+];
+
+/// Engine settings for a bench run
+pub struct BenchOptions {
+    pub depth: u8,
+    pub threads: Option<u32>,
+    pub hash: Option<u32>,
+}
+
+/// Search every bench position at a fixed depth and report nodes, time and
+/// nodes per second, to compare engine builds, binaries and settings on
+/// this machine. The hash is cleared before each position so runs repeat.
+pub async fn run(stockfish_path: &str, options: BenchOptions) -> Result<()> {
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    if let Some(threads) = options.threads {
+        engine.set_option("Threads", &threads.to_string()).await?;
+    }
+    if let Some(hash) = options.hash {
+        engine.set_option("Hash", &hash.to_string()).await?;
+    }
+    println!(
+        "\n=== Bench: {}, depth {}, {} thread(s), {} hash ===\n",
+        match engine.name() {
+            "" => stockfish_path,
+            name => name,
+        },
+        options.depth,
+        options
+            .threads
+            .map_or("default".to_string(), |t| t.to_string()),
+        options
+            .hash
+            .map_or("default".to_string(), |mb| format!("{} MB", mb))
+    );
+    println!(
+        "{:<26} {:>12} {:>9} {:>12}",
+        "Position", "Nodes", "Time", "Nodes/s"
+    );
+
+    let go = format!("go depth {}", options.depth);
+    let mut total_nodes = 0;
+    let mut total_time = Duration::ZERO;
+    for (name, fen) in POSITIONS {
+        let board = Board::from_str(fen).map_err(|e| anyhow!("Bad bench position: {}", e))?;
+        engine.new_game().await?;
+        let started = Instant::now();
+        let result = engine.search(&board, &go).await?;
+        let elapsed = started.elapsed();
+        println!(
+            "{:<26} {:>12} {:>8.2}s {:>12}",
+            name,
+            result.nodes,
+            elapsed.as_secs_f64(),
+            nodes_per_second(result.nodes, elapsed)
+        );
+        total_nodes += result.nodes;
+        total_time += elapsed;
+    }
+
+    println!(
+        "\n{:<26} {:>12} {:>8.2}s {:>12}",
+        "Total",
+        total_nodes,
+        total_time.as_secs_f64(),
+        nodes_per_second(total_nodes, total_time)
+    );
+    if total_nodes == 0 {
+        println!("The engine reported no node counts, so only the times can be compared.");
+    }
+    Ok(())
+}
+
+fn nodes_per_second(nodes: u64, elapsed: Duration) -> u64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => (nodes as f64 / secs) as u64,
+        _ => 0,
+    }
+}
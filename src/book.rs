@@ -0,0 +1,314 @@
+use crate::pgn;
+use crate::polyglot;
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, File, Piece, Rank, Square};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Bytes per book entry: key, move, weight, learn
+const ENTRY_SIZE: usize = 16;
+
+/// One Polyglot book entry
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    key: u64,
+    raw_move: u16,
+    weight: u16,
+}
+
+/// An opening book in Polyglot's format: 16-byte big-endian entries sorted
+/// by Polyglot position key, so books from other programs work and books
+/// built here work elsewhere. Lookups go by position, so a book line is
+/// found whatever move order reached it.
+pub struct Book {
+    entries: Vec<Entry>,
+}
+
+impl Book {
+    pub fn load(path: &str) -> Result<Self> {
+        let bytes =
+            std::fs::read(path).map_err(|e| anyhow!("Failed to read book {}: {}", path, e))?;
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(anyhow!("{} is not a Polyglot book", path));
+        }
+
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| Entry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                raw_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Book { entries })
+    }
+
+    /// Whether the position has any book moves, however it was reached
+    pub fn contains(&self, board: &Board) -> bool {
+        !self.moves(board).is_empty()
+    }
+
+    /// Book moves for a position with their weights, most played first
+    pub fn moves(&self, board: &Board) -> Vec<(ChessMove, u16)> {
+        let key = position_key(board);
+        let start = self.entries.partition_point(|e| e.key < key);
+        let mut moves: Vec<(ChessMove, u16)> = self.entries[start..]
+            .iter()
+            .take_while(|e| e.key == key)
+            .filter_map(|e| {
+                decode_move(board, e.raw_move)
+                    .filter(|m| board.legal(*m))
+                    .map(|m| (m, e.weight))
+            })
+            .collect();
+        moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+        moves
+    }
+
+    /// Pick a book move at random, in proportion to the weights
+    pub fn pick(&self, board: &Board) -> Option<ChessMove> {
+        let moves = self.moves(board);
+        let total: u32 = moves.iter().map(|(_, w)| *w as u32).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rand::rng().random_range(0..total);
+        for (chess_move, weight) in moves {
+            if roll < weight as u32 {
+                return Some(chess_move);
+            }
+            roll -= weight as u32;
+        }
+        None
+    }
+}
+
+/// Summary of a `book build` run
+pub struct BuildReport {
+    pub files: usize,
+    pub games: usize,
+    pub skipped_files: usize,
+    pub entries: usize,
+    /// Positions reached by more than one move order
+    pub transpositions: usize,
+}
+
+/// Build a weighted book from every game in `pgn_path` (a PGN file or a
+/// folder of them), looking at the first `max_ply` plies of each game.
+/// A move scores 2 when its side went on to win and 1 for a draw.
+pub fn build(pgn_path: &Path, out: &Path, max_ply: usize) -> Result<BuildReport> {
+    let files = pgn_files(pgn_path)?;
+    let mut counts: HashMap<(u64, u16), u32> = HashMap::new();
+    let mut report = BuildReport {
+        files: files.len(),
+        games: 0,
+        skipped_files: 0,
+        entries: 0,
+        transpositions: 0,
+    };
+    // Every (position, move) that led to each position, to spot transpositions
+    let mut arrivals: HashMap<u64, HashSet<(u64, u16)>> = HashMap::new();
+
+    for file in &files {
+        let games = match pgn::load_pgn_file(&file.to_string_lossy()) {
+            Ok(games) => games,
+            Err(e) => {
+                println!("Skipping {}: {}", file.display(), e);
+                report.skipped_files += 1;
+                continue;
+            }
+        };
+
+        for game in games {
+            report.games += 1;
+            let winner = game.winner();
+            let drawn = game.header("Result") == Some("1/2-1/2");
+            let mut board = game.start;
+            for chess_move in game.moves.iter().take(max_ply) {
+                let mover = board.side_to_move();
+                let score = match winner {
+                    Some(color) if color == mover => 2,
+                    None if drawn => 1,
+                    _ => 0,
+                };
+                let from = (position_key(&board), encode_move(&board, *chess_move));
+                if score > 0 {
+                    *counts.entry(from).or_default() += score;
+                }
+                board = board.make_move_new(*chess_move);
+                arrivals
+                    .entry(position_key(&board))
+                    .or_default()
+                    .insert(from);
+            }
+        }
+    }
+
+    // Weights are 16 bits; scale down if a popular line overflows them
+    let max = counts.values().copied().max().unwrap_or(0);
+    let scale = if max > u16::MAX as u32 {
+        u16::MAX as f64 / max as f64
+    } else {
+        1.0
+    };
+    let mut entries: Vec<Entry> = counts
+        .into_iter()
+        .map(|((key, raw_move), count)| Entry {
+            key,
+            raw_move,
+            weight: ((count as f64 * scale) as u16).max(1),
+        })
+        .collect();
+    entries.sort_by_key(|e| (e.key, std::cmp::Reverse(e.weight), e.raw_move));
+
+    let mut bytes = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for entry in &entries {
+        bytes.extend_from_slice(&entry.key.to_be_bytes());
+        bytes.extend_from_slice(&entry.raw_move.to_be_bytes());
+        bytes.extend_from_slice(&entry.weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+    }
+    std::fs::write(out, bytes).map_err(|e| anyhow!("Failed to write {}: {}", out.display(), e))?;
+
+    report.entries = entries.len();
+    report.transpositions = arrivals.values().filter(|from| from.len() > 1).count();
+    Ok(report)
+}
+
+fn pgn_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pgn"))
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(anyhow!("No .pgn files found in {}", path.display()));
+    }
+    Ok(files)
+}
+
+/// Polyglot key of a position: equal for transpositions, since it only
+/// depends on the pieces, side to move, castling rights and en passant file
+pub fn position_key(board: &Board) -> u64 {
+    polyglot::key(board)
+}
+
+/// Polyglot move encoding: to-file, to-rank, from-file, from-rank and
+/// promotion piece in 3-bit fields. Castling is written as the king
+/// capturing its own rook.
+fn encode_move(board: &Board, chess_move: ChessMove) -> u16 {
+    let source = chess_move.get_source();
+    let mut dest = chess_move.get_dest();
+    let is_castle = board.piece_on(source) == Some(Piece::King)
+        && source
+            .get_file()
+            .to_index()
+            .abs_diff(dest.get_file().to_index())
+            == 2;
+    if is_castle {
+        let rook_file = if dest.get_file() == File::G {
+            File::H
+        } else {
+            File::A
+        };
+        dest = Square::make_square(dest.get_rank(), rook_file);
+    }
+
+    let promotion = match chess_move.get_promotion() {
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(Piece::Queen) => 4,
+        _ => 0,
+    };
+    (dest.get_file().to_index() as u16)
+        | (dest.get_rank().to_index() as u16) << 3
+        | (source.get_file().to_index() as u16) << 6
+        | (source.get_rank().to_index() as u16) << 9
+        | promotion << 12
+}
+
+fn decode_move(board: &Board, raw_move: u16) -> Option<ChessMove> {
+    let field = |shift: u16| ((raw_move >> shift) & 7) as usize;
+    let source = Square::make_square(Rank::from_index(field(9)), File::from_index(field(6)));
+    let mut dest = Square::make_square(Rank::from_index(field(3)), File::from_index(field(0)));
+    let promotion = match field(12) {
+        0 => None,
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => return None,
+    };
+
+    // King takes own rook means castling
+    let mover = board.color_on(source)?;
+    if board.piece_on(source) == Some(Piece::King)
+        && board.piece_on(dest) == Some(Piece::Rook)
+        && board.color_on(dest) == Some(mover)
+    {
+        let king_file = if dest.get_file() == File::H {
+            File::G
+        } else {
+            File::C
+        };
+        dest = Square::make_square(dest.get_rank(), king_file);
+    }
+
+    Some(ChessMove::new(source, dest, promotion))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn check(fen: &str, uci: &str, raw: u16) {
+        let board = Board::from_str(fen).unwrap();
+        let chess_move = ChessMove::from_str(uci).unwrap();
+        assert_eq!(encode_move(&board, chess_move), raw, "{}", uci);
+        assert_eq!(decode_move(&board, raw), Some(chess_move), "{}", uci);
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        check(start, "e2e4", 4 | 3 << 3 | 4 << 6 | 1 << 9);
+        check(start, "g1f3", 5 | 2 << 3 | 6 << 6);
+        check(
+            "4k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "a7a8q",
+            7 << 3 | 6 << 9 | 4 << 12,
+        );
+        check(
+            "4k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "a7a8n",
+            7 << 3 | 6 << 9 | 1 << 12,
+        );
+    }
+
+    #[test]
+    fn castling_is_king_takes_rook() {
+        let white = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        check(white, "e1g1", 7 | 4 << 6);
+        check(white, "e1c1", 4 << 6);
+        let black = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        check(black, "e8g8", 7 | 7 << 3 | 4 << 6 | 7 << 9);
+        check(black, "e8c8", 7 << 3 | 4 << 6 | 7 << 9);
+    }
+
+    #[test]
+    fn start_position_key() {
+        assert_eq!(position_key(&Board::default()), 0x463b96181691fc9c);
+    }
+}
@@ -1,23 +1,283 @@
-use crate::stockfish::StockfishEngine;
-use crate::ui::{display_board_for_player, get_user_input, print_help};
+use crate::analysis::AutoAnalyzer;
+use crate::annotate;
+use crate::book::Book;
+use crate::coach::{Coach, KeyMove};
+use crate::commands::CommandRegistry;
+use crate::consult;
+use crate::control;
+use crate::crash;
+use crate::diff;
+use crate::eval;
+use crate::exchange;
+use crate::fen;
+use crate::house_rules::{self, HouseRules};
+use crate::html;
+use crate::kibitz::Kibitzer;
+use crate::motifs::{self, Motif};
+use crate::mouse::{self, Input};
+use crate::movelist::{self, MoveEntry, format_duration};
+use crate::overlay;
+use crate::pgn;
+use crate::plan;
+use crate::scoresheet;
+use crate::scripting::Scripts;
+use crate::sessions;
+use crate::sound::{self, Sound};
+use crate::status;
+use crate::stockfish::{EngineError, MATE_SCORE, Score, StockfishEngine, format_centipawns};
+use crate::terminal;
+use crate::transcript::{Entry, Transcript};
+use crate::ui::{
+    Notation, Verbosity, board_text, color_name, display_board_for_player, display_board_with_eval,
+    display_checkmate, get_piece_char, get_user_input, notation, print_help, select, verbosity,
+};
 use anyhow::{Result, anyhow};
-use chess::{ChessMove, Color, Game, MoveGen, Piece, Square};
+use chess::{BitBoard, Board, ChessMove, Color, EMPTY, Game, MoveGen, Piece, Square};
+use chrono::{DateTime, Local, Utc};
+use rand::seq::IteratorRandom;
+use std::io::IsTerminal;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Depth of the null-move search behind the `threat` command
+const THREAT_DEPTH: u8 = 12;
+/// Depth of the search behind the `hint` command
+const HINT_DEPTH: u8 = 12;
+/// Default depth of the `analyze` command
+const ANALYZE_DEPTH: u8 = 18;
+/// Depth of the analysis behind `export html`
+const HTML_DEPTH: u8 = 12;
+/// Depth of the search that looks for the player's blunders
+const BLUNDER_DEPTH: u8 = 12;
+/// Most centipawns one move counts for in `average_loss`, so a single
+/// missed mate doesn't swamp the rest
+const MAX_COUNTED_LOSS: i32 = 1000;
+/// Moves of the engine's line shown by `plan`
+const PLAN_LINE_MOVES: usize = 6;
+/// Depth of the evaluation written to the status file
+const STATUS_DEPTH: u8 = 8;
+/// Depth of the quick search used by `moves --ranked`
+const RANKING_DEPTH: u8 = 8;
+/// Depth of the search checking that a recapture is worth offering
+const RECAPTURE_DEPTH: u8 = 10;
+/// Depth of the searches judging the player's moves for the coach
+const COACH_DEPTH: u8 = 12;
+/// Takebacks allowed per game unless changed with `--no-takebacks`
+const TAKEBACKS_PER_GAME: u32 = 3;
+
+/// Centipawns a varied opening move may trail the engine's best one by
+const VARIETY_MARGIN: i32 = 50;
+
+/// Random choice among the computer's best opening moves, so repeated games
+/// don't repeat move for move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpeningVariety {
+    /// Book or engine moves to choose from
+    pub choices: usize,
+    /// Moves of the game during which the computer varies its play
+    pub moves: usize,
+}
+
+impl OpeningVariety {
+    /// Parse `N:K`, choosing among the top N moves for the first K moves.
+    /// `0` turns variety off.
+    pub fn parse(text: &str) -> Result<Option<Self>> {
+        if text.trim() == "0" {
+            return Ok(None);
+        }
+        let (choices, moves) = text
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Opening variety must look like N:K, e.g. 3:6"))?;
+        let choices: usize = choices
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid number of choices: {}", choices))?;
+        let moves: usize = moves
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid number of moves: {}", moves))?;
+        Ok((choices > 1 && moves > 0).then_some(OpeningVariety { choices, moves }))
+    }
+}
+
+/// When the computer gives up a lost game instead of playing it out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResignThreshold {
+    /// Centipawns the computer must be behind by
+    pub centipawns: i32,
+    /// Consecutive computer moves it must have been that far behind for
+    pub moves: usize,
+}
+
+impl ResignThreshold {
+    /// Parse `CP:N`, resigning after N moves in a row scored at least CP
+    /// centipawns down. `0` turns resigning off.
+    pub fn parse(text: &str) -> Result<Option<Self>> {
+        if text.trim() == "0" {
+            return Ok(None);
+        }
+        let (centipawns, moves) = text
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Resign threshold must look like CP:N, e.g. 600:3"))?;
+        let centipawns: i32 = centipawns
+            .trim()
+            .parse()
+            .ok()
+            .filter(|cp: &i32| *cp > 0)
+            .ok_or_else(|| anyhow!("Invalid number of centipawns: {}", centipawns))?;
+        let moves: usize = moves
+            .trim()
+            .parse()
+            .ok()
+            .filter(|moves: &usize| *moves > 0)
+            .ok_or_else(|| anyhow!("Invalid number of moves: {}", moves))?;
+        Ok(Some(ResignThreshold { centipawns, moves }))
+    }
+}
+
+/// Settings chosen for one game, passed to its constructor so that games
+/// of a match, simul or session can each have their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameOptions {
+    /// Takebacks allowed in the game (`--no-takebacks` makes it 0)
+    pub takebacks: u32,
+    /// Ask before playing each of the player's moves (`--confirm-moves`)
+    pub confirm_moves: bool,
+    /// Start with the evaluation bar beside the board (`--eval-bar`)
+    pub eval_bar: bool,
+    /// Vary the computer's opening moves (`--opening-variety`)
+    pub opening_variety: Option<OpeningVariety>,
+    /// Shortest time the computer takes to reply (`--min-move-delay`)
+    pub min_move_delay: Duration,
+    /// Start out describing what each move changed (`--move-diff`)
+    pub move_diff: bool,
+    /// Keep at most this many positions for undo (`undo_states` in the
+    /// config); older ones are rebuilt from the move list when needed
+    pub undo_limit: Option<usize>,
+    /// Turn `undo` and `redo` off (`--no-undo`)
+    pub no_undo: bool,
+    /// Let the computer resign a hopeless game (`--resign-threshold`); it
+    /// plays to the end without one
+    pub resign_threshold: Option<ResignThreshold>,
+    /// Offer to play the player's only recapture when it is also the
+    /// engine's choice (`--auto-recapture`)
+    pub auto_recapture: bool,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            takebacks: TAKEBACKS_PER_GAME,
+            confirm_moves: false,
+            eval_bar: false,
+            opening_variety: None,
+            min_move_delay: Duration::ZERO,
+            move_diff: false,
+            undo_limit: None,
+            no_undo: false,
+            resign_threshold: None,
+            auto_recapture: false,
+        }
+    }
+}
+
+/// Something that happened during a game besides the moves themselves
+pub struct GameEvent {
+    pub time: String,
+    /// Number of moves played when it happened
+    pub ply: usize,
+    pub text: String,
+}
 
 pub struct ChessGame {
     game: Game,
     engine: StockfishEngine,
     player_color: Color,
     move_history: Vec<(ChessMove, String, String)>, // (move, description, detailed_description)
+    start: Board,                                   // Position the game began from
     game_states: Vec<Game>,                         // Stack of game states for undo/redo
-    current_state_index: usize,                     // Current position in the game_states stack
+    state_base: usize, // Moves before game_states[0]; older states were compacted away
+    current_state_index: usize, // Moves of full_move_history played on the board
     full_move_history: Vec<(ChessMove, String, String)>, // Complete history for redo reconstruction
+    full_move_times: Vec<Duration>, // Time spent on each move of full_move_history
+    consultant: Option<StockfishEngine>, // Second engine the computer consults with
+    kibitzer: Option<Kibitzer>, // Background commentary engine
+    coach: Option<Coach>, // Comments on the player's mistakes
+    analyzer: Option<AutoAnalyzer>, // Analyzes while the player thinks
+    analysis_path: Option<String>, // Engine for auto-analysis if not the opponent's
+    book: Option<Book>, // Opening book the computer plays from
+    in_book: bool,     // Whether the current position is in the book
+    scripts: Option<Scripts>, // User script hooks
+    commands: CommandRegistry, // In-game commands at the move prompt
+    events: Vec<GameEvent>, // Takebacks and other notable events
+    competitive: bool, // Rated/ladder game: no analysis undo
+    house_rules: HouseRules, // Rule tweaks of a casual game
+    options: GameOptions, // Settings chosen for this game
+    takebacks_used: u32, // Takebacks used of the options' allowance
+    clock: Option<[Duration; 2]>, // Remaining time of White and Black in timed games
+    flagged: Option<Color>, // Side that ran out of time
+    last_line: Option<EngineLine>, // Most recent engine line, for `preview`
+    eval_bar: bool,    // Draw an evaluation bar beside the board
+    move_diff: bool,   // Describe what each move changed
+    eval: Option<(Board, Score)>, // Quick evaluation of the latest position, White's view
+    simul: bool,       // One board of a simultaneous exhibition
+    simul_request: Option<SimulTurn>, // Set by `board <n>` and `overview` in a simul
+    session: Option<String>, // Saved session the game belongs to
+    session_switch: Option<String>, // Session to play next, set by `session switch`
+    started: DateTime<Local>, // When the game began
+    ended: Option<DateTime<Local>>, // When it was decided
+    motifs_recorded: usize, // Plies whose blunder motifs are already in the statistics
+    losing_moves: usize, // Computer moves in a row scored below the resign threshold
+    transcript: Transcript, // Everything shown and typed, for `export transcript`
+}
+
+/// What a turn on one board of a simul ended with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulTurn {
+    /// Both sides moved, or the game ended
+    Moved,
+    /// The player asked to go to this board (1-based)
+    Board(usize),
+    Overview,
+    Quit,
+}
+
+/// A line the engine suggested, kept so `preview` can step through it
+struct EngineLine {
+    /// Game position the line was asked for
+    position: Board,
+    /// Where the line starts; differs from `position` for a threat
+    start: Board,
+    moves: Vec<ChessMove>,
 }
 
 impl ChessGame {
-    pub async fn new(stockfish_path: &str, difficulty: u8) -> Result<Self> {
+    /// Start a game against the engine; the player is asked for a color
+    /// unless `color` is given
+    pub async fn new(
+        stockfish_path: &str,
+        difficulty: u8,
+        board: Board,
+        color: Option<Color>,
+        options: GameOptions,
+    ) -> Result<Self> {
         let mut engine = StockfishEngine::new(stockfish_path).await?;
         engine.set_difficulty(difficulty).await?;
+        Self::with_engine(engine, board, color, options)
+    }
+
+    /// Start a game with an engine already set up, such as the one the
+    /// previous game of a match used; the player is asked for a color
+    /// unless `color` is given
+    pub fn with_engine(
+        engine: StockfishEngine,
+        board: Board,
+        color: Option<Color>,
+        options: GameOptions,
+    ) -> Result<Self> {
+        if let Some(color) = color {
+            return Ok(Self::from_position(engine, color, board, options));
+        }
 
         // Ask player for color preference
         println!("Choose your color:");
@@ -35,30 +295,333 @@ impl ChessGame {
             }
         };
 
-        let game = Game::new();
+        Ok(Self::from_position(engine, player_color, board, options))
+    }
 
-        Ok(ChessGame {
+    /// Start a game from an arbitrary position with an already configured engine
+    pub fn from_position(
+        mut engine: StockfishEngine,
+        player_color: Color,
+        board: Board,
+        options: GameOptions,
+    ) -> Self {
+        let game = Game::new_with_board(board);
+        engine.show_progress(verbosity() > Verbosity::Quiet);
+
+        ChessGame {
             game: game.clone(),
             engine,
             player_color,
             move_history: Vec::new(),
+            start: board,
             game_states: vec![game], // Start with initial position
+            state_base: 0,
             current_state_index: 0,
             full_move_history: Vec::new(),
-        })
+            full_move_times: Vec::new(),
+            consultant: None,
+            kibitzer: None,
+            coach: None,
+            analyzer: None,
+            analysis_path: None,
+            book: None,
+            in_book: true,
+            scripts: None,
+            commands: builtin_commands().expect("built-in commands have unique names"),
+            events: Vec::new(),
+            competitive: false,
+            house_rules: house_rules::get(),
+            options,
+            takebacks_used: 0,
+            clock: None,
+            flagged: None,
+            last_line: None,
+            eval_bar: options.eval_bar,
+            move_diff: options.move_diff,
+            eval: None,
+            simul: false,
+            simul_request: None,
+            session: None,
+            session_switch: None,
+            started: Local::now(),
+            ended: None,
+            motifs_recorded: 0,
+            losing_moves: 0,
+            transcript: Transcript::default(),
+        }
+    }
+
+    /// Play the moves of a saved game without asking the engine, so the
+    /// game continues where it was left
+    pub fn replay_moves(&mut self, moves: &[ChessMove]) -> Result<()> {
+        for &chess_move in moves {
+            let board = self.game.current_position();
+            if !board.legal(chess_move) {
+                return Err(anyhow!("Saved move {} is illegal here", chess_move));
+            }
+            let side = board.side_to_move();
+            let name = color_name(side);
+            let who = if side == self.player_color {
+                "You"
+            } else {
+                "Computer"
+            };
+            let description = self.describe_move(&chess_move, &board);
+            let move_entry = (
+                chess_move,
+                name.to_string(),
+                format!("{} ({}): {}", name, who, description),
+            );
+            self.record_move(move_entry, Duration::ZERO);
+        }
+        self.note_book_position(false);
+        Ok(())
+    }
+
+    /// Keep this game in a saved session, storing it after every move.
+    /// Adds the `session` command for managing and switching sessions.
+    pub fn set_session(&mut self, name: &str) -> Result<()> {
+        self.session = Some(name.to_string());
+        self.commands.register(
+            "session list|new <name>|switch <name>|close <name>",
+            &[],
+            "Manage saved games: new and switch put this one aside and play another",
+            |game, args| {
+                Box::pin(async move {
+                    game.session_command(args)?;
+                    Ok(())
+                })
+            },
+        )
+    }
+
+    /// Session to play next after `run` returns, if the player switched
+    pub fn take_session_switch(&mut self) -> Option<String> {
+        self.session_switch.take()
+    }
+
+    fn session_command(&mut self, args: &str) -> Result<()> {
+        let current = self.session.clone().unwrap_or_default();
+        let (action, name) = args.split_once(' ').unwrap_or((args, ""));
+        let name = name.trim();
+        let result = match (action, name) {
+            ("list" | "", _) => sessions::list(),
+            ("new", name) if !name.is_empty() => {
+                let difficulty = sessions::get(&current)?.difficulty;
+                sessions::create(name, difficulty, self.player_color).map(|()| {
+                    self.session_switch = Some(name.to_string());
+                })
+            }
+            ("switch", name) if name == current => {
+                println!("You are already playing session '{}'.", name);
+                Ok(())
+            }
+            ("switch", name) if !name.is_empty() => sessions::get(name).map(|_| {
+                self.session_switch = Some(name.to_string());
+            }),
+            ("close", name) if name == current => {
+                println!("Finish or resign this game instead, or close it from another session.");
+                Ok(())
+            }
+            ("close", name) if !name.is_empty() => sessions::close(name).map(|()| {
+                println!("Session '{}' closed.", name);
+            }),
+            _ => {
+                println!("Usage: session list|new <name>|switch <name>|close <name>");
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            println!("{}", e);
+        }
+        if self.session_switch.is_some() {
+            println!("Session '{}' saved.", current);
+        }
+        Ok(())
+    }
+
+    /// Make this game one board of a simul: `board <n>` switches boards and
+    /// `overview` shows all of them
+    pub fn set_simul(&mut self) -> Result<()> {
+        self.simul = true;
+        self.commands.register(
+            "overview",
+            &["boards"],
+            "Show the status of every board in the simul",
+            |game, _| {
+                Box::pin(async move {
+                    game.simul_request = Some(SimulTurn::Overview);
+                    Ok(())
+                })
+            },
+        )
     }
 
-    pub async fn run(&mut self) -> Result<()> {
+    /// Play one turn of a simul board: the player's move and the computer's
+    /// reply, unless the player leaves the board first
+    pub async fn play_simul_turn(&mut self) -> Result<SimulTurn> {
+        if self.game.current_position().side_to_move() == self.player_color {
+            self.show_board();
+            match self.handle_player_turn().await? {
+                GameAction::Quit => return Ok(SimulTurn::Quit),
+                GameAction::Continue => {}
+            }
+            if let Some(request) = self.simul_request.take() {
+                return Ok(request);
+            }
+        }
+        if self.outcome() == GameOutcome::Unfinished
+            && self.game.current_position().side_to_move() != self.player_color
+        {
+            self.make_computer_move().await?;
+        }
+
+        // Repetition and fifty-move draws are claimed automatically
+        if self.game.can_declare_draw() {
+            self.game.declare_draw();
+        }
+        if self.outcome() != GameOutcome::Unfinished {
+            self.ended = Some(Local::now());
+            self.display_game_result();
+            self.log_event(format!("Game ended: {}", self.outcome().pgn_result()));
+            self.run_game_end_hook();
+        }
+        Ok(SimulTurn::Moved)
+    }
+
+    /// One line for the simul overview: move number, last move and state
+    pub fn summary(&self) -> String {
+        let board = self.game.current_position();
+        let last = self
+            .move_history
+            .last()
+            .map(|(chess_move, _, _)| {
+                let before = self.position_after(self.move_history.len() - 1);
+                pgn::move_to_san(&before, *chess_move)
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let state = match self.outcome() {
+            GameOutcome::Unfinished if board.side_to_move() == self.player_color => {
+                "your move".to_string()
+            }
+            GameOutcome::Unfinished => "computer to move".to_string(),
+            outcome => format!("finished {}", outcome.pgn_result()),
+        };
+        format!(
+            "move {:>3}  last {:<8} material {:+}  {}",
+            self.move_number(),
+            last,
+            material_balance(&board, self.player_color),
+            state
+        )
+    }
+
+    /// Play with clocks: each side loses on time when its clock runs out.
+    /// The engine is told the remaining times and manages its own.
+    pub fn set_clock(&mut self, white: Duration, black: Duration) {
+        self.clock = Some([white, black]);
+    }
+
+    /// Rated and ladder games count: analysis `undo`/`redo` is switched off
+    /// and only the limited, logged `takeback` remains. House rules don't
+    /// apply either.
+    pub fn set_competitive(&mut self) {
+        self.competitive = true;
+        self.house_rules = HouseRules::default();
+    }
+
+    /// Make the computer a consultation team: both engines are asked for every move
+    pub fn set_consultant(&mut self, engine: StockfishEngine) {
+        self.consultant = Some(engine);
+    }
+
+    pub fn set_book(&mut self, book: Book) {
+        self.book = Some(book);
+    }
+
+    /// Load user scripts; their custom commands join the built-in ones
+    pub fn set_scripts(&mut self, scripts: Scripts) {
+        for name in scripts.commands() {
+            let usage = format!("{} [args]", name);
+            let registered =
+                self.commands
+                    .register(&usage, &[], "Script command", move |game, args| {
+                        let input = format!("{} {}", name, args);
+                        Box::pin(async move {
+                            if let Some(scripts) = &mut game.scripts {
+                                scripts.run_command(input.trim());
+                            }
+                            Ok(())
+                        })
+                    });
+            if let Err(e) = registered {
+                println!("[script] Skipping command: {}", e);
+            }
+        }
+        self.scripts = Some(scripts);
+    }
+
+    /// Run auto-analysis with this engine rather than another instance of
+    /// the opponent, which may be a weakened network
+    pub fn set_analysis_engine(&mut self, path: &str) {
+        self.analysis_path = Some(path.to_string());
+    }
+
+    /// Have a second engine comment on both sides' moves as the game goes
+    pub fn set_kibitzer(&mut self, kibitzer: Kibitzer) {
+        self.kibitzer = Some(kibitzer);
+    }
+
+    /// Have a coach explain the player's mistakes as they happen
+    pub fn set_coach(&mut self, coach: Coach) {
+        self.coach = Some(coach);
+    }
+
+    /// The game's engine, to play the next game of a match without
+    /// restarting it
+    pub fn into_engine(self) -> StockfishEngine {
+        self.engine
+    }
+
+    pub async fn run(&mut self) -> Result<GameOutcome> {
         println!("\nGame started! You are playing as {:?}", self.player_color);
-        display_board_for_player(&self.game.current_position(), self.player_color);
+        if self.house_rules.any() {
+            println!("House rules: {}", self.house_rules.describe());
+        }
+        self.log_event(format!("Game started as {:?}", self.player_color));
+        self.engine.new_game().await?;
+        self.redraw_board().await?;
 
-        // If player is black, let computer make first move
-        if self.player_color == Color::Black {
+        // If it's not the player's move, let computer make first move
+        if self.game.current_position().side_to_move() != self.player_color {
             self.make_computer_move().await?;
         }
 
         loop {
-            if self.game.result().is_some() {
+            self.update_status_file().await?;
+            crash::note_game(
+                self.game.current_position().to_string(),
+                self.to_pgn("Crash report", None),
+            );
+            self.note_control_state();
+            if let Some(name) = &self.session {
+                let moves: Vec<ChessMove> = self.move_history.iter().map(|(m, _, _)| *m).collect();
+                sessions::update(name, &moves)?;
+            }
+
+            // Repetition and fifty-move draws are claimed automatically
+            if self.game.can_declare_draw() {
+                self.game.declare_draw();
+            }
+
+            if self.outcome() != GameOutcome::Unfinished {
+                self.ended = Some(Local::now());
+                if self.house_rules.capture_the_king
+                    && self.game.current_position().status() == chess::BoardStatus::Checkmate
+                {
+                    self.capture_king()?;
+                }
+                sound::play(Sound::GameEnd);
                 self.display_game_result();
                 break;
             }
@@ -75,126 +638,507 @@ impl ChessGame {
             }
         }
 
-        Ok(())
+        self.log_event(format!("Game ended: {}", self.outcome().pgn_result()));
+        if verbosity() > Verbosity::Quiet {
+            self.print_time_summary();
+        }
+        terminal::restore_title();
+        self.run_game_end_hook();
+        Ok(self.outcome())
+    }
+
+    pub fn outcome(&self) -> GameOutcome {
+        if let Some(flagged) = self.flagged {
+            // Running out of time only loses if the opponent could still mate
+            return if has_mating_material(&self.game.current_position(), !flagged) {
+                GameOutcome::Timeout(!flagged)
+            } else {
+                GameOutcome::Draw
+            };
+        }
+        match self.game.result() {
+            Some(chess::GameResult::WhiteCheckmates) | Some(chess::GameResult::BlackResigns) => {
+                GameOutcome::Win(Color::White)
+            }
+            Some(chess::GameResult::BlackCheckmates) | Some(chess::GameResult::WhiteResigns) => {
+                GameOutcome::Win(Color::Black)
+            }
+            // Every move would put the king where it can be taken
+            Some(chess::GameResult::Stalemate) if self.house_rules.capture_the_king => {
+                GameOutcome::Win(!self.game.current_position().side_to_move())
+            }
+            Some(_) => GameOutcome::Draw,
+            None if is_insufficient_material(&self.game.current_position()) => GameOutcome::Draw,
+            None => GameOutcome::Unfinished,
+        }
     }
 
     async fn handle_player_turn(&mut self) -> Result<GameAction> {
-        println!("\nYour turn! Enter a move (e.g., 'e2e4') or 'h' for help:");
+        let mouse = mouse::enabled();
+        if mouse {
+            self.draw_board_at_top(None);
+        }
+        if verbosity() > Verbosity::Quiet {
+            if mouse {
+                println!("\nYour turn! Click a piece, type a move or 'h' for help:");
+            } else {
+                println!("\nYour turn! Enter a move (e.g., 'e2e4') or 'h' for help:");
+            }
+        }
+        self.show_clocks();
 
+        // Thinking time runs from the start of the turn, commands included
+        let turn_started = Instant::now();
+        if self.options.auto_recapture
+            && let Some(recapture) = self.obvious_recapture().await?
+        {
+            let board = self.game.current_position();
+            overlay::show_move(&board, self.player_color, recapture);
+            println!(
+                "Recapture with {}? (Y/n)",
+                pgn::move_to_san(&board, recapture)
+            );
+            let answer = get_user_input()?;
+            if self.run_clock(self.player_color, turn_started.elapsed()) {
+                return Ok(GameAction::Continue);
+            }
+            if matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes") {
+                self.make_player_move(recapture, turn_started.elapsed());
+                self.run_clock_after_move(self.player_color, turn_started.elapsed());
+                self.redraw_board().await?;
+                return Ok(GameAction::Continue);
+            }
+        }
+
+        // Clicks map to squares only while the board is still at the top of
+        // the screen, not after command output has scrolled it
+        let mut board_at_top = mouse;
+        let mut selected: Option<Square> = None;
         loop {
-            let input = get_user_input()?.trim().to_lowercase();
+            // Commands such as undo change the position without ending the turn
+            self.note_control_state();
+            if let Some(analyzer) = &self.analyzer {
+                // Leave a line above the prompt for the readout
+                println!();
+                analyzer.start(self.game.current_position());
+            }
+            let input = if mouse {
+                mouse::read_input()?
+            } else {
+                Input::Line(get_user_input()?)
+            };
+            if let Some(analyzer) = &self.analyzer {
+                analyzer.stop().await;
+            }
+            if self.run_clock(self.player_color, turn_started.elapsed()) {
+                return Ok(GameAction::Continue);
+            }
+
+            // Commands get their arguments as typed, so file names keep their case
+            let typed = match &input {
+                Input::Line(text) => text.trim().to_string(),
+                Input::Click(_, _) => String::new(),
+            };
+            if !typed.is_empty() {
+                self.transcript.record(Entry::Command(typed.clone()));
+            }
+            let input = match input {
+                Input::Line(text) => text.trim().to_lowercase(),
+                Input::Click(_, _) if !board_at_top => {
+                    self.draw_board_at_top(None);
+                    board_at_top = true;
+                    println!("Board redrawn, click again.");
+                    continue;
+                }
+                // A click stands for typing the square
+                Input::Click(column, row) => match mouse::square_at(column, row, self.player_color)
+                {
+                    Some(square) => square.to_string(),
+                    None => {
+                        selected = None;
+                        self.draw_board_at_top(None);
+                        continue;
+                    }
+                },
+            };
 
             match input.as_str() {
                 "q" | "quit" => return Ok(GameAction::Quit),
                 "h" | "help" => {
-                    print_help();
+                    let (lines, hidden) = self.commands.help_lines(self);
+                    print_help(&lines, hidden);
                     continue;
                 }
-                "moves" => {
-                    self.show_legal_moves();
-                    continue;
+                _ => {}
+            }
+
+            let command = if input == "?" {
+                match self.commands.palette(self)? {
+                    Some(command) => Some(command),
+                    None => {
+                        board_at_top = false;
+                        continue;
+                    }
                 }
-                "history" => {
-                    self.show_move_history();
-                    continue;
+            } else {
+                self.commands
+                    .lookup(&typed)
+                    .map(|(handler, args)| (handler, args.to_string()))
+            };
+            if let Some((handler, args)) = command {
+                handler(self, &args).await?;
+                if self.simul_request.is_some() {
+                    return Ok(GameAction::Continue);
                 }
-                "show" | "showboard" | "board" => {
-                    display_board_for_player(&self.game.current_position(), self.player_color);
-                    continue;
+                if self.session_switch.is_some() {
+                    return Ok(GameAction::Quit);
                 }
-                "fen" => {
-                    self.show_fen();
-                    continue;
+                // An imported position can leave the computer to move
+                if self.game.current_position().side_to_move() != self.player_color {
+                    return Ok(GameAction::Continue);
                 }
-                "undo" | "u" => {
-                    if self.undo_move() {
-                        display_board_for_player(&self.game.current_position(), self.player_color);
+                board_at_top = false;
+                continue;
+            }
+
+            // Two-step entry: a lone square selects a piece, the next one
+            // is its destination
+            let board = self.game.current_position();
+            let input = match (selected.take(), Square::from_str(&input)) {
+                (_, Ok(_)) if input.len() != 2 => input,
+                (Some(from), Ok(to))
+                    if (overlay::targets(&board, from) & BitBoard::from_square(to)) != EMPTY =>
+                {
+                    format!("{}{}", from, to)
+                }
+                (_, Ok(square))
+                    if board.color_on(square) == Some(self.player_color)
+                        && overlay::targets(&board, square) != EMPTY =>
+                {
+                    selected = Some(square);
+                    if board_at_top {
+                        self.draw_board_at_top(selected);
+                    } else {
+                        overlay::show_targets(&board, self.player_color, square);
+                    }
+                    if verbosity() > Verbosity::Quiet {
+                        println!("Where to? Enter the destination square, or pick another piece.");
                     }
                     continue;
                 }
-                "redo" | "re" => {
-                    if self.redo_move() {
-                        display_board_for_player(&self.game.current_position(), self.player_color);
+                // Without a piece picked, a typed square a pawn can move to
+                // is the pawn's move in SAN
+                (None, Ok(square))
+                    if !typed.is_empty()
+                        && MoveGen::new_legal(&board).any(|m| {
+                            m.get_dest() == square
+                                && board.piece_on(m.get_source()) == Some(Piece::Pawn)
+                        }) =>
+                {
+                    input
+                }
+                (_, Ok(_)) => {
+                    if board_at_top {
+                        self.draw_board_at_top(None);
                     }
+                    println!("Pick one of your pieces that can move.");
                     continue;
                 }
-                _ => {
-                    match self.parse_and_make_move(&input) {
-                        Ok(_move_made) => {
-                            // Add player move to history (describe_move is called inside parse_and_make_move now)
-                            display_board_for_player(
-                                &self.game.current_position(),
-                                self.player_color,
-                            );
-                            return Ok(GameAction::Continue);
-                        }
-                        Err(e) => {
-                            println!("Invalid move: {}. Try again.", e);
-                            continue;
-                        }
-                    }
+                _ => input,
+            };
+
+            let chess_move = match self.parse_player_move(&input) {
+                Ok(Some(chess_move)) => chess_move,
+                Ok(None) => {
+                    println!("Move cancelled, enter another one.");
+                    continue;
                 }
+                Err(e) => {
+                    println!("Invalid move: {}. Try again.", e);
+                    continue;
+                }
+            };
+            if self.options.confirm_moves && !self.confirm_move(chess_move)? {
+                println!("Move cancelled, enter another one.");
+                continue;
             }
+
+            let before = self.game.current_position();
+            self.make_player_move(chess_move, turn_started.elapsed());
+            self.run_clock_after_move(self.player_color, turn_started.elapsed());
+            self.redraw_board().await?;
+            self.coach_move(before, chess_move).await?;
+            return Ok(GameAction::Continue);
         }
     }
 
-    fn parse_and_make_move(&mut self, input: &str) -> Result<ChessMove> {
-        // Handle different input formats
-        let chess_move = if input.len() == 4 {
-            // Standard algebraic notation like "e2e4"
-            let from_str = &input[0..2];
-            let to_str = &input[2..4];
-
-            let from = Square::from_str(from_str)
-                .map_err(|_| anyhow!("Invalid from square: {}", from_str))?;
-            let to =
-                Square::from_str(to_str).map_err(|_| anyhow!("Invalid to square: {}", to_str))?;
+    /// The player's recapture after the opponent took something, when it is
+    /// the only move back onto that square and a quick full-strength search
+    /// picks it too
+    async fn obvious_recapture(&mut self) -> Result<Option<ChessMove>> {
+        let Some(&(last, _, _)) = self.move_history.last() else {
+            return Ok(None);
+        };
+        let before = self.position_after(self.move_history.len() - 1);
+        let square = last.get_dest();
+        if before.side_to_move() == self.player_color || before.piece_on(square).is_none() {
+            return Ok(None);
+        }
+        let board = self.game.current_position();
+        let recaptures: Vec<ChessMove> = MoveGen::new_legal(&board)
+            .filter(|m| m.get_dest() == square && self.house_rules.allows(&board, *m))
+            .collect();
+        let [recapture] = recaptures[..] else {
+            return Ok(None);
+        };
+        let result = self
+            .engine
+            .analyze(&board, &format!("go depth {}", RECAPTURE_DEPTH))
+            .await?;
+        Ok((result.best_move == Some(recapture)).then_some(recapture))
+    }
 
-            ChessMove::new(from, to, None)
-        } else if input.len() == 5 {
-            // Promotion moves like "e7e8q"
-            let from_str = &input[0..2];
-            let to_str = &input[2..4];
-            let promotion_str = &input[4..5];
+    /// Have the coach comment on the player's move when the engine finds it
+    /// a mistake. Rated and ladder games get no coaching.
+    async fn coach_move(&mut self, before: Board, chess_move: ChessMove) -> Result<()> {
+        if self.coach.is_none() || self.competitive {
+            return Ok(());
+        }
+        let after = before.make_move_new(chess_move);
+        if after.status() != chess::BoardStatus::Ongoing {
+            return Ok(());
+        }
+        let go = format!("go depth {}", COACH_DEPTH);
+        let best = self.engine.analyze(&before, &go).await?;
+        let reply = self.engine.analyze(&after, &go).await?;
+        let (Some(score_before), Some(score_after), Some(best_move)) =
+            (best.score, reply.score, best.best_move)
+        else {
+            return Ok(());
+        };
+        // The reply is scored for the opponent
+        let loss = score_before.as_centipawns() + score_after.as_centipawns();
+        if best_move == chess_move || !annotate::is_mistake(loss) {
+            return Ok(());
+        }
 
-            let from = Square::from_str(from_str)
-                .map_err(|_| anyhow!("Invalid from square: {}", from_str))?;
-            let to =
-                Square::from_str(to_str).map_err(|_| anyhow!("Invalid to square: {}", to_str))?;
+        let mut key_move = KeyMove {
+            fen: before.to_string(),
+            played: pgn::move_to_san(&before, chess_move),
+            best: pgn::move_to_san(&before, best_move),
+            eval_before: score_before.to_string(),
+            // Flipped from the opponent's side to the player's
+            eval_after: score_after.for_white(Color::Black).to_string(),
+            loss,
+            best_line: pgn::line_to_san(&before, &best.pv),
+            refutation: pgn::line_to_san(&after, &reply.pv),
+            prompt: String::new(),
+        };
+        key_move.prompt = key_move.prompt_text();
+        let Some(mut coach) = self.coach.take() else {
+            return Ok(());
+        };
+        let comment = coach.comment(&key_move).await;
+        self.coach = Some(coach);
+        println!("Coach: {}", comment);
+        self.transcript
+            .record(Entry::Engine(format!("Coach: {}", comment)));
+        Ok(())
+    }
 
-            let promotion = match promotion_str {
-                "q" => Some(chess::Piece::Queen),
-                "r" => Some(chess::Piece::Rook),
-                "b" => Some(chess::Piece::Bishop),
-                "n" => Some(chess::Piece::Knight),
-                _ => return Err(anyhow!("Invalid promotion piece: {}", promotion_str)),
-            };
+    /// Show the move on the board in SAN and ask before playing it
+    fn confirm_move(&self, chess_move: ChessMove) -> Result<bool> {
+        let board = self.game.current_position();
+        overlay::show_move(&board, self.player_color, chess_move);
+        println!("Play {}? (y/n)", pgn::move_to_san(&board, chess_move));
+        let answer = get_user_input()?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
 
-            ChessMove::new(from, to, promotion)
+    /// Turn the player's input into a move. Input that fits several legal
+    /// moves (a promotion without its piece, or a piece and destination that
+    /// two pieces can reach) opens a menu at an interactive prompt; None
+    /// means the player cancelled it.
+    fn parse_player_move(&self, input: &str) -> Result<Option<ChessMove>> {
+        let board = self.game.current_position();
+        let typed = input.trim();
+        let input = normalize_move_input(input);
+        // A capital piece letter is SAN, which may name the file or rank
+        // the piece comes from (Nbd2, R1e2, Qh4xe1); coordinates such as
+        // B1C3 still read as coordinates
+        let candidates = if typed.starts_with(['K', 'Q', 'R', 'B', 'N'])
+            && ChessMove::from_str(&input).is_err()
+        {
+            pgn::san_candidates(&board, typed)
         } else {
-            return Err(anyhow!(
-                "Invalid move format. Use format like 'e2e4' or 'e7e8q' for promotions"
-            ));
+            self.candidate_moves(&input)
         };
+        let mut candidates = match candidates {
+            Ok(candidates) => candidates,
+            Err(e) => return self.offer_correction(&input, e),
+        };
+        let forbidden = candidates
+            .iter()
+            .find_map(|&m| self.house_rules.forbids(&board, m));
+        candidates.retain(|&m| self.house_rules.allows(&board, m));
+        match candidates.as_slice() {
+            [] if forbidden.is_some() => Err(anyhow!("{}", forbidden.unwrap_or_default())),
+            [] => self.offer_correction(&input, anyhow!("Move is not legal in current position")),
+            [chess_move] => Ok(Some(*chess_move)),
+            _ if !std::io::stdin().is_terminal() => Err(pgn::ambiguity(&board, typed, &candidates)),
+            _ => {
+                let promotion = candidates[0].get_promotion().is_some();
+                let labels: Vec<String> = candidates
+                    .iter()
+                    .map(|&m| match m.get_promotion() {
+                        Some(piece) if promotion => format!("{:?}", piece),
+                        _ => pgn::move_to_san(&board, m),
+                    })
+                    .collect();
+                let question = if promotion {
+                    "Promote to:"
+                } else {
+                    "Which move?"
+                };
+                Ok(select(question, &labels)?.map(|i| candidates[i]))
+            }
+        }
+    }
 
-        // Verify the move is legal
-        let legal_moves: Vec<ChessMove> =
-            MoveGen::new_legal(&self.game.current_position()).collect();
+    /// Legal moves matching coordinate input (e2e4, e7e8q, or e7e8 for every
+    /// promotion), a pawn move (e4, e8q), a piece letter and destination
+    /// (nf3, bxc4), a pawn capture (exd5) or castling (o-o, 0-0-0). Expects input cleaned up by
+    /// `normalize_move_input`.
+    fn candidate_moves(&self, input: &str) -> Result<Vec<ChessMove>> {
+        let board = self.game.current_position();
+        let legal_moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+        let square =
+            |text: &str| Square::from_str(text).map_err(|_| anyhow!("Invalid square: {}", text));
+        let bytes = input.as_bytes();
+
+        let castling = match input {
+            "oo" | "00" => Some(chess::File::G),
+            "ooo" | "000" => Some(chess::File::C),
+            _ => None,
+        };
+        if let Some(file) = castling {
+            return Ok(legal_moves
+                .into_iter()
+                .filter(|m| {
+                    board.piece_on(m.get_source()) == Some(Piece::King)
+                        && m.get_source().get_file() == chess::File::E
+                        && m.get_dest().get_file() == file
+                })
+                .collect());
+        }
 
-        if !legal_moves.contains(&chess_move) {
-            return Err(anyhow!("Move is not legal in current position"));
+        if (2..=3).contains(&input.len())
+            && (b'a'..=b'h').contains(&bytes[0])
+            && bytes[1].is_ascii_digit()
+        {
+            // Pawn move like "e4" or "e8q"; "b8q" is the b-pawn, not a bishop
+            let to = square(&input[..2])?;
+            let promotion = parse_promotion(&input[2..])?;
+            return Ok(legal_moves
+                .into_iter()
+                .filter(|m| {
+                    board.piece_on(m.get_source()) == Some(Piece::Pawn)
+                        && m.get_source().get_file() == to.get_file()
+                        && m.get_dest() == to
+                        && (promotion.is_none() || m.get_promotion() == promotion)
+                })
+                .collect());
         }
 
-        // Describe the move BEFORE making it (when we can still see the piece)
-        let move_description = self.describe_move(&chess_move, &self.game.current_position());
+        let piece = match input.chars().next() {
+            Some('k') => Some(Piece::King),
+            Some('q') => Some(Piece::Queen),
+            Some('r') => Some(Piece::Rook),
+            Some('b') => Some(Piece::Bishop),
+            Some('n') => Some(Piece::Knight),
+            _ => None,
+        };
+        let pawn_capture =
+            input.len() >= 4 && bytes[1] == b'x' && (b'a'..=b'h').contains(&bytes[0]);
+        if let Some(piece) = piece
+            && (input.len() == 3 || (input.len() == 4 && &input[1..2] == "x"))
+        {
+            // Piece and destination like "nf3" or "bxc4"
+            let to = square(&input[input.len() - 2..])?;
+            let moves: Vec<ChessMove> = legal_moves
+                .iter()
+                .copied()
+                .filter(|m| m.get_dest() == to && board.piece_on(m.get_source()) == Some(piece))
+                .collect();
+            // "bxc4" is a b-pawn capture when no bishop can take
+            if !moves.is_empty() || !pawn_capture {
+                return Ok(moves);
+            }
+        }
 
-        // Make the move
-        self.game.make_move(chess_move);
+        if pawn_capture {
+            // Pawn file and destination like "exd5" or "exd8q"
+            let file = (bytes[0] - b'a') as usize;
+            let to = square(&input[2..4])?;
+            let promotion = parse_promotion(&input[4..])?;
+            return Ok(legal_moves
+                .into_iter()
+                .filter(|m| {
+                    board.piece_on(m.get_source()) == Some(Piece::Pawn)
+                        && m.get_source().get_file().to_index() == file
+                        && m.get_dest() == to
+                        && (promotion.is_none() || m.get_promotion() == promotion)
+                })
+                .collect());
+        }
+
+        if input.len() != 4 && input.len() != 5 {
+            return Err(anyhow!(
+                "Invalid move format. Use format like 'e2e4', 'e4', 'Nf3' or 'e7e8q' for promotions"
+            ));
+        }
+        let from = square(&input[0..2])?;
+        let to = square(&input[2..4])?;
+        let promotion = parse_promotion(&input[4..])?;
+        Ok(legal_moves
+            .into_iter()
+            .filter(|m| m.get_source() == from && m.get_dest() == to)
+            // Without a piece, a promotion matches all four choices
+            .filter(|m| promotion.is_none() || m.get_promotion() == promotion)
+            .collect())
+    }
 
-        // Save game state for undo/redo
-        self.save_game_state();
+    /// When coordinate input is one slip away from exactly one legal move (a
+    /// wrong, missing, extra or swapped character), offer that move instead
+    /// of the error. None means the player turned the suggestion down.
+    fn offer_correction(&self, input: &str, error: anyhow::Error) -> Result<Option<ChessMove>> {
+        if !(3..=6).contains(&input.len()) {
+            return Err(error);
+        }
+        let board = self.game.current_position();
+        let close: Vec<ChessMove> = self
+            .house_rules
+            .legal_moves(&board)
+            .into_iter()
+            .filter(|m| typo_distance(input, &m.to_string()) <= 1)
+            .collect();
+        let [suggestion] = close.as_slice() else {
+            return Err(error);
+        };
+        let san = pgn::move_to_san(&board, *suggestion);
+        if !std::io::stdin().is_terminal() {
+            return Err(anyhow!("{}; did you mean {} ({})?", error, suggestion, san));
+        }
+        println!("Did you mean {} ({})? (y/n)", suggestion, san);
+        let answer = get_user_input()?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes").then_some(*suggestion))
+    }
+
+    fn make_player_move(&mut self, chess_move: ChessMove, time: Duration) {
+        // Describe the move BEFORE making it (when we can still see the piece)
+        let move_description = self.describe_move(&chess_move, &self.game.current_position());
+        self.kibitz(chess_move);
 
-        // Add to history
         let player_color_str = if self.player_color == Color::White {
             "White"
         } else {
@@ -206,24 +1150,77 @@ impl ChessGame {
             player_color_str.to_string(),
             detailed_description,
         );
-        self.move_history.push(move_entry.clone());
-        self.full_move_history.push(move_entry);
 
-        Ok(chess_move)
+        let before = self.game.current_position();
+        self.record_move(move_entry, time);
+        self.note_book_position(true);
+        self.run_move_hook(&before, chess_move, true);
     }
 
     async fn make_computer_move(&mut self) -> Result<()> {
-        println!("\nComputer is thinking...");
+        if verbosity() > Verbosity::Quiet {
+            println!("\nComputer is thinking...");
+        }
+        let started = Instant::now();
+        if let Some([white, black]) = self.clock {
+            self.engine.set_clock(white, black);
+            if let Some(consultant) = &mut self.consultant {
+                consultant.set_clock(white, black);
+            }
+        }
 
-        let best_move = self
-            .engine
-            .get_best_move(&self.game.current_position())
-            .await?;
+        // A garbled or illegal answer gets one retry; after that, or if the
+        // engine has no move or died, the computer forfeits the game
+        let mut retried = false;
+        let best_move = loop {
+            let error = match self.request_computer_move().await {
+                Ok(chess_move) => break chess_move,
+                Err(e) => e,
+            };
+            match error.downcast_ref::<EngineError>() {
+                Some(EngineError::GameOver) => return Ok(()),
+                Some(EngineError::IllegalMove(_) | EngineError::Garbage(_)) if !retried => {
+                    println!("Engine error ({}), asking again...", error);
+                    retried = true;
+                }
+                Some(engine_error) => {
+                    println!(
+                        "\nThe computer cannot continue ({}) and forfeits the game.",
+                        engine_error
+                    );
+                    self.game.resign(!self.player_color);
+                    return Ok(());
+                }
+                None => return Err(error),
+            }
+        };
+
+        if self.should_resign() {
+            println!("\nThe computer resigns.");
+            self.log_event("Computer resigned a lost position".to_string());
+            self.game.resign(!self.player_color);
+            return Ok(());
+        }
+
+        // The wait is for show, so only the search counts on the clock
+        let thinking = started.elapsed();
+        if thinking < self.options.min_move_delay {
+            tokio::time::sleep(self.options.min_move_delay - thinking).await;
+        }
+
+        if self.run_clock(!self.player_color, thinking) {
+            return Ok(());
+        }
 
         // Describe the move before making it
         let move_description = self.describe_move(&best_move, &self.game.current_position());
 
-        println!("Computer plays: {} ({})", best_move, move_description);
+        let shown = match notation() {
+            Notation::San => pgn::move_to_san(&self.game.current_position(), best_move),
+            Notation::Coordinate => best_move.to_string(),
+        };
+        println!("Computer plays: {} ({})", shown, move_description);
+        self.kibitz(best_move);
 
         // Add computer move to history
         let computer_color_str = if self.player_color == Color::White {
@@ -238,31 +1235,637 @@ impl ChessGame {
             computer_color_str.to_string(),
             detailed_description,
         );
-        self.move_history.push(move_entry.clone());
-        self.full_move_history.push(move_entry);
+        self.run_clock_after_move(!self.player_color, thinking);
+
+        let before = self.game.current_position();
+        self.record_move(move_entry, thinking);
+        self.note_book_position(true);
+        self.run_move_hook(&before, best_move, false);
+
+        self.redraw_board().await?;
+
+        Ok(())
+    }
+
+    /// Refresh the `--status-file` summary and the terminal title with a
+    /// quick evaluation
+    async fn update_status_file(&mut self) -> Result<()> {
+        if !status::enabled() && !terminal::show_title() {
+            return Ok(());
+        }
+        let board = self.game.current_position();
+        let eval = self.quick_eval().await?;
+        status::update(&board, self.move_number(), eval, None);
+
+        // e.g. "minichess — move 24, +1.30, your turn"
+        let mut fields = vec![format!("move {}", self.move_number())];
+        fields.extend(eval.map(|eval| eval.to_string()));
+        fields.push(if board.status() != chess::BoardStatus::Ongoing {
+            "game over".to_string()
+        } else if board.side_to_move() == self.player_color {
+            "your turn".to_string()
+        } else {
+            "computer's turn".to_string()
+        });
+        terminal::set_title(&format!("minichess — {}", fields.join(", ")));
+        Ok(())
+    }
+
+    /// Hand the game's state to the `--control-socket` listener, if any
+    fn note_control_state(&self) {
+        if !control::enabled() {
+            return;
+        }
+        let board = self.game.current_position();
+        let side = |color: Color| color_name(color).to_lowercase();
+        let turn = if board.status() != chess::BoardStatus::Ongoing
+            || self.outcome() != GameOutcome::Unfinished
+        {
+            format!("game over {}", self.outcome().pgn_result())
+        } else {
+            format!("{} to move", side(board.side_to_move()))
+        };
+        control::note_game(control::GameState {
+            fen: board.to_string(),
+            board: board_text(&board, self.player_color),
+            moves: self.san_moves(),
+            status: format!(
+                "{}, you are {}, move {}",
+                turn,
+                side(self.player_color),
+                self.move_number()
+            ),
+            pgn: self.to_pgn("Casual game", None),
+            player: self.player_color,
+            over: board.status() != chess::BoardStatus::Ongoing
+                || self.outcome() != GameOutcome::Unfinished,
+        });
+    }
+
+    /// Number of the move about to be played, counting from the start position
+    fn move_number(&self) -> usize {
+        let starts_with_black = self.start.side_to_move() == Color::Black;
+        (self.move_history.len() + starts_with_black as usize) / 2 + 1
+    }
+
+    /// Clear the screen and draw the board where clicks can find it, with
+    /// the legal moves of the selected piece marked
+    fn draw_board_at_top(&self, selected: Option<Square>) {
+        mouse::clear_screen();
+        let board = self.game.current_position();
+        match selected {
+            Some(square) => overlay::show_targets(&board, self.player_color, square),
+            None => self.show_board(),
+        }
+        if let Some(last) = self.san_moves().last() {
+            println!("Last move: {}", last);
+        }
+    }
+
+    /// Quick evaluation of the current position from White's point of view,
+    /// shared by the eval bar, status file and terminal title. None once the
+    /// game is over.
+    async fn quick_eval(&mut self) -> Result<Option<Score>> {
+        let board = self.game.current_position();
+        if board.status() != chess::BoardStatus::Ongoing {
+            return Ok(None);
+        }
+        if let Some((evaluated, score)) = self.eval
+            && evaluated == board
+        {
+            return Ok(Some(score));
+        }
+        let score = self
+            .engine
+            .analyze(&board, &format!("go depth {}", STATUS_DEPTH))
+            .await?
+            .score
+            .map(|score| score.for_white(board.side_to_move()));
+        if let Some(score) = score {
+            self.eval = Some((board, score));
+        }
+        Ok(score)
+    }
+
+    /// Redraw the board after a move, with a fresh evaluation bar if it is
+    /// on; skipped in quiet mode, where the `show` command still draws it
+    async fn redraw_board(&mut self) -> Result<()> {
+        // A mate is drawn once, inside the result banner
+        let board = self.game.current_position();
+        if verbosity() > Verbosity::Quiet && board.status() != chess::BoardStatus::Checkmate {
+            if self.eval_bar {
+                self.quick_eval().await?;
+            }
+            self.show_board();
+            self.record_board();
+            if self.move_diff {
+                self.show_move_diff();
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the current board in the transcript
+    fn record_board(&mut self) {
+        let board = board_text(&self.game.current_position(), self.player_color);
+        self.transcript.record(Entry::Board(board));
+    }
+
+    /// What the last move changed: material, check, new threats and defenders
+    fn show_move_diff(&self) {
+        let plies = self.move_history.len();
+        if plies == 0 {
+            return;
+        }
+        let before = self.position_after(plies - 1);
+        for line in diff::describe(&before, &self.game.current_position()) {
+            println!("  {}", line);
+        }
+    }
+
+    /// Draw the current position, with the evaluation bar when it is on and
+    /// the position has been evaluated
+    fn show_board(&self) {
+        let board = self.game.current_position();
+        let eval = match self.eval {
+            Some((evaluated, score)) if self.eval_bar && evaluated == board => Some(score),
+            _ => None,
+        };
+        display_board_with_eval(&board, self.player_color, eval);
+    }
+
+    /// Tell user scripts about a move that has just been played
+    fn run_move_hook(&mut self, before: &Board, chess_move: ChessMove, by_player: bool) {
+        sound::play(Sound::for_move(before, chess_move));
+        let san = pgn::move_to_san(before, chess_move);
+        let starts_with_black = self.start.side_to_move() == Color::Black;
+        let number = (self.move_history.len() - 1 + starts_with_black as usize) / 2 + 1;
+        self.transcript.record(Entry::Move {
+            number: match before.side_to_move() {
+                Color::White => format!("{}.", number),
+                Color::Black => format!("{}...", number),
+            },
+            san: san.clone(),
+            by: if by_player { "You" } else { "Computer" },
+        });
+        if let Some(scripts) = &mut self.scripts {
+            let fen = self.game.current_position().to_string();
+            scripts.on_move(&san, &fen, by_player);
+        }
+    }
+
+    fn run_game_end_hook(&mut self) {
+        let result = self.outcome().pgn_result();
+        let moves = self.san_moves().join(" ");
+        if let Some(scripts) = &mut self.scripts {
+            scripts.on_game_end(result, &moves);
+        }
+    }
+
+    /// Hand a move that is about to be played to the kibitzer, if any
+    fn kibitz(&self, chess_move: ChessMove) {
+        if let Some(kibitzer) = &self.kibitzer {
+            kibitzer.observe(self.game.current_position(), chess_move, self.move_number());
+        }
+    }
+
+    /// Whether the computer's score for its move has now been below the
+    /// resign threshold for enough moves in a row
+    fn should_resign(&mut self) -> bool {
+        let score = self.engine.take_last_score();
+        let Some(threshold) = self.options.resign_threshold else {
+            return false;
+        };
+        if score.is_some_and(|score| score.as_centipawns() <= -threshold.centipawns) {
+            self.losing_moves += 1;
+        } else {
+            self.losing_moves = 0;
+        }
+        self.losing_moves >= threshold.moves
+    }
+
+    async fn request_computer_move(&mut self) -> Result<ChessMove> {
+        let position = self.game.current_position();
+        let chess_move = self.choose_computer_move().await?;
+        if self.house_rules.allows(&position, chess_move) {
+            return Ok(chess_move);
+        }
+        // Book, consultation and varied moves come from elsewhere; ask the
+        // engine again with only the moves the house rules allow
+        self.engine
+            .get_best_move_among(&position, &self.house_rules.legal_moves(&position))
+            .await
+    }
+
+    async fn choose_computer_move(&mut self) -> Result<ChessMove> {
+        let position = self.game.current_position();
+        let variety = self
+            .options
+            .opening_variety
+            .filter(|variety| self.move_number() <= variety.moves);
+        let book_move = self.book.as_ref().and_then(|book| match variety {
+            // Any of the most played moves, not just the favourite
+            Some(variety) => book
+                .moves(&position)
+                .iter()
+                .take(variety.choices)
+                .map(|(chess_move, _)| *chess_move)
+                .choose(&mut rand::rng()),
+            None => book.pick(&position),
+        });
+        if let Some(book_move) = book_move {
+            println!("(book move)");
+            return Ok(book_move);
+        }
+        if let Some(variety) = variety
+            && self.consultant.is_none()
+        {
+            let candidates = self
+                .engine
+                .candidate_moves(&position, variety.choices)
+                .await?;
+            let best = candidates[0].1.as_centipawns();
+            return Ok(candidates
+                .iter()
+                .filter(|(_, score)| best - score.as_centipawns() <= VARIETY_MARGIN)
+                .map(|(chess_move, _)| *chess_move)
+                .choose(&mut rand::rng())
+                .unwrap_or(candidates[0].0));
+        }
+        match &mut self.consultant {
+            Some(consultant) => consult::choose_move(&mut self.engine, consultant, &position).await,
+            None => self.engine.get_best_move(&position).await,
+        }
+    }
+
+    /// Show what the opponent would play if it were their move again
+    async fn show_threat(&mut self) -> Result<()> {
+        let position = self.game.current_position();
+        // Passing is impossible while in check: the threat is already on the board
+        let Some(flipped) = position.null_move() else {
+            println!("\nYou are in check - deal with that first!");
+            return Ok(());
+        };
 
-        self.game.make_move(best_move);
+        println!("\nLooking for threats...");
+        let result = self
+            .engine
+            .analyze(&flipped, &format!("go depth {}", THREAT_DEPTH))
+            .await?;
 
-        // Save game state for undo/redo
-        self.save_game_state();
+        match (result.best_move, result.score) {
+            (Some(threat), Some(score)) => {
+                overlay::show_move(&position, self.player_color, threat);
+                let mut text = format!(
+                    "Threat: {} ({} for your opponent)",
+                    pgn::move_to_san(&flipped, threat),
+                    score
+                );
+                if result.pv.len() > 1 {
+                    text.push_str(&format!(
+                        "\nLine: {}",
+                        pgn::line_to_san(&flipped, &result.pv)
+                    ));
+                }
+                println!("{}", text);
+                self.transcript.record(Entry::Engine(text));
+                self.last_line = Some(EngineLine {
+                    position,
+                    start: flipped,
+                    moves: result.pv,
+                });
+            }
+            _ => println!("Your opponent has no moves to threaten with."),
+        }
+        Ok(())
+    }
 
-        display_board_for_player(&self.game.current_position(), self.player_color);
+    /// Book moves for the current position, found by position so any move
+    /// order that reaches it counts
+    fn show_book_moves(&self) {
+        let Some(book) = &self.book else {
+            println!("\nNo opening book loaded (start with --book FILE).");
+            return;
+        };
+        let board = self.game.current_position();
+        let moves = book.moves(&board);
+        if moves.is_empty() {
+            println!("\nThis position is not in the book.");
+            return;
+        }
 
+        let total: u32 = moves.iter().map(|(_, w)| *w as u32).sum();
+        println!("\nBook moves:");
+        for (chess_move, weight) in moves {
+            println!(
+                "  {:<8} {:>5.1}%",
+                pgn::move_to_san(&board, chess_move),
+                weight as f64 * 100.0 / total as f64
+            );
+        }
+    }
+
+    /// Tell the player when a game that had left the book comes back into
+    /// it through a different move order (`announce` is off for undo/redo)
+    fn note_book_position(&mut self, announce: bool) {
+        let Some(book) = &self.book else {
+            return;
+        };
+        let in_book = book.contains(&self.game.current_position());
+        if announce && in_book && !self.in_book {
+            println!("Back in book by transposition.");
+        }
+        self.in_book = in_book;
+    }
+
+    /// Start or stop a second engine analyzing while it's the player's turn
+    async fn set_auto_analysis(&mut self, on: bool) -> Result<()> {
+        if !on {
+            if self.analyzer.take().is_some() {
+                println!("Auto-analysis off.");
+            }
+            return Ok(());
+        }
+        if self.analyzer.is_some() {
+            println!("Auto-analysis is already on.");
+        } else if !std::io::stdout().is_terminal() {
+            println!("Auto-analysis needs an interactive terminal.");
+        } else {
+            println!("Starting analysis engine...");
+            let path = self.analysis_path.as_deref().unwrap_or(self.engine.path());
+            self.analyzer = Some(AutoAnalyzer::spawn(path).await?);
+            println!("Auto-analysis on. The readout updates above the prompt.");
+        }
         Ok(())
     }
 
+    /// Suggest the engine's best move for the player, drawn on the board
+    async fn show_hint(&mut self) -> Result<()> {
+        let position = self.game.current_position();
+        println!("\nLooking for a good move...");
+        let result = self
+            .engine
+            .analyze(&position, &format!("go depth {}", HINT_DEPTH))
+            .await?;
+
+        match (result.best_move, result.score) {
+            (Some(best), Some(score)) => {
+                overlay::show_move(&position, self.player_color, best);
+                let mut text = format!("Hint: {} ({})", pgn::move_to_san(&position, best), score);
+                if result.pv.len() > 1 {
+                    text.push_str(&format!(
+                        "\nLine: {}",
+                        pgn::line_to_san(&position, &result.pv)
+                    ));
+                }
+                println!("{}", text);
+                self.transcript.record(Entry::Engine(text));
+                self.last_line = Some(EngineLine {
+                    position,
+                    start: position,
+                    moves: result.pv,
+                });
+            }
+            _ => println!("No moves available."),
+        }
+        Ok(())
+    }
+
+    /// Deeper search than `hint`: evaluation, best move and the full line,
+    /// which `preview` can then step through
+    async fn analyze_position(&mut self, depth: u8) -> Result<()> {
+        let position = self.game.current_position();
+        println!("\nAnalyzing to depth {}...", depth);
+        let result = self
+            .engine
+            .analyze(&position, &format!("go depth {}", depth))
+            .await?;
+
+        match (result.best_move, result.score) {
+            (Some(best), Some(score)) => {
+                let text = format!(
+                    "Evaluation: {} at depth {}, best move {}\nLine: {}",
+                    score,
+                    result.depth,
+                    pgn::move_to_san(&position, best),
+                    pgn::line_to_san(&position, &result.pv)
+                );
+                println!("{}", text);
+                println!("Type 'preview' to step through the line on the board.");
+                self.transcript.record(Entry::Engine(text));
+                // The deeper score also replaces the eval bar's quick one
+                self.eval = Some((position, score.for_white(position.side_to_move())));
+                self.last_line = Some(EngineLine {
+                    position,
+                    start: position,
+                    moves: result.pv,
+                });
+            }
+            _ => println!("No moves available."),
+        }
+        Ok(())
+    }
+
+    /// Beginner guidance: the phase of the game, generic advice for it that
+    /// fits the position, and the plan behind the engine's best line
+    async fn show_plan(&mut self) -> Result<()> {
+        let position = self.game.current_position();
+        let phase = plan::phase(&position, self.move_number());
+        println!("\n=== {} ===", phase.name());
+        for tip in plan::advice(&position, self.player_color, phase) {
+            println!("  • {}", tip);
+        }
+
+        if position.side_to_move() != self.player_color
+            || position.status() != chess::BoardStatus::Ongoing
+        {
+            return Ok(());
+        }
+        let result = self
+            .engine
+            .analyze(&position, &format!("go depth {}", HINT_DEPTH))
+            .await?;
+        if let (Some(score), false) = (result.score, result.pv.is_empty()) {
+            let line = &result.pv[..result.pv.len().min(PLAN_LINE_MOVES)];
+            println!(
+                "Engine's idea ({}): {}",
+                score,
+                pgn::line_to_san(&position, line)
+            );
+            println!("Type 'preview' to play it through on the board.");
+            self.last_line = Some(EngineLine {
+                position,
+                start: position,
+                moves: result.pv,
+            });
+        }
+        Ok(())
+    }
+
+    /// Step through the last engine line on a scratch board, then return to
+    /// the game. The game itself is never touched.
+    fn preview_line(&self) -> Result<()> {
+        let Some(line) = &self.last_line else {
+            println!("No line to preview yet. Run 'analyze' (or 'hint') first.");
+            return Ok(());
+        };
+        if line.position != self.game.current_position() {
+            println!("The last line was for another position. Run 'analyze' again.");
+            return Ok(());
+        }
+
+        let mut boards = vec![line.start];
+        for &chess_move in &line.moves {
+            let board = *boards.last().unwrap();
+            if !board.legal(chess_move) {
+                break;
+            }
+            boards.push(board.make_move_new(chess_move));
+        }
+        let length = boards.len() - 1;
+
+        let mut step = 0;
+        loop {
+            if step == 0 {
+                display_board_for_player(&boards[0], self.player_color);
+            } else {
+                overlay::show_move(&boards[step], self.player_color, line.moves[step - 1]);
+            }
+            println!(
+                "Preview {}/{}: {}",
+                step,
+                length,
+                pgn::line_to_san(&line.start, &line.moves[..step])
+            );
+            println!("[n]ext (Enter), [p]rev, [s]tart, [e]nd, [q] back to the game");
+            match get_user_input()?.trim().to_lowercase().as_str() {
+                "" | "n" | "next" => step = (step + 1).min(length),
+                "p" | "prev" => step = step.saturating_sub(1),
+                "s" | "start" => step = 0,
+                "e" | "end" => step = length,
+                "q" | "quit" | "back" => break,
+                _ => println!("Unknown preview command."),
+            }
+        }
+
+        println!("Back to the game.");
+        self.show_board();
+        Ok(())
+    }
+
+    /// List legal moves in SAN, one line per piece that can move
     fn show_legal_moves(&self) {
-        let legal_moves: Vec<ChessMove> =
-            MoveGen::new_legal(&self.game.current_position()).collect();
+        let board = self.game.current_position();
+        let mut legal_moves = self.house_rules.legal_moves(&board);
+        legal_moves.sort_by_key(|m| (piece_order(board.piece_on(m.get_source())), m.get_source()));
+
+        println!("\nLegal moves:");
+        let mut moves = legal_moves.iter().peekable();
+        while let Some(first) = moves.next() {
+            let source = first.get_source();
+            let mut group = vec![pgn::move_to_san(&board, *first)];
+            while let Some(next) = moves.next_if(|m| m.get_source() == source) {
+                group.push(pgn::move_to_san(&board, *next));
+            }
+            println!(
+                "  {} {}: {}",
+                get_piece_char(&board, source),
+                source,
+                group.join(", ")
+            );
+        }
+    }
+
+    /// List legal moves sorted by a quick engine evaluation, best first
+    async fn show_ranked_moves(&mut self) -> Result<()> {
+        let board = self.game.current_position();
+        println!("\nRanking moves...");
+
+        let mut ranked = Vec::new();
+        for chess_move in self.house_rules.legal_moves(&board) {
+            let after = board.make_move_new(chess_move);
+            let result = self
+                .engine
+                .analyze(&after, &format!("go depth {}", RANKING_DEPTH))
+                .await?;
+            // Scores come back for the opponent; terminal positions have none
+            let score = match (result.score, after.status()) {
+                (Some(score), _) => -score.as_centipawns(),
+                (None, chess::BoardStatus::Checkmate) => MATE_SCORE,
+                (None, _) => 0,
+            };
+            ranked.push((score, pgn::move_to_san(&board, chess_move)));
+        }
+        ranked.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        println!("Moves ranked by engine (depth {}):", RANKING_DEPTH);
+        for (i, (score, san)) in ranked.iter().enumerate() {
+            println!("{:>3}. {:<8} {}", i + 1, san, format_centipawns(*score));
+        }
+        Ok(())
+    }
+
+    pub fn player_color(&self) -> Color {
+        self.player_color
+    }
+
+    /// Position after the first `plies` moves of the game
+    pub fn position_after(&self, plies: usize) -> Board {
+        self.move_history
+            .iter()
+            .take(plies)
+            .fold(self.start, |board, (m, _, _)| board.make_move_new(*m))
+    }
+
+    /// When the game began and, once decided, when it ended
+    pub fn times(&self) -> (DateTime<Local>, Option<DateTime<Local>>) {
+        (self.started, self.ended)
+    }
+
+    /// Name and version the engine reported, e.g. "Stockfish 17"
+    pub fn engine_name(&self) -> &str {
+        self.engine.name()
+    }
+
+    /// How the computer was set up: its limits, plus any book or consultant
+    pub fn engine_settings(&self) -> String {
+        let mut settings = self.engine.settings_summary();
+        if self.book.is_some() {
+            settings.push_str(", opening book");
+        }
+        if let Some(consultant) = &self.consultant {
+            settings.push_str(&format!(", consulting {}", consultant.name()));
+        }
+        settings
+    }
+
+    /// Takebacks the player used this game
+    pub fn takebacks_used(&self) -> u32 {
+        self.takebacks_used
+    }
+
+    /// Takebacks still allowed this game
+    fn takebacks_left(&self) -> u32 {
+        self.options.takebacks.saturating_sub(self.takebacks_used)
+    }
+
+    /// Moves played so far in coordinate notation, e.g. "e2e4"
+    pub fn coordinate_moves(&self) -> Vec<String> {
+        self.move_history
+            .iter()
+            .map(|(chess_move, _, _)| chess_move.to_string())
+            .collect()
+    }
+
+    /// Time spent on each move played so far
+    pub fn move_times(&self) -> &[Duration] {
+        &self.full_move_times[..self.move_history.len()]
+    }
 
-        println!("\nLegal moves:");
-        for (i, mv) in legal_moves.iter().enumerate() {
-            print!("{} ", mv);
-            if (i + 1) % 8 == 0 {
-                println!();
-            }
-        }
-        println!();
+    /// Moves played so far, in Standard Algebraic Notation
+    pub fn san_moves(&self) -> Vec<String> {
+        let moves: Vec<ChessMove> = self.move_history.iter().map(|(m, _, _)| *m).collect();
+        pgn::moves_to_san(&self.start, &moves)
     }
 
     fn show_move_history(&self) {
@@ -272,17 +1875,15 @@ impl ChessGame {
         }
 
         println!("\n=== Move History (Algebraic Notation) ===");
-        for (i, (chess_move, _player, _detailed)) in self.move_history.iter().enumerate() {
+        for (i, algebraic) in self.san_moves().iter().enumerate() {
             let move_number = (i / 2) + 1;
 
             if i % 2 == 0 {
                 // White's move (or first player's move)
-                let algebraic = self.to_algebraic_notation(chess_move, i);
-                print!("{}. {} ", move_number, algebraic);
+                print!("{}. {}{} ", move_number, algebraic, self.move_time_label(i));
             } else {
                 // Black's move (or second player's move)
-                let algebraic = self.to_algebraic_notation(chess_move, i);
-                println!("{}", algebraic);
+                println!("{}{}", algebraic, self.move_time_label(i));
             }
         }
 
@@ -295,11 +1896,55 @@ impl ChessGame {
         for (i, (_chess_move, _player, detailed_description)) in
             self.move_history.iter().enumerate()
         {
-            println!("{}. {}", i + 1, detailed_description);
+            println!(
+                "{}. {}{}",
+                i + 1,
+                detailed_description,
+                self.move_time_label(i)
+            );
         }
         println!("==========================================\n");
     }
 
+    /// The moves so far in one of the move list styles; the table analyzes
+    /// every position for its evaluations
+    async fn show_move_list(&mut self, style: movelist::Style) -> Result<()> {
+        if self.move_history.is_empty() {
+            println!("\nNo moves played yet.");
+            return Ok(());
+        }
+        let positions: Vec<Board> = (1..=self.move_history.len())
+            .map(|plies| self.position_after(plies))
+            .collect();
+        let evals = if style == movelist::Style::Table {
+            Some(self.analyze_game(&positions, BLUNDER_DEPTH).await?)
+        } else {
+            None
+        };
+        let moves: Vec<MoveEntry> = self
+            .move_history
+            .iter()
+            .zip(self.san_moves())
+            .enumerate()
+            .map(|(i, ((chess_move, _, _), san))| MoveEntry {
+                san,
+                coordinate: chess_move.to_string(),
+                time: self.full_move_times.get(i).copied(),
+                eval: evals
+                    .as_ref()
+                    .map(|evals| evals[i].score.for_white(positions[i].side_to_move())),
+            })
+            .collect();
+        let black_first = self.start.side_to_move() == Color::Black;
+        println!();
+        print!(
+            "{}",
+            movelist::render(&moves, black_first, self.outcome().pgn_result(), style)
+        );
+        println!();
+        Ok(())
+    }
+
     fn describe_move(&self, chess_move: &ChessMove, board: &chess::Board) -> String {
         let from_square = chess_move.get_source();
         let to_square = chess_move.get_dest();
@@ -370,127 +2015,375 @@ impl ChessGame {
         )
     }
 
-    fn to_algebraic_notation(&self, chess_move: &ChessMove, move_index: usize) -> String {
-        // Reconstruct the board state at the time of this move
-        let mut temp_game = Game::new();
+    /// Check `side`'s clock after it has thought for `elapsed`; true (and the
+    /// game lost on time) if its time is up
+    fn run_clock(&mut self, side: Color, elapsed: Duration) -> bool {
+        let Some(clock) = &self.clock else {
+            return false;
+        };
+        if elapsed < clock[side.to_index()] {
+            return false;
+        }
+        self.flagged = Some(side);
+        self.log_event(format!("{:?} ran out of time", side));
+        true
+    }
 
-        // Replay all moves up to (but not including) this move
-        for i in 0..move_index {
-            temp_game.make_move(self.move_history[i].0);
+    /// Take a move's thinking time off the mover's clock
+    fn run_clock_after_move(&mut self, side: Color, elapsed: Duration) {
+        if let Some(clock) = &mut self.clock {
+            clock[side.to_index()] = clock[side.to_index()].saturating_sub(elapsed);
         }
+    }
 
-        let board = temp_game.current_position();
-        let from_square = chess_move.get_source();
-        let to_square = chess_move.get_dest();
+    fn show_clocks(&self) {
+        if let Some([white, black]) = self.clock {
+            println!(
+                "Clock: White {} | Black {}",
+                format_clock(white),
+                format_clock(black)
+            );
+        }
+    }
 
-        // Get the piece that's moving
-        let piece = board.piece_on(from_square);
-        let piece_color = board.color_on(from_square);
+    /// " (4.2s)" after a move in the history, empty if its time is unknown
+    fn move_time_label(&self, index: usize) -> String {
+        self.full_move_times
+            .get(index)
+            .map(|time| format!(" ({})", format_duration(*time)))
+            .unwrap_or_default()
+    }
 
-        // Check if it's a capture
-        let is_capture = board.piece_on(to_square).is_some();
+    /// Whether the move at `index` in the history was the player's
+    fn is_player_move(&self, index: usize) -> bool {
+        let first = self.start.side_to_move();
+        let mover = if index.is_multiple_of(2) {
+            first
+        } else {
+            !first
+        };
+        mover == self.player_color
+    }
 
-        // Check for castling first
-        if piece == Some(Piece::King) {
-            let king_start = if piece_color == Some(Color::White) {
-                Square::E1
-            } else {
-                Square::E8
-            };
-            if from_square == king_start {
-                if to_square == Square::G1 || to_square == Square::G8 {
-                    return "0-0".to_string();
-                } else if to_square == Square::C1 || to_square == Square::C8 {
-                    return "0-0-0".to_string();
-                }
-            }
+    /// Total, average and longest thinking time of each side
+    fn print_time_summary(&self) {
+        let played = self.move_history.len();
+        if played == 0 {
+            return;
         }
 
-        let mut notation = String::new();
+        let sans = self.san_moves();
+        println!("\n=== Time Usage ===");
+        for (name, by_player) in [("You", true), ("Computer", false)] {
+            let times: Vec<(usize, Duration)> = self.full_move_times[..played]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| self.is_player_move(*i) == by_player)
+                .map(|(i, time)| (i, *time))
+                .collect();
+            let Some(&(longest_index, longest)) = times.iter().max_by_key(|(_, time)| *time) else {
+                continue;
+            };
+            let total: Duration = times.iter().map(|(_, time)| *time).sum();
+            println!(
+                "{:<9} {} total, {} per move, longest {} ({})",
+                name,
+                format_duration(total),
+                format_duration(total / times.len() as u32),
+                format_duration(longest),
+                sans[longest_index]
+            );
+        }
+        println!("==================\n");
+    }
 
-        // Add piece letter (except for pawns)
-        match piece {
-            Some(Piece::King) => notation.push('K'),
-            Some(Piece::Queen) => notation.push('Q'),
-            Some(Piece::Rook) => notation.push('R'),
-            Some(Piece::Bishop) => notation.push('B'),
-            Some(Piece::Knight) => notation.push('N'),
-            Some(Piece::Pawn) => {
-                // For pawn captures, include the file
-                if is_capture {
-                    notation.push(from_square.to_string().chars().next().unwrap());
-                }
-            }
-            None => return chess_move.to_string(), // Fallback
+    /// White's and Black's names: "Player" and the engine's name
+    fn player_names(&self) -> (String, String) {
+        let computer = match self.engine.name() {
+            "" => "Computer".to_string(),
+            name => name.to_string(),
+        };
+        if self.player_color == Color::White {
+            ("Player".to_string(), computer)
+        } else {
+            (computer, "Player".to_string())
         }
+    }
 
-        // Check for disambiguation (if multiple pieces of same type can reach the destination)
-        if piece != Some(Piece::Pawn) && piece != Some(Piece::King) {
-            let legal_moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
-            let same_piece_moves: Vec<ChessMove> = legal_moves
-                .iter()
-                .filter(|m| {
-                    m.get_dest() == to_square
-                        && board.piece_on(m.get_source()) == piece
-                        && m.get_source() != from_square
-                })
-                .cloned()
-                .collect();
+    /// The moves so far as a score sheet with White and Black columns
+    pub fn to_scoresheet(&self, format: scoresheet::Format) -> String {
+        let (white, black) = self.player_names();
+        let header = scoresheet::SheetHeader {
+            white: &white,
+            black: &black,
+            date: &self.started.format("%Y-%m-%d").to_string(),
+            result: self.outcome().pgn_result(),
+        };
+        let black_first = self.start.side_to_move() == Color::Black;
+        scoresheet::render(&header, &self.san_moves(), black_first, format)
+    }
 
-            if !same_piece_moves.is_empty() {
-                // Need disambiguation
-                let from_file = from_square.to_string().chars().next().unwrap();
-                let from_rank = from_square.to_string().chars().nth(1).unwrap();
+    /// The game so far as PGN, with each move's thinking time as `[%emt]`.
+    /// `round` is set for the games of a match.
+    pub fn to_pgn(&self, event: &str, round: Option<usize>) -> String {
+        let start = self.start;
+        let headers = self.pgn_headers(event, round);
+        let moves: Vec<(String, Option<String>)> = self
+            .san_moves()
+            .into_iter()
+            .enumerate()
+            .map(|(i, san)| {
+                let emt = self.full_move_times.get(i).map(|time| movelist::emt(*time));
+                (san, emt)
+            })
+            .collect();
+        pgn::write_game(&headers, &start, &moves, self.outcome().pgn_result())
+    }
 
-                // Check if file disambiguation is enough
-                let same_file = same_piece_moves
-                    .iter()
-                    .any(|m| m.get_source().to_string().chars().next().unwrap() == from_file);
+    /// Tag pairs describing the game: players, dates and times, engine setup
+    fn pgn_headers(&self, event: &str, round: Option<usize>) -> Vec<(String, String)> {
+        let start = self.start;
+        let (white, black) = self.player_names();
+        let result = self.outcome().pgn_result();
+
+        let mut headers = vec![
+            ("Event".to_string(), event.to_string()),
+            ("Site".to_string(), "minichess".to_string()),
+            (
+                "Date".to_string(),
+                self.started.format("%Y.%m.%d").to_string(),
+            ),
+            (
+                "Round".to_string(),
+                round.map_or("-".to_string(), |n| n.to_string()),
+            ),
+            ("White".to_string(), white),
+            ("Black".to_string(), black),
+            ("Result".to_string(), result.to_string()),
+        ];
+        let utc = self.started.with_timezone(&Utc);
+        headers.extend([
+            ("UTCDate".to_string(), utc.format("%Y.%m.%d").to_string()),
+            ("UTCTime".to_string(), utc.format("%H:%M:%S").to_string()),
+            (
+                "Time".to_string(),
+                self.started.format("%H:%M:%S").to_string(),
+            ),
+            (
+                "TimeZone".to_string(),
+                self.started.format("%:z").to_string(),
+            ),
+        ]);
+        if let Some(ended) = self.ended {
+            let utc = ended.with_timezone(&Utc);
+            headers.push(("EndDate".to_string(), utc.format("%Y.%m.%d").to_string()));
+            headers.push(("EndTime".to_string(), utc.format("%H:%M:%S").to_string()));
+        }
+        if !self.engine.name().is_empty() {
+            headers.push(("Engine".to_string(), self.engine.name().to_string()));
+        }
+        if let Some(level) = self.engine.skill() {
+            headers.push(("Difficulty".to_string(), level.to_string()));
+        }
+        headers.push(("EngineSettings".to_string(), self.engine_settings()));
+        if self.house_rules.any() {
+            headers.push(("HouseRules".to_string(), self.house_rules.describe()));
+        }
+        if start != Board::default() {
+            headers.push(("SetUp".to_string(), "1".to_string()));
+            headers.push(("FEN".to_string(), start.to_string()));
+        }
+        headers
+    }
 
-                if !same_file {
-                    notation.push(from_file);
-                } else {
-                    // Need rank disambiguation
-                    notation.push(from_rank);
+    /// Write the game as a standalone HTML page with a board replayer, the
+    /// moves with engine comments, and an evaluation graph. Every position
+    /// is analyzed at full strength first.
+    async fn export_html(&mut self, path: &str) -> Result<()> {
+        let start = self.start;
+        let positions: Vec<Board> = (0..=self.move_history.len())
+            .map(|plies| self.position_after(plies))
+            .collect();
+
+        let evals = self.analyze_game(&positions, HTML_DEPTH).await?;
+
+        let moves: Vec<html::HtmlMove> = self
+            .move_history
+            .iter()
+            .enumerate()
+            .map(|(ply, (chess_move, _, _))| {
+                let board = &positions[ply];
+                let next = &positions[ply + 1];
+                let (before, best) = (evals[ply].score, evals[ply].best_move);
+                let after = evals[ply + 1].score;
+                // The score after the move is the opponent's, so flip it back to the mover
+                let loss = before.as_centipawns() + after.as_centipawns();
+                let mark = annotate::move_mark(loss);
+                let mut comment = Vec::new();
+                if !mark.is_empty()
+                    && let Some(best) = best.filter(|best| best != chess_move)
+                {
+                    comment.push(format!("best was {}", pgn::move_to_san(board, best)));
                 }
-            }
-        }
+                if annotate::is_blunder(loss) {
+                    let motif = motifs::classify(next, &evals[ply + 1].pv);
+                    comment.push(format!("loses to a {}", motif.name()));
+                }
+                let white_view = after.for_white(next.side_to_move());
+                let eval = match white_view {
+                    Score::Centipawns(cp) => cp as f64 / 100.0,
+                    Score::Mate(moves) if moves > 0 => 10.0,
+                    Score::Mate(0) if next.status() == chess::BoardStatus::Checkmate => {
+                        if next.side_to_move() == Color::White {
+                            -10.0
+                        } else {
+                            10.0
+                        }
+                    }
+                    Score::Mate(_) => -10.0,
+                };
+                html::HtmlMove {
+                    san: format!("{}{}", pgn::move_to_san(board, *chess_move), mark),
+                    fen: next.to_string(),
+                    comment: (!comment.is_empty()).then(|| comment.join(", ")),
+                    eval: Some(eval),
+                }
+            })
+            .collect();
 
-        // Add capture notation
-        if is_capture {
-            notation.push('x');
-        }
+        let page = html::render(
+            &self.pgn_headers("Casual game", None),
+            &start.to_string(),
+            &moves,
+        );
+        std::fs::write(path, page).map_err(|e| anyhow!("Could not write {}: {}", path, e))?;
+        println!("Game page written to {}", path);
+        Ok(())
+    }
 
-        // Add destination square
-        notation.push_str(&to_square.to_string());
+    /// Evaluate each of `positions` at full strength, showing progress
+    async fn analyze_game(
+        &mut self,
+        positions: &[Board],
+        depth: u8,
+    ) -> Result<Vec<annotate::PositionEval>> {
+        self.engine.prepare_for_analysis().await?;
+        let mut evals = Vec::with_capacity(positions.len());
+        for (i, board) in positions.iter().enumerate() {
+            print!("\rAnalyzing position {}/{}", i + 1, positions.len());
+            std::io::Write::flush(&mut std::io::stdout())?;
+            let eval = match board.status() {
+                chess::BoardStatus::Checkmate => annotate::PositionEval {
+                    score: Score::Mate(0),
+                    best_move: None,
+                    pv: Vec::new(),
+                },
+                chess::BoardStatus::Stalemate => annotate::PositionEval {
+                    score: Score::Centipawns(0),
+                    best_move: None,
+                    pv: Vec::new(),
+                },
+                chess::BoardStatus::Ongoing => {
+                    let result = self
+                        .engine
+                        .analyze(board, &format!("go depth {}", depth))
+                        .await?;
+                    annotate::PositionEval {
+                        score: result.score.unwrap_or(Score::Centipawns(0)),
+                        best_move: result.best_move,
+                        pv: result.pv,
+                    }
+                }
+            };
+            evals.push(eval);
+        }
+        println!();
+        Ok(evals)
+    }
 
-        // Add promotion
-        if let Some(promotion_piece) = chess_move.get_promotion() {
-            notation.push('=');
-            match promotion_piece {
-                Piece::Queen => notation.push('Q'),
-                Piece::Rook => notation.push('R'),
-                Piece::Bishop => notation.push('B'),
-                Piece::Knight => notation.push('N'),
-                _ => {}
+    /// List the player's blunders with the tactic that refutes each, and add
+    /// the motifs to the statistics kept across games
+    async fn show_blunders(&mut self) -> Result<()> {
+        let positions: Vec<Board> = (0..=self.move_history.len())
+            .map(|plies| self.position_after(plies))
+            .collect();
+        let played = positions[..self.move_history.len()]
+            .iter()
+            .any(|board| board.side_to_move() == self.player_color);
+        if !played {
+            println!("\nYou haven't made a move yet.");
+            return Ok(());
+        }
+        let evals = self.analyze_game(&positions, BLUNDER_DEPTH).await?;
+
+        let mut found: Vec<(usize, Motif)> = Vec::new();
+        for (ply, (chess_move, _, _)) in self.move_history.iter().enumerate() {
+            let board = &positions[ply];
+            let next = &positions[ply + 1];
+            let loss = evals[ply].score.as_centipawns() + evals[ply + 1].score.as_centipawns();
+            if board.side_to_move() != self.player_color || !annotate::is_blunder(loss) {
+                continue;
+            }
+            let motif = motifs::classify(next, &evals[ply + 1].pv);
+            if found.is_empty() {
+                println!("\n=== Your Blunders ===");
             }
+            let number = format!("{}.{}", ply / 2 + 1, if ply % 2 == 0 { "" } else { ".." });
+            let refutation = evals[ply + 1]
+                .pv
+                .first()
+                .map(|&reply| format!(", refuted by {}", pgn::move_to_san(next, reply)))
+                .unwrap_or_default();
+            let best = evals[ply]
+                .best_move
+                .map(|best| format!(" (best was {})", pgn::move_to_san(board, best)))
+                .unwrap_or_default();
+            println!(
+                "{} {}?? loses to a {}{}{}",
+                number,
+                pgn::move_to_san(board, *chess_move),
+                motif.name(),
+                refutation,
+                best
+            );
+            found.push((ply, motif));
+        }
+        if found.is_empty() {
+            println!("\nNo blunders so far.");
         }
 
-        // Check for check or checkmate (we'd need to make the move and see)
-        let mut temp_board = board.clone();
-        temp_board = temp_board.make_move_new(*chess_move);
+        // Running the command again later in the game must not count old blunders twice
+        let new: Vec<Motif> = found
+            .iter()
+            .filter(|(ply, _)| *ply >= self.motifs_recorded)
+            .map(|&(_, motif)| motif)
+            .collect();
+        motifs::record(&new)?;
+        self.motifs_recorded = self.move_history.len();
+        Ok(())
+    }
 
-        if temp_board.checkers().popcnt() > 0 {
-            // It's check, but is it checkmate?
-            let legal_after: Vec<ChessMove> = MoveGen::new_legal(&temp_board).collect();
-            if legal_after.is_empty() {
-                notation.push('#'); // Checkmate
-            } else {
-                notation.push('+'); // Check
-            }
+    /// Average centipawns the player's moves lost against the engine's best,
+    /// None if they made no move
+    pub async fn average_loss(&mut self) -> Result<Option<f64>> {
+        let positions: Vec<Board> = (0..=self.move_history.len())
+            .map(|plies| self.position_after(plies))
+            .collect();
+        let played: Vec<usize> = (0..self.move_history.len())
+            .filter(|&ply| positions[ply].side_to_move() == self.player_color)
+            .collect();
+        if played.is_empty() {
+            return Ok(None);
         }
-
-        notation
+        let evals = self.analyze_game(&positions, BLUNDER_DEPTH).await?;
+        let total: i32 = played
+            .iter()
+            .map(|&ply| {
+                (evals[ply].score.as_centipawns() + evals[ply + 1].score.as_centipawns())
+                    .clamp(0, MAX_COUNTED_LOSS)
+            })
+            .sum();
+        Ok(Some(total as f64 / played.len() as f64))
     }
 
     fn show_fen(&self) {
@@ -543,93 +2436,334 @@ impl ChessGame {
         println!("=============================\n");
     }
 
-    fn save_game_state(&mut self) {
-        // Remove any future states if we're in the middle of history
-        if self.current_state_index < self.game_states.len() - 1 {
-            self.game_states.truncate(self.current_state_index + 1);
-            // Also truncate full history to match
-            self.full_move_history.truncate(self.current_state_index);
+    /// The transcript with a header naming the players, the start and the
+    /// result so far; Markdown for `.md` files
+    fn transcript_text(&self, markdown: bool) -> String {
+        let header = vec![
+            format!("Started: {}", self.started.format("%Y-%m-%d %H:%M")),
+            format!("You: {}", color_name(self.player_color)),
+            format!(
+                "Computer: {} ({})",
+                self.engine_name(),
+                self.engine_settings()
+            ),
+            format!("Start: {}", self.start),
+            format!("Result: {}", self.outcome().pgn_result()),
+        ];
+        self.transcript
+            .render("minichess transcript", &header, markdown)
+    }
+
+    fn export(&self, args: &str) {
+        let (kind, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let rest = rest.trim();
+        let board = self.game.current_position();
+        let result = match kind {
+            "scoresheet" => {
+                let mut format = scoresheet::Format::Text;
+                let mut file = None;
+                for word in rest.split_whitespace() {
+                    match word {
+                        "--markdown" | "--md" | "md" | "markdown" => {
+                            format = scoresheet::Format::Markdown
+                        }
+                        path => file = Some(path),
+                    }
+                }
+                let sheet = self.to_scoresheet(format);
+                match file {
+                    Some(path) => std::fs::write(path, &sheet)
+                        .map(|()| format!("Score sheet written to {}", path))
+                        .map_err(|e| anyhow!("Could not write {}: {}", path, e)),
+                    None => Ok(format!("\n{}", sheet)),
+                }
+            }
+            "fen" if !rest.is_empty() => std::fs::write(rest, format!("{}\n", board))
+                .map(|()| format!("Position written to {}", rest))
+                .map_err(|e| anyhow!("Could not write {}: {}", rest, e)),
+            "epd" if !rest.is_empty() => {
+                let (path, comment) = rest.split_once(' ').unwrap_or((rest, ""));
+                let mut operations = vec![(
+                    "id",
+                    format!(
+                        "minichess {} move {}",
+                        self.started.format("%Y-%m-%d %H:%M"),
+                        self.move_number()
+                    ),
+                )];
+                if let Some((chess_move, _, _)) = self.move_history.last() {
+                    let before = self.position_after(self.move_history.len() - 1);
+                    operations.push((
+                        "c1",
+                        format!("after {}", pgn::move_to_san(&before, *chess_move)),
+                    ));
+                }
+                if !comment.trim().is_empty() {
+                    operations.push(("c0", comment.trim().to_string()));
+                }
+                fen::append_epd(path, &fen::to_epd(&board, &operations))
+                    .map(|()| format!("Position added to {}", path))
+            }
+            "transcript" if !rest.is_empty() => {
+                let markdown = rest.ends_with(".md") || rest.ends_with(".markdown");
+                std::fs::write(rest, self.transcript_text(markdown))
+                    .map(|()| format!("Transcript written to {}", rest))
+                    .map_err(|e| anyhow!("Could not write {}: {}", rest, e))
+            }
+            _ => Ok(
+                "Usage: export scoresheet [--markdown] [FILE] | export fen FILE | export epd FILE [COMMENT] | export html FILE | export transcript FILE"
+                    .to_string(),
+            ),
+        };
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(e) => println!("{}", e),
         }
+    }
 
-        // Add the new state
-        self.game_states.push(self.game.clone());
-        self.current_state_index = self.game_states.len() - 1;
+    fn import(&mut self, args: &str) {
+        let Some(path) = args.strip_prefix("fen ").map(str::trim) else {
+            println!("Usage: import fen FILE");
+            return;
+        };
+        if self.competitive {
+            println!("Positions can't be imported in rated or ladder games.");
+            return;
+        }
+        match fen::read_position_file(path) {
+            Ok(board) => {
+                self.restart_from(board);
+                println!("Starting over from the position in {}.", path);
+                self.show_board();
+            }
+            Err(e) => println!("{}", e),
+        }
     }
 
-    fn undo_move(&mut self) -> bool {
-        if self.current_state_index == 0 {
-            println!("Cannot undo: Already at the beginning of the game.");
-            return false;
+    /// Throw the game away and continue from `board`, keeping sides and settings
+    fn restart_from(&mut self, board: Board) {
+        self.log_event(format!("Restarted from {}", board));
+        self.game = Game::new_with_board(board);
+        self.start = board;
+        self.game_states = vec![self.game.clone()];
+        self.state_base = 0;
+        self.current_state_index = 0;
+        self.move_history.clear();
+        self.full_move_history.clear();
+        self.full_move_times.clear();
+        self.last_line = None;
+        self.eval = None;
+        self.note_book_position(false);
+    }
+
+    /// Play a move and add it to the history along with its position for
+    /// undo. Moves that were undone and not redone are dropped: a new move
+    /// starts a new line.
+    fn record_move(&mut self, entry: (ChessMove, String, String), time: Duration) {
+        self.drop_undone_moves();
+        let chess_move = entry.0;
+        self.move_history.push(entry.clone());
+        self.full_move_history.push(entry);
+        self.full_move_times.push(time);
+        self.game.make_move(chess_move);
+        self.game_states.push(self.game.clone());
+        self.current_state_index += 1;
+
+        // Past the limit the oldest states go; the move list still has them
+        if let Some(limit) = self.options.undo_limit
+            && self.game_states.len() > limit.max(1)
+        {
+            let excess = self.game_states.len() - limit.max(1);
+            self.game_states.drain(..excess);
+            self.state_base += excess;
         }
+    }
 
-        if !self.move_history.is_empty() {
-            // Always undo back to the player's turn
-            let mut moves_undone = 0;
-            let target_moves = if self.game.current_position().side_to_move() == self.player_color {
-                // It's currently player's turn, undo 2 moves (player + computer)
-                2
-            } else {
-                // It's currently computer's turn, undo 1 move (computer) to get back to player's turn
-                1
-            };
+    /// Forget the moves after the current position, so they can't be redone
+    fn drop_undone_moves(&mut self) {
+        let index = self.current_state_index;
+        if index < self.state_base {
+            // Undone past the kept states: the current one starts them afresh
+            self.game_states = vec![self.game.clone()];
+            self.state_base = index;
+        } else {
+            self.game_states.truncate(index - self.state_base + 1);
+        }
+        self.full_move_history.truncate(index);
+        self.full_move_times.truncate(index);
+    }
 
-            for _ in 0..target_moves {
-                if self.current_state_index > 0 && !self.move_history.is_empty() {
-                    self.current_state_index -= 1;
-                    let (undone_move, _player, description) = self.move_history.pop().unwrap();
-                    println!("Undone: {} - {}", undone_move, description);
-                    moves_undone += 1;
-                } else {
-                    break;
+    /// The game after `index` moves of the full history, replayed from the
+    /// start when its state has been compacted away
+    fn game_at(&self, index: usize) -> Game {
+        match index.checked_sub(self.state_base) {
+            Some(kept) => self.game_states[kept].clone(),
+            None => {
+                let mut game = Game::new_with_board(self.start);
+                for (chess_move, _, _) in &self.full_move_history[..index] {
+                    game.make_move(*chess_move);
                 }
+                game
             }
+        }
+    }
 
-            if moves_undone > 0 {
-                self.game = self.game_states[self.current_state_index].clone();
+    /// The position after `index` moves of the full history
+    fn board_at(&self, index: usize) -> Board {
+        match index.checked_sub(self.state_base) {
+            Some(kept) => self.game_states[kept].current_position(),
+            None => self.full_move_history[..index]
+                .iter()
+                .fold(self.start, |board, (m, _, _)| board.make_move_new(*m)),
+        }
+    }
 
-                // Rebuild move_history to match current state
-                self.move_history = self.full_move_history[0..self.current_state_index].to_vec();
+    /// Whether the player is to move in the position after `index` moves
+    fn player_to_move_at(&self, index: usize) -> bool {
+        self.board_at(index).side_to_move() == self.player_color
+    }
 
-                // Ensure we're back to the player's turn
-                if self.game.current_position().side_to_move() != self.player_color {
-                    println!("Warning: Not your turn after undo. Game state may be inconsistent.");
-                }
+    /// Jump to the position after `index` moves of the full history
+    fn go_to_state(&mut self, index: usize) {
+        self.current_state_index = index;
+        self.game = self.game_at(index);
+        self.move_history = self.full_move_history[..index].to_vec();
+    }
 
-                return true;
-            }
-        }
+    /// The move that led from state `index` to the next, in SAN with its
+    /// original description
+    fn history_line(&self, index: usize) -> String {
+        let (chess_move, _, description) = &self.full_move_history[index];
+        let board = self.board_at(index);
+        format!(
+            "{} - {}",
+            pgn::move_to_san(&board, *chess_move),
+            description
+        )
+    }
 
-        println!("Cannot undo: No moves to undo.");
-        false
+    /// Go back to the latest earlier position with the player to move, taking
+    /// back the player's last move together with the reply to it
+    fn undo_move(&mut self) -> bool {
+        let Some(target) = (0..self.current_state_index)
+            .rev()
+            .find(|&index| self.player_to_move_at(index))
+        else {
+            println!("Cannot undo: none of your moves to take back.");
+            return false;
+        };
+        for index in (target..self.current_state_index).rev() {
+            println!("Undone: {}", self.history_line(index));
+        }
+        self.go_to_state(target);
+        true
     }
 
+    /// Replay undone moves, both the player's and the computer's, up to the
+    /// next position with the player to move or the end of the line
     fn redo_move(&mut self) -> bool {
-        if self.current_state_index >= self.game_states.len() - 1 {
-            println!("Cannot redo: Already at the latest position.");
+        let last = self.full_move_history.len();
+        if self.current_state_index == last {
+            println!("Cannot redo: no undone moves.");
             return false;
         }
+        let target = (self.current_state_index + 1..=last)
+            .find(|&index| self.player_to_move_at(index))
+            .unwrap_or(last);
+        for index in self.current_state_index..target {
+            println!("Redone: {}", self.history_line(index));
+        }
+        self.go_to_state(target);
+        true
+    }
 
-        // Redo moves to get back to player's turn (usually 2 moves: player + computer)
-        let available_states = self.game_states.len() - 1 - self.current_state_index;
-        let moves_to_redo = std::cmp::min(2, available_states);
-
-        for _ in 0..moves_to_redo {
-            if self.current_state_index < self.game_states.len() - 1 {
-                self.current_state_index += 1;
+    /// Competitive undo: take back your last move (and the reply to it). It
+    /// uses up one of the game's takebacks, is logged, and cannot be redone.
+    fn take_back(&mut self) -> bool {
+        if self.takebacks_left() == 0 {
+            if self.takebacks_used == 0 {
+                println!("Takebacks are disabled for this game.");
+            } else {
+                println!("No takebacks left in this game.");
             }
+            return false;
+        }
+
+        let played = self.move_history.len();
+        if !self.undo_move() {
+            return false;
         }
+        let taken: Vec<String> = self.full_move_history[self.current_state_index..played]
+            .iter()
+            .map(|(chess_move, _, _)| chess_move.to_string())
+            .collect();
+        self.drop_undone_moves();
+
+        self.takebacks_used += 1;
+        println!("Takeback used, {} left.", self.takebacks_left());
+        self.log_event(format!(
+            "Takeback of {} ({} left)",
+            taken.join(" "),
+            self.takebacks_left()
+        ));
+        true
+    }
+
+    fn log_event(&mut self, text: String) {
+        self.transcript.record(Entry::Event(text.clone()));
+        self.events.push(GameEvent {
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+            ply: self.move_history.len(),
+            text,
+        });
+    }
 
-        self.game = self.game_states[self.current_state_index].clone();
+    fn show_events(&self) {
+        if self.events.is_empty() {
+            println!("\nNo events yet.");
+            return;
+        }
+        println!("\n=== Event Log ===");
+        for event in &self.events {
+            println!("{}  after {} moves  {}", event.time, event.ply, event.text);
+        }
+        println!();
+    }
 
-        // Rebuild move_history to match current state
-        self.move_history = self.full_move_history[0..self.current_state_index].to_vec();
+    /// Under capture-the-king a mate is finished by taking the king: the
+    /// player has to find the capture, or watches the computer make it
+    fn capture_king(&self) -> Result<()> {
+        let board = self.game.current_position();
+        let king = board.king_square(board.side_to_move());
+        let takers: Vec<Square> = board.checkers().into_iter().collect();
+        let capture = |from: Square| match board.piece_on(from) {
+            Some(Piece::Pawn) | None => format!("{}x{}", &from.to_string()[..1], king),
+            Some(piece) => format!("{}x{}", pgn::piece_char(piece), king),
+        };
+        if board.side_to_move() == self.player_color {
+            println!("\nThe computer captures your king: {}", capture(takers[0]));
+            return Ok(());
+        }
 
+        display_board_for_player(&board, self.player_color);
         println!(
-            "Redone to position {} (move {})",
-            self.current_state_index,
-            (self.current_state_index + 1) / 2
+            "\nThe king can't escape. Take it! Enter the capture (e.g. '{}{}'):",
+            takers[0], king
         );
-        return true;
+        loop {
+            let input = get_user_input()?.trim().to_lowercase();
+            let taken = takers.iter().find(|&&from| {
+                input == format!("{}{}", from, king) || input == capture(from).to_lowercase()
+            });
+            match taken {
+                Some(&from) => {
+                    println!("{} takes the king!", capture(from));
+                    return Ok(());
+                }
+                None if input == "q" || input == "quit" => return Ok(()),
+                None => println!("That doesn't take the king. Which piece is giving check?"),
+            }
+        }
     }
 
     #[allow(dead_code)]
@@ -638,20 +2772,34 @@ impl ChessGame {
     }
 
     fn display_game_result(&self) {
-        match self.game.result() {
-            Some(chess::GameResult::WhiteCheckmates) => {
-                if self.player_color == Color::White {
-                    println!("\nCongratulations! You won by checkmate!");
-                } else {
-                    println!("\nComputer wins by checkmate!");
-                }
+        if let Some(flagged) = self.flagged {
+            let who = if flagged == self.player_color {
+                "Your"
+            } else {
+                "The computer's"
+            };
+            match self.outcome() {
+                GameOutcome::Draw => println!(
+                    "\n{} time ran out, but there is no mating material left: draw.",
+                    who
+                ),
+                _ => println!("\n{} time ran out! {:?} wins on time.", who, !flagged),
             }
-            Some(chess::GameResult::BlackCheckmates) => {
-                if self.player_color == Color::Black {
-                    println!("\nCongratulations! You won by checkmate!");
+            return;
+        }
+        match self.game.result() {
+            Some(chess::GameResult::WhiteCheckmates) | Some(chess::GameResult::BlackCheckmates) => {
+                let headline = if self.outcome() == GameOutcome::Win(self.player_color) {
+                    "Congratulations! You won by checkmate!"
                 } else {
-                    println!("\nComputer wins by checkmate!");
-                }
+                    "Computer wins by checkmate!"
+                };
+                display_checkmate(
+                    &self.game.current_position(),
+                    self.player_color,
+                    headline,
+                    self.outcome().pgn_result(),
+                );
             }
             Some(chess::GameResult::WhiteResigns) => {
                 println!("\nWhite resigns!");
@@ -659,6 +2807,13 @@ impl ChessGame {
             Some(chess::GameResult::BlackResigns) => {
                 println!("\nBlack resigns!");
             }
+            Some(chess::GameResult::Stalemate) if self.house_rules.capture_the_king => {
+                let stuck = self.game.current_position().side_to_move();
+                println!(
+                    "\nStalemate! Under capture-the-king, {:?} has to walk into capture: {:?} wins!",
+                    stuck, !stuck
+                );
+            }
             Some(chess::GameResult::Stalemate) => {
                 println!("\nGame ended in stalemate - it's a draw!");
             }
@@ -668,6 +2823,9 @@ impl ChessGame {
             Some(chess::GameResult::DrawDeclared) => {
                 println!("\nGame ended in a draw (insufficient material or repetition)!");
             }
+            None if is_insufficient_material(&self.game.current_position()) => {
+                println!("\nGame ended in a draw by insufficient material!");
+            }
             None => {
                 println!("\nGame in progress...");
             }
@@ -675,6 +2833,483 @@ impl ChessGame {
     }
 }
 
+/// Final state of a game, from the board's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    Win(Color),
+    /// Won by the given side because the other's time ran out
+    Timeout(Color),
+    Draw,
+    Unfinished,
+}
+
+impl GameOutcome {
+    /// The result as written in PGN
+    pub fn pgn_result(self) -> &'static str {
+        match self {
+            GameOutcome::Win(Color::White) | GameOutcome::Timeout(Color::White) => "1-0",
+            GameOutcome::Win(Color::Black) | GameOutcome::Timeout(Color::Black) => "0-1",
+            GameOutcome::Draw => "1/2-1/2",
+            GameOutcome::Unfinished => "*",
+        }
+    }
+}
+
+/// The commands available at the move prompt besides moves, quit and help
+fn builtin_commands() -> Result<CommandRegistry> {
+    let mut commands = CommandRegistry::default();
+    commands.register(
+        "moves [--ranked]",
+        &[],
+        "Show all legal moves grouped by piece; --ranked sorts them by a quick engine evaluation",
+        |game, args| {
+            Box::pin(async move {
+                match args {
+                    "--ranked" | "-r" => game.show_ranked_moves().await?,
+                    _ => game.show_legal_moves(),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "history [table|inline|coordinate|pgn]",
+        &[],
+        "Show move history, optionally as a table with evals, a paragraph, coordinates or PGN",
+        |game, args| {
+            Box::pin(async move {
+                if args.is_empty() {
+                    game.show_move_history();
+                    return Ok(());
+                }
+                match args.parse() {
+                    Ok(style) => game.show_move_list(style).await?,
+                    Err(e) => println!("{}", e),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "export scoresheet [--markdown] [FILE]|fen FILE|epd FILE [COMMENT]|html FILE|transcript FILE",
+        &[],
+        "Print the moves as a score sheet (or write it to FILE), save the position as FEN, add it to an EPD collection, write an analyzed HTML page of the game, or save a log of everything typed and shown",
+        |game, args| {
+            Box::pin(async move {
+                match args.split_once(' ') {
+                    Some(("html", path)) if !path.trim().is_empty() => {
+                        if let Err(e) = game.export_html(path.trim()).await {
+                            println!("{}", e);
+                        }
+                    }
+                    _ => game.export(args),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "import fen FILE",
+        &[],
+        "Start over from the position in a FEN or EPD file",
+        |game, args| {
+            Box::pin(async move {
+                game.import(args);
+                Ok(())
+            })
+        },
+    )?;
+    commands.register("pgn", &[], "Print the game so far as PGN", |game, _| {
+        Box::pin(async move {
+            println!("\n{}", game.to_pgn("Casual game", None));
+            Ok(())
+        })
+    })?;
+    commands.register(
+        "save FILE",
+        &[],
+        "Write the game so far to a PGN file, to resume later with --load-pgn",
+        |game, args| {
+            Box::pin(async move {
+                let path = args.trim();
+                if path.is_empty() {
+                    println!("Usage: save FILE");
+                    return Ok(());
+                }
+                match std::fs::write(path, game.to_pgn("Casual game", None)) {
+                    Ok(()) => println!("Game saved to {}", path),
+                    Err(e) => println!("Could not write {}: {}", path, e),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "show",
+        &["showboard", "board"],
+        "Redisplay the current board; in a simul, 'board <n>' switches to board n",
+        |game, args| {
+            Box::pin(async move {
+                match args.parse::<usize>() {
+                    Ok(number) if game.simul => {
+                        game.simul_request = Some(SimulTurn::Board(number));
+                    }
+                    _ => {
+                        game.show_board();
+                        game.record_board();
+                    }
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "fen",
+        &[],
+        "Show FEN notation of current position",
+        |game, _| {
+            Box::pin(async move {
+                game.show_fen();
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "eval",
+        &[],
+        "Show Stockfish's term-by-term evaluation of the position",
+        |game, _| {
+            Box::pin(
+                async move { eval::show(&mut game.engine, &game.game.current_position()).await },
+            )
+        },
+    )?;
+    commands.register(
+        "scan",
+        &[],
+        "Mark your pieces that are hanging (!), pinned (~), attacked (*) or undefended (°)",
+        |game, _| {
+            Box::pin(async move {
+                overlay::scan(&game.game.current_position(), game.player_color);
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "heatmap",
+        &[],
+        "Color squares by how strongly each side controls them",
+        |game, _| {
+            Box::pin(async move {
+                overlay::heatmap(&game.game.current_position(), game.player_color);
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "threat",
+        &[],
+        "Show what your opponent threatens to play next, drawn on the board",
+        |game, _| Box::pin(game.show_threat()),
+    )?;
+    commands.register(
+        "hint",
+        &[],
+        "Show the engine's suggested move, drawn on the board",
+        |game, _| Box::pin(game.show_hint()),
+    )?;
+    commands.register(
+        "analyze [depth]",
+        &["analyse"],
+        "Show the engine's evaluation and best line (default depth 18)",
+        |game, args| {
+            Box::pin(async move {
+                match args {
+                    "" => game.analyze_position(ANALYZE_DEPTH).await?,
+                    depth => match depth.parse::<u8>() {
+                        Ok(depth @ 1..) => game.analyze_position(depth).await?,
+                        _ => println!("Usage: analyze [depth]"),
+                    },
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "blunders",
+        &[],
+        "Find your blunders so far and name the tactic that punishes each",
+        |game, _| Box::pin(game.show_blunders()),
+    )?;
+    commands.register(
+        "evalbar on|off",
+        &[],
+        "Show an evaluation bar beside the board, updated after every move",
+        |game, args| {
+            Box::pin(async move {
+                match args {
+                    "on" => {
+                        game.eval_bar = true;
+                        game.quick_eval().await?;
+                        game.show_board();
+                    }
+                    "off" => game.eval_bar = false,
+                    _ => println!("Usage: evalbar on|off"),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "diff on|off",
+        &[],
+        "Describe what each move changed: material, checks, new threats and defenders",
+        |game, args| {
+            Box::pin(async move {
+                match args {
+                    "on" => {
+                        game.move_diff = true;
+                        game.show_move_diff();
+                    }
+                    "off" => game.move_diff = false,
+                    _ => println!("Usage: diff on|off"),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "exchanges <square>",
+        &["see"],
+        "Play out the captures on a square and tell whether starting them wins material",
+        |game, args| {
+            Box::pin(async move {
+                let board = game.game.current_position();
+                match Square::from_str(args) {
+                    Ok(square) => match exchange::evaluate(&board, square) {
+                        Some(exchange) => exchange::narrate(&exchange),
+                        None => println!("There is no piece on {} to capture.", square),
+                    },
+                    Err(_) => println!("Usage: exchanges <square>, e.g. exchanges e5"),
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "plan",
+        &[],
+        "Explain the game phase and what to aim for, with the engine's idea",
+        |game, _| Box::pin(game.show_plan()),
+    )?;
+    commands.register(
+        "preview",
+        &["pv"],
+        "Step through the last analyzed line on a scratch board",
+        |game, _| Box::pin(async move { game.preview_line() }),
+    )?;
+    commands.register(
+        "book",
+        &[],
+        "Show opening book moves for this position (with --book)",
+        |game, _| {
+            Box::pin(async move {
+                game.show_book_moves();
+                Ok(())
+            })
+        },
+    )?;
+    commands.register(
+        "auto-analysis on|off",
+        &[],
+        "Keep a live engine readout above the prompt on your turn",
+        |game, args| {
+            Box::pin(async move {
+                match args {
+                    "on" => game.set_auto_analysis(true).await,
+                    "off" => game.set_auto_analysis(false).await,
+                    _ => {
+                        println!("Usage: auto-analysis on|off");
+                        Ok(())
+                    }
+                }
+            })
+        },
+    )?;
+    commands.register("undo", &["u"], "Undo last move(s)", |game, _| {
+        Box::pin(async move {
+            if game.competitive {
+                println!("Undo is off in rated and ladder games; 'takeback' is allowed.");
+            } else if game.options.no_undo {
+                println!("Undo is off (--no-undo); 'takeback' is allowed.");
+            } else if game.undo_move() {
+                game.note_book_position(false);
+                game.show_board();
+            }
+            Ok(())
+        })
+    })?;
+    commands.register("redo", &["re"], "Redo undone move(s)", |game, _| {
+        Box::pin(async move {
+            if game.competitive || game.options.no_undo {
+                println!("Redo is off in rated and ladder games and with --no-undo.");
+            } else if game.redo_move() {
+                game.note_book_position(false);
+                game.show_board();
+            }
+            Ok(())
+        })
+    })?;
+    commands.register(
+        "takeback",
+        &["tb"],
+        "Take back your last move; limited per game and logged",
+        |game, _| {
+            Box::pin(async move {
+                if game.take_back() {
+                    game.note_book_position(false);
+                    game.show_board();
+                }
+                Ok(())
+            })
+        },
+    )?;
+    commands.register("events", &[], "Show the game's event log", |game, _| {
+        Box::pin(async move {
+            game.show_events();
+            Ok(())
+        })
+    })?;
+
+    // Commands that only make sense at some points of a game
+    let undo_allowed = |game: &ChessGame| !game.competitive && !game.options.no_undo;
+    commands.only_when("undo", move |game| {
+        undo_allowed(game) && game.current_state_index > 0
+    })?;
+    commands.only_when("redo", move |game| {
+        undo_allowed(game) && game.current_state_index < game.full_move_history.len()
+    })?;
+    commands.only_when("takeback", |game| {
+        game.takebacks_left() > 0 && !game.move_history.is_empty()
+    })?;
+    commands.only_when("blunders", |game| !game.move_history.is_empty())?;
+    commands.only_when("history", |game| !game.move_history.is_empty())?;
+    commands.only_when("book", |game| game.book.is_some())?;
+    commands.only_when("preview", |game| {
+        game.last_line
+            .as_ref()
+            .is_some_and(|line| line.position == game.game.current_position())
+    })?;
+    commands.only_when("events", |game| !game.events.is_empty())?;
+    Ok(commands)
+}
+
+/// Undo common slips in a typed move: spaces or dashes between the squares
+/// (e2 e4, e2-e4, o-o), an "x" between coordinates (e4xd5), "=" before a
+/// promotion piece, and check, mate or annotation marks
+fn normalize_move_input(input: &str) -> String {
+    let mut text: Vec<char> = input
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '=' | '+' | '#' | '!' | '?'))
+        .collect();
+    if text.len() >= 5 && text[0].is_ascii_lowercase() && text[1].is_ascii_digit() && text[2] == 'x'
+    {
+        text.remove(2);
+    }
+    text.into_iter().collect()
+}
+
+fn parse_promotion(text: &str) -> Result<Option<Piece>> {
+    match text {
+        "" => Ok(None),
+        "q" => Ok(Some(Piece::Queen)),
+        "r" => Ok(Some(Piece::Rook)),
+        "b" => Ok(Some(Piece::Bishop)),
+        "n" => Ok(Some(Piece::Knight)),
+        other => Err(anyhow!("Invalid promotion piece: {}", other)),
+    }
+}
+
+/// Edits between two strings, counting a swap of neighbouring characters
+/// as one (optimal string alignment distance)
+fn typo_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Remaining clock time as m:ss
+fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Whether `color` has more than a bare king or a single minor piece
+fn has_mating_material(board: &Board, color: Color) -> bool {
+    let own = *board.color_combined(color);
+    let heavy =
+        (*board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen))
+            & own;
+    let minors = (*board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop)) & own;
+    heavy.popcnt() > 0 || minors.popcnt() > 1
+}
+
+/// Material of `color` minus the opponent's, in pawns
+fn material_balance(board: &Board, color: Color) -> i32 {
+    board
+        .combined()
+        .into_iter()
+        .filter_map(|square| {
+            let piece = board.piece_on(square).filter(|p| *p != Piece::King)?;
+            let sign = if board.color_on(square) == Some(color) {
+                1
+            } else {
+                -1
+            };
+            Some(sign * exchange::value(piece))
+        })
+        .sum()
+}
+
+/// Neither side can possibly deliver mate (bare kings or a single minor piece)
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy =
+        *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+    let minors = *board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop);
+    heavy.popcnt() == 0 && minors.popcnt() <= 1
+}
+
+/// Sort key listing the king first and pawns last
+fn piece_order(piece: Option<Piece>) -> u8 {
+    match piece {
+        Some(Piece::King) => 0,
+        Some(Piece::Queen) => 1,
+        Some(Piece::Rook) => 2,
+        Some(Piece::Bishop) => 3,
+        Some(Piece::Knight) => 4,
+        Some(Piece::Pawn) => 5,
+        None => 6,
+    }
+}
+
 enum GameAction {
     Continue,
     Quit,
@@ -0,0 +1,185 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::time::Duration;
+
+/// How long the coach gets to answer unless the config says otherwise
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A move worth a comment, with the engine's view of it, as posted to the
+/// coach endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyMove {
+    /// Position before the move
+    pub fen: String,
+    pub played: String,
+    pub best: String,
+    /// Scores from the player's point of view before and after the move
+    pub eval_before: String,
+    pub eval_after: String,
+    /// Centipawns the move lost against the engine's choice
+    pub loss: i32,
+    /// The engine's line from the position before the move
+    pub best_line: String,
+    /// How the engine answers the move that was played
+    pub refutation: String,
+    /// The same as a ready-made question, for endpoints that just forward
+    /// text to a language model
+    pub prompt: String,
+}
+
+impl KeyMove {
+    pub fn prompt_text(&self) -> String {
+        format!(
+            "Position (FEN): {}\nThe student played {} and the engine preferred {}. \
+             The evaluation went from {} to {} for the student. \
+             Engine line after the best move: {}. Engine reply to the move played: {}. \
+             In two or three sentences a club player can follow, explain what was wrong \
+             with {} and what idea {} carries.",
+            self.fen,
+            self.played,
+            self.best,
+            self.eval_before,
+            self.eval_after,
+            self.best_line,
+            self.refutation,
+            self.played,
+            self.best
+        )
+    }
+
+    /// A plain comment from the engine's numbers alone, for when the
+    /// coach can't be reached
+    fn offline_comment(&self) -> String {
+        format!(
+            "{} costs about {:.1} pawns ({} to {}). {} was stronger, e.g. {}; after {} the engine answers {}.",
+            self.played,
+            self.loss as f64 / 100.0,
+            self.eval_before,
+            self.eval_after,
+            self.best,
+            self.best_line,
+            self.played,
+            self.refutation
+        )
+    }
+}
+
+/// Failed requests in a row after which the coach is left alone for the
+/// rest of the game
+#[cfg(feature = "coach")]
+const MAX_FAILURES: u32 = 2;
+
+/// Comments on the player's mistakes from an HTTP endpoint, typically a
+/// small proxy in front of a language model. Each key move is posted as
+/// JSON; the answer is either plain text or JSON with a `comment` field.
+/// Requests go through `curl` and are cut off after the timeout, and when
+/// the coach fails the comment is built from the engine's analysis instead.
+#[cfg(feature = "coach")]
+pub struct Coach {
+    url: String,
+    timeout: Duration,
+    failures: u32,
+}
+
+#[cfg(feature = "coach")]
+impl Coach {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(anyhow!("Coach endpoint must be an http(s) URL: {}", url));
+        }
+        Ok(Coach {
+            url: url.to_string(),
+            timeout,
+            failures: 0,
+        })
+    }
+
+    /// The coach's comment on a key move, or the offline one marked as
+    /// such when the coach is unreachable, slow or makes no sense
+    pub async fn comment(&mut self, key_move: &KeyMove) -> String {
+        if self.failures >= MAX_FAILURES {
+            return format!("{} (offline)", key_move.offline_comment());
+        }
+        match self.request(key_move).await {
+            Ok(comment) => {
+                self.failures = 0;
+                comment
+            }
+            Err(e) => {
+                self.failures += 1;
+                if self.failures == MAX_FAILURES {
+                    println!(
+                        "Coach unavailable ({}); using engine comments for the rest of the game.",
+                        e
+                    );
+                }
+                format!("{} (offline)", key_move.offline_comment())
+            }
+        }
+    }
+
+    async fn request(&self, key_move: &KeyMove) -> Result<String> {
+        use std::process::Stdio;
+        use tokio::io::AsyncWriteExt;
+        use tokio::process::Command;
+
+        let body = serde_json::to_string(key_move)?;
+        let mut child = Command::new("curl")
+            .args(["-fsS", "-X", "POST", "-H", "Content-Type: application/json"])
+            .args(["--max-time", &self.timeout.as_secs_f64().to_string()])
+            .args(["--data-binary", "@-", &self.url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("failed to run curl: {}", e))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("failed to pass the request to curl"))?;
+        stdin.write_all(body.as_bytes()).await?;
+        drop(stdin);
+
+        // curl keeps to --max-time; this is the backstop if it hangs anyway
+        let output = tokio::time::timeout(self.timeout * 2, child.wait_with_output())
+            .await
+            .map_err(|_| anyhow!("timed out"))??;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let comment = match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(value) if value.is_object() => value["comment"]
+                .as_str()
+                .ok_or_else(|| anyhow!("answer has no \"comment\""))?
+                .trim()
+                .to_string(),
+            _ => text,
+        };
+        if comment.is_empty() {
+            return Err(anyhow!("empty answer"));
+        }
+        Ok(comment)
+    }
+}
+
+/// Stand-in when minichess is built without the `coach` feature
+#[cfg(not(feature = "coach"))]
+pub struct Coach;
+
+#[cfg(not(feature = "coach"))]
+impl Coach {
+    pub fn new(_url: &str, _timeout: Duration) -> Result<Self> {
+        Err(anyhow!(
+            "This build has no coach support; rebuild with `cargo build --features coach`"
+        ))
+    }
+
+    pub async fn comment(&mut self, key_move: &KeyMove) -> String {
+        key_move.offline_comment()
+    }
+}
@@ -0,0 +1,160 @@
+use crate::chess_game::ChessGame;
+use crate::ui::{get_user_input, select};
+use anyhow::{Result, anyhow};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + 'a>>;
+/// Runs a command with the text typed after its name
+pub type CommandHandler = Rc<dyn for<'a> Fn(&'a mut ChessGame, &'a str) -> CommandFuture<'a>>;
+/// Whether a command applies in the game's current state
+pub type Availability = Rc<dyn Fn(&ChessGame) -> bool>;
+
+struct GameCommand {
+    name: String,
+    aliases: Vec<String>,
+    usage: String,
+    help: String,
+    handler: CommandHandler,
+    /// Shown in help and the palette only when this says so; always when None
+    available: Option<Availability>,
+}
+
+impl GameCommand {
+    fn applies(&self, game: &ChessGame) -> bool {
+        self.available
+            .as_ref()
+            .is_none_or(|available| available(game))
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        std::iter::once(&self.usage)
+            .chain(&self.aliases)
+            .chain(std::iter::once(&self.help))
+            .any(|text| text.to_lowercase().contains(query))
+    }
+}
+
+/// The in-game commands available at the move prompt. Built-in commands and
+/// script commands are registered here instead of being matched by hand, and
+/// the help screen and the command palette are generated from the
+/// registrations.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<GameCommand>,
+}
+
+impl CommandRegistry {
+    /// Register a command. `usage` starts with the command's name, e.g.
+    /// `"moves [--ranked]"`; the name and aliases must not be taken yet.
+    pub fn register(
+        &mut self,
+        usage: &str,
+        aliases: &[&str],
+        help: &str,
+        handler: impl for<'a> Fn(&'a mut ChessGame, &'a str) -> CommandFuture<'a> + 'static,
+    ) -> Result<()> {
+        let name = usage
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| anyhow!("A command needs a name"))?;
+        for word in std::iter::once(name).chain(aliases.iter().copied()) {
+            if self.find(word).is_some() {
+                return Err(anyhow!("Command '{}' is already registered", word));
+            }
+        }
+
+        self.commands.push(GameCommand {
+            name: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            usage: usage.to_string(),
+            help: help.to_string(),
+            handler: Rc::new(handler),
+            available: None,
+        });
+        Ok(())
+    }
+
+    /// List a command in help and the palette only while `available` holds,
+    /// e.g. `redo` after an undo. It can still be typed at any time.
+    pub fn only_when(
+        &mut self,
+        name: &str,
+        available: impl Fn(&ChessGame) -> bool + 'static,
+    ) -> Result<()> {
+        let command = self
+            .commands
+            .iter_mut()
+            .find(|c| c.name == name)
+            .ok_or_else(|| anyhow!("No command '{}' to restrict", name))?;
+        command.available = Some(Rc::new(available));
+        Ok(())
+    }
+
+    /// The handler for a line of input, with the arguments after the command name
+    pub fn lookup<'a>(&self, input: &'a str) -> Option<(CommandHandler, &'a str)> {
+        let (word, args) = input.split_once(' ').unwrap_or((input, ""));
+        self.find(word)
+            .map(|command| (command.handler.clone(), args.trim()))
+    }
+
+    /// One (usage, help) pair per command that applies right now, in
+    /// registration order, and the number of commands left out
+    pub fn help_lines(&self, game: &ChessGame) -> (Vec<(String, String)>, usize) {
+        let lines: Vec<(String, String)> = self
+            .commands
+            .iter()
+            .filter(|command| command.applies(game))
+            .map(|command| {
+                let spellings: Vec<String> = std::iter::once(&command.usage)
+                    .chain(&command.aliases)
+                    .map(|s| format!("'{}'", s))
+                    .collect();
+                (spellings.join(" or "), command.help.clone())
+            })
+            .collect();
+        let hidden = self.commands.len() - lines.len();
+        (lines, hidden)
+    }
+
+    /// Searchable menu of the commands that apply right now: the ones whose
+    /// name, alias or description contains the query. Returns the chosen
+    /// command with its arguments, asked for when its usage takes any.
+    pub fn palette(&self, game: &ChessGame) -> Result<Option<(CommandHandler, String)>> {
+        println!("\nSearch commands (part of a name or description, Enter lists all):");
+        let query = get_user_input()?.trim().to_lowercase();
+        let found: Vec<&GameCommand> = self
+            .commands
+            .iter()
+            .filter(|command| command.applies(game) && command.matches(&query))
+            .collect();
+        if found.is_empty() {
+            println!("No command matches '{}'.", query);
+            return Ok(None);
+        }
+
+        let labels: Vec<String> = found
+            .iter()
+            .map(|command| format!("{} - {}", command.usage, command.help))
+            .collect();
+        let Some(choice) = select("Run which command?", &labels)? else {
+            return Ok(None);
+        };
+        let command = found[choice];
+        let args = if command.usage.contains(' ') {
+            println!("Arguments: {} (Enter for none)", command.usage);
+            get_user_input()?.trim().to_string()
+        } else {
+            String::new()
+        };
+        Ok(Some((command.handler.clone(), args)))
+    }
+
+    fn find(&self, word: &str) -> Option<&GameCommand> {
+        self.commands.iter().find(|c| {
+            c.name.eq_ignore_ascii_case(word)
+                || c.aliases.iter().any(|a| a.eq_ignore_ascii_case(word))
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use crate::house_rules::HouseRules;
+use crate::install;
+use crate::piece_set::{self, PieceSet};
+use crate::storage;
+use crate::ui::{BoardStyle, Coordinates, Notation, get_user_input};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const CONFIG_FILE: &str = "config.json";
+
+/// Settings chosen in the setup wizard. They apply to every profile and are
+/// overridden by command-line options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub stockfish_path: Option<String>,
+    pub difficulty: Option<u8>,
+    /// "white" or "black"; asked before every game when unset
+    pub color: Option<String>,
+    pub notation: Notation,
+    pub board_style: BoardStyle,
+    /// Twelve glyphs, White's KQRBNP then Black's, for the "custom" board style
+    pub custom_pieces: Option<String>,
+    /// Show the move number, eval and whose turn it is in the terminal title
+    pub terminal_title: bool,
+    /// Positions kept for undo; older moves are replayed from the move list
+    /// when undone to. Unlimited when unset.
+    pub undo_states: Option<usize>,
+    /// Rule tweaks for casual games; `--house-rules` replaces them
+    pub house_rules: HouseRules,
+    /// Columns the terminal draws a chess symbol in, 1 or 2; guessed from
+    /// the terminal when unset
+    pub piece_width: Option<u8>,
+    /// Where the board's rank and file labels go; `--coords` overrides it
+    pub coordinates: Coordinates,
+    /// API token of a Lichess BOT account for `lichess-bot`; the
+    /// LICHESS_BOT_TOKEN environment variable takes precedence
+    pub lichess_token: Option<String>,
+    /// Input shortcuts: typing a name such as "!a" runs its commands,
+    /// separated by ";", e.g. "analyze 18; threat"
+    pub macros: BTreeMap<String, String>,
+    /// "CP:N": the computer resigns after N moves in a row at least CP
+    /// centipawns behind; `--resign-threshold` overrides it
+    pub resign_threshold: Option<String>,
+    /// HTTP endpoint that comments on the player's mistakes (needs the
+    /// coach feature); `--coach` overrides it
+    pub coach_url: Option<String>,
+    /// Seconds the coach gets to answer before the engine's own comment is
+    /// shown instead; 5 when unset
+    pub coach_timeout: Option<u64>,
+}
+
+pub fn path() -> Result<PathBuf> {
+    Ok(storage::data_dir()?.join(CONFIG_FILE))
+}
+
+/// The saved configuration, or None before the first run
+pub fn load() -> Result<Option<Config>> {
+    let path = path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+}
+
+pub fn save(config: &Config) -> Result<()> {
+    let path = path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_string_pretty(config)?)
+        .map_err(|e| anyhow!("Failed to write {}: {}", tmp_path.display(), e))?;
+    std::fs::rename(&tmp_path, &path)
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Ask for the engine, strength, color, notation, board style and terminal
+/// title, and save the answers
+pub fn wizard() -> Result<Config> {
+    println!("\n=== minichess setup ===");
+    println!("Press Enter to accept the default in [brackets].\n");
+
+    let stockfish_path = ask_engine()?;
+    let difficulty = loop {
+        let answer = ask("Computer strength, 1 (weakest) to 20 (strongest)", "5")?;
+        match answer.parse::<u8>() {
+            Ok(level @ 1..=20) => break level,
+            _ => println!("Please enter a number from 1 to 20."),
+        }
+    };
+    let color = match choose(
+        "Your color",
+        &["White", "Black", "Ask before every game"],
+        3,
+    )? {
+        1 => Some("white".to_string()),
+        2 => Some("black".to_string()),
+        _ => None,
+    };
+    let notation = match choose(
+        "Move notation for the computer's moves",
+        &["SAN (Nf3)", "Coordinates (g1f3)"],
+        1,
+    )? {
+        2 => Notation::Coordinate,
+        _ => Notation::San,
+    };
+    let (board_style, custom_pieces) = match choose(
+        "Board style",
+        &[
+            "Unicode pieces (♔ ♕ ♖ ♗ ♘ ♙)",
+            "Letters (K Q R B N P)",
+            "Custom glyphs",
+        ],
+        1,
+    )? {
+        2 => (BoardStyle::Ascii, None),
+        3 => (BoardStyle::Custom, Some(ask_piece_glyphs()?)),
+        _ => (BoardStyle::Unicode, None),
+    };
+    let terminal_title = ask(
+        "Show the move, eval and whose turn it is in the terminal title? (y/n)",
+        "n",
+    )?
+    .eq_ignore_ascii_case("y");
+
+    let config = Config {
+        stockfish_path,
+        difficulty: Some(difficulty),
+        color,
+        notation,
+        board_style,
+        custom_pieces,
+        terminal_title,
+        // Not asked by the wizard: keep what the file already says
+        ..load().ok().flatten().unwrap_or_default()
+    };
+    save(&config)?;
+    println!(
+        "\nSaved to {}. Run 'minichess setup' to change it.\n",
+        path()?.display()
+    );
+    Ok(config)
+}
+
+/// Twelve glyphs for the custom board style, checked before they are saved
+fn ask_piece_glyphs() -> Result<String> {
+    loop {
+        let answer = ask(
+            &format!("Glyphs for White's then Black's {}", piece_set::GLYPH_ORDER),
+            piece_set::GLYPH_ORDER,
+        )?;
+        match PieceSet::parse(&answer) {
+            Ok(_) => return Ok(answer),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn ask_engine() -> Result<Option<String>> {
+    if let Some(found) = locate_stockfish() {
+        let found = found.to_string_lossy().into_owned();
+        println!("Found Stockfish at {}", found);
+        let answer = ask("Use it? (y/n)", "y")?;
+        if !answer.eq_ignore_ascii_case("n") {
+            return Ok(Some(found));
+        }
+    } else {
+        println!("Stockfish was not found in your PATH.");
+        let answer = ask("Download the official build now? (y/n)", "y")?;
+        if !answer.eq_ignore_ascii_case("n") {
+            match install::install_stockfish(install::DEFAULT_RELEASE) {
+                Ok(binary) => return Ok(Some(binary.to_string_lossy().into_owned())),
+                Err(e) => println!("Download failed: {}", e),
+            }
+        }
+        println!("Get it from https://stockfishchess.org/download/ and enter its path.");
+    }
+
+    let answer = ask("Path to the Stockfish executable", "stockfish")?;
+    Ok(Some(answer))
+}
+
+/// Look for a Stockfish binary in PATH and a few usual install locations
+pub fn locate_stockfish() -> Option<PathBuf> {
+    let names: &[&str] = if cfg!(windows) {
+        &["stockfish.exe"]
+    } else {
+        &["stockfish"]
+    };
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    dirs.extend(
+        ["/usr/games", "/usr/local/bin", "/opt/homebrew/bin"]
+            .iter()
+            .map(PathBuf::from),
+    );
+
+    dirs.iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+fn ask(question: &str, default: &str) -> Result<String> {
+    println!("{} [{}]", question, default);
+    let answer = get_user_input()?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer.to_string()
+    })
+}
+
+/// Numbered menu; returns the 1-based choice
+fn choose(question: &str, options: &[&str], default: usize) -> Result<usize> {
+    println!("{}:", question);
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}. {}", i + 1, option);
+    }
+    loop {
+        let answer = ask("Choice", &default.to_string())?;
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return Ok(n),
+            _ => println!("Please enter a number from 1 to {}.", options.len()),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::pgn;
+use crate::stockfish::{EngineError, StockfishEngine};
+use anyhow::Result;
+use chess::{Board, BoardStatus, ChessMove};
+
+/// Depth of the first search and of the cross-evaluation of the candidates
+const CONSULT_DEPTH: u8 = 10;
+
+/// Let two engines agree on a move. Both search the position; if they pick
+/// different moves, each scores both candidates and votes for the one it
+/// likes better. A split vote goes to the higher combined score, and an exact
+/// tie to the first engine's choice.
+pub async fn choose_move(
+    first: &mut StockfishEngine,
+    second: &mut StockfishEngine,
+    board: &Board,
+) -> Result<ChessMove> {
+    if board.status() != BoardStatus::Ongoing {
+        return Err(EngineError::GameOver.into());
+    }
+
+    let go = format!("go depth {}", CONSULT_DEPTH);
+    let (first_result, second_result) =
+        tokio::try_join!(first.search(board, &go), second.search(board, &go))?;
+
+    let first_move = checked_move(board, first_result.best_move)?;
+    let second_move = checked_move(board, second_result.best_move)?;
+
+    if first_move == second_move {
+        println!(
+            "Both engines agree on {}",
+            pgn::move_to_san(board, first_move)
+        );
+        return Ok(first_move);
+    }
+
+    // Scores after a move are from the opponent's side, so negate them
+    let first_after = board.make_move_new(first_move);
+    let second_after = board.make_move_new(second_move);
+    let ((a_first, a_second), (b_first, b_second)) = tokio::try_join!(
+        async {
+            let score = first.evaluate(&first_after, CONSULT_DEPTH).await?;
+            let other = first.evaluate(&second_after, CONSULT_DEPTH).await?;
+            Ok::<_, anyhow::Error>((-score.as_centipawns(), -other.as_centipawns()))
+        },
+        async {
+            let score = second.evaluate(&first_after, CONSULT_DEPTH).await?;
+            let other = second.evaluate(&second_after, CONSULT_DEPTH).await?;
+            Ok::<_, anyhow::Error>((-score.as_centipawns(), -other.as_centipawns()))
+        },
+    )?;
+
+    let first_san = pgn::move_to_san(board, first_move);
+    let second_san = pgn::move_to_san(board, second_move);
+    println!(
+        "Engines disagree: {} ({:+.2} / {:+.2}) vs {} ({:+.2} / {:+.2})",
+        first_san,
+        a_first as f64 / 100.0,
+        b_first as f64 / 100.0,
+        second_san,
+        a_second as f64 / 100.0,
+        b_second as f64 / 100.0
+    );
+
+    let votes_for_first = (a_first >= a_second) as u8 + (b_first >= b_second) as u8;
+    let chosen = match votes_for_first {
+        2 => first_move,
+        0 => second_move,
+        _ if a_first + b_first > a_second + b_second => first_move,
+        _ if a_first + b_first < a_second + b_second => second_move,
+        _ => first_move,
+    };
+
+    println!(
+        "Consultation settles on {}",
+        if chosen == first_move {
+            first_san
+        } else {
+            second_san
+        }
+    );
+    Ok(chosen)
+}
+
+fn checked_move(board: &Board, best_move: Option<ChessMove>) -> Result<ChessMove> {
+    match best_move {
+        Some(chess_move) if board.legal(chess_move) => Ok(chess_move),
+        Some(chess_move) => Err(EngineError::IllegalMove(chess_move.to_string()).into()),
+        None => Err(EngineError::NoMove.into()),
+    }
+}
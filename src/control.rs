@@ -0,0 +1,180 @@
+use crate::editor;
+use crate::pgn;
+use crate::ui;
+use anyhow::{Result, anyhow};
+use chess::{Board, Color, MoveGen};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STATE: Mutex<Option<GameState>> = Mutex::new(None);
+
+/// What the control socket reports about the game in progress, as last
+/// noted by the game loop
+#[derive(Clone)]
+pub struct GameState {
+    pub fen: String,
+    /// The board as drawn, without colors
+    pub board: String,
+    /// Moves so far in SAN
+    pub moves: Vec<String>,
+    /// e.g. "white to move, you are white, move 12"
+    pub status: String,
+    pub pgn: String,
+    pub player: Color,
+    pub over: bool,
+}
+
+/// Commands answered on the socket itself; every other line is typed at the
+/// game's prompt
+const QUERIES: &[(&str, &str)] = &[
+    ("ping", "Check the connection"),
+    ("status", "Whose turn it is, your color and the move number"),
+    ("fen", "The current position as FEN"),
+    ("board", "The board as drawn, without colors"),
+    ("moves", "Moves played so far in SAN"),
+    ("legal", "Legal moves in the current position in SAN"),
+    ("pgn", "The game so far as PGN"),
+    (
+        "type LINE",
+        "Type LINE at the prompt, even when it is one of these",
+    ),
+    ("help", "This list"),
+];
+
+/// Whether a control socket is listening
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Keep the state the socket reports. Called by the game loop before every
+/// turn and prompt.
+pub fn note_game(state: GameState) {
+    if let Ok(mut current) = STATE.lock() {
+        *current = Some(state);
+    }
+}
+
+/// The state of the game in progress, if any
+pub fn game_state() -> Option<GameState> {
+    STATE.lock().ok().and_then(|state| state.clone())
+}
+
+/// The answer to one line from a client: the lines to send before `ok`, or
+/// the reason for an `error`
+fn answer(line: &str) -> Result<Vec<String>> {
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    if command == "ping" || command == "help" {
+        return Ok(match command {
+            "ping" => vec!["pong".to_string()],
+            _ => QUERIES
+                .iter()
+                .map(|(usage, help)| format!("{:<10} {}", usage, help))
+                .chain(["Anything else is typed at the prompt".to_string()])
+                .collect(),
+        });
+    }
+    let is_query = QUERIES
+        .iter()
+        .any(|(usage, _)| usage.split(' ').next() == Some(command));
+    if command == "type" || !is_query {
+        let typed = if command == "type" { args } else { line };
+        if typed.is_empty() {
+            return Err(anyhow!("Nothing to type"));
+        }
+        ui::type_line(typed);
+        return Ok(Vec::new());
+    }
+
+    let state = game_state().ok_or_else(|| anyhow!("No game in progress"))?;
+    Ok(match command {
+        "status" => vec![state.status],
+        "fen" => vec![state.fen],
+        "board" => state.board.lines().map(String::from).collect(),
+        "moves" => vec![state.moves.join(" ")],
+        "legal" => {
+            let board =
+                Board::from_str(&state.fen).map_err(|_| anyhow!("Bad position {}", state.fen))?;
+            let legal: Vec<String> = MoveGen::new_legal(&board)
+                .map(|chess_move| pgn::move_to_san(&board, chess_move))
+                .collect();
+            vec![legal.join(" ")]
+        }
+        _ => state.pgn.lines().map(String::from).collect(),
+    })
+}
+
+/// Listen on a Unix domain socket for line-based commands from editors,
+/// overlays and scripts. Each reply is any number of lines followed by `ok`
+/// or `error <reason>`. Lines starting with `{` are requests of the editor
+/// protocol instead, answered with one line of JSON each.
+#[cfg(unix)]
+pub fn start(path: &str, stockfish_path: &str) -> Result<()> {
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    // A socket left behind by an earlier run is in the way; anything else
+    // at that path is not ours to remove
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        use std::os::unix::fs::FileTypeExt;
+        if !metadata.file_type().is_socket() {
+            return Err(anyhow!("{} exists and is not a socket", path));
+        }
+        std::fs::remove_file(path).map_err(|e| anyhow!("Failed to remove {}: {}", path, e))?;
+    }
+    let listener =
+        UnixListener::bind(path).map_err(|e| anyhow!("Failed to listen on {}: {}", path, e))?;
+    ui::share_input();
+    ENABLED.store(true, Ordering::SeqCst);
+    println!("Control socket listening on {}", path);
+
+    let analyst = Arc::new(tokio::sync::Mutex::new(editor::Analyst::new(
+        stockfish_path,
+    )));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let analyst = analyst.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                let mut session = editor::Session::default();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let mut reply = String::new();
+                    if line.starts_with('{') {
+                        reply = editor::handle(line, &mut session, &analyst).await;
+                        reply.push('\n');
+                    } else {
+                        match answer(line) {
+                            Ok(body) => {
+                                for text in body {
+                                    reply.push_str(&text);
+                                    reply.push('\n');
+                                }
+                                reply.push_str("ok\n");
+                            }
+                            Err(e) => reply.push_str(&format!("error {}\n", e)),
+                        }
+                    }
+                    if writer.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Stand-in on systems without Unix domain sockets
+#[cfg(not(unix))]
+pub fn start(_path: &str, _stockfish_path: &str) -> Result<()> {
+    Err(anyhow!(
+        "--control-socket needs Unix domain sockets, which this system lacks"
+    ))
+}
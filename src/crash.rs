@@ -0,0 +1,167 @@
+use crate::config;
+use crate::storage;
+use crate::ui::get_user_input;
+use anyhow::{Result, anyhow};
+use chrono::Local;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Engine lines kept for a crash report
+const TRANSCRIPT_LINES: usize = 200;
+/// Config keys whose values are never written to a report
+const SECRET_WORDS: [&str; 4] = ["token", "key", "secret", "password"];
+
+static TRANSCRIPT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static GAME: Mutex<Option<GameSnapshot>> = Mutex::new(None);
+
+/// The game in progress, as last noted by the game loop
+struct GameSnapshot {
+    fen: String,
+    pgn: String,
+}
+
+/// Keep a line of the UCI conversation; `>` for what was sent to the
+/// engine, `<` for what it answered
+pub fn log_uci(direction: char, line: &str) {
+    if let Ok(mut transcript) = TRANSCRIPT.lock() {
+        if transcript.len() == TRANSCRIPT_LINES {
+            transcript.pop_front();
+        }
+        transcript.push_back(format!("{} {}", direction, line.trim_end()));
+    }
+}
+
+/// Remember the current game so a report can reproduce it. Called by the
+/// game loop before every turn.
+pub fn note_game(fen: String, pgn: String) {
+    if let Ok(mut game) = GAME.lock() {
+        *game = Some(GameSnapshot { fen, pgn });
+    }
+}
+
+/// After an error that ended a game, offer to write a diagnostic bundle for
+/// a bug report. Errors before any game started, such as a mistyped
+/// option, don't ask.
+pub fn offer_report(error: &anyhow::Error) {
+    let game_started = GAME.lock().is_ok_and(|game| game.is_some());
+    if !game_started || !std::io::stdin().is_terminal() {
+        return;
+    }
+    eprintln!("\nError: {:#}", error);
+    println!("This looks like a bug. Write a crash report to attach to an issue? (y/n)");
+    let answer = get_user_input().unwrap_or_default();
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        return;
+    }
+    match write_report(error) {
+        Ok(path) => println!(
+            "Crash report written to {}. Please look it over before sharing.",
+            path.display()
+        ),
+        Err(e) => println!("Could not write the crash report: {}", e),
+    }
+}
+
+fn write_report(error: &anyhow::Error) -> Result<PathBuf> {
+    let mut text = String::new();
+    text.push_str("# minichess crash report\n\n");
+    text.push_str(&format!(
+        "Version: {}\nOS: {} {}\nTime: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        Local::now().format("%Y-%m-%d %H:%M:%S %:z")
+    ));
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .map(|arg| redact_text(&arg))
+        .collect();
+    text.push_str(&format!("Arguments: {}\n", args.join(" ")));
+
+    text.push_str("\n## Error\n\n");
+    for (i, cause) in error.chain().enumerate() {
+        text.push_str(&format!(
+            "{}{}\n",
+            if i == 0 { "" } else { "caused by: " },
+            cause
+        ));
+    }
+
+    if let Ok(game) = GAME.lock()
+        && let Some(game) = game.as_ref()
+    {
+        text.push_str(&format!(
+            "\n## Position\n\n{}\n\n## Game\n\n{}\n",
+            game.fen, game.pgn
+        ));
+    }
+
+    text.push_str("\n## Config\n\n");
+    text.push_str(&redacted_config().unwrap_or_else(|e| format!("({})", e)));
+    text.push('\n');
+
+    text.push_str("\n## Recent engine transcript\n\n");
+    if let Ok(transcript) = TRANSCRIPT.lock() {
+        for line in transcript.iter() {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    let dir = storage::data_dir()?.join("crash-reports");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!(
+        "crash-{}.txt",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::write(&path, text)
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The config file as saved, with secrets and remote engine addresses
+/// blanked out
+fn redacted_config() -> Result<String> {
+    let path = config::path()?;
+    if !path.exists() {
+        return Ok("(no config file)".to_string());
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let mut value: Value =
+        serde_json::from_str(&text).map_err(|e| anyhow!("Invalid config file: {}", e))?;
+    redact(&mut value);
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SECRET_WORDS.iter().any(|word| key.contains(word)) && !value.is_null() {
+                    *value = Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::String(text) => *text = redact_text(text),
+        _ => {}
+    }
+}
+
+/// Remote engine specs name a user and host; keep only the kind of link
+fn redact_text(text: &str) -> String {
+    for scheme in ["ssh:", "tcp:"] {
+        if let Some(at) = text.find(scheme) {
+            return format!("{}{}<redacted>", &text[..at], scheme);
+        }
+    }
+    text.to_string()
+}
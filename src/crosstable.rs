@@ -0,0 +1,259 @@
+use crate::chess_game::GameOutcome;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+/// z-score of a two-sided 95% confidence interval
+const Z_95: f64 = 1.959964;
+
+/// Results of a match or tournament between named players
+#[derive(Debug, Default, Serialize)]
+pub struct Crosstable {
+    pub players: Vec<String>,
+    pub games: Vec<TableGame>,
+}
+
+/// One game: player indexes for White and Black, and the PGN result
+#[derive(Debug, Clone, Serialize)]
+pub struct TableGame {
+    pub white: usize,
+    pub black: usize,
+    pub result: String,
+}
+
+/// Elo difference of the first player over the second, with its 95% error
+/// margin and likelihood of superiority
+#[derive(Debug, Serialize)]
+pub struct EloEstimate {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub score: f64,
+    pub elo: f64,
+    pub error: f64,
+    pub los: f64,
+    /// Frequencies of game-pair scores 0, ½, 1, 1½ and 2, when the error
+    /// comes from the pentanomial model
+    pub pentanomial: Option<[u32; 5]>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    #[serde(flatten)]
+    table: &'a Crosstable,
+    scores: Vec<f64>,
+    elo: Option<EloEstimate>,
+}
+
+impl Crosstable {
+    pub fn new(players: Vec<String>) -> Self {
+        Crosstable {
+            players,
+            games: Vec::new(),
+        }
+    }
+
+    /// Record a finished game; unfinished games are left out
+    pub fn add(&mut self, white: usize, black: usize, outcome: GameOutcome) {
+        if outcome != GameOutcome::Unfinished {
+            self.games.push(TableGame {
+                white,
+                black,
+                result: outcome.pgn_result().to_string(),
+            });
+        }
+    }
+
+    /// Points of `player` in `game`, None if they did not play in it
+    fn points(game: &TableGame, player: usize) -> Option<f64> {
+        let white_points = match game.result.as_str() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            _ => 0.5,
+        };
+        if game.white == player {
+            Some(white_points)
+        } else if game.black == player {
+            Some(1.0 - white_points)
+        } else {
+            None
+        }
+    }
+
+    pub fn scores(&self) -> Vec<f64> {
+        (0..self.players.len())
+            .map(|player| {
+                self.games
+                    .iter()
+                    .filter_map(|game| Self::points(game, player))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Elo estimate for a two-player match. With `pentanomial`, consecutive
+    /// games are treated as pairs played from the same opening with colors
+    /// reversed, which gives tighter and more honest error bars.
+    pub fn elo_estimate(&self, pentanomial: bool) -> Option<EloEstimate> {
+        if self.players.len() != 2 || self.games.is_empty() {
+            return None;
+        }
+        let points: Vec<f64> = self
+            .games
+            .iter()
+            .filter_map(|game| Self::points(game, 0))
+            .collect();
+        let count = |value: f64| points.iter().filter(|&&p| p == value).count() as u32;
+        let (wins, draws, losses) = (count(1.0), count(0.5), count(0.0));
+        let n = points.len() as f64;
+        let score = points.iter().sum::<f64>() / n;
+
+        // Standard error of the mean score, per game or per game pair
+        let (std_error, frequencies) = if pentanomial && points.len() >= 2 {
+            let pairs: Vec<f64> = points.chunks_exact(2).map(|p| p[0] + p[1]).collect();
+            let mut frequencies = [0u32; 5];
+            for pair in &pairs {
+                frequencies[(pair * 2.0).round() as usize] += 1;
+            }
+            let mean = pairs.iter().sum::<f64>() / pairs.len() as f64;
+            let variance =
+                pairs.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / pairs.len() as f64;
+            // A pair is worth two games, so halve its deviation
+            (
+                (variance / pairs.len() as f64).sqrt() / 2.0,
+                Some(frequencies),
+            )
+        } else {
+            let variance = points.iter().map(|p| (p - score).powi(2)).sum::<f64>() / n;
+            ((variance / n).sqrt(), None)
+        };
+
+        let low = elo_from_score(score - Z_95 * std_error);
+        let high = elo_from_score(score + Z_95 * std_error);
+        let decisive = (wins + losses) as f64;
+        let los = if decisive == 0.0 {
+            0.5
+        } else {
+            0.5 * (1.0 + erf((wins as f64 - losses as f64) / (2.0 * decisive).sqrt()))
+        };
+
+        Some(EloEstimate {
+            wins,
+            draws,
+            losses,
+            score,
+            elo: elo_from_score(score),
+            error: (high - low) / 2.0,
+            los,
+            pentanomial: frequencies,
+        })
+    }
+
+    /// Crosstable with one row per player, sorted by score, and for two
+    /// players the Elo estimate below it
+    pub fn print(&self, pentanomial: bool) {
+        let scores = self.scores();
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let width = self.players.iter().map(|p| p.len()).max().unwrap_or(0);
+
+        println!("\n=== Crosstable ===");
+        for (rank, &player) in order.iter().enumerate() {
+            let results: Vec<String> = order
+                .iter()
+                .map(|&opponent| {
+                    if opponent == player {
+                        return "x".to_string();
+                    }
+                    let text: String = self
+                        .games
+                        .iter()
+                        .filter(|g| g.white == opponent || g.black == opponent)
+                        .filter_map(|game| Self::points(game, player))
+                        .map(|p| {
+                            if p == 1.0 {
+                                '1'
+                            } else if p == 0.0 {
+                                '0'
+                            } else {
+                                '='
+                            }
+                        })
+                        .collect();
+                    if text.is_empty() {
+                        "-".to_string()
+                    } else {
+                        text
+                    }
+                })
+                .collect();
+            println!(
+                "{:>2}. {:<width$}  {:>5}  {}",
+                rank + 1,
+                self.players[player],
+                scores[player],
+                results.join("  "),
+                width = width
+            );
+        }
+
+        if let Some(estimate) = self.elo_estimate(pentanomial) {
+            println!(
+                "\n{} vs {}: +{} ={} -{} ({:.1}%)",
+                self.players[0],
+                self.players[1],
+                estimate.wins,
+                estimate.draws,
+                estimate.losses,
+                estimate.score * 100.0
+            );
+            println!(
+                "Elo difference: {} ± {:.1} (95%), LOS {:.1}%",
+                format_elo(estimate.elo),
+                estimate.error,
+                estimate.los * 100.0
+            );
+            if let Some(pairs) = estimate.pentanomial {
+                println!(
+                    "Game pairs [0, ½, 1, 1½, 2]: [{}]",
+                    pairs.map(|n| n.to_string()).join(", ")
+                );
+            }
+        }
+        println!();
+    }
+
+    /// The crosstable, scores and Elo estimate as JSON
+    pub fn write_json(&self, path: &str, pentanomial: bool) -> Result<()> {
+        let report = Report {
+            table: self,
+            scores: self.scores(),
+            elo: self.elo_estimate(pentanomial),
+        };
+        let text = serde_json::to_string_pretty(&report)?;
+        std::fs::write(path, text).map_err(|e| anyhow!("Failed to write {}: {}", path, e))
+    }
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn format_elo(elo: f64) -> String {
+    if elo.abs() > 1000.0 {
+        // A perfect or zero score has no finite estimate
+        if elo > 0.0 { "+inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{:+.1}", elo)
+    }
+}
+
+/// Error function (Abramowitz and Stegun 7.1.26, accurate to 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - polynomial * (-x * x).exp();
+    if x < 0.0 { -value } else { value }
+}
@@ -0,0 +1,121 @@
+use crate::exchange::{name, value};
+use crate::overlay::attackers;
+use crate::pgn::piece_char;
+use chess::{BitBoard, Board, Color, EMPTY, Piece, Square};
+
+/// What a move changed, one short line per kind of change: material, check,
+/// enemy pieces it attacks, own pieces left under attack, and own pieces
+/// that gained or lost their last defender. Works from the attack maps of
+/// the two positions, so it needs no engine.
+pub fn describe(before: &Board, after: &Board) -> Vec<String> {
+    let mover = before.side_to_move();
+    let opponent = !mover;
+    let mut lines = Vec::new();
+
+    let balance_before = material(before);
+    let balance_after = material(after);
+    if balance_after != balance_before {
+        let captured: Vec<&str> = (*before.color_combined(opponent)
+            & !*after.color_combined(opponent))
+        .into_iter()
+        .filter_map(|square| before.piece_on(square))
+        .map(name)
+        .collect();
+        let mut line = String::from("Material: ");
+        if !captured.is_empty() {
+            line.push_str(&format!(
+                "{:?} wins a {}, ",
+                mover,
+                captured.join(" and a ")
+            ));
+        }
+        line.push_str(&match balance_after {
+            0 => "now even".to_string(),
+            b if b > 0 => format!("White +{}", b),
+            b => format!("Black +{}", -b),
+        });
+        lines.push(line);
+    }
+
+    if *after.checkers() != EMPTY {
+        lines.push(format!("{:?} gives check", mover));
+    }
+
+    // Enemy pieces stay put while the mover moves, so a piece attacked now
+    // and not before is a new target
+    let targets: Vec<String> = pieces(after, opponent)
+        .filter(|&square| {
+            attackers(after, square, mover) != EMPTY && attackers(before, square, mover) == EMPTY
+        })
+        .map(|square| {
+            let undefended = attackers(after, square, opponent) == EMPTY;
+            format!(
+                "{}{}",
+                label(after, square),
+                if undefended { " (undefended)" } else { "" }
+            )
+        })
+        .collect();
+    if !targets.is_empty() {
+        lines.push(format!("New threats: {}", targets.join(", ")));
+    }
+
+    // The moved piece stands somewhere new, so it counts as newly attacked
+    // wherever it is attacked
+    let moved = *after.color_combined(mover) & !*before.color_combined(mover);
+    let exposed: Vec<String> = pieces(after, mover)
+        .filter(|&square| {
+            attackers(after, square, opponent) != EMPTY
+                && (moved & BitBoard::from_square(square) != EMPTY
+                    || attackers(before, square, opponent) == EMPTY)
+        })
+        .map(|square| label(after, square))
+        .collect();
+    if !exposed.is_empty() {
+        lines.push(format!("Now attacked: {}", exposed.join(", ")));
+    }
+
+    let stayed =
+        || pieces(after, mover).filter(|&square| moved & BitBoard::from_square(square) == EMPTY);
+    let defended = |board: &Board, square: Square| attackers(board, square, mover) != EMPTY;
+    let lost: Vec<String> = stayed()
+        .filter(|&square| defended(before, square) && !defended(after, square))
+        .map(|square| label(after, square))
+        .collect();
+    if !lost.is_empty() {
+        lines.push(format!("No longer defended: {}", lost.join(", ")));
+    }
+    let gained: Vec<String> = stayed()
+        .filter(|&square| !defended(before, square) && defended(after, square))
+        .map(|square| label(after, square))
+        .collect();
+    if !gained.is_empty() {
+        lines.push(format!("Newly defended: {}", gained.join(", ")));
+    }
+    lines
+}
+
+/// White's material minus Black's, in pawns
+fn material(board: &Board) -> i32 {
+    let side = |color: Color| -> i32 {
+        pieces(board, color)
+            .filter_map(|square| board.piece_on(square))
+            .map(value)
+            .sum()
+    };
+    side(Color::White) - side(Color::Black)
+}
+
+/// Squares of `color`'s pieces other than the king
+fn pieces(board: &Board, color: Color) -> impl Iterator<Item = Square> {
+    (*board.color_combined(color) & !*board.pieces(Piece::King)).into_iter()
+}
+
+/// "Nc6", or "pawn on e5"
+fn label(board: &Board, square: Square) -> String {
+    match board.piece_on(square) {
+        Some(Piece::Pawn) => format!("pawn on {}", square),
+        Some(piece) => format!("{}{}", piece_char(piece), square),
+        None => square.to_string(),
+    }
+}
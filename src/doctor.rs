@@ -0,0 +1,244 @@
+use crate::book::Book;
+use crate::config;
+use crate::stockfish::StockfishEngine;
+use crate::storage;
+use crate::terminal;
+use crate::ui;
+use anyhow::Result;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Handshakes slower than this are flagged; engines that load a network
+/// (lc0) are expected to take longer
+const SLOW_HANDSHAKE: Duration = Duration::from_secs(2);
+const SLOW_PING: Duration = Duration::from_millis(200);
+
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn ok(&self, message: &str) {
+        println!("  [ok]   {}", message);
+    }
+
+    fn warn(&mut self, message: &str) {
+        self.warnings += 1;
+        println!("  [warn] {}", message);
+    }
+
+    fn fail(&mut self, message: &str) {
+        self.failures += 1;
+        println!("  [FAIL] {}", message);
+    }
+}
+
+/// Check everything minichess depends on and print a diagnosis, suitable
+/// for pasting into a bug report
+pub async fn run(stockfish_path: &str, weights: Option<&str>, book: Option<&str>) -> Result<()> {
+    let mut report = Report::default();
+    println!(
+        "minichess {} on {} ({})",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    println!("\nEngine");
+    check_engine(&mut report, stockfish_path).await;
+
+    println!("\nTerminal");
+    check_terminal(&mut report);
+
+    println!("\nConfiguration");
+    check_config(&mut report, weights, book);
+
+    println!("\nData directory");
+    check_data_dir(&mut report);
+
+    println!();
+    match (report.failures, report.warnings) {
+        (0, 0) => println!("Everything looks good."),
+        (0, warnings) => println!("No problems found, {} warning(s).", warnings),
+        (failures, warnings) => println!(
+            "{} problem(s) and {} warning(s) found, see [FAIL] lines above.",
+            failures, warnings
+        ),
+    }
+    Ok(())
+}
+
+async fn check_engine(report: &mut Report, stockfish_path: &str) {
+    let started = Instant::now();
+    let mut engine = match StockfishEngine::new(stockfish_path).await {
+        Ok(engine) => engine,
+        Err(e) => {
+            report.fail(&format!("Cannot start engine '{}': {}", stockfish_path, e));
+            return;
+        }
+    };
+    let handshake = started.elapsed();
+
+    let name = if engine.name().is_empty() {
+        "unnamed engine"
+    } else {
+        engine.name()
+    };
+    report.ok(&format!("Engine '{}' found: {}", stockfish_path, name));
+
+    let message = format!("UCI handshake took {} ms", handshake.as_millis());
+    if handshake > SLOW_HANDSHAKE && !engine.is_lc0() {
+        report.warn(&format!("{} (slow)", message));
+    } else {
+        report.ok(&message);
+    }
+
+    match engine.ping().await {
+        Ok(ping) if ping > SLOW_PING => report.warn(&format!(
+            "isready round trip took {} ms (slow)",
+            ping.as_millis()
+        )),
+        Ok(ping) => report.ok(&format!("isready round trip took {} ms", ping.as_millis())),
+        Err(e) => report.fail(&format!("Engine stopped answering: {}", e)),
+    }
+
+    if !engine.supports_option("Skill Level") && !engine.is_lc0() {
+        report.warn("Engine has no 'Skill Level' option, --difficulty will limit nodes instead");
+    }
+}
+
+fn check_terminal(report: &mut Report) {
+    if std::io::stdout().is_terminal() {
+        report.ok("Output is a terminal");
+    } else {
+        report.warn("Output is not a terminal: live status lines and auto-analysis are disabled");
+    }
+
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    if cfg!(windows) {
+        let console = terminal::console();
+        if console.ansi {
+            report.ok("Console interprets color codes (virtual terminal processing)");
+        } else {
+            report
+                .warn("Console ignores color codes: use Windows Terminal or Windows 10 and later");
+        }
+        if console.unicode {
+            report.ok("Console uses the UTF-8 code page: ♔ ♛ ┌─┐");
+        } else {
+            report.warn("Console can't switch to UTF-8: pieces are shown as letters");
+        }
+    } else if locale.to_uppercase().replace('-', "").contains("UTF8") {
+        report.ok(&format!("Unicode locale ({}): ♔ ♛ ┌─┐", locale));
+    } else {
+        report.warn(&format!(
+            "Locale '{}' may not be UTF-8; chess pieces and board lines could show as garbage",
+            locale
+        ));
+    }
+
+    let width = ui::piece_width();
+    report.ok(&format!(
+        "Chess symbols are padded as {} column(s) wide; if the board's lines don't meet, \
+         try --piece-width {}",
+        width,
+        3 - width
+    ));
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if colorterm.contains("truecolor") || colorterm.contains("24bit") || term.contains("256color") {
+        report.ok(&format!(
+            "256 colors supported (TERM={}): \x1b[48;5;33m  \x1b[48;5;229m  \x1b[48;5;160m  \x1b[0m",
+            term
+        ));
+    } else if term.is_empty() || term == "dumb" {
+        report
+            .warn("No color support detected (TERM is unset or dumb); heatmaps will be unreadable");
+    } else {
+        report.warn(&format!(
+            "TERM={} may not support 256 colors used by heatmaps and move arrows",
+            term
+        ));
+    }
+}
+
+fn check_config(report: &mut Report, weights: Option<&str>, book: Option<&str>) {
+    match config::load() {
+        Ok(Some(_)) => report.ok("Config file is valid"),
+        Ok(None) => report.warn("No config file yet, run 'minichess setup'"),
+        Err(e) => report.fail(&e.to_string()),
+    }
+    match storage::profile_dir() {
+        Ok(dir) => {
+            report.ok(&format!("Player profile '{}'", storage::profile_name()));
+            check_data_files(report, &dir);
+        }
+        Err(e) => report.fail(&format!("Cannot locate profile directory: {}", e)),
+    }
+
+    if let Some(weights) = weights {
+        if Path::new(weights).is_file() {
+            report.ok(&format!("Weights file {}", weights));
+        } else {
+            report.fail(&format!("Weights file {} does not exist", weights));
+        }
+    }
+    if let Some(book) = book {
+        match Book::load(book) {
+            Ok(_) => report.ok(&format!("Opening book {}", book)),
+            Err(e) => report.fail(&e.to_string()),
+        }
+    }
+}
+
+/// Every saved data file must still parse, or the feature using it fails
+fn check_data_files(report: &mut Report, dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        report.ok("No saved data yet");
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string())
+            });
+        match parsed {
+            Ok(_) => report.ok(&format!("{} is valid", path.display())),
+            Err(e) => report.fail(&format!("{} is corrupt: {}", path.display(), e)),
+        }
+    }
+}
+
+fn check_data_dir(report: &mut Report) {
+    let dir = match storage::data_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            report.fail(&e.to_string());
+            return;
+        }
+    };
+
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        report.fail(&format!("Cannot create {}: {}", dir.display(), e));
+        return;
+    }
+    let probe = dir.join(".doctor-probe");
+    match std::fs::write(&probe, b"ok") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            report.ok(&format!("{} is writable", dir.display()));
+        }
+        Err(e) => report.fail(&format!("{} is not writable: {}", dir.display(), e)),
+    }
+}
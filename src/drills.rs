@@ -0,0 +1,246 @@
+use crate::pgn;
+use crate::review;
+use crate::stockfish::{Score, StockfishEngine};
+use crate::storage;
+use crate::ui::{display_board_for_player, get_user_input};
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+const STATS_FILE: &str = "drill_stats.json";
+/// Depth used to verify that the mate is still on after each move
+const VERIFY_DEPTH: u8 = 16;
+
+pub struct MatePuzzle<'a> {
+    pub pattern: &'a str,
+    pub fen: &'a str,
+    pub mate_in: u8,
+    pub solution: &'a str,
+}
+
+const PUZZLES: &[MatePuzzle<'static>] = &[
+    MatePuzzle {
+        pattern: "back-rank",
+        fen: "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+        mate_in: 1,
+        solution: "1. Ra8#",
+    },
+    MatePuzzle {
+        pattern: "back-rank",
+        fen: "r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1",
+        mate_in: 1,
+        solution: "1... Ra1#",
+    },
+    MatePuzzle {
+        pattern: "back-rank",
+        fen: "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1",
+        mate_in: 2,
+        solution: "1. Rd8+ Rxd8 2. Rxd8#",
+    },
+    MatePuzzle {
+        pattern: "smothered",
+        fen: "6rk/6pp/8/6N1/8/8/8/6K1 w - - 0 1",
+        mate_in: 1,
+        solution: "1. Nf7#",
+    },
+    MatePuzzle {
+        pattern: "smothered",
+        fen: "r6k/6pp/7N/8/2Q5/8/8/6K1 w - - 0 1",
+        mate_in: 2,
+        solution: "1. Qg8+ Rxg8 2. Nf7#",
+    },
+    MatePuzzle {
+        pattern: "smothered",
+        fen: "5rk1/5Npp/8/8/2Q5/8/8/6K1 w - - 0 1",
+        mate_in: 3,
+        solution: "1. Nh6+ Kh8 2. Qg8+ Rxg8 3. Nf7#",
+    },
+    MatePuzzle {
+        pattern: "arabian",
+        fen: "7k/1R6/5N2/8/8/8/8/6K1 w - - 0 1",
+        mate_in: 1,
+        solution: "1. Rh7#",
+    },
+    MatePuzzle {
+        pattern: "anastasia",
+        fen: "8/4N1pk/8/8/8/8/8/K2R4 w - - 0 1",
+        mate_in: 1,
+        solution: "1. Rh1#",
+    },
+    MatePuzzle {
+        pattern: "boden",
+        fen: "2kr4/p2n4/8/8/5B2/8/4B3/6K1 w - - 0 1",
+        mate_in: 1,
+        solution: "1. Ba6#",
+    },
+    MatePuzzle {
+        pattern: "damiano",
+        fen: "5rk1/5p2/6P1/7Q/8/8/8/6K1 w - - 0 1",
+        mate_in: 1,
+        solution: "1. Qh7#",
+    },
+    MatePuzzle {
+        pattern: "ladder",
+        fen: "4k3/8/8/8/8/8/R7/1R4K1 w - - 0 1",
+        mate_in: 2,
+        solution: "1. Ra7 Kd8 2. Rb8#",
+    },
+];
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PatternStats {
+    pub attempts: u32,
+    pub solved: u32,
+}
+
+pub type DrillStats = BTreeMap<String, PatternStats>;
+
+pub fn patterns() -> Vec<&'static str> {
+    let mut patterns: Vec<&'static str> = PUZZLES.iter().map(|p| p.pattern).collect();
+    patterns.dedup();
+    patterns
+}
+
+/// Run a session of checkmate drills, optionally restricted to one pattern
+pub async fn run(stockfish_path: &str, pattern: Option<&str>) -> Result<()> {
+    let mut puzzles: Vec<&MatePuzzle<'static>> = PUZZLES
+        .iter()
+        .filter(|p| pattern.is_none_or(|name| p.pattern == name))
+        .collect();
+    if puzzles.is_empty() {
+        return Err(anyhow!(
+            "Unknown pattern '{}', available patterns: {}",
+            pattern.unwrap_or_default(),
+            patterns().join(", ")
+        ));
+    }
+    puzzles.shuffle(&mut rand::rng());
+
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    engine.set_difficulty(20).await?;
+
+    let mut stats: DrillStats = storage::load(STATS_FILE)?;
+
+    println!("\n=== Checkmate Drills ===");
+    println!("Find the mate. Enter moves in SAN (Qh7#) or coordinates (h5h7), 'q' to stop.");
+
+    for puzzle in puzzles {
+        let solved = match play_puzzle(&mut engine, puzzle).await? {
+            Some(solved) => solved,
+            None => break,
+        };
+
+        let entry = stats.entry(puzzle.pattern.to_string()).or_default();
+        entry.attempts += 1;
+        if solved {
+            entry.solved += 1;
+        } else {
+            review::add_failed_puzzle(puzzle)?;
+        }
+        storage::save(STATS_FILE, &stats)?;
+    }
+
+    print_stats(&stats);
+    Ok(())
+}
+
+/// Play one puzzle. Returns None if the player quit.
+pub async fn play_puzzle(
+    engine: &mut StockfishEngine,
+    puzzle: &MatePuzzle<'_>,
+) -> Result<Option<bool>> {
+    let mut board = Board::from_str(puzzle.fen)
+        .map_err(|_| anyhow!("Invalid drill position: {}", puzzle.fen))?;
+    let solver = board.side_to_move();
+
+    println!(
+        "\n--- {} mate, mate in {} ---",
+        pattern_name(puzzle.pattern),
+        puzzle.mate_in
+    );
+    display_board_for_player(&board, solver);
+
+    for moves_left in (1..=puzzle.mate_in).rev() {
+        let chess_move = loop {
+            println!("\nYour move:");
+            let input = get_user_input()?;
+            let input = input.trim();
+            if input.eq_ignore_ascii_case("q") || input.eq_ignore_ascii_case("quit") {
+                return Ok(None);
+            }
+            match pgn::parse_move(&board, input) {
+                Ok(chess_move) => break chess_move,
+                Err(e) => println!("Invalid move: {}. Try again.", e),
+            }
+        };
+
+        println!("You played {}", pgn::move_to_san(&board, chess_move));
+        board = board.make_move_new(chess_move);
+
+        if board.status() == BoardStatus::Checkmate {
+            println!("Checkmate! Puzzle solved.");
+            return Ok(Some(true));
+        }
+
+        // The mate must still be forced within the remaining moves
+        let still_mating = moves_left > 1
+            && match engine.evaluate(&board, VERIFY_DEPTH).await? {
+                Score::Mate(n) => n < 0 && -n < moves_left as i32,
+                Score::Centipawns(_) => false,
+            };
+        if !still_mating {
+            println!("That doesn't force mate. Solution: {}", puzzle.solution);
+            return Ok(Some(false));
+        }
+
+        let reply = engine.get_best_move(&board).await?;
+        println!("Opponent plays {}", pgn::move_to_san(&board, reply));
+        board = board.make_move_new(reply);
+        display_board_for_player(&board, solver);
+    }
+
+    println!("Out of moves. Solution: {}", puzzle.solution);
+    Ok(Some(false))
+}
+
+pub fn print_stats(stats: &DrillStats) {
+    println!("\n=== Drill Success Rates ===");
+    if stats.is_empty() {
+        println!("No drills attempted yet.");
+    }
+    for (pattern, entry) in stats {
+        let rate = if entry.attempts > 0 {
+            entry.solved as f64 * 100.0 / entry.attempts as f64
+        } else {
+            0.0
+        };
+        println!(
+            "{:<12} {:>3}/{:<3} solved ({:.0}%)",
+            pattern_name(pattern),
+            entry.solved,
+            entry.attempts,
+            rate
+        );
+    }
+    println!("===========================\n");
+}
+
+pub fn load_stats() -> Result<DrillStats> {
+    storage::load(STATS_FILE)
+}
+
+pub fn pattern_name(pattern: &str) -> &str {
+    match pattern {
+        "back-rank" => "Back rank",
+        "smothered" => "Smothered",
+        "arabian" => "Arabian",
+        "anastasia" => "Anastasia's",
+        "boden" => "Boden's",
+        "damiano" => "Damiano's",
+        "ladder" => "Ladder",
+        other => other,
+    }
+}
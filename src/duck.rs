@@ -0,0 +1,494 @@
+use crate::exchange::value;
+use crate::ui::{display_cells, piece_set};
+use crate::variant::{Outcome, Variant};
+use anyhow::{Result, anyhow};
+use chess::{
+    ALL_SQUARES, BitBoard, Board, ChessMove, Color, EMPTY, File, Piece, Rank, Square,
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+};
+use rand::seq::IteratorRandom;
+use std::str::FromStr;
+
+const PROMOTIONS: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+/// Score of a move that takes the king, above any material
+const KING_CAPTURE: i32 = 1000;
+
+/// A Duck chess position. The `chess` crate's board can't hold it: kings
+/// may be left en prise and captured, and the duck blocks a square.
+#[derive(Clone)]
+pub struct DuckPosition {
+    squares: [Option<(Piece, Color)>; 64],
+    side: Color,
+    /// Kingside and queenside castling rights of White, then Black
+    castling: [(bool, bool); 2],
+    /// Square a pawn can capture onto en passant
+    en_passant: Option<Square>,
+    duck: Option<Square>,
+    /// Side whose king has been captured
+    king_taken: Option<Color>,
+}
+
+/// A piece move followed by the duck's new square
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DuckMove {
+    pub piece_move: ChessMove,
+    pub duck: Square,
+}
+
+impl DuckPosition {
+    fn from_board(board: &Board) -> Self {
+        let mut squares = [None; 64];
+        for square in *board.combined() {
+            squares[square.to_index()] = board.piece_on(square).zip(board.color_on(square));
+        }
+        let rights = |color| {
+            let rights = board.castle_rights(color);
+            (rights.has_kingside(), rights.has_queenside())
+        };
+        DuckPosition {
+            squares,
+            side: board.side_to_move(),
+            castling: [rights(Color::White), rights(Color::Black)],
+            en_passant: None,
+            duck: None,
+            king_taken: None,
+        }
+    }
+
+    fn piece_on(&self, square: Square) -> Option<(Piece, Color)> {
+        self.squares[square.to_index()]
+    }
+
+    fn pieces_of(&self, color: Color) -> BitBoard {
+        ALL_SQUARES
+            .iter()
+            .filter(|square| matches!(self.piece_on(**square), Some((_, c)) if c == color))
+            .fold(EMPTY, |bits, square| bits | BitBoard::from_square(*square))
+    }
+
+    fn duck_bits(&self) -> BitBoard {
+        self.duck.map_or(EMPTY, BitBoard::from_square)
+    }
+
+    /// Squares with a piece or the duck on them
+    fn occupied(&self) -> BitBoard {
+        self.pieces_of(Color::White) | self.pieces_of(Color::Black) | self.duck_bits()
+    }
+
+    /// Every move of the side to move, with the duck as a blocker. There is
+    /// no check in Duck chess, so a king may step into attack.
+    fn piece_moves(&self) -> Vec<ChessMove> {
+        let us = self.side;
+        let occupied = self.occupied();
+        let own = self.pieces_of(us);
+        let enemy = self.pieces_of(!us);
+        let blocked = own | self.duck_bits();
+        let mut moves = Vec::new();
+
+        for from in own {
+            let Some((piece, _)) = self.piece_on(from) else {
+                continue;
+            };
+            let targets = match piece {
+                Piece::Pawn => {
+                    let mut targets = get_pawn_attacks(from, us, enemy);
+                    if let Some(ep) = self.en_passant {
+                        targets |=
+                            get_pawn_attacks(from, us, BitBoard::from_square(ep)) & !occupied;
+                    }
+                    if let Some(one) = from.forward(us)
+                        && occupied & BitBoard::from_square(one) == EMPTY
+                    {
+                        targets |= BitBoard::from_square(one);
+                        let start_rank = if us == Color::White {
+                            Rank::Second
+                        } else {
+                            Rank::Seventh
+                        };
+                        if from.get_rank() == start_rank
+                            && let Some(two) = one.forward(us)
+                            && occupied & BitBoard::from_square(two) == EMPTY
+                        {
+                            targets |= BitBoard::from_square(two);
+                        }
+                    }
+                    targets
+                }
+                Piece::Knight => get_knight_moves(from) & !blocked,
+                Piece::Bishop => get_bishop_moves(from, occupied) & !blocked,
+                Piece::Rook => get_rook_moves(from, occupied) & !blocked,
+                Piece::Queen => {
+                    (get_bishop_moves(from, occupied) | get_rook_moves(from, occupied)) & !blocked
+                }
+                Piece::King => get_king_moves(from) & !blocked,
+            };
+            for to in targets {
+                let last_rank = if us == Color::White {
+                    Rank::Eighth
+                } else {
+                    Rank::First
+                };
+                if piece == Piece::Pawn && to.get_rank() == last_rank {
+                    for promotion in PROMOTIONS {
+                        moves.push(ChessMove::new(from, to, Some(promotion)));
+                    }
+                } else {
+                    moves.push(ChessMove::new(from, to, None));
+                }
+            }
+        }
+        moves.extend(self.castling_moves(occupied));
+        moves
+    }
+
+    /// Castling needs only the rights and empty squares between king and
+    /// rook (the duck counts as a piece); attacked squares don't matter
+    fn castling_moves(&self, occupied: BitBoard) -> Vec<ChessMove> {
+        let us = self.side;
+        let rank = back_rank(us);
+        let king = Square::make_square(rank, File::E);
+        if self.piece_on(king) != Some((Piece::King, us)) {
+            return Vec::new();
+        }
+        let (kingside, queenside) = self.castling[us.to_index()];
+        let empty = |files: &[File]| {
+            files.iter().all(|&file| {
+                occupied & BitBoard::from_square(Square::make_square(rank, file)) == EMPTY
+            })
+        };
+        let rook_on =
+            |file| self.piece_on(Square::make_square(rank, file)) == Some((Piece::Rook, us));
+        let mut moves = Vec::new();
+        if kingside && rook_on(File::H) && empty(&[File::F, File::G]) {
+            moves.push(ChessMove::new(
+                king,
+                Square::make_square(rank, File::G),
+                None,
+            ));
+        }
+        if queenside && rook_on(File::A) && empty(&[File::B, File::C, File::D]) {
+            moves.push(ChessMove::new(
+                king,
+                Square::make_square(rank, File::C),
+                None,
+            ));
+        }
+        moves
+    }
+
+    /// The position after the piece move, before the duck is placed
+    fn after_piece_move(&self, chess_move: ChessMove) -> Self {
+        let mut next = self.clone();
+        let (from, to) = (chess_move.get_source(), chess_move.get_dest());
+        let Some((piece, us)) = self.piece_on(from) else {
+            return next;
+        };
+
+        if let Some((Piece::King, color)) = self.piece_on(to) {
+            next.king_taken = Some(color);
+        }
+        if piece == Piece::Pawn && Some(to) == self.en_passant && self.piece_on(to).is_none() {
+            let taken = Square::make_square(from.get_rank(), to.get_file());
+            next.squares[taken.to_index()] = None;
+        }
+        if piece == Piece::King && from.get_file() == File::E {
+            let rank = from.get_rank();
+            let rook_move = match to.get_file() {
+                File::G => Some((File::H, File::F)),
+                File::C => Some((File::A, File::D)),
+                _ => None,
+            };
+            if let Some((rook_from, rook_to)) = rook_move {
+                next.squares[Square::make_square(rank, rook_from).to_index()] = None;
+                next.squares[Square::make_square(rank, rook_to).to_index()] =
+                    Some((Piece::Rook, us));
+            }
+        }
+        next.squares[from.to_index()] = None;
+        next.squares[to.to_index()] = Some((chess_move.get_promotion().unwrap_or(piece), us));
+
+        next.en_passant = None;
+        if piece == Piece::Pawn
+            && from
+                .get_rank()
+                .to_index()
+                .abs_diff(to.get_rank().to_index())
+                == 2
+        {
+            next.en_passant = from.forward(us);
+        }
+        // A king or rook leaving home, or a rook captured there, ends castling on that side
+        for square in [from, to] {
+            for color in [Color::White, Color::Black] {
+                let rank = back_rank(color);
+                let rights = &mut next.castling[color.to_index()];
+                if square == Square::make_square(rank, File::E) {
+                    *rights = (false, false);
+                } else if square == Square::make_square(rank, File::H) {
+                    rights.0 = false;
+                } else if square == Square::make_square(rank, File::A) {
+                    rights.1 = false;
+                }
+            }
+        }
+        next
+    }
+
+    /// Put the duck down and hand the move to the other side
+    fn with_duck(&self, duck: Square) -> Self {
+        let mut next = self.clone();
+        next.duck = Some(duck);
+        next.side = !self.side;
+        next
+    }
+
+    /// Squares the duck may go to after a piece move: empty ones other than
+    /// the square it stands on
+    fn duck_squares(&self) -> Vec<Square> {
+        let occupied = self.occupied();
+        ALL_SQUARES
+            .iter()
+            .copied()
+            .filter(|&square| occupied & BitBoard::from_square(square) == EMPTY)
+            .collect()
+    }
+
+    /// Whether a piece of `by` attacks `square`, the duck blocking lines
+    fn attacked(&self, square: Square, by: Color) -> bool {
+        let occupied = self.occupied();
+        let own = self.pieces_of(by);
+        let of = |pieces: &[Piece]| {
+            own.into_iter()
+                .filter(|&s| matches!(self.piece_on(s), Some((p, _)) if pieces.contains(&p)))
+                .fold(EMPTY, |bits, s| bits | BitBoard::from_square(s))
+        };
+        get_pawn_attacks(square, !by, of(&[Piece::Pawn])) != EMPTY
+            || get_knight_moves(square) & of(&[Piece::Knight]) != EMPTY
+            || get_bishop_moves(square, occupied) & of(&[Piece::Bishop, Piece::Queen]) != EMPTY
+            || get_rook_moves(square, occupied) & of(&[Piece::Rook, Piece::Queen]) != EMPTY
+            || get_king_moves(square) & of(&[Piece::King]) != EMPTY
+    }
+
+    /// Value of the best capture available to the side to move
+    fn best_capture(&self) -> i32 {
+        self.piece_moves()
+            .iter()
+            .filter_map(|m| self.piece_on(m.get_dest()))
+            .map(|(piece, _)| capture_value(piece))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn back_rank(color: Color) -> Rank {
+    if color == Color::White {
+        Rank::First
+    } else {
+        Rank::Eighth
+    }
+}
+
+fn capture_value(piece: Piece) -> i32 {
+    if piece == Piece::King {
+        KING_CAPTURE
+    } else {
+        value(piece)
+    }
+}
+
+/// Duck chess: after moving a piece, the player moves the duck to any
+/// empty square, where it blocks both sides. No engine plays it, so the
+/// computer is a simple one-move lookahead.
+pub struct DuckChess;
+
+impl Variant for DuckChess {
+    type Position = DuckPosition;
+    type Move = DuckMove;
+
+    fn name(&self) -> &'static str {
+        "Duck Chess"
+    }
+
+    fn rules(&self) -> &'static str {
+        "After every move, put the duck (D) on an empty square other than its current one. \
+         Nothing can move onto or through the duck. There is no check: capture the king to win, \
+         and a player with no moves wins too."
+    }
+
+    fn input_help(&self) -> &'static str {
+        "a move in coordinates then the duck's square, e.g. 'e2e4 @d5' or 'e7e8q@a3'"
+    }
+
+    fn start(&self) -> DuckPosition {
+        DuckPosition::from_board(&Board::default())
+    }
+
+    fn side_to_move(&self, position: &DuckPosition) -> Color {
+        position.side
+    }
+
+    fn legal_moves(&self, position: &DuckPosition) -> Vec<DuckMove> {
+        position
+            .piece_moves()
+            .into_iter()
+            .flat_map(|piece_move| {
+                let after = position.after_piece_move(piece_move);
+                after
+                    .duck_squares()
+                    .into_iter()
+                    .filter(move |&duck| Some(duck) != position.duck)
+                    .map(move |duck| DuckMove { piece_move, duck })
+            })
+            .collect()
+    }
+
+    fn list_moves(&self, position: &DuckPosition) -> Vec<String> {
+        position
+            .piece_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect()
+    }
+
+    fn parse_move(&self, position: &DuckPosition, text: &str) -> Result<DuckMove> {
+        let text = text.to_lowercase();
+        let (piece_text, duck_text) = match text.split_once('@') {
+            Some((piece, duck)) => (piece.trim(), duck.trim()),
+            None => text
+                .split_once(' ')
+                .map_or((text.as_str(), ""), |(piece, duck)| {
+                    (piece.trim(), duck.trim())
+                }),
+        };
+        let mut piece_move = ChessMove::from_str(piece_text)
+            .map_err(|_| anyhow!("'{}' is not a move like e2e4", piece_text))?;
+        let moves = position.piece_moves();
+        // A pawn reaching the last rank becomes a queen unless told otherwise
+        if piece_move.get_promotion().is_none()
+            && moves.contains(&ChessMove::new(
+                piece_move.get_source(),
+                piece_move.get_dest(),
+                Some(Piece::Queen),
+            ))
+        {
+            piece_move = ChessMove::new(
+                piece_move.get_source(),
+                piece_move.get_dest(),
+                Some(Piece::Queen),
+            );
+        }
+        if !moves.contains(&piece_move) {
+            return Err(anyhow!("{} is not a legal move here", piece_move));
+        }
+        if duck_text.is_empty() {
+            return Err(anyhow!(
+                "Say where the duck goes too, e.g. '{} @d5'",
+                piece_move
+            ));
+        }
+        let duck = Square::from_str(duck_text)
+            .map_err(|_| anyhow!("'{}' is not a square for the duck", duck_text))?;
+        if Some(duck) == position.duck {
+            return Err(anyhow!("The duck must move to a different square"));
+        }
+        if !position
+            .after_piece_move(piece_move)
+            .duck_squares()
+            .contains(&duck)
+        {
+            return Err(anyhow!("The duck can only go to an empty square"));
+        }
+        Ok(DuckMove { piece_move, duck })
+    }
+
+    fn play(&self, position: &DuckPosition, chess_move: DuckMove) -> DuckPosition {
+        position
+            .after_piece_move(chess_move.piece_move)
+            .with_duck(chess_move.duck)
+    }
+
+    fn outcome(&self, position: &DuckPosition) -> Option<Outcome> {
+        if let Some(loser) = position.king_taken {
+            return Some(Outcome::Win(!loser, "the king was captured".to_string()));
+        }
+        if position.piece_moves().is_empty() {
+            return Some(Outcome::Win(
+                position.side,
+                "no moves left, which wins in Duck chess".to_string(),
+            ));
+        }
+        None
+    }
+
+    fn move_text(&self, _position: &DuckPosition, chess_move: DuckMove) -> String {
+        format!("{}@{}", chess_move.piece_move, chess_move.duck)
+    }
+
+    fn display(&self, position: &DuckPosition, perspective: Color) {
+        let pieces = piece_set();
+        display_cells(perspective, |square| {
+            if Some(square) == position.duck {
+                "\x1b[43m\x1b[30m D \x1b[0m".to_string()
+            } else {
+                match position.piece_on(square) {
+                    Some((piece, color)) => format!(" {} ", pieces.glyph(piece, color)),
+                    None => "   ".to_string(),
+                }
+            }
+        });
+    }
+
+    /// Take the most material that can't simply be taken back, then put the
+    /// duck where it leaves the opponent the least to capture
+    async fn computer_move(&mut self, position: &DuckPosition) -> Result<DuckMove> {
+        let us = position.side;
+        let scored: Vec<(i32, ChessMove)> = position
+            .piece_moves()
+            .into_iter()
+            .map(|piece_move| {
+                let after = position.after_piece_move(piece_move);
+                let gain = position
+                    .piece_on(piece_move.get_dest())
+                    .map_or(0, |(piece, _)| capture_value(piece));
+                let risk = match after.piece_on(piece_move.get_dest()) {
+                    Some((piece, _)) if after.attacked(piece_move.get_dest(), !us) => {
+                        capture_value(piece)
+                    }
+                    _ => 0,
+                };
+                (gain - risk, piece_move)
+            })
+            .collect();
+        let best = scored
+            .iter()
+            .map(|(score, _)| *score)
+            .max()
+            .ok_or_else(|| anyhow!("No moves to play"))?;
+        let piece_move = scored
+            .iter()
+            .filter(|(score, _)| *score == best)
+            .map(|(_, m)| *m)
+            .choose(&mut rand::rng())
+            .ok_or_else(|| anyhow!("No moves to play"))?;
+
+        let after = position.after_piece_move(piece_move);
+        let placements: Vec<(i32, Square)> = after
+            .duck_squares()
+            .into_iter()
+            .filter(|&duck| Some(duck) != position.duck)
+            .map(|duck| (after.with_duck(duck).best_capture(), duck))
+            .collect();
+        let least = placements
+            .iter()
+            .map(|(threat, _)| *threat)
+            .min()
+            .unwrap_or(0);
+        let duck = placements
+            .iter()
+            .filter(|(threat, _)| *threat == least)
+            .map(|(_, duck)| *duck)
+            .choose(&mut rand::rng())
+            .ok_or_else(|| anyhow!("No square for the duck"))?;
+        Ok(DuckMove { piece_move, duck })
+    }
+}
@@ -0,0 +1,259 @@
+use crate::pgn;
+use chess::Board;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// ECO code, name and moves of the openings recognized, from broad families
+/// down to common main lines
+const OPENINGS: &[(&str, &str, &str)] = &[
+    ("A00", "Hungarian Opening", "g3"),
+    ("A00", "Polish Opening", "b4"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A04", "Réti Opening", "Nf3"),
+    ("A05", "Réti Opening", "Nf3 Nf6"),
+    ("A06", "Réti Opening", "Nf3 d5"),
+    ("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    ("A10", "English Opening", "c4"),
+    ("A13", "English Opening", "c4 e6"),
+    ("A15", "English Opening: Anglo-Indian", "c4 Nf6"),
+    ("A20", "English Opening: Reversed Sicilian", "c4 e5"),
+    ("A30", "English Opening: Symmetrical", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A40", "Englund Gambit", "d4 e5"),
+    ("A43", "Old Benoni Defense", "d4 c5"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A46", "Indian Defense", "d4 Nf6 Nf3"),
+    ("A50", "Indian Defense", "d4 Nf6 c4"),
+    ("A51", "Budapest Gambit", "d4 Nf6 c4 e5"),
+    ("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("B00", "King's Pawn Game", "e4"),
+    ("B00", "Nimzowitsch Defense", "e4 Nc6"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    ("B02", "Alekhine's Defense", "e4 Nf6"),
+    ("B06", "Modern Defense", "e4 g6"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    (
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e4 c6 d4 d5 e5",
+    ),
+    (
+        "B13",
+        "Caro-Kann Defense: Exchange Variation",
+        "e4 c6 d4 d5 exd5 cxd5",
+    ),
+    ("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3"),
+    (
+        "B18",
+        "Caro-Kann Defense: Classical Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5",
+    ),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    (
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e4 c5 d4 cxd4 c3",
+    ),
+    ("B22", "Sicilian Defense: Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defense: Closed", "e4 c5 Nc3"),
+    ("B27", "Sicilian Defense", "e4 c5 Nf3"),
+    ("B30", "Sicilian Defense", "e4 c5 Nf3 Nc6"),
+    (
+        "B30",
+        "Sicilian Defense: Rossolimo Variation",
+        "e4 c5 Nf3 Nc6 Bb5",
+    ),
+    (
+        "B32",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4",
+    ),
+    (
+        "B33",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6",
+    ),
+    ("B40", "Sicilian Defense: French Variation", "e4 c5 Nf3 e6"),
+    ("B50", "Sicilian Defense", "e4 c5 Nf3 d6"),
+    ("B54", "Sicilian Defense: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    (
+        "B56",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3",
+    ),
+    (
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    (
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    ("C00", "French Defense", "e4 e6"),
+    (
+        "C01",
+        "French Defense: Exchange Variation",
+        "e4 e6 d4 d5 exd5",
+    ),
+    ("C02", "French Defense: Advance Variation", "e4 e6 d4 d5 e5"),
+    (
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e4 e6 d4 d5 Nd2",
+    ),
+    ("C10", "French Defense", "e4 e6 d4 d5 Nc3"),
+    (
+        "C11",
+        "French Defense: Classical Variation",
+        "e4 e6 d4 d5 Nc3 Nf6",
+    ),
+    (
+        "C15",
+        "French Defense: Winawer Variation",
+        "e4 e6 d4 d5 Nc3 Bb4",
+    ),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C51", "Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C53", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3"),
+    ("C55", "Two Knights Defense", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    (
+        "C57",
+        "Two Knights Defense: Knight Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5",
+    ),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    (
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    ("C70", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4"),
+    (
+        "C77",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6",
+    ),
+    (
+        "C78",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O",
+    ),
+    (
+        "C84",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D00", "London System", "d4 d5 Bf4"),
+    ("D02", "Queen's Pawn Game", "d4 d5 Nf3"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    (
+        "D07",
+        "Queen's Gambit Declined: Chigorin Defense",
+        "d4 d5 c4 Nc6",
+    ),
+    (
+        "D08",
+        "Queen's Gambit Declined: Albin Countergambit",
+        "d4 d5 c4 e5",
+    ),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D35", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3 Nf6"),
+    ("D43", "Semi-Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6"),
+    ("D80", "Grünfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("E00", "Indian Defense", "d4 Nf6 c4 e6"),
+    ("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3 d5 Bg2"),
+    ("E10", "Indian Defense", "d4 Nf6 c4 e6 Nf3"),
+    ("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    ("E70", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6"),
+    (
+        "E90",
+        "King's Indian Defense: Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3",
+    ),
+];
+
+/// The named opening a game reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Classification {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Plies into the game at which the opening's position was reached; the
+    /// move after it is where the game left the book line
+    pub ply: usize,
+}
+
+/// Position key of every opening line and the line's length in plies
+fn positions() -> &'static HashMap<u64, (usize, &'static str, &'static str)> {
+    static POSITIONS: OnceLock<HashMap<u64, (usize, &'static str, &'static str)>> = OnceLock::new();
+    POSITIONS.get_or_init(|| {
+        let mut positions = HashMap::new();
+        for &(code, name, moves) in OPENINGS {
+            let mut board = Board::default();
+            let mut plies = 0;
+            for san in moves.split_whitespace() {
+                let chess_move = pgn::parse_san(&board, san)
+                    .unwrap_or_else(|e| panic!("Bad ECO line {} {}: {}", code, moves, e));
+                board = board.make_move_new(chess_move);
+                plies += 1;
+            }
+            positions.insert(board.get_hash(), (plies, code, name));
+        }
+        positions
+    })
+}
+
+/// Name the opening of a game given as SAN moves from the initial position:
+/// the longest known line whose position the game reaches, so transpositions
+/// are recognized. None when the game leaves every known line at once.
+pub fn classify(moves: &[String]) -> Option<Classification> {
+    let positions = positions();
+    let mut board = Board::default();
+    let mut best: Option<(usize, Classification)> = None;
+    for (ply, san) in moves.iter().enumerate() {
+        let Ok(chess_move) = pgn::parse_san(&board, san) else {
+            break;
+        };
+        board = board.make_move_new(chess_move);
+        if let Some(&(plies, code, name)) = positions.get(&board.get_hash())
+            && best.is_none_or(|(longest, _)| plies > longest)
+        {
+            best = Some((
+                plies,
+                Classification {
+                    code,
+                    name,
+                    ply: ply + 1,
+                },
+            ));
+        }
+    }
+    best.map(|(_, classification)| classification)
+}
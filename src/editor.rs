@@ -0,0 +1,258 @@
+use crate::control;
+use crate::fen;
+use crate::pgn;
+use crate::stockfish::StockfishEngine;
+use crate::ui;
+use chess::{Board, ChessMove, MoveGen};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::str::FromStr;
+use tokio::sync::Mutex;
+
+/// Versions of the editor protocol this build speaks, oldest first
+pub const PROTOCOL_VERSIONS: &[u32] = &[1];
+/// What a client may ask for in protocol version 1
+const CAPABILITIES: &[&str] = &[
+    "get_board",
+    "legal_moves",
+    "make_move",
+    "analyze",
+    "command",
+];
+/// Search depth of `analyze` when the client gives none, and the most it
+/// may ask for
+const DEFAULT_DEPTH: u8 = 16;
+const MAX_DEPTH: u8 = 30;
+
+/// One request: a JSON object on a line of its own
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Why a request failed: a stable code for programs and a message for people
+struct Failure {
+    code: &'static str,
+    message: String,
+}
+
+fn fail(code: &'static str, message: impl Into<String>) -> Failure {
+    Failure {
+        code,
+        message: message.into(),
+    }
+}
+
+/// What one connection has agreed on
+#[derive(Default)]
+pub struct Session {
+    version: Option<u32>,
+}
+
+/// An engine of its own for `analyze`, started on first use, so analysis
+/// never disturbs the engine the game is played against
+pub struct Analyst {
+    stockfish_path: String,
+    engine: Option<StockfishEngine>,
+}
+
+impl Analyst {
+    pub fn new(stockfish_path: &str) -> Self {
+        Analyst {
+            stockfish_path: stockfish_path.to_string(),
+            engine: None,
+        }
+    }
+}
+
+/// Answer one request line with one response line (without the newline):
+/// `{"id": .., "result": {..}}` or `{"id": .., "error": {"code": .., "message": ..}}`
+pub async fn handle(line: &str, session: &mut Session, analyst: &Mutex<Analyst>) -> String {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return json!({
+                "id": Value::Null,
+                "error": {"code": "parse_error", "message": e.to_string()},
+            })
+            .to_string();
+        }
+    };
+    let response = match dispatch(&request, session, analyst).await {
+        Ok(result) => json!({"id": request.id, "result": result}),
+        Err(failure) => json!({
+            "id": request.id,
+            "error": {"code": failure.code, "message": failure.message},
+        }),
+    };
+    response.to_string()
+}
+
+async fn dispatch(
+    request: &Request,
+    session: &mut Session,
+    analyst: &Mutex<Analyst>,
+) -> Result<Value, Failure> {
+    if request.method == "hello" {
+        return hello(&request.params, session);
+    }
+    if session.version.is_none() {
+        return Err(fail(
+            "handshake_required",
+            "Send \"hello\" with the protocol versions you speak first",
+        ));
+    }
+    match request.method.as_str() {
+        "get_board" => {
+            let state = game_state()?;
+            Ok(json!({
+                "fen": state.fen,
+                "board": state.board,
+                "turn": ui::color_name(board_of(&state)?.side_to_move()).to_lowercase(),
+                "player": ui::color_name(state.player).to_lowercase(),
+                "moves": state.moves,
+                "status": state.status,
+                "over": state.over,
+            }))
+        }
+        "legal_moves" => {
+            let board = board_of(&game_state()?)?;
+            let moves: Vec<Value> = MoveGen::new_legal(&board)
+                .map(|chess_move| move_json(&board, chess_move))
+                .collect();
+            Ok(json!({"moves": moves}))
+        }
+        "make_move" => make_move(&request.params),
+        "analyze" => analyze(&request.params, analyst).await,
+        "command" => {
+            let line = string_param(&request.params, "line")?;
+            ui::type_line(line);
+            Ok(json!({"queued": true}))
+        }
+        other => Err(fail("unknown_method", format!("No method \"{}\"", other))),
+    }
+}
+
+/// Agree on the newest version both sides speak; the client lists its
+/// versions in `versions`
+fn hello(params: &Value, session: &mut Session) -> Result<Value, Failure> {
+    let offered: Vec<u32> = params["versions"]
+        .as_array()
+        .ok_or_else(|| fail("invalid_params", "\"versions\" must be a list of numbers"))?
+        .iter()
+        .filter_map(|version| version.as_u64())
+        .map(|version| version as u32)
+        .collect();
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .rev()
+        .find(|version| offered.contains(version))
+        .copied()
+        .ok_or_else(|| {
+            fail(
+                "unsupported_version",
+                format!(
+                    "This minichess speaks protocol versions {:?}",
+                    PROTOCOL_VERSIONS
+                ),
+            )
+        })?;
+    session.version = Some(version);
+    Ok(json!({
+        "version": version,
+        "server": format!("minichess {}", env!("CARGO_PKG_VERSION")),
+        "capabilities": CAPABILITIES,
+    }))
+}
+
+/// Check a move against the position and type it at the player's prompt
+fn make_move(params: &Value) -> Result<Value, Failure> {
+    let text = string_param(params, "move")?;
+    let state = game_state()?;
+    let board = board_of(&state)?;
+    if state.over {
+        return Err(fail("game_over", "The game is over"));
+    }
+    if board.side_to_move() != state.player {
+        return Err(fail("not_your_turn", "It is the computer's move"));
+    }
+    let chess_move =
+        pgn::parse_move(&board, text).map_err(|e| fail("illegal_move", e.to_string()))?;
+    ui::type_line(&chess_move.to_string());
+    let mut result = move_json(&board, chess_move);
+    result["queued"] = json!(true);
+    Ok(result)
+}
+
+/// The analysis engine's view of the game's position, or of `fen` when given
+async fn analyze(params: &Value, analyst: &Mutex<Analyst>) -> Result<Value, Failure> {
+    let depth = match &params["depth"] {
+        Value::Null => DEFAULT_DEPTH,
+        value => value
+            .as_u64()
+            .filter(|depth| (1..=MAX_DEPTH as u64).contains(depth))
+            .ok_or_else(|| {
+                fail(
+                    "invalid_params",
+                    format!("\"depth\" must be a number from 1 to {}", MAX_DEPTH),
+                )
+            })? as u8,
+    };
+    let board = match params["fen"].as_str() {
+        Some(text) => fen::parse_fen(text).map_err(|e| fail("invalid_params", e.to_string()))?,
+        None => board_of(&game_state()?)?,
+    };
+
+    let mut analyst = analyst.lock().await;
+    if analyst.engine.is_none() {
+        let mut engine = StockfishEngine::new(&analyst.stockfish_path)
+            .await
+            .map_err(|e| fail("engine_error", e.to_string()))?;
+        engine
+            .set_difficulty(20)
+            .await
+            .map_err(|e| fail("engine_error", e.to_string()))?;
+        analyst.engine = Some(engine);
+    }
+    let Some(engine) = analyst.engine.as_mut() else {
+        return Err(fail("engine_error", "No analysis engine"));
+    };
+    let result = match engine.analyze(&board, &format!("go depth {}", depth)).await {
+        Ok(result) => result,
+        Err(e) => {
+            // Start afresh next time
+            analyst.engine = None;
+            return Err(fail("engine_error", e.to_string()));
+        }
+    };
+    Ok(json!({
+        "depth": result.depth,
+        "score": result.score.map(|score| score.for_white(board.side_to_move()).to_string()),
+        "best": result.best_move.map(|chess_move| move_json(&board, chess_move)),
+        "pv": pgn::line_to_san(&board, &result.pv)
+            .split_whitespace()
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn game_state() -> Result<control::GameState, Failure> {
+    control::game_state().ok_or_else(|| fail("no_game", "No game in progress"))
+}
+
+fn board_of(state: &control::GameState) -> Result<Board, Failure> {
+    Board::from_str(&state.fen).map_err(|_| fail("no_game", format!("Bad position {}", state.fen)))
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, Failure> {
+    params[name]
+        .as_str()
+        .ok_or_else(|| fail("invalid_params", format!("\"{}\" must be a string", name)))
+}
+
+fn move_json(board: &Board, chess_move: ChessMove) -> Value {
+    json!({"uci": chess_move.to_string(), "san": pgn::move_to_san(board, chess_move)})
+}
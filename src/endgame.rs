@@ -0,0 +1,262 @@
+use crate::chess_game::{ChessGame, GameOptions, GameOutcome};
+use crate::pgn::piece_char;
+use crate::stats;
+use crate::stockfish::StockfishEngine;
+use anyhow::{Result, anyhow};
+use chess::{ALL_SQUARES, Board, BoardStatus, Color, MoveGen, Piece, Rank, Square};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::str::FromStr;
+
+/// Depth of the engine's estimate of a generated position's result, and of
+/// its search for every move. This is an engine judgement, not a tablebase.
+const ASSESSMENT_DEPTH: u8 = 20;
+/// Engine scores (for the stronger side) treated as a clear win or a dead draw
+const WIN_THRESHOLD: i32 = 300;
+const DRAW_THRESHOLD: i32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndgameType {
+    KPvK,
+    KRvK,
+    KQvKR,
+}
+
+impl FromStr for EndgameType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_uppercase().as_str() {
+            "KPVK" => Ok(EndgameType::KPvK),
+            "KRVK" => Ok(EndgameType::KRvK),
+            "KQVKR" => Ok(EndgameType::KQvKR),
+            _ => Err(anyhow!(
+                "Unknown endgame type '{}', expected KPvK, KRvK or KQvKR",
+                s
+            )),
+        }
+    }
+}
+
+impl EndgameType {
+    fn name(&self) -> &'static str {
+        match self {
+            EndgameType::KPvK => "King and Pawn vs King",
+            EndgameType::KRvK => "King and Rook vs King",
+            EndgameType::KQvKR => "King and Queen vs King and Rook",
+        }
+    }
+
+    /// Pieces besides the kings, for the stronger and the defending side
+    fn material(&self) -> (&'static [Piece], &'static [Piece]) {
+        match self {
+            EndgameType::KPvK => (&[Piece::Pawn], &[]),
+            EndgameType::KRvK => (&[Piece::Rook], &[]),
+            EndgameType::KQvKR => (&[Piece::Queen], &[Piece::Rook]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpectedResult {
+    Win,
+    Draw,
+}
+
+/// Play out a random position of the chosen endgame class against Stockfish at full skill and depth
+pub async fn run(
+    stockfish_path: &str,
+    endgame: EndgameType,
+    defend: bool,
+    options: GameOptions,
+) -> Result<()> {
+    let mut rng = rand::rng();
+    let strong_side = if rng.random_bool(0.5) {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let player_color = if defend { !strong_side } else { strong_side };
+
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    engine.set_difficulty(20).await?;
+    engine.set_min_depth(ASSESSMENT_DEPTH);
+
+    println!("Generating a {} position...", endgame.name());
+
+    // Keep generating until the engine gives a clear verdict for the stronger side
+    let (board, expected) = loop {
+        let board = match random_position(&mut rng, endgame, strong_side, player_color) {
+            Some(board) => board,
+            None => continue,
+        };
+
+        let score = engine
+            .evaluate(&board, ASSESSMENT_DEPTH)
+            .await?
+            .as_centipawns();
+        let strong_score = if board.side_to_move() == strong_side {
+            score
+        } else {
+            -score
+        };
+
+        if strong_score >= WIN_THRESHOLD {
+            break (board, ExpectedResult::Win);
+        } else if strong_score.abs() <= DRAW_THRESHOLD {
+            break (board, ExpectedResult::Draw);
+        }
+    };
+
+    println!("\n=== Endgame Training: {} ===", endgame.name());
+    println!("FEN: {}", board);
+    println!(
+        "You are {} ({} side). Engine estimate: {}",
+        if player_color == Color::White {
+            "White"
+        } else {
+            "Black"
+        },
+        if defend { "defending" } else { "stronger" },
+        match expected {
+            ExpectedResult::Win => "win for the stronger side",
+            ExpectedResult::Draw => "draw",
+        }
+    );
+
+    let mut game = ChessGame::from_position(engine, player_color, board, options);
+    let outcome = game.run().await?;
+    stats::record_game(&mut game, "endgame", 20).await?;
+
+    report(outcome, expected, strong_side, player_color);
+    Ok(())
+}
+
+fn report(outcome: GameOutcome, expected: ExpectedResult, strong_side: Color, player_color: Color) {
+    let defending = player_color != strong_side;
+
+    let verdict = match (outcome, expected) {
+        (GameOutcome::Unfinished, _) => "Game abandoned - no verdict.",
+        (GameOutcome::Win(winner) | GameOutcome::Timeout(winner), ExpectedResult::Win)
+            if winner == player_color =>
+        {
+            "Converted! You won the position the engine rated as winning."
+        }
+        (GameOutcome::Win(_) | GameOutcome::Timeout(_), ExpectedResult::Win) => {
+            "As the engine predicted, the stronger side won. Try to set more problems next time."
+        }
+        (GameOutcome::Draw, ExpectedResult::Win) if defending => {
+            "Held! You saved a position the engine rated as lost."
+        }
+        (GameOutcome::Draw, ExpectedResult::Win) => {
+            "Not converted: the position was winning but ended in a draw."
+        }
+        (GameOutcome::Draw, ExpectedResult::Draw) if defending => {
+            "Held! You drew the position the engine rated as drawn."
+        }
+        (GameOutcome::Draw, ExpectedResult::Draw) => {
+            "Correct result: the engine rated the position as a draw."
+        }
+        (GameOutcome::Win(winner) | GameOutcome::Timeout(winner), ExpectedResult::Draw)
+            if winner == player_color =>
+        {
+            "You beat the estimate - the engine went wrong in a position it rated as drawn."
+        }
+        (GameOutcome::Win(_) | GameOutcome::Timeout(_), ExpectedResult::Draw) => {
+            "Lost a position the engine rated as drawn."
+        }
+    };
+
+    println!("\n=== Endgame Verdict ===");
+    println!("{}", verdict);
+    println!("=======================\n");
+}
+
+/// Place the kings and extra material on random squares. Returns None if the
+/// position is illegal, already decided, or starts with a capture available.
+fn random_position(
+    rng: &mut impl Rng,
+    endgame: EndgameType,
+    strong_side: Color,
+    to_move: Color,
+) -> Option<Board> {
+    let (strong_pieces, weak_pieces) = endgame.material();
+
+    let mut pieces = vec![(Piece::King, strong_side), (Piece::King, !strong_side)];
+    pieces.extend(strong_pieces.iter().map(|p| (*p, strong_side)));
+    pieces.extend(weak_pieces.iter().map(|p| (*p, !strong_side)));
+
+    let mut squares: Vec<Square> = ALL_SQUARES.to_vec();
+    squares.shuffle(rng);
+
+    let mut placement: Vec<(Square, Piece, Color)> = Vec::new();
+    for (piece, color) in pieces {
+        let square = squares.iter().position(|sq| {
+            piece != Piece::Pawn || !matches!(sq.get_rank(), Rank::First | Rank::Eighth)
+        })?;
+        placement.push((squares.remove(square), piece, color));
+    }
+
+    // Kings may never stand next to each other
+    let (king_a, king_b) = (placement[0].0, placement[1].0);
+    let rank_distance = king_a
+        .get_rank()
+        .to_index()
+        .abs_diff(king_b.get_rank().to_index());
+    let file_distance = king_a
+        .get_file()
+        .to_index()
+        .abs_diff(king_b.get_file().to_index());
+    if rank_distance <= 1 && file_distance <= 1 {
+        return None;
+    }
+
+    let fen = format!(
+        "{} {} - - 0 1",
+        placement_fen(&placement),
+        if to_move == Color::White { "w" } else { "b" }
+    );
+    let board = Board::from_str(&fen).ok()?;
+
+    let has_capture = MoveGen::new_legal(&board).any(|m| board.piece_on(m.get_dest()).is_some());
+    if board.status() != BoardStatus::Ongoing || has_capture {
+        return None;
+    }
+
+    Some(board)
+}
+
+fn placement_fen(placement: &[(Square, Piece, Color)]) -> String {
+    let mut fen = String::new();
+
+    for rank in (0..8).rev() {
+        let mut empty = 0;
+        for file in 0..8 {
+            let square =
+                Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
+            match placement.iter().find(|(sq, _, _)| *sq == square) {
+                Some((_, piece, color)) => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    let c = piece_char(*piece);
+                    fen.push(if *color == Color::White {
+                        c
+                    } else {
+                        c.to_ascii_lowercase()
+                    });
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if rank > 0 {
+            fen.push('/');
+        }
+    }
+
+    fen
+}
@@ -0,0 +1,215 @@
+use crate::chess_game::{GameOutcome, is_insufficient_material};
+use crate::crosstable::Crosstable;
+use crate::fen;
+use crate::pgn;
+use crate::stockfish::StockfishEngine;
+use anyhow::{Result, anyhow};
+use chess::{Board, Color, Game};
+
+/// Games longer than this are adjudicated as draws
+const MAX_PLIES: usize = 400;
+
+/// Settings of an engine-vs-engine match
+pub struct MatchOptions {
+    pub games: usize,
+    /// Search limit for every move, e.g. "go movetime 100"
+    pub go_command: String,
+    /// EPD or PGN file with the starting positions
+    pub openings: Option<String>,
+    /// Where to write the games as PGN
+    pub pgn_out: Option<String>,
+    /// Where to export the crosstable and Elo estimate as JSON
+    pub json_out: Option<String>,
+    /// Estimate the error over game pairs rather than single games
+    pub pentanomial: bool,
+}
+
+/// Play `engine_a` against `engine_b`. Colors alternate every game; with an
+/// opening suite, each opening is played twice so both engines get both sides.
+pub async fn run(engine_a: &str, engine_b: &str, options: MatchOptions) -> Result<()> {
+    let openings = match &options.openings {
+        Some(path) => load_openings(path)?,
+        None => vec![Board::default()],
+    };
+    let mut engines = [
+        StockfishEngine::new(engine_a).await?,
+        StockfishEngine::new(engine_b).await?,
+    ];
+    let names: Vec<String> = engines
+        .iter()
+        .zip([engine_a, engine_b])
+        .map(|(engine, path)| match engine.name() {
+            "" => path.to_string(),
+            name => name.to_string(),
+        })
+        .collect();
+    println!(
+        "\n=== {} vs {}: {} games, {} opening(s) ===",
+        names[0],
+        names[1],
+        options.games,
+        openings.len()
+    );
+
+    let mut table = Crosstable::new(names.clone());
+    let mut pgn_text = String::new();
+    for round in 1..=options.games {
+        let opening_index = (round - 1) / 2 % openings.len();
+        // Engine A has White in odd games
+        let a_is_white = round % 2 == 1;
+        let white = if a_is_white { 0 } else { 1 };
+
+        let (outcome, moves) = play_game(
+            &mut engines,
+            white,
+            openings[opening_index],
+            &options.go_command,
+        )
+        .await?;
+        table.add(white, 1 - white, outcome);
+        let score = table.scores();
+        println!(
+            "Game {:>3}: {} - {} (opening {}): {:<7}  score {} - {}",
+            round,
+            names[white],
+            names[1 - white],
+            opening_index + 1,
+            outcome.pgn_result(),
+            score[0],
+            score[1]
+        );
+
+        let start = openings[opening_index];
+        let mut headers = vec![
+            ("Event".to_string(), "Engine match".to_string()),
+            ("Site".to_string(), "minichess".to_string()),
+            (
+                "Date".to_string(),
+                chrono::Local::now().format("%Y.%m.%d").to_string(),
+            ),
+            ("Round".to_string(), round.to_string()),
+            ("White".to_string(), names[white].clone()),
+            ("Black".to_string(), names[1 - white].clone()),
+            ("Result".to_string(), outcome.pgn_result().to_string()),
+        ];
+        if start != Board::default() {
+            headers.push(("SetUp".to_string(), "1".to_string()));
+            headers.push(("FEN".to_string(), start.to_string()));
+        }
+        let mut board = start;
+        let sans: Vec<(String, Option<String>)> = moves
+            .iter()
+            .map(|&chess_move| {
+                let san = pgn::move_to_san(&board, chess_move);
+                board = board.make_move_new(chess_move);
+                (san, None)
+            })
+            .collect();
+        pgn_text.push_str(&pgn::write_game(
+            &headers,
+            &start,
+            &sans,
+            outcome.pgn_result(),
+        ));
+        pgn_text.push('\n');
+    }
+
+    table.print(options.pentanomial);
+    if let Some(path) = &options.pgn_out {
+        std::fs::write(path, pgn_text).map_err(|e| anyhow!("Failed to write {}: {}", path, e))?;
+        println!("Games saved to {}", path);
+    }
+    if let Some(path) = &options.json_out {
+        table.write_json(path, options.pentanomial)?;
+        println!("Report saved to {}", path);
+    }
+    Ok(())
+}
+
+/// Play one game; `white` is the index of the engine playing White. An
+/// engine that returns no legal move forfeits.
+async fn play_game(
+    engines: &mut [StockfishEngine; 2],
+    white: usize,
+    start: Board,
+    go_command: &str,
+) -> Result<(GameOutcome, Vec<chess::ChessMove>)> {
+    let mut game = Game::new_with_board(start);
+    let mut moves = Vec::new();
+    for engine in engines.iter_mut() {
+        engine.new_game().await?;
+    }
+
+    loop {
+        if game.can_declare_draw() {
+            game.declare_draw();
+        }
+        let board = game.current_position();
+        match game.result() {
+            Some(chess::GameResult::WhiteCheckmates) | Some(chess::GameResult::BlackResigns) => {
+                return Ok((GameOutcome::Win(Color::White), moves));
+            }
+            Some(chess::GameResult::BlackCheckmates) | Some(chess::GameResult::WhiteResigns) => {
+                return Ok((GameOutcome::Win(Color::Black), moves));
+            }
+            Some(_) => return Ok((GameOutcome::Draw, moves)),
+            None if is_insufficient_material(&board) || moves.len() >= MAX_PLIES => {
+                return Ok((GameOutcome::Draw, moves));
+            }
+            None => {}
+        }
+
+        let side = board.side_to_move();
+        let engine = if side == Color::White {
+            &mut engines[white]
+        } else {
+            &mut engines[1 - white]
+        };
+        match engine.search(&board, go_command).await?.best_move {
+            Some(chess_move) if board.legal(chess_move) => {
+                game.make_move(chess_move);
+                moves.push(chess_move);
+            }
+            _ => {
+                println!("{} returned no legal move and forfeits.", engine.name());
+                game.resign(side);
+            }
+        }
+    }
+}
+
+/// Starting positions from an EPD file (one position per line) or a PGN file
+/// (the position at the end of each game)
+fn load_openings(path: &str) -> Result<Vec<Board>> {
+    let openings = if path.to_lowercase().ends_with(".pgn") {
+        pgn::load_pgn_file(path)?
+            .iter()
+            .map(|game| {
+                game.moves
+                    .iter()
+                    .fold(game.start, |board, &m| board.make_move_new(m))
+            })
+            .collect()
+    } else {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read opening suite {}: {}", path, e))?;
+        let mut boards = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || line.trim_start().starts_with('#') {
+                continue;
+            }
+            // EPD has the first four FEN fields followed by operations
+            let fen_text = format!("{} 0 1", fields[..4].join(" "));
+            let board = fen::parse_fen(&fen_text)
+                .map_err(|e| anyhow!("{} line {}: {}", path, number + 1, e))?;
+            boards.push(board);
+        }
+        boards
+    };
+
+    if openings.is_empty() {
+        return Err(anyhow!("No openings found in {}", path));
+    }
+    Ok(openings)
+}
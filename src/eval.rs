@@ -0,0 +1,100 @@
+use crate::stockfish::StockfishEngine;
+use anyhow::Result;
+use chess::Board;
+
+/// One row of Stockfish's classical evaluation table, in pawns from White's
+/// point of view: (middlegame, endgame)
+struct EvalTerm {
+    name: String,
+    white: Option<(f64, f64)>,
+    black: Option<(f64, f64)>,
+    total: Option<(f64, f64)>,
+}
+
+/// Print Stockfish's static evaluation of the position term by term
+pub async fn show(engine: &mut StockfishEngine, board: &Board) -> Result<()> {
+    let lines = engine.static_eval(board).await?;
+    let terms: Vec<EvalTerm> = lines.iter().filter_map(|l| parse_term(l)).collect();
+
+    println!("\n=== Static Evaluation (pawns, + is good for White) ===");
+    if terms.is_empty() {
+        println!("This Stockfish version does not report individual terms.");
+    } else {
+        println!(
+            "{:<14} {:>13} {:>13} {:>13}",
+            "Term", "White", "Black", "Total"
+        );
+        println!(
+            "{:<14} {:>13} {:>13} {:>13}",
+            "", "MG    EG", "MG    EG", "MG    EG"
+        );
+        for term in &terms {
+            println!(
+                "{:<14} {:>13} {:>13} {:>13}{}",
+                term.name,
+                format_pair(term.white),
+                format_pair(term.black),
+                format_pair(term.total),
+                verdict(term.total)
+            );
+        }
+    }
+
+    // Summary lines look like "Final evaluation: +0.25 (white side)"
+    for line in &lines {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Classical evaluation")
+            || trimmed.starts_with("NNUE evaluation")
+            || trimmed.starts_with("Final evaluation")
+            || trimmed.starts_with("Total evaluation")
+        {
+            println!("{}", trimmed);
+        }
+    }
+    println!("======================================================\n");
+    Ok(())
+}
+
+/// Parse a table row such as
+/// `    Mobility |  0.86  1.29 |  0.86  1.29 |  0.00  0.00`
+fn parse_term(line: &str) -> Option<EvalTerm> {
+    let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+    if columns.len() != 4 {
+        return None;
+    }
+    let name = columns[0];
+    if name.is_empty() || name == "Term" || !name.chars().all(|c| c.is_alphabetic() || c == ' ') {
+        return None;
+    }
+
+    Some(EvalTerm {
+        name: name.to_string(),
+        white: parse_pair(columns[1]),
+        black: parse_pair(columns[2]),
+        total: parse_pair(columns[3]),
+    })
+}
+
+/// "0.86  1.29" -> Some((0.86, 1.29)); "----  ----" -> None
+fn parse_pair(column: &str) -> Option<(f64, f64)> {
+    let mut values = column.split_whitespace().map(|v| v.parse::<f64>().ok());
+    match (values.next(), values.next()) {
+        (Some(Some(mg)), Some(Some(eg))) => Some((mg, eg)),
+        _ => None,
+    }
+}
+
+fn format_pair(pair: Option<(f64, f64)>) -> String {
+    match pair {
+        Some((mg, eg)) => format!("{:+.2} {:+.2}", mg, eg),
+        None => "-- --".to_string(),
+    }
+}
+
+fn verdict(total: Option<(f64, f64)>) -> &'static str {
+    match total {
+        Some((mg, eg)) if mg + eg >= 0.5 => "  favours White",
+        Some((mg, eg)) if mg + eg <= -0.5 => "  favours Black",
+        _ => "",
+    }
+}
@@ -0,0 +1,98 @@
+use crate::annotate::{self, PositionEval};
+use crate::fen;
+use crate::stockfish::Score;
+use anyhow::{Result, anyhow};
+use chess::Board;
+use serde::Serialize;
+use std::io::Write;
+
+/// One evaluated position as written to the output
+#[derive(Serialize)]
+struct Row {
+    fen: String,
+    /// Centipawns from the side to move's point of view; None for a mate
+    cp: Option<i32>,
+    /// Moves to mate, negative when the side to move is getting mated
+    mate: Option<i32>,
+    best_move: Option<String>,
+    /// Principal variation in coordinate notation
+    pv: Vec<String>,
+}
+
+impl Row {
+    fn new(board: &Board, eval: &PositionEval) -> Self {
+        let (cp, mate) = match eval.score {
+            Score::Centipawns(cp) => (Some(cp), None),
+            Score::Mate(moves) => (None, Some(moves)),
+        };
+        Row {
+            fen: board.to_string(),
+            cp,
+            mate,
+            best_move: eval.best_move.map(|m| m.to_string()),
+            pv: eval.pv.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+/// Evaluate every position of a FEN or EPD file and write the score, best
+/// move and principal variation of each, in file order, as CSV or JSON
+pub async fn run(
+    stockfish_path: &str,
+    path: &str,
+    out: Option<&str>,
+    depth: u8,
+    engines: usize,
+    format: &str,
+) -> Result<()> {
+    if !matches!(format, "csv" | "json") {
+        return Err(anyhow!("Unknown format '{}', expected csv or json", format));
+    }
+    let positions = fen::read_positions(path)?;
+    if positions.is_empty() {
+        return Err(anyhow!("No positions found in {}", path));
+    }
+    // Progress goes to stderr so the results can be piped
+    eprintln!(
+        "Evaluating {} position(s) at depth {}...",
+        positions.len(),
+        depth
+    );
+    let evals = annotate::analyze_positions(stockfish_path, &positions, depth, engines).await?;
+    let rows: Vec<Row> = positions
+        .iter()
+        .zip(&evals)
+        .map(|(board, eval)| Row::new(board, eval))
+        .collect();
+
+    let mut text = Vec::new();
+    match format {
+        "json" => {
+            serde_json::to_writer_pretty(&mut text, &rows)?;
+            writeln!(text)?;
+        }
+        _ => {
+            writeln!(text, "fen,cp,mate,best_move,pv")?;
+            for row in &rows {
+                writeln!(
+                    text,
+                    "{},{},{},{},{}",
+                    row.fen,
+                    row.cp.map(|cp| cp.to_string()).unwrap_or_default(),
+                    row.mate.map(|mate| mate.to_string()).unwrap_or_default(),
+                    row.best_move.as_deref().unwrap_or_default(),
+                    row.pv.join(" ")
+                )?;
+            }
+        }
+    }
+
+    match out {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|e| anyhow!("Failed to write {}: {}", path, e))?;
+            eprintln!("Evaluations written to {}", path);
+        }
+        None => std::io::stdout().write_all(&text)?,
+    }
+    Ok(())
+}
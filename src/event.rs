@@ -0,0 +1,951 @@
+use crate::chess_game::{GameOutcome, is_insufficient_material};
+use crate::crosstable::{Crosstable, TableGame};
+use crate::network;
+use crate::pgn;
+use crate::storage;
+use crate::ui::{display_board_for_player, get_user_input};
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus, ChessMove, Color, Piece};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+const EVENTS_FILE: &str = "events.json";
+
+/// Halfmoves without a capture or pawn move after which the game is drawn
+const FIFTY_MOVE_PLIES: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    Swiss,
+    RoundRobin,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "swiss" => Ok(Format::Swiss),
+            "round-robin" | "roundrobin" | "rr" => Ok(Format::RoundRobin),
+            _ => Err(anyhow!("--format must be swiss or round-robin")),
+        }
+    }
+}
+
+/// A club tournament between human players
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub created: String,
+    pub format: Format,
+    pub players: Vec<String>,
+    pub rounds: usize,
+    /// Rounds paired so far, the current one last
+    pub schedule: Vec<Round>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Round {
+    pub boards: Vec<Pairing>,
+    /// Player sitting out this round, scored as a win
+    pub bye: Option<usize>,
+}
+
+/// One board of a round: player indexes and, once played, the PGN result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pairing {
+    pub white: usize,
+    pub black: usize,
+    pub result: Option<String>,
+    /// Moves in coordinate notation, when the game was played here or
+    /// loaded from PGN
+    #[serde(default)]
+    pub moves: Vec<String>,
+}
+
+/// How to host a board for a networked game
+pub struct HostOptions {
+    pub port: u16,
+    /// Player sitting at this computer
+    pub player: String,
+    pub base_minutes: u32,
+    pub increment_seconds: u32,
+}
+
+/// Score, tiebreaks and games played of one player
+struct Standing {
+    player: usize,
+    score: f64,
+    buchholz: f64,
+    sonneborn_berger: f64,
+    games: usize,
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid event name '{}': use letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Rounds needed for everyone to meet everyone once
+fn round_robin_rounds(players: usize) -> usize {
+    if players.is_multiple_of(2) {
+        players - 1
+    } else {
+        players
+    }
+}
+
+/// Points of White for a PGN result
+fn white_points(result: &str) -> f64 {
+    match result {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        _ => 0.5,
+    }
+}
+
+/// Accept the usual ways of writing a result
+pub fn parse_result(text: &str) -> Result<&'static str> {
+    match text {
+        "1-0" | "white" => Ok("1-0"),
+        "0-1" | "black" => Ok("0-1"),
+        "1/2-1/2" | "1/2" | "=" | "draw" => Ok("1/2-1/2"),
+        _ => Err(anyhow!("Result must be 1-0, 0-1 or 1/2-1/2")),
+    }
+}
+
+impl Event {
+    fn pairings(&self) -> impl Iterator<Item = &Pairing> {
+        self.schedule.iter().flat_map(|round| &round.boards)
+    }
+
+    fn finished(&self) -> impl Iterator<Item = &Pairing> {
+        self.pairings().filter(|p| p.result.is_some())
+    }
+
+    fn current_round(&self) -> Option<&Round> {
+        self.schedule.last()
+    }
+
+    fn round_complete(&self) -> bool {
+        self.current_round()
+            .is_none_or(|round| round.boards.iter().all(|p| p.result.is_some()))
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.schedule.len() == self.rounds && self.round_complete()
+    }
+
+    fn have_met(&self, a: usize, b: usize) -> bool {
+        self.pairings()
+            .any(|p| (p.white == a && p.black == b) || (p.white == b && p.black == a))
+    }
+
+    fn had_bye(&self, player: usize) -> bool {
+        self.schedule.iter().any(|round| round.bye == Some(player))
+    }
+
+    /// Games with White minus games with Black
+    fn color_balance(&self, player: usize) -> i32 {
+        self.pairings()
+            .map(|p| {
+                if p.white == player {
+                    1
+                } else if p.black == player {
+                    -1
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    fn last_color(&self, player: usize) -> Option<Color> {
+        let mut latest_first = self.schedule.iter().rev().flat_map(|round| &round.boards);
+        latest_first.find_map(|p| {
+            if p.white == player {
+                Some(Color::White)
+            } else if p.black == player {
+                Some(Color::Black)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn scores(&self) -> Vec<f64> {
+        let mut scores = vec![0.0; self.players.len()];
+        for game in self.finished() {
+            let points = white_points(game.result.as_deref().unwrap_or_default());
+            scores[game.white] += points;
+            scores[game.black] += 1.0 - points;
+        }
+        for round in &self.schedule {
+            if let Some(player) = round.bye {
+                scores[player] += 1.0;
+            }
+        }
+        scores
+    }
+
+    /// Players by score, then Buchholz (the sum of the opponents' scores),
+    /// then Sonneborn-Berger (the scores of beaten opponents plus half of
+    /// those drawn)
+    fn standings(&self) -> Vec<Standing> {
+        let scores = self.scores();
+        let mut standings: Vec<Standing> = (0..self.players.len())
+            .map(|player| Standing {
+                player,
+                score: scores[player],
+                buchholz: 0.0,
+                sonneborn_berger: 0.0,
+                games: 0,
+            })
+            .collect();
+        for game in self.finished() {
+            let points = white_points(game.result.as_deref().unwrap_or_default());
+            for (player, opponent, points) in [
+                (game.white, game.black, points),
+                (game.black, game.white, 1.0 - points),
+            ] {
+                let standing = &mut standings[player];
+                standing.games += 1;
+                standing.buchholz += scores[opponent];
+                standing.sonneborn_berger += points * scores[opponent];
+            }
+        }
+        standings.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.buchholz.total_cmp(&a.buchholz))
+                .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+                .then(a.player.cmp(&b.player))
+        });
+        standings
+    }
+
+    /// Add the next round to the schedule
+    fn pair_next_round(&mut self) {
+        let round = match self.format {
+            Format::Swiss => self.pair_swiss(),
+            Format::RoundRobin => self.pair_round_robin(self.schedule.len()),
+        };
+        self.schedule.push(round);
+    }
+
+    /// Pair players with the nearest-ranked opponent they haven't met yet.
+    /// The lowest-ranked player without a bye sits out when the count is
+    /// odd.
+    fn pair_swiss(&self) -> Round {
+        let scores = self.scores();
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]).then(a.cmp(&b)));
+
+        let mut bye = None;
+        if !order.len().is_multiple_of(2) {
+            let player = order
+                .iter()
+                .rev()
+                .copied()
+                .find(|&p| !self.had_bye(p))
+                .unwrap_or(order[order.len() - 1]);
+            order.retain(|&p| p != player);
+            bye = Some(player);
+        }
+
+        // Rematches only when there is no other way to pair everyone
+        let pairs = match_players(&order, &|a, b| !self.have_met(a, b))
+            .or_else(|| match_players(&order, &|_, _| true))
+            .unwrap_or_default();
+        Round {
+            boards: pairs
+                .into_iter()
+                .map(|(higher, lower)| self.assign_colors(higher, lower))
+                .collect(),
+            bye,
+        }
+    }
+
+    /// White goes to whoever has played Black more often, then to whoever
+    /// had Black last, then to the higher-ranked player
+    fn assign_colors(&self, higher: usize, lower: usize) -> Pairing {
+        let (white, black) = match self.color_balance(higher).cmp(&self.color_balance(lower)) {
+            std::cmp::Ordering::Less => (higher, lower),
+            std::cmp::Ordering::Greater => (lower, higher),
+            std::cmp::Ordering::Equal => {
+                if self.last_color(higher) == Some(Color::White) {
+                    (lower, higher)
+                } else {
+                    (higher, lower)
+                }
+            }
+        };
+        Pairing {
+            white,
+            black,
+            result: None,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Round `round` of the circle method: the first player stays put while
+    /// the others rotate one place each round. The first board alternates
+    /// colors from round to round and on the others the seat in the first
+    /// half has White, so everyone's colors end up even or one apart.
+    fn pair_round_robin(&self, round: usize) -> Round {
+        let mut seats: Vec<Option<usize>> = (0..self.players.len()).map(Some).collect();
+        if !seats.len().is_multiple_of(2) {
+            seats.push(None);
+        }
+        let rotating = seats.len() - 1;
+        seats[1..].rotate_right(round % rotating);
+
+        let mut result = Round::default();
+        for board in 0..seats.len() / 2 {
+            let (a, b) = (seats[board], seats[seats.len() - 1 - board]);
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    let (white, black) = if board > 0 || round.is_multiple_of(2) {
+                        (a, b)
+                    } else {
+                        (b, a)
+                    };
+                    result.boards.push(Pairing {
+                        white,
+                        black,
+                        result: None,
+                        moves: Vec::new(),
+                    });
+                }
+                (Some(player), None) | (None, Some(player)) => result.bye = Some(player),
+                (None, None) => {}
+            }
+        }
+        result
+    }
+
+    fn print_round(&self) {
+        let Some(round) = self.current_round() else {
+            return;
+        };
+        println!("\n=== Round {} of {} ===", self.schedule.len(), self.rounds);
+        let width = self.players.iter().map(|p| p.len()).max().unwrap_or(0);
+        for (i, pairing) in round.boards.iter().enumerate() {
+            println!(
+                "Board {:>2}: {:<width$} - {:<width$}  {}",
+                i + 1,
+                self.players[pairing.white],
+                self.players[pairing.black],
+                pairing.result.as_deref().unwrap_or("*"),
+                width = width
+            );
+        }
+        if let Some(player) = round.bye {
+            println!("Bye: {}", self.players[player]);
+        }
+    }
+
+    /// Record the result of board `board` (1-based) of the current round,
+    /// pairing the next round once every board is done
+    fn record(&mut self, board: usize, result: &str, moves: Vec<String>) -> Result<()> {
+        let round = self
+            .schedule
+            .last_mut()
+            .ok_or_else(|| anyhow!("The event has no rounds yet"))?;
+        let count = round.boards.len();
+        let pairing = board
+            .checked_sub(1)
+            .and_then(|i| round.boards.get_mut(i))
+            .ok_or_else(|| anyhow!("There is no board {}; pick 1 to {}", board, count))?;
+        pairing.result = Some(result.to_string());
+        if !moves.is_empty() {
+            pairing.moves = moves;
+        }
+
+        if self.round_complete() {
+            if self.schedule.len() < self.rounds {
+                self.pair_next_round();
+                self.print_round();
+            } else {
+                println!("\nThat was the last game of the event.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Index of the first board of the current round still to be played
+    fn next_board(&self) -> Option<usize> {
+        self.current_round()?
+            .boards
+            .iter()
+            .position(|p| p.result.is_none())
+    }
+}
+
+/// Pair `players` in order, each with the first later player `allowed`
+/// against them, backtracking when the rest can't be paired
+fn match_players(
+    players: &[usize],
+    allowed: &dyn Fn(usize, usize) -> bool,
+) -> Option<Vec<(usize, usize)>> {
+    let Some((&first, rest)) = players.split_first() else {
+        return Some(Vec::new());
+    };
+    for (i, &opponent) in rest.iter().enumerate() {
+        if !allowed(first, opponent) {
+            continue;
+        }
+        let mut remaining = rest.to_vec();
+        remaining.remove(i);
+        if let Some(mut pairs) = match_players(&remaining, allowed) {
+            pairs.insert(0, (first, opponent));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+fn load_events() -> Result<BTreeMap<String, Event>> {
+    storage::load(EVENTS_FILE)
+}
+
+fn get(name: &str) -> Result<(BTreeMap<String, Event>, Event)> {
+    let mut events = load_events()?;
+    let event = events
+        .remove(name)
+        .ok_or_else(|| anyhow!("No event named '{}' (see 'event list')", name))?;
+    Ok((events, event))
+}
+
+fn put(mut events: BTreeMap<String, Event>, name: &str, event: Event) -> Result<()> {
+    events.insert(name.to_string(), event);
+    storage::save(EVENTS_FILE, &events)
+}
+
+/// Start an event and pair its first round. Swiss events default to enough
+/// rounds to find a clear winner, round robins to a full cycle.
+pub fn create(
+    name: &str,
+    players: Vec<String>,
+    format: Format,
+    rounds: Option<usize>,
+) -> Result<()> {
+    check_name(name)?;
+    if players.len() < 2 {
+        return Err(anyhow!("An event needs at least two players"));
+    }
+    for (i, player) in players.iter().enumerate() {
+        if players[..i].contains(player) {
+            return Err(anyhow!("Player '{}' is listed twice", player));
+        }
+    }
+
+    let events = load_events()?;
+    if events.contains_key(name) {
+        return Err(anyhow!("Event '{}' already exists", name));
+    }
+
+    let most = round_robin_rounds(players.len());
+    let rounds = match (rounds, format) {
+        (Some(rounds), _) if (1..=most).contains(&rounds) => rounds,
+        (Some(_), _) => {
+            return Err(anyhow!(
+                "--rounds must be from 1 to {} for {} players",
+                most,
+                players.len()
+            ));
+        }
+        (None, Format::RoundRobin) => most,
+        (None, Format::Swiss) => {
+            (players.len().next_power_of_two().trailing_zeros() as usize).clamp(1, most)
+        }
+    };
+
+    let mut event = Event {
+        created: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        format,
+        players,
+        rounds,
+        schedule: Vec::new(),
+    };
+    event.pair_next_round();
+    println!(
+        "Event '{}' created: {} players, {} rounds.",
+        name,
+        event.players.len(),
+        event.rounds
+    );
+    event.print_round();
+    put(events, name, event)
+}
+
+pub fn list() -> Result<()> {
+    let events = load_events()?;
+    if events.is_empty() {
+        println!("No events yet. Start one with 'event new <name> --players A,B,C,D'.");
+        return Ok(());
+    }
+    for (name, event) in &events {
+        let state = if event.is_over() {
+            "finished".to_string()
+        } else {
+            format!("round {} of {}", event.schedule.len(), event.rounds)
+        };
+        println!(
+            "{:<20} {:<11} {:>2} players  {}  (since {})",
+            name,
+            match event.format {
+                Format::Swiss => "swiss",
+                Format::RoundRobin => "round-robin",
+            },
+            event.players.len(),
+            state,
+            event.created
+        );
+    }
+    Ok(())
+}
+
+/// Record a game played elsewhere, optionally with its moves from a PGN file
+pub fn record_result(name: &str, board: usize, result: &str, pgn_file: Option<&str>) -> Result<()> {
+    let (events, mut event) = get(name)?;
+    if event.is_over() {
+        return Err(anyhow!("Event '{}' is already finished", name));
+    }
+
+    let mut moves = Vec::new();
+    if let Some(path) = pgn_file {
+        let game = pgn::load_pgn_file(path)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("{} holds no games", path))?;
+        if game.start != Board::default() {
+            return Err(anyhow!("{} doesn't start from the initial position", path));
+        }
+        moves = game.moves.iter().map(|m| m.to_string()).collect();
+    }
+
+    event.record(board, result, moves)?;
+    println!("Board {}: {}", board, result);
+    put(events, name, event)
+}
+
+/// Play the next board of the current round (or `board`, 1-based) at this
+/// terminal, passing it between the two players, or host it for the
+/// opponent to join over the network
+pub async fn play(name: &str, board: Option<usize>, host: Option<HostOptions>) -> Result<()> {
+    let (events, mut event) = get(name)?;
+    if event.is_over() {
+        event_summary(&event);
+        return Ok(());
+    }
+
+    let index = match board {
+        Some(board) => board
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("Boards are numbered from 1"))?,
+        None => event
+            .next_board()
+            .ok_or_else(|| anyhow!("Every board of this round has a result"))?,
+    };
+    let round = event.current_round().expect("events have a first round");
+    let pairing = round
+        .boards
+        .get(index)
+        .ok_or_else(|| anyhow!("There is no board {}", index + 1))?
+        .clone();
+    if pairing.result.is_some() {
+        return Err(anyhow!("Board {} already has a result", index + 1));
+    }
+    let white = event.players[pairing.white].clone();
+    let black = event.players[pairing.black].clone();
+    println!(
+        "\n=== {} round {}, board {}: {} (White) vs {} (Black) ===",
+        name,
+        event.schedule.len(),
+        index + 1,
+        white,
+        black
+    );
+
+    let (moves, outcome) = match host {
+        Some(options) => {
+            let color = if options.player == white {
+                Color::White
+            } else if options.player == black {
+                Color::Black
+            } else {
+                return Err(anyhow!(
+                    "{} doesn't play on board {}; --as must be {} or {}",
+                    options.player,
+                    index + 1,
+                    white,
+                    black
+                ));
+            };
+            let (game, _) = network::host(
+                options.port,
+                Some(color),
+                options.base_minutes,
+                options.increment_seconds,
+            )
+            .await?;
+            (game.moves, game.outcome)
+        }
+        None => play_hotseat(&white, &black)?,
+    };
+
+    if outcome == GameOutcome::Unfinished {
+        println!("Game stopped; board {} stays open.", index + 1);
+        return Ok(());
+    }
+    let moves = moves.iter().map(|m| m.to_string()).collect();
+    event.record(index + 1, outcome.pgn_result(), moves)?;
+    println!("Board {}: {}", index + 1, outcome.pgn_result());
+    put(events, name, event)
+}
+
+/// Two players at one terminal; the board turns to face the side to move
+fn play_hotseat(white: &str, black: &str) -> Result<(Vec<ChessMove>, GameOutcome)> {
+    println!("Enter moves in SAN or coordinates. 'draw' offers a draw, 'resign' gives up,");
+    println!("'abort' stops without a result.");
+
+    let mut board = Board::default();
+    let mut moves = Vec::new();
+    let mut seen: HashMap<u64, u32> = HashMap::from([(board.get_hash(), 1)]);
+    let mut quiet_plies = 0;
+
+    loop {
+        let side = board.side_to_move();
+        let (name, opponent) = match side {
+            Color::White => (white, black),
+            Color::Black => (black, white),
+        };
+
+        match board.status() {
+            BoardStatus::Checkmate => {
+                display_board_for_player(&board, side);
+                println!("Checkmate, {} wins!", opponent);
+                return Ok((moves, GameOutcome::Win(!side)));
+            }
+            BoardStatus::Stalemate => {
+                display_board_for_player(&board, side);
+                println!("Stalemate, the game is a draw.");
+                return Ok((moves, GameOutcome::Draw));
+            }
+            BoardStatus::Ongoing => {}
+        }
+        if is_insufficient_material(&board) {
+            println!("Neither side can mate, the game is a draw.");
+            return Ok((moves, GameOutcome::Draw));
+        }
+        if seen.get(&board.get_hash()).is_some_and(|&n| n >= 3) {
+            println!("Threefold repetition, the game is a draw.");
+            return Ok((moves, GameOutcome::Draw));
+        }
+        if quiet_plies >= FIFTY_MOVE_PLIES {
+            println!("Fifty moves without a capture or pawn move, the game is a draw.");
+            return Ok((moves, GameOutcome::Draw));
+        }
+
+        display_board_for_player(&board, side);
+        println!("{} ({:?}) to move:", name, side);
+        let input = get_user_input()?;
+        match input.trim() {
+            "" => {}
+            "abort" => return Ok((moves, GameOutcome::Unfinished)),
+            "resign" => {
+                println!("{} resigns, {} wins.", name, opponent);
+                return Ok((moves, GameOutcome::Win(!side)));
+            }
+            "draw" => {
+                println!("{}, {} offers a draw. Accept? (y/n)", opponent, name);
+                if get_user_input()?.trim().eq_ignore_ascii_case("y") {
+                    println!("Draw agreed.");
+                    return Ok((moves, GameOutcome::Draw));
+                }
+                println!("Draw declined.");
+            }
+            text => match pgn::parse_move(&board, text) {
+                Ok(chess_move) => {
+                    let resets = board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+                        || board.piece_on(chess_move.get_dest()).is_some();
+                    quiet_plies = if resets { 0 } else { quiet_plies + 1 };
+                    board = board.make_move_new(chess_move);
+                    *seen.entry(board.get_hash()).or_default() += 1;
+                    moves.push(chess_move);
+                }
+                Err(e) => println!("Invalid move: {}. Try again.", e),
+            },
+        }
+    }
+}
+
+/// Standings with tiebreaks, then the crosstable
+pub fn standings(name: &str) -> Result<()> {
+    let (_, event) = get(name)?;
+    event_summary(&event);
+    Ok(())
+}
+
+fn event_summary(event: &Event) {
+    let width = event.players.iter().map(|p| p.len()).max().unwrap_or(0);
+    let state = if event.is_over() {
+        "final".to_string()
+    } else {
+        format!("after round {} of {}", event.schedule.len(), event.rounds)
+    };
+    println!("\n=== Standings ({}) ===", state);
+    println!(
+        "    {:<width$}  {:>5}  {:>5}  {:>8}  {:>5}",
+        "Player",
+        "Score",
+        "Games",
+        "Buchholz",
+        "S-B",
+        width = width
+    );
+    for (rank, standing) in event.standings().iter().enumerate() {
+        println!(
+            "{:>2}. {:<width$}  {:>5}  {:>5}  {:>8}  {:>5}",
+            rank + 1,
+            event.players[standing.player],
+            standing.score,
+            standing.games,
+            standing.buchholz,
+            standing.sonneborn_berger,
+            width = width
+        );
+    }
+
+    let mut table = Crosstable::new(event.players.clone());
+    table.games = event
+        .finished()
+        .map(|p| TableGame {
+            white: p.white,
+            black: p.black,
+            result: p.result.clone().unwrap_or_default(),
+        })
+        .collect();
+    if event.players.len() > 2 {
+        table.print(false);
+    }
+    if !event.is_over() {
+        event.print_round();
+    }
+}
+
+/// Every finished game of the event as PGN
+pub fn export(name: &str, out: Option<&str>) -> Result<()> {
+    let (_, event) = get(name)?;
+    let mut text = String::new();
+    for (number, round) in event.schedule.iter().enumerate() {
+        for pairing in round.boards.iter().filter(|p| p.result.is_some()) {
+            let result = pairing.result.as_deref().unwrap_or("*");
+            let headers = vec![
+                ("Event".to_string(), name.to_string()),
+                ("Date".to_string(), event.created.replace('-', ".")),
+                ("Round".to_string(), (number + 1).to_string()),
+                ("White".to_string(), event.players[pairing.white].clone()),
+                ("Black".to_string(), event.players[pairing.black].clone()),
+                ("Result".to_string(), result.to_string()),
+            ];
+            let mut board = Board::default();
+            let mut moves = Vec::new();
+            for uci in &pairing.moves {
+                let chess_move = ChessMove::from_str(uci)
+                    .ok()
+                    .filter(|m| board.legal(*m))
+                    .ok_or_else(|| anyhow!("Corrupt move '{}' in event '{}'", uci, name))?;
+                moves.push((pgn::move_to_san(&board, chess_move), None));
+                board = board.make_move_new(chess_move);
+            }
+            text.push_str(&pgn::write_game(
+                &headers,
+                &Board::default(),
+                &moves,
+                result,
+            ));
+            text.push('\n');
+        }
+    }
+
+    match out {
+        Some(path) => {
+            std::fs::write(path, &text).map_err(|e| anyhow!("Failed to write {}: {}", path, e))?;
+            println!("Games of '{}' written to {}", name, path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_event(players: usize, format: Format, rounds: usize) -> Event {
+        let mut event = Event {
+            created: "2026-01-05".to_string(),
+            format,
+            players: (1..=players).map(|i| format!("player{}", i)).collect(),
+            rounds,
+            schedule: Vec::new(),
+        };
+        event.pair_next_round();
+        event
+    }
+
+    /// Finish the current round, the lower-numbered player winning every
+    /// game, and pair the next one
+    fn play_round(event: &mut Event) {
+        for pairing in &mut event.schedule.last_mut().unwrap().boards {
+            let result = if pairing.white < pairing.black {
+                "1-0"
+            } else {
+                "0-1"
+            };
+            pairing.result = Some(result.to_string());
+        }
+        if event.schedule.len() < event.rounds {
+            event.pair_next_round();
+        }
+    }
+
+    fn play_all(event: &mut Event) {
+        while !event.is_over() {
+            play_round(event);
+        }
+    }
+
+    /// Every player once per round, on a board or with the bye
+    fn assert_everyone_seated(event: &Event) {
+        for round in &event.schedule {
+            let mut seated: Vec<usize> = round
+                .boards
+                .iter()
+                .flat_map(|p| [p.white, p.black])
+                .chain(round.bye)
+                .collect();
+            seated.sort();
+            assert_eq!(seated, (0..event.players.len()).collect::<Vec<_>>());
+        }
+    }
+
+    fn meetings(event: &Event, a: usize, b: usize) -> usize {
+        event
+            .pairings()
+            .filter(|p| (p.white, p.black) == (a, b) || (p.white, p.black) == (b, a))
+            .count()
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let mut event = new_event(8, Format::Swiss, 4);
+        play_all(&mut event);
+        assert_eq!(event.schedule.len(), 4);
+        assert_everyone_seated(&event);
+        for a in 0..8 {
+            for b in a + 1..8 {
+                assert!(meetings(&event, a, b) <= 1, "{} and {} met twice", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn swiss_pairs_leaders_together() {
+        let mut event = new_event(4, Format::Swiss, 2);
+        play_round(&mut event);
+        // Players 0 and 1 won their first games and now meet
+        assert_eq!(meetings(&event, 0, 1), 1);
+        assert_eq!(meetings(&event, 2, 3), 1);
+    }
+
+    #[test]
+    fn swiss_bye_goes_to_a_new_player_each_round() {
+        let mut event = new_event(5, Format::Swiss, 5);
+        play_all(&mut event);
+        assert_everyone_seated(&event);
+        let mut byes: Vec<usize> = event.schedule.iter().filter_map(|r| r.bye).collect();
+        assert_eq!(byes.len(), 5);
+        byes.sort();
+        byes.dedup();
+        assert_eq!(byes.len(), 5);
+        // Each game hands out one point and each bye counts as a win
+        let total: f64 = event.scores().iter().sum();
+        assert_eq!(total, event.finished().count() as f64 + 5.0);
+    }
+
+    #[test]
+    fn swiss_balances_colors() {
+        let mut event = new_event(6, Format::Swiss, 4);
+        play_all(&mut event);
+        for player in 0..6 {
+            assert!(
+                event.color_balance(player).abs() <= 2,
+                "player {} has color balance {}",
+                player,
+                event.color_balance(player)
+            );
+        }
+        // Nobody has White in the second round after having it in the first
+        // unless their opponent had it too
+        let first = &event.schedule[0].boards;
+        for pairing in &event.schedule[1].boards {
+            let had_white = |p: usize| first.iter().any(|f| f.white == p);
+            assert!(!had_white(pairing.white) || had_white(pairing.black));
+        }
+    }
+
+    #[test]
+    fn round_robin_meets_everyone_once() {
+        for players in 2..=9 {
+            let rounds = round_robin_rounds(players);
+            let mut event = new_event(players, Format::RoundRobin, rounds);
+            play_all(&mut event);
+            assert_eq!(event.schedule.len(), rounds);
+            assert_everyone_seated(&event);
+            for a in 0..players {
+                for b in a + 1..players {
+                    assert_eq!(meetings(&event, a, b), 1, "{} and {} of {}", a, b, players);
+                }
+            }
+            let byes = event.schedule.iter().filter(|r| r.bye.is_some()).count();
+            assert_eq!(
+                byes,
+                if players.is_multiple_of(2) {
+                    0
+                } else {
+                    players
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn round_robin_balances_colors() {
+        for players in 2..=9 {
+            let mut event = new_event(players, Format::RoundRobin, round_robin_rounds(players));
+            play_all(&mut event);
+            for player in 0..players {
+                assert!(
+                    event.color_balance(player).abs() <= 1,
+                    "player {} of {} has color balance {}",
+                    player,
+                    players,
+                    event.color_balance(player)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::overlay::attackers_within;
+use crate::pgn::piece_char;
+use chess::{BitBoard, Board, Color, EMPTY, Piece, Square};
+
+/// One capture of an exchange: who takes, with what, from where
+pub struct Capture {
+    pub color: Color,
+    pub piece: Piece,
+    pub from: Square,
+    /// Piece standing on the square before this capture
+    pub captured: Piece,
+}
+
+/// Result of a static exchange evaluation on one square
+pub struct Exchange {
+    pub square: Square,
+    /// Every capture available in order, cheapest attacker first
+    pub captures: Vec<Capture>,
+    /// How many of them are played when each side stops as soon as going on
+    /// would lose material
+    pub played: usize,
+    /// Material won by the side making the first capture, in pawns
+    pub gain: i32,
+}
+
+pub fn value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight | Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => 100,
+    }
+}
+
+/// Static exchange evaluation: both sides keep capturing on `square` with
+/// their least valuable attacker, sliders behind a piece joining in once it
+/// has moved. Pins and checks are not considered, and the king only
+/// captures when nothing can take it back. None if the square is empty.
+pub fn evaluate(board: &Board, square: Square) -> Option<Exchange> {
+    let mut on_square = board.piece_on(square)?;
+    let mut color = !board.color_on(square)?;
+    let mut occupied = *board.combined();
+    let mut captures = Vec::new();
+
+    loop {
+        let attackers = attackers_within(board, square, color, occupied);
+        let Some((piece, from)) = cheapest(board, attackers) else {
+            break;
+        };
+        if piece == Piece::King
+            && attackers_within(
+                board,
+                square,
+                !color,
+                occupied & !BitBoard::from_square(from),
+            ) != EMPTY
+        {
+            break;
+        }
+        captures.push(Capture {
+            color,
+            piece,
+            from,
+            captured: on_square,
+        });
+        occupied &= !BitBoard::from_square(from);
+        on_square = piece;
+        color = !color;
+    }
+
+    // Work back from the end: each side only captures if it comes out ahead
+    let mut best = vec![0; captures.len() + 1];
+    for i in (0..captures.len()).rev() {
+        best[i] = (value(captures[i].captured) - best[i + 1]).max(0);
+    }
+    let played = (0..captures.len())
+        .find(|&i| value(captures[i].captured) - best[i + 1] < 0)
+        .unwrap_or(captures.len());
+    // What the first capture nets, even when starting is a mistake
+    let gain = match captures.first() {
+        Some(first) => value(first.captured) - best[1],
+        None => 0,
+    };
+
+    Some(Exchange {
+        square,
+        captures,
+        played,
+        gain,
+    })
+}
+
+fn cheapest(board: &Board, attackers: BitBoard) -> Option<(Piece, Square)> {
+    [
+        Piece::Pawn,
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+        Piece::King,
+    ]
+    .into_iter()
+    .find_map(|piece| {
+        let candidates = attackers & *board.pieces(piece);
+        (candidates != EMPTY).then(|| (piece, candidates.to_square()))
+    })
+}
+
+/// Walk through the exchange in words and say whether starting it pays
+pub fn narrate(exchange: &Exchange) {
+    let Some(first) = exchange.captures.first() else {
+        println!("Nothing can capture on {}.", exchange.square);
+        return;
+    };
+
+    println!("\nCaptures on {}:", exchange.square);
+    for (i, capture) in exchange.captures.iter().enumerate() {
+        let note = if i == exchange.played {
+            "  <- best to stop before this"
+        } else {
+            ""
+        };
+        println!(
+            "  {}. {:?} {}{}x{} takes the {}{}",
+            i + 1,
+            capture.color,
+            piece_label(capture.piece),
+            capture.from,
+            exchange.square,
+            name(capture.captured),
+            note
+        );
+    }
+
+    let side = format!("{:?}", first.color);
+    match exchange.gain {
+        gain if gain > 0 => println!(
+            "{} wins {} pawn{} of material by starting the exchange.",
+            side,
+            gain,
+            if gain == 1 { "" } else { "s" }
+        ),
+        0 => println!("Starting the exchange is an even trade for {}.", side),
+        gain => println!(
+            "Capturing here loses {} pawn{} for {}: the recapture costs more than the capture wins.",
+            -gain,
+            if gain == -1 { "" } else { "s" },
+            side
+        ),
+    }
+    if exchange.played > 1 && exchange.played < exchange.captures.len() {
+        println!(
+            "With best play the exchange stops after {} captures: going on would lose material.",
+            exchange.played
+        );
+    }
+}
+
+fn piece_label(piece: Piece) -> String {
+    match piece {
+        Piece::Pawn => String::new(),
+        piece => piece_char(piece).to_string(),
+    }
+}
+
+pub fn name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "pawn",
+        Piece::Knight => "knight",
+        Piece::Bishop => "bishop",
+        Piece::Rook => "rook",
+        Piece::Queen => "queen",
+        Piece::King => "king",
+    }
+}
@@ -0,0 +1,353 @@
+use crate::ui::color_name;
+use anyhow::{Result, anyhow};
+use chess::{
+    BitBoard, Board, Color, EMPTY, File, Piece, Rank, Square, get_bishop_moves, get_king_moves,
+    get_knight_moves, get_pawn_attacks, get_rook_moves,
+};
+use std::str::FromStr;
+
+/// Parse a FEN string, checking each field so that a bad FEN is reported
+/// with the exact problem instead of a generic "invalid FEN"
+pub fn parse_fen(text: &str) -> Result<Board> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() != 6 && fields.len() != 4 {
+        return Err(anyhow!(
+            "FEN must have 6 fields (placement, side, castling, en passant, halfmove, fullmove), found {}",
+            fields.len()
+        ));
+    }
+
+    let placement = parse_placement(fields[0])?;
+
+    let side_to_move = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        other => {
+            return Err(anyhow!(
+                "Side to move (field 2) must be 'w' or 'b', found '{}'",
+                other
+            ));
+        }
+    };
+
+    check_material(&placement)?;
+    check_castling(fields[2], &placement)?;
+    check_en_passant(fields[3], side_to_move, &placement)?;
+
+    if fields.len() == 6 {
+        fields[4].parse::<u32>().map_err(|_| {
+            anyhow!(
+                "Halfmove clock (field 5) must be a number, found '{}'",
+                fields[4]
+            )
+        })?;
+        match fields[5].parse::<u32>() {
+            Ok(n) if n >= 1 => {}
+            _ => {
+                return Err(anyhow!(
+                    "Fullmove number (field 6) must be a number of at least 1, found '{}'",
+                    fields[5]
+                ));
+            }
+        }
+    }
+
+    // The side that just moved can't have left its own king in check
+    let waiting_king = king_square(&placement, !side_to_move);
+    if attacked(&placement, waiting_king, side_to_move) {
+        return Err(anyhow!(
+            "{} is to move but {} is already in check, which is impossible",
+            color_name(side_to_move),
+            color_name(!side_to_move)
+        ));
+    }
+
+    let full = if fields.len() == 4 {
+        format!("{} 0 1", fields.join(" "))
+    } else {
+        fields.join(" ")
+    };
+    Board::from_str(&full).map_err(|_| anyhow!("Illegal position: {}", full))
+}
+
+type Placement = Vec<(Square, Piece, Color)>;
+
+/// Read a position from a file holding a FEN or an EPD line; the first
+/// line that isn't blank or a `#` comment counts
+pub fn read_position_file(path: &str) -> Result<Board> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or_else(|| anyhow!("No position found in {}", path))?;
+    parse_position_line(line).map_err(|e| anyhow!("{}: {}", path, e))
+}
+
+/// Every position in a file of FEN or EPD lines, skipping blank lines and
+/// `#` comments. Errors name the offending line.
+pub fn read_positions(path: &str) -> Result<Vec<Board>> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+    text.lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            parse_position_line(line).map_err(|e| anyhow!("{} line {}: {}", path, number + 1, e))
+        })
+        .collect()
+}
+
+/// A FEN, or an EPD record whose first four fields are used
+fn parse_position_line(line: &str) -> Result<Board> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    // EPD has the first four FEN fields followed by operations
+    let is_fen = fields.len() == 6 && fields[4..].iter().all(|f| f.parse::<u32>().is_ok());
+    let fen_text = if is_fen || fields.len() < 4 {
+        line.to_string()
+    } else {
+        format!("{} 0 1", fields[..4].join(" "))
+    };
+    parse_fen(&fen_text)
+}
+
+/// One EPD record: the first four FEN fields and `opcode "operand";` pairs.
+/// minichess writes `id`, `c0` for the user's comment and `c1` for the move
+/// that led to the position.
+pub fn to_epd(board: &Board, operations: &[(&str, String)]) -> String {
+    let fen = board.to_string();
+    let mut record = fen.split_whitespace().take(4).collect::<Vec<_>>().join(" ");
+    for (opcode, operand) in operations {
+        record.push_str(&format!(" {} \"{}\";", opcode, operand.replace('"', "'")));
+    }
+    record
+}
+
+/// Add a record to an EPD file, creating the file if needed, so a
+/// collection of positions can be built up over several sessions
+pub fn append_epd(path: &str, record: &str) -> Result<()> {
+    use std::io::Write;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow!("Failed to open {}: {}", path, e))?;
+    writeln!(file, "{}", record).map_err(|e| anyhow!("Failed to write {}: {}", path, e))
+}
+
+fn parse_placement(field: &str) -> Result<Placement> {
+    let ranks: Vec<&str> = field.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(anyhow!(
+            "Piece placement (field 1) must have 8 ranks separated by '/', found {}",
+            ranks.len()
+        ));
+    }
+
+    let mut placement = Vec::new();
+    for (row, rank_text) in ranks.iter().enumerate() {
+        let rank_number = 8 - row;
+        let mut file = 0;
+        for c in rank_text.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                if !(1..=8).contains(&empty) {
+                    return Err(anyhow!("Rank {}: invalid empty count '{}'", rank_number, c));
+                }
+                file += empty as usize;
+                continue;
+            }
+
+            let piece = match c.to_ascii_uppercase() {
+                'K' => Piece::King,
+                'Q' => Piece::Queen,
+                'R' => Piece::Rook,
+                'B' => Piece::Bishop,
+                'N' => Piece::Knight,
+                'P' => Piece::Pawn,
+                _ => return Err(anyhow!("Rank {}: unknown piece '{}'", rank_number, c)),
+            };
+            if file >= 8 {
+                return Err(anyhow!("Rank {} has more than 8 squares", rank_number));
+            }
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let square =
+                Square::make_square(Rank::from_index(rank_number - 1), File::from_index(file));
+            placement.push((square, piece, color));
+            file += 1;
+        }
+        if file != 8 {
+            return Err(anyhow!(
+                "Rank {} describes {} squares instead of 8",
+                rank_number,
+                file
+            ));
+        }
+    }
+    Ok(placement)
+}
+
+fn check_material(placement: &Placement) -> Result<()> {
+    for color in [Color::White, Color::Black] {
+        let count = |piece: Piece| {
+            placement
+                .iter()
+                .filter(|(_, p, c)| *p == piece && *c == color)
+                .count()
+        };
+        match count(Piece::King) {
+            1 => {}
+            0 => return Err(anyhow!("{} has no king", color_name(color))),
+            n => return Err(anyhow!("{} has {} kings", color_name(color), n)),
+        }
+        if count(Piece::Pawn) > 8 {
+            return Err(anyhow!("{} has more than 8 pawns", color_name(color)));
+        }
+        let total = placement.iter().filter(|(_, _, c)| *c == color).count();
+        if total > 16 {
+            return Err(anyhow!(
+                "{} has {} pieces (at most 16)",
+                color_name(color),
+                total
+            ));
+        }
+    }
+
+    for (square, piece, color) in placement {
+        if *piece == Piece::Pawn && matches!(square.get_rank(), Rank::First | Rank::Eighth) {
+            return Err(anyhow!(
+                "{} pawn on {}: pawns can't stand on the first or last rank",
+                color_name(*color),
+                square
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_castling(field: &str, placement: &Placement) -> Result<()> {
+    if field == "-" {
+        return Ok(());
+    }
+
+    let mut seen = Vec::new();
+    for right in field.chars() {
+        let (color, rook_square, side) = match right {
+            'K' => (Color::White, Square::H1, "kingside"),
+            'Q' => (Color::White, Square::A1, "queenside"),
+            'k' => (Color::Black, Square::H8, "kingside"),
+            'q' => (Color::Black, Square::A8, "queenside"),
+            _ => {
+                return Err(anyhow!(
+                    "Castling rights (field 3) may only contain K, Q, k, q or '-', found '{}'",
+                    right
+                ));
+            }
+        };
+        if seen.contains(&right) {
+            return Err(anyhow!("Castling right '{}' is listed twice", right));
+        }
+        seen.push(right);
+
+        let king_square = if color == Color::White {
+            Square::E1
+        } else {
+            Square::E8
+        };
+        if !placement.contains(&(king_square, Piece::King, color)) {
+            return Err(anyhow!(
+                "Castling right '{}': the {} king is not on {}",
+                right,
+                color_name(color).to_lowercase(),
+                king_square
+            ));
+        }
+        if !placement.contains(&(rook_square, Piece::Rook, color)) {
+            return Err(anyhow!(
+                "Castling right '{}': no {} rook on {} for {} castling",
+                right,
+                color_name(color).to_lowercase(),
+                rook_square,
+                side
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_en_passant(field: &str, side_to_move: Color, placement: &Placement) -> Result<()> {
+    if field == "-" {
+        return Ok(());
+    }
+
+    let square = Square::from_str(field).map_err(|_| {
+        anyhow!(
+            "En passant square (field 4) must be a square or '-', found '{}'",
+            field
+        )
+    })?;
+
+    // The pawn that just moved two squares stands one rank beyond the target
+    let (expected_rank, pawn_rank, mover) = if side_to_move == Color::White {
+        (Rank::Sixth, Rank::Fifth, Color::Black)
+    } else {
+        (Rank::Third, Rank::Fourth, Color::White)
+    };
+    if square.get_rank() != expected_rank {
+        return Err(anyhow!(
+            "En passant square {} is impossible with {} to move (must be on rank {})",
+            square,
+            color_name(side_to_move),
+            expected_rank.to_index() + 1
+        ));
+    }
+
+    let pawn_square = Square::make_square(pawn_rank, square.get_file());
+    if !placement.contains(&(pawn_square, Piece::Pawn, mover)) {
+        return Err(anyhow!(
+            "En passant square {} but no {} pawn on {} that could have just moved",
+            square,
+            color_name(mover).to_lowercase(),
+            pawn_square
+        ));
+    }
+    if placement.iter().any(|(sq, _, _)| *sq == square) {
+        return Err(anyhow!("En passant square {} is occupied", square));
+    }
+    Ok(())
+}
+
+fn king_square(placement: &Placement, color: Color) -> Square {
+    placement
+        .iter()
+        .find(|(_, piece, c)| *piece == Piece::King && *c == color)
+        .map(|(square, _, _)| *square)
+        .expect("kings are checked before this")
+}
+
+/// Whether any piece of `by` attacks `square`
+fn attacked(placement: &Placement, square: Square, by: Color) -> bool {
+    let occupied = placement
+        .iter()
+        .fold(EMPTY, |bb, (sq, _, _)| bb | BitBoard::from_square(*sq));
+
+    placement
+        .iter()
+        .filter(|(_, _, color)| *color == by)
+        .any(|(from, piece, _)| {
+            let attacks = match piece {
+                Piece::Pawn => get_pawn_attacks(*from, by, !EMPTY),
+                Piece::Knight => get_knight_moves(*from),
+                Piece::Bishop => get_bishop_moves(*from, occupied),
+                Piece::Rook => get_rook_moves(*from, occupied),
+                Piece::Queen => get_bishop_moves(*from, occupied) | get_rook_moves(*from, occupied),
+                Piece::King => get_king_moves(*from),
+            };
+            (attacks & BitBoard::from_square(square)) != EMPTY
+        })
+}
@@ -0,0 +1,217 @@
+use crate::fen;
+use crate::pgn;
+use crate::stats::{self, GameRecord};
+use crate::storage;
+use anyhow::{Result, anyhow};
+use chess::{BitBoard, Board, Color, File, Piece, Rank, Square, get_file, get_rank};
+use std::str::FromStr;
+
+/// Games listed at most
+const GAMES_SHOWN: usize = 50;
+
+/// What a stored position has to look like
+pub enum Query {
+    /// The same position: placement, side to move, castling rights and en
+    /// passant square, compared as EPD
+    Position(String),
+    /// Only the piece placement, whoever is to move
+    Placement(String),
+    /// Every clause holds
+    Pattern(Vec<Clause>),
+}
+
+/// "white queen on h-file": pieces of a kind, of one or either color, with
+/// at least one of them on the given squares
+pub struct Clause {
+    color: Option<Color>,
+    piece: Piece,
+    squares: BitBoard,
+}
+
+impl Query {
+    /// A full FEN (or its first four fields), or just the placement field
+    pub fn fen(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.split_whitespace().count() > 1 {
+            let board = fen::parse_fen(text)?;
+            return Ok(Query::Position(fen::to_epd(&board, &[])));
+        }
+        // Check the placement with either side to move; only one of them
+        // may leave the other side's king in check
+        fen::parse_fen(&format!("{} w - - 0 1", text))
+            .or_else(|e| fen::parse_fen(&format!("{} b - - 0 1", text)).map_err(|_| e))?;
+        Ok(Query::Placement(text.to_string()))
+    }
+
+    /// Clauses such as "white queen on h-file" or "black pawn on 7th rank",
+    /// joined with "and" or commas
+    pub fn pattern(text: &str) -> Result<Self> {
+        let clauses = text
+            .to_lowercase()
+            .replace(',', " and ")
+            .split(" and ")
+            .map(str::trim)
+            .filter(|clause| !clause.is_empty())
+            .map(Clause::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        if clauses.is_empty() {
+            return Err(anyhow!("--pattern needs at least one clause"));
+        }
+        Ok(Query::Pattern(clauses))
+    }
+
+    fn matches(&self, board: &Board) -> bool {
+        match self {
+            Query::Position(epd) => fen::to_epd(board, &[]) == *epd,
+            Query::Placement(placement) => board
+                .to_string()
+                .split_whitespace()
+                .next()
+                .is_some_and(|field| field == placement),
+            Query::Pattern(clauses) => clauses.iter().all(|clause| clause.matches(board)),
+        }
+    }
+}
+
+impl Clause {
+    fn matches(&self, board: &Board) -> bool {
+        let mut pieces = *board.pieces(self.piece) & self.squares;
+        if let Some(color) = self.color {
+            pieces &= board.color_combined(color);
+        }
+        pieces.popcnt() > 0
+    }
+}
+
+impl FromStr for Clause {
+    type Err = anyhow::Error;
+
+    fn from_str(clause: &str) -> Result<Self> {
+        let usage = || {
+            anyhow!(
+                "Can't read \"{}\"; clauses look like \"white queen on h-file\", \"black pawn on 7th rank\" or \"knight on d5\"",
+                clause
+            )
+        };
+        let (subject, area) = clause.split_once(" on ").ok_or_else(usage)?;
+        let words: Vec<&str> = subject.split_whitespace().collect();
+        let (color, piece) = match words.as_slice() {
+            [color, piece] => (Some(parse_color(color).ok_or_else(usage)?), *piece),
+            [piece] => (None, *piece),
+            _ => return Err(usage()),
+        };
+        Ok(Clause {
+            color,
+            piece: parse_piece(piece).ok_or_else(usage)?,
+            squares: parse_area(area.trim()).ok_or_else(usage)?,
+        })
+    }
+}
+
+fn parse_color(word: &str) -> Option<Color> {
+    match word {
+        "white" => Some(Color::White),
+        "black" => Some(Color::Black),
+        _ => None,
+    }
+}
+
+fn parse_piece(word: &str) -> Option<Piece> {
+    match word.strip_suffix('s').unwrap_or(word) {
+        "king" => Some(Piece::King),
+        "queen" => Some(Piece::Queen),
+        "rook" => Some(Piece::Rook),
+        "bishop" => Some(Piece::Bishop),
+        "knight" => Some(Piece::Knight),
+        "pawn" => Some(Piece::Pawn),
+        _ => None,
+    }
+}
+
+/// A square ("e4"), a file ("h-file", "h file", "file h") or a rank
+/// ("7th rank", "rank 7")
+fn parse_area(text: &str) -> Option<BitBoard> {
+    if let Ok(square) = Square::from_str(text) {
+        return Some(BitBoard::from_square(square));
+    }
+    let words: Vec<&str> = text.split(['-', ' ']).filter(|w| !w.is_empty()).collect();
+    let (kind, value) = match words.as_slice() {
+        [value, kind @ ("file" | "rank")] | [kind @ ("file" | "rank"), value] => (*kind, *value),
+        _ => return None,
+    };
+    let mut chars = value.chars();
+    let first = chars.next()?;
+    if kind == "file" {
+        let index = "abcdefgh".find(first).filter(|_| chars.next().is_none())?;
+        return Some(get_file(File::from_index(index)));
+    }
+    let digit = first.to_digit(10).filter(|d| (1..=8).contains(d))?;
+    // "7", "7th", "1st", "2nd", "3rd"
+    if !matches!(chars.as_str(), "" | "st" | "nd" | "rd" | "th") {
+        return None;
+    }
+    Some(get_rank(Rank::from_index(digit as usize - 1)))
+}
+
+/// Go through the profile's stored games and list the games and plies
+/// where a position matching the query came up
+pub fn run(records: &[GameRecord], query: &Query) {
+    println!("\n=== Position search ({}) ===", storage::profile_name());
+    let mut found = 0;
+    for (index, record) in records.iter().enumerate() {
+        let moves = record.known_moves();
+        // Games that began from a set-up position keep no moves
+        if moves.is_empty() {
+            continue;
+        }
+        let mut board = Board::default();
+        let mut hits = Vec::new();
+        if query.matches(&board) {
+            hits.push("start".to_string());
+        }
+        for (ply, chess_move) in moves.into_iter().enumerate() {
+            let before = board;
+            board = board.make_move_new(chess_move);
+            if query.matches(&board) {
+                let dots = if before.side_to_move() == Color::White {
+                    "."
+                } else {
+                    "..."
+                };
+                hits.push(format!(
+                    "ply {} ({}{}{})",
+                    ply + 1,
+                    ply / 2 + 1,
+                    dots,
+                    pgn::move_to_san(&before, chess_move)
+                ));
+            }
+        }
+        if hits.is_empty() {
+            continue;
+        }
+        found += 1;
+        if found <= GAMES_SHOWN {
+            println!(
+                "#{} {} {} as {}, {}: {}",
+                index + 1,
+                record.date,
+                record.mode,
+                record.player_color,
+                record.result,
+                hits.join(", ")
+            );
+        }
+    }
+    match found {
+        0 => println!("No stored game reaches such a position."),
+        n if n > GAMES_SHOWN => println!("... and {} more game(s)", n - GAMES_SHOWN),
+        _ => {}
+    }
+    println!(
+        "\n{} of {} game(s) match. Only rated and ladder games keep every move; the others are searched through their first {} plies.",
+        found,
+        records.len(),
+        stats::LINE_PLIES
+    );
+}
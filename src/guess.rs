@@ -0,0 +1,157 @@
+use crate::pgn::{self, PgnGame};
+use crate::stockfish::{StockfishEngine, format_centipawns};
+use crate::ui::{color_name, display_board_for_player, get_user_input};
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, Color};
+
+const POINTS_FOR_EXACT_MATCH: u32 = 10;
+
+/// "Guess the move" training: replay a master game and ask the player to
+/// find each of the winner's moves
+pub async fn run(
+    stockfish_path: &str,
+    pgn_path: &str,
+    game_number: usize,
+    side: Option<Color>,
+    depth: u8,
+) -> Result<()> {
+    let games = pgn::load_pgn_file(pgn_path)?;
+    let game = games.get(game_number.saturating_sub(1)).ok_or_else(|| {
+        anyhow!(
+            "PGN file contains {} game(s), cannot open game {}",
+            games.len(),
+            game_number
+        )
+    })?;
+
+    let guess_color = match side.or_else(|| game.winner()) {
+        Some(color) => color,
+        None => {
+            println!(
+                "This game has no winner, you will guess White's moves (use --side to change)"
+            );
+            Color::White
+        }
+    };
+
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+
+    print_game_header(game, guess_color);
+
+    let mut board = game.start;
+    let mut points = 0;
+    let mut guesses = 0;
+    let mut exact_matches = 0;
+
+    for &actual in &game.moves {
+        let actual_san = pgn::move_to_san(&board, actual);
+
+        if board.side_to_move() != guess_color {
+            println!("{} plays {}", color_name(board.side_to_move()), actual_san);
+            board = board.make_move_new(actual);
+            continue;
+        }
+
+        display_board_for_player(&board, guess_color);
+        let guess = match prompt_guess(&board)? {
+            Some(guess) => guess,
+            None => break,
+        };
+        guesses += 1;
+
+        if guess == actual {
+            exact_matches += 1;
+            points += POINTS_FOR_EXACT_MATCH;
+            println!(
+                "Correct! {} is the move that was played. +{} points",
+                actual_san, POINTS_FOR_EXACT_MATCH
+            );
+        } else {
+            let guess_san = pgn::move_to_san(&board, guess);
+            println!("Checking your move with the engine...");
+
+            // Scores come back from the opponent's point of view after the move
+            let actual_eval = -engine
+                .evaluate(&board.make_move_new(actual), depth)
+                .await?
+                .as_centipawns();
+            let guess_eval = -engine
+                .evaluate(&board.make_move_new(guess), depth)
+                .await?
+                .as_centipawns();
+
+            let awarded = points_for_eval_loss(actual_eval - guess_eval);
+            points += awarded;
+
+            println!(
+                "The game continued {} ({}). Your move {} ({}). +{} points",
+                actual_san,
+                format_centipawns(actual_eval),
+                guess_san,
+                format_centipawns(guess_eval),
+                awarded
+            );
+        }
+
+        board = board.make_move_new(actual);
+    }
+
+    println!("\n=== Guess the Move Results ===");
+    println!("Moves guessed:  {}", guesses);
+    println!("Exact matches:  {}", exact_matches);
+    println!(
+        "Score:          {} / {}",
+        points,
+        guesses as u32 * POINTS_FOR_EXACT_MATCH
+    );
+    if let Some(result) = game.header("Result") {
+        println!("Game result:    {}", result);
+    }
+    println!("==============================\n");
+
+    Ok(())
+}
+
+fn print_game_header(game: &PgnGame, guess_color: Color) {
+    println!(
+        "\n=== {} vs {} ===",
+        game.header("White").unwrap_or("?"),
+        game.header("Black").unwrap_or("?")
+    );
+    if let Some(event) = game.header("Event") {
+        println!("Event: {}", event);
+    }
+    println!(
+        "Guess {}'s moves. Enter moves in SAN (Nf3) or coordinates (g1f3), 'q' to stop.",
+        color_name(guess_color)
+    );
+}
+
+fn prompt_guess(board: &Board) -> Result<Option<ChessMove>> {
+    loop {
+        println!("\nYour guess:");
+        let input = get_user_input()?;
+        let input = input.trim();
+
+        if input.eq_ignore_ascii_case("q") || input.eq_ignore_ascii_case("quit") {
+            return Ok(None);
+        }
+
+        match pgn::parse_move(board, input) {
+            Ok(chess_move) => return Ok(Some(chess_move)),
+            Err(e) => println!("Invalid move: {}. Try again.", e),
+        }
+    }
+}
+
+/// Partial credit for a move that differs from the one played, based on how
+/// much worse the engine thinks it is
+fn points_for_eval_loss(loss: i32) -> u32 {
+    match loss {
+        i32::MIN..=0 => 8,
+        1..=25 => 6,
+        26..=75 => 4,
+        76..=150 => 2,
+        _ => 0,
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, MoveGen, Piece};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+static HOUSE_RULES: OnceLock<HouseRules> = OnceLock::new();
+
+/// Rule tweaks for casual games, set in the config file's `house_rules`
+/// block or with `--house-rules`. Rated and ladder games ignore them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HouseRules {
+    pub no_castling: bool,
+    /// Pawns promote only to queens
+    pub queen_promotion: bool,
+    /// Checkmate ends the game only once the king is actually taken, and a
+    /// stalemated side loses because any move would walk into capture
+    pub capture_the_king: bool,
+}
+
+/// Names accepted by `--house-rules`, in the order they are listed
+const NAMES: [&str; 3] = ["no-castling", "queen-promotion", "capture-the-king"];
+
+/// Use these rules for casual games. Called once from main.
+pub fn set(rules: HouseRules) {
+    let _ = HOUSE_RULES.set(rules);
+}
+
+pub fn get() -> HouseRules {
+    HOUSE_RULES.get().copied().unwrap_or_default()
+}
+
+impl HouseRules {
+    /// Comma-separated rule names, e.g. "no-castling,queen-promotion", or
+    /// "none" to turn off rules the config file enables
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = HouseRules::default();
+        for name in text.split(',').map(|name| name.trim().to_lowercase()) {
+            match name.as_str() {
+                "none" | "" => {}
+                "no-castling" => rules.no_castling = true,
+                "queen-promotion" | "queen-only" => rules.queen_promotion = true,
+                "capture-the-king" | "king-capture" => rules.capture_the_king = true,
+                other => {
+                    return Err(anyhow!(
+                        "Unknown house rule '{}', choose from: {}",
+                        other,
+                        NAMES.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn any(&self) -> bool {
+        *self != HouseRules::default()
+    }
+
+    /// The rules in force, for the start-of-game banner
+    pub fn describe(&self) -> String {
+        [
+            (self.no_castling, "no castling"),
+            (self.queen_promotion, "pawns promote only to queens"),
+            (self.capture_the_king, "capture the king to win"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, text)| *text)
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    /// Why the rules forbid a legal move, if they do
+    pub fn forbids(&self, board: &Board, chess_move: ChessMove) -> Option<&'static str> {
+        if self.no_castling && is_castling(board, chess_move) {
+            return Some("castling is off by house rules");
+        }
+        if self.queen_promotion
+            && matches!(chess_move.get_promotion(), Some(p) if p != Piece::Queen)
+        {
+            return Some("pawns promote only to queens by house rules");
+        }
+        None
+    }
+
+    pub fn allows(&self, board: &Board, chess_move: ChessMove) -> bool {
+        self.forbids(board, chess_move).is_none()
+    }
+
+    /// The legal moves these rules leave. Removing castling or an
+    /// under-promotion always leaves another move, so the rules never turn a
+    /// position into stalemate.
+    pub fn legal_moves(&self, board: &Board) -> Vec<ChessMove> {
+        MoveGen::new_legal(board)
+            .filter(|&chess_move| self.allows(board, chess_move))
+            .collect()
+    }
+}
+
+/// A king moving two files is castling
+fn is_castling(board: &Board, chess_move: ChessMove) -> bool {
+    board.piece_on(chess_move.get_source()) == Some(Piece::King)
+        && chess_move
+            .get_source()
+            .get_file()
+            .to_index()
+            .abs_diff(chess_move.get_dest().get_file().to_index())
+            == 2
+}
@@ -0,0 +1,253 @@
+use serde::Serialize;
+
+/// One move of an exported game
+#[derive(Serialize)]
+pub struct HtmlMove {
+    pub san: String,
+    /// Position after the move
+    pub fen: String,
+    pub comment: Option<String>,
+    /// Evaluation after the move in pawns from White's point of view,
+    /// mates clamped to ±10
+    pub eval: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct GameData<'a> {
+    start: &'a str,
+    moves: &'a [HtmlMove],
+}
+
+/// Evaluations beyond this many pawns are drawn at the edge of the graph
+const GRAPH_LIMIT: f64 = 5.0;
+const GRAPH_WIDTH: f64 = 600.0;
+const GRAPH_HEIGHT: f64 = 120.0;
+
+/// A standalone page (no external files or scripts) with a board that steps
+/// through the game, the move list with comments, and an evaluation graph.
+/// Clicking a move or a point on the graph jumps to that position.
+pub fn render(headers: &[(String, String)], start_fen: &str, moves: &[HtmlMove]) -> String {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map_or("?", |(_, value)| value.as_str())
+    };
+    let title = format!("{} - {}", header("White"), header("Black"));
+    let data = serde_json::to_string(&GameData {
+        start: start_fen,
+        moves,
+    })
+    .unwrap_or_else(|_| "{}".to_string())
+    // Keep "</script>" inside strings from ending the script early
+    .replace("</", "<\\/");
+
+    let details: String = headers
+        .iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "White" | "Black" | "FEN" | "SetUp"))
+        .map(|(name, value)| format!("<dt>{}</dt><dd>{}</dd>", escape(name), escape(value)))
+        .collect();
+
+    PAGE.replace("{title}", &escape(&title))
+        .replace("{details}", &details)
+        .replace("{graph}", &eval_graph(moves))
+        .replace("{data}", &data)
+}
+
+/// The evaluation after each move as an SVG line, White's advantage upward
+fn eval_graph(moves: &[HtmlMove]) -> String {
+    let points: Vec<(usize, f64)> = moves
+        .iter()
+        .enumerate()
+        .filter_map(|(ply, m)| m.eval.map(|eval| (ply, eval)))
+        .collect();
+    if points.is_empty() {
+        return String::new();
+    }
+    let step = GRAPH_WIDTH / moves.len().max(1) as f64;
+    let y = |eval: f64| {
+        GRAPH_HEIGHT / 2.0
+            - eval.clamp(-GRAPH_LIMIT, GRAPH_LIMIT) / GRAPH_LIMIT * GRAPH_HEIGHT / 2.0
+    };
+    let line: Vec<String> = points
+        .iter()
+        .map(|&(ply, eval)| format!("{:.1},{:.1}", (ply as f64 + 0.5) * step, y(eval)))
+        .collect();
+    let targets: String = (0..moves.len())
+        .map(|ply| {
+            format!(
+                "<rect class=\"hit\" data-ply=\"{}\" x=\"{:.1}\" y=\"0\" width=\"{:.1}\" height=\"{}\"/>",
+                ply + 1,
+                ply as f64 * step,
+                step,
+                GRAPH_HEIGHT
+            )
+        })
+        .collect();
+    format!(
+        "<svg id=\"graph\" viewBox=\"0 0 {w} {h}\" preserveAspectRatio=\"none\">\
+         <rect width=\"{w}\" height=\"{h}\" class=\"bg\"/>\
+         <line x1=\"0\" y1=\"{mid}\" x2=\"{w}\" y2=\"{mid}\" class=\"axis\"/>\
+         <polyline points=\"{line}\" class=\"eval\"/>\
+         <line id=\"cursor\" x1=\"0\" y1=\"0\" x2=\"0\" y2=\"{h}\" class=\"cursor\"/>\
+         {targets}</svg>",
+        w = GRAPH_WIDTH,
+        h = GRAPH_HEIGHT,
+        mid = GRAPH_HEIGHT / 2.0,
+        line = line.join(" "),
+        targets = targets
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { font-family: sans-serif; margin: 2em; color: #222; }
+main { display: flex; gap: 2em; flex-wrap: wrap; }
+#board { display: grid; grid-template-columns: repeat(8, 52px); border: 2px solid #444; width: max-content; }
+#board div { width: 52px; height: 52px; font-size: 40px; line-height: 52px; text-align: center; }
+.light { background: #eed8b5; } .dark { background: #b48963; }
+.last { box-shadow: inset 0 0 0 3px #e0c341; }
+#controls { margin: 0.5em 0; } #controls button { font-size: 1.1em; width: 3em; }
+#moves { max-height: 420px; overflow-y: auto; min-width: 18em; }
+#moves span.move { cursor: pointer; padding: 0 0.2em; }
+#moves span.current { background: #ffe27a; }
+#moves .number { color: #888; } #moves .comment { color: #2a6; font-size: 0.9em; }
+#graph { width: 600px; height: 120px; display: block; margin-top: 1em; }
+#graph .bg { fill: #f4f4f4; } #graph .axis { stroke: #aaa; }
+#graph .eval { fill: none; stroke: #333; stroke-width: 2; }
+#graph .cursor { stroke: #d33; stroke-width: 2; } #graph .hit { fill: transparent; cursor: pointer; }
+dl { display: grid; grid-template-columns: auto 1fr; gap: 0 1em; font-size: 0.9em; }
+dt { color: #666; } dd { margin: 0; }
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<main>
+<section>
+<div id="board"></div>
+<div id="controls">
+<button id="first">&#x23EE;</button><button id="prev">&#x25C0;</button><button id="next">&#x25B6;</button><button id="last">&#x23ED;</button>
+<button id="flip">&#x21C5;</button>
+</div>
+<div id="status"></div>
+{graph}
+</section>
+<section>
+<div id="moves"></div>
+<dl>{details}</dl>
+</section>
+</main>
+<script>
+const game = {data};
+const glyphs = { K: "♔", Q: "♕", R: "♖", B: "♗", N: "♘", P: "♙",
+                 k: "♚", q: "♛", r: "♜", b: "♝", n: "♞", p: "♟" };
+const fens = [game.start].concat(game.moves.map(m => m.fen));
+let ply = 0;
+let flipped = false;
+
+function placement(fen) {
+  const squares = [];
+  for (const row of fen.split(" ")[0].split("/")) {
+    for (const c of row) {
+      if (c >= "1" && c <= "8") { for (let i = 0; i < Number(c); i++) squares.push(""); }
+      else squares.push(c);
+    }
+  }
+  return squares;
+}
+
+function drawBoard() {
+  const now = placement(fens[ply]);
+  const before = ply > 0 ? placement(fens[ply - 1]) : now;
+  const board = document.getElementById("board");
+  board.innerHTML = "";
+  for (let i = 0; i < 64; i++) {
+    const index = flipped ? 63 - i : i;
+    const cell = document.createElement("div");
+    const row = Math.floor(index / 8), col = index % 8;
+    cell.className = (row + col) % 2 === 0 ? "light" : "dark";
+    if (now[index] !== before[index]) cell.classList.add("last");
+    cell.textContent = glyphs[now[index]] || "";
+    board.appendChild(cell);
+  }
+}
+
+function drawMoves() {
+  const list = document.getElementById("moves");
+  const blackFirst = game.start.split(" ")[1] === "b";
+  const firstNumber = Number(game.start.split(" ")[5] || 1);
+  list.innerHTML = "";
+  game.moves.forEach((move, i) => {
+    const whiteMove = (i + (blackFirst ? 1 : 0)) % 2 === 0;
+    if (whiteMove || i === 0) {
+      const number = document.createElement("span");
+      number.className = "number";
+      number.textContent = " " + (firstNumber + Math.floor((i + (blackFirst ? 1 : 0)) / 2)) + (whiteMove ? ". " : "... ");
+      list.appendChild(number);
+    }
+    const span = document.createElement("span");
+    span.className = "move";
+    span.dataset.ply = i + 1;
+    span.textContent = move.san;
+    span.onclick = () => go(i + 1);
+    list.appendChild(span);
+    if (move.comment) {
+      const comment = document.createElement("span");
+      comment.className = "comment";
+      comment.textContent = " {" + move.comment + "} ";
+      list.appendChild(comment);
+    }
+  });
+}
+
+function go(target) {
+  ply = Math.max(0, Math.min(fens.length - 1, target));
+  drawBoard();
+  document.querySelectorAll("#moves span.move").forEach(span => {
+    span.classList.toggle("current", Number(span.dataset.ply) === ply);
+  });
+  const current = document.querySelector("#moves span.current");
+  if (current) current.scrollIntoView({ block: "nearest" });
+  const move = game.moves[ply - 1];
+  let status = ply === 0 ? "Start position" : "After " + move.san;
+  if (move && move.eval !== null) status += " (" + (move.eval > 0 ? "+" : "") + move.eval.toFixed(2) + ")";
+  document.getElementById("status").textContent = status;
+  const cursor = document.getElementById("cursor");
+  if (cursor) {
+    const x = (Math.max(ply, 1) - 0.5) * 600 / Math.max(game.moves.length, 1);
+    cursor.setAttribute("x1", x);
+    cursor.setAttribute("x2", x);
+  }
+}
+
+document.getElementById("first").onclick = () => go(0);
+document.getElementById("prev").onclick = () => go(ply - 1);
+document.getElementById("next").onclick = () => go(ply + 1);
+document.getElementById("last").onclick = () => go(fens.length - 1);
+document.getElementById("flip").onclick = () => { flipped = !flipped; drawBoard(); };
+document.querySelectorAll("#graph .hit").forEach(rect => {
+  rect.onclick = () => go(Number(rect.dataset.ply));
+});
+document.addEventListener("keydown", event => {
+  if (event.key === "ArrowLeft") go(ply - 1);
+  if (event.key === "ArrowRight") go(ply + 1);
+  if (event.key === "Home") go(0);
+  if (event.key === "End") go(fens.length - 1);
+});
+drawMoves();
+go(0);
+</script>
+</body>
+</html>
+"##;
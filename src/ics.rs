@@ -0,0 +1,285 @@
+use crate::pgn;
+use crate::ui::{self, display_board_for_player};
+use anyhow::{Result, anyhow};
+use chess::{Board, Color};
+use std::str::FromStr;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::net::tcp::OwnedWriteHalf;
+
+pub const DEFAULT_HOST: &str = "freechess.org";
+pub const DEFAULT_PORT: u16 = 5000;
+
+/// Telnet "interpret as command" byte, followed by two option bytes
+const TELNET_IAC: u8 = 255;
+
+/// Position of an ICS game, decoded from a style 12 board line
+struct IcsBoard {
+    board: Board,
+    game_number: u32,
+    white_name: String,
+    black_name: String,
+    /// Our color, or None when observing
+    my_color: Option<Color>,
+    white_seconds: i64,
+    black_seconds: i64,
+    last_move: String,
+}
+
+/// Free Internet Chess Server client: log in, seek or accept games and play
+/// them with the regular board display and move input
+pub async fn run(host: &str, port: u16, handle: &str, password: Option<&str>) -> Result<()> {
+    let stream = TcpStream::connect((host, port))
+        .await
+        .map_err(|e| anyhow!("Failed to connect to {}:{}: {}", host, port, e))?;
+    let (mut server, mut writer) = stream.into_split();
+
+    println!("Connected to {}:{}", host, port);
+    println!("Type ICS commands directly (seek 5 0, accept, match <player>, resign, ...).");
+    println!(
+        "During a game, enter moves in SAN or coordinates. 'show' redraws the board, 'quit' exits."
+    );
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut pending = String::new();
+    let mut buffer = [0u8; 4096];
+    let mut logged_in = false;
+    let mut current: Option<IcsBoard> = None;
+
+    loop {
+        tokio::select! {
+            read = server.read(&mut buffer) => {
+                let count = read?;
+                if count == 0 {
+                    println!("\nConnection closed by server.");
+                    return Ok(());
+                }
+                pending.push_str(&String::from_utf8_lossy(&strip_telnet(&buffer[..count])));
+
+                for line in take_lines(&mut pending) {
+                    if let Some(board) = parse_style12(&line) {
+                        show_board(&board);
+                        current = Some(board);
+                    } else if line.starts_with("login:") {
+                        send(&mut writer, handle).await?;
+                    } else if line.starts_with("password:") {
+                        let password = password.ok_or_else(|| {
+                            anyhow!("Server asked for a password, use --password")
+                        })?;
+                        send(&mut writer, password).await?;
+                    } else if line.starts_with("Press return") {
+                        send(&mut writer, "").await?;
+                    } else if line.starts_with("fics%") {
+                        if !logged_in {
+                            // Machine-readable boards, no seek advertisement spam
+                            logged_in = true;
+                            send(&mut writer, "set style 12").await?;
+                            send(&mut writer, "set seek 0").await?;
+                            send(&mut writer, "set bell 0").await?;
+                        }
+                    } else if !line.trim().is_empty() {
+                        println!("{}", line);
+                    }
+                }
+            }
+            input = ui::next_input(&mut stdin) => {
+                let Some(input) = input? else {
+                    return Ok(());
+                };
+                let input = input.trim();
+
+                match input {
+                    "quit" | "q" => {
+                        send(&mut writer, "quit").await?;
+                        return Ok(());
+                    }
+                    "show" | "board" => match &current {
+                        Some(board) => show_board(board),
+                        None => println!("No game in progress."),
+                    },
+                    _ => {
+                        let command = match &current {
+                            Some(game) if game.my_color == Some(game.board.side_to_move()) => {
+                                // Send legal moves in SAN, which every ICS understands
+                                match pgn::parse_move(&game.board, input) {
+                                    Ok(chess_move) => pgn::move_to_san(&game.board, chess_move)
+                                        .trim_end_matches(['+', '#'])
+                                        .to_string(),
+                                    Err(_) => input.to_string(),
+                                }
+                            }
+                            _ => input.to_string(),
+                        };
+                        send(&mut writer, &command).await?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn send(writer: &mut OwnedWriteHalf, command: &str) -> Result<()> {
+    writer
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn strip_telnet(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut skip = 0;
+    for &b in bytes {
+        if skip > 0 {
+            skip -= 1;
+        } else if b == TELNET_IAC {
+            skip = 2;
+        } else if b != b'\r' {
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// Split complete lines off the buffer. Prompts ("login: ", "fics% ") are
+/// not newline-terminated, so a trailing prompt is returned as a line too.
+fn take_lines(pending: &mut String) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    while let Some(pos) = pending.find('\n') {
+        lines.push(pending[..pos].to_string());
+        pending.drain(..=pos);
+    }
+
+    let rest = pending.trim_start();
+    if rest.starts_with("login:")
+        || rest.starts_with("password:")
+        || rest.starts_with("fics%")
+        || (rest.starts_with("Press return") && rest.trim_end().ends_with(':'))
+    {
+        lines.push(rest.to_string());
+        pending.clear();
+    }
+
+    // A prompt can also prefix the next line, e.g. "fics% <12> ..."
+    lines
+        .into_iter()
+        .flat_map(|line| match line.strip_prefix("fics% ") {
+            Some(rest) if !rest.is_empty() => vec!["fics%".to_string(), rest.to_string()],
+            _ => vec![line],
+        })
+        .collect()
+}
+
+fn parse_style12(line: &str) -> Option<IcsBoard> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.first() != Some(&"<12>") || fields.len() < 30 {
+        return None;
+    }
+
+    // Ranks 8 to 1, '-' for empty squares
+    let placement = fields[1..=8]
+        .iter()
+        .map(|rank| {
+            let mut out = String::new();
+            let mut empty = 0;
+            for c in rank.chars() {
+                if c == '-' {
+                    empty += 1;
+                } else {
+                    if empty > 0 {
+                        out.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    out.push(c);
+                }
+            }
+            if empty > 0 {
+                out.push_str(&empty.to_string());
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let side_to_move = if fields[9] == "W" {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    let mut castling = String::new();
+    for (flag, c) in fields[11..=14].iter().zip(['K', 'Q', 'k', 'q']) {
+        if *flag == "1" {
+            castling.push(c);
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+
+    // Field 10 is the file of a pawn that just moved two squares, or -1
+    let en_passant = match fields[10].parse::<i32>() {
+        Ok(file @ 0..=7) => format!(
+            "{}{}",
+            (b'a' + file as u8) as char,
+            if side_to_move == Color::White { 6 } else { 3 }
+        ),
+        _ => "-".to_string(),
+    };
+
+    let fen = format!(
+        "{} {} {} {} {} {}",
+        placement,
+        if side_to_move == Color::White {
+            "w"
+        } else {
+            "b"
+        },
+        castling,
+        en_passant,
+        fields[15],
+        fields[26]
+    );
+    let board = Board::from_str(&fen).ok()?;
+
+    // Relation: 1 = our move, -1 = opponent's move, anything else = observing/examining
+    let my_color = match fields[19] {
+        "1" => Some(side_to_move),
+        "-1" => Some(!side_to_move),
+        _ => None,
+    };
+
+    Some(IcsBoard {
+        board,
+        game_number: fields[16].parse().unwrap_or(0),
+        white_name: fields[17].to_string(),
+        black_name: fields[18].to_string(),
+        my_color,
+        white_seconds: fields[24].parse().unwrap_or(0),
+        black_seconds: fields[25].parse().unwrap_or(0),
+        last_move: fields[29].to_string(),
+    })
+}
+
+fn show_board(game: &IcsBoard) {
+    display_board_for_player(&game.board, game.my_color.unwrap_or(Color::White));
+    println!(
+        "Game {}: {} {} | {} {} | last move: {}",
+        game.game_number,
+        game.white_name,
+        format_clock(game.white_seconds),
+        game.black_name,
+        format_clock(game.black_seconds),
+        game.last_move
+    );
+    if game.my_color == Some(game.board.side_to_move()) {
+        println!("Your move:");
+    }
+}
+
+fn format_clock(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+    format!("{}{}:{:02}", sign, seconds / 60, seconds % 60)
+}
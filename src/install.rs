@@ -0,0 +1,185 @@
+use crate::config;
+use crate::integrity;
+use crate::storage;
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Stockfish release installed by `engine install` unless another tag is given
+pub const DEFAULT_RELEASE: &str = "sf_17.1";
+const RELEASES_API: &str =
+    "https://api.github.com/repos/official-stockfish/Stockfish/releases/tags";
+
+/// `engine install`: download Stockfish and make it the configured engine
+pub fn run(release: &str) -> Result<()> {
+    let binary = install_stockfish(release)?;
+    let mut settings = config::load()?.unwrap_or_default();
+    settings.stockfish_path = Some(binary.to_string_lossy().into_owned());
+    config::save(&settings)?;
+    println!("Saved as your engine in {}.", config::path()?.display());
+    Ok(())
+}
+
+/// Download the official Stockfish build for this machine into the data
+/// directory and verify its SHA-256 checksum. Returns the installed binary.
+pub fn install_stockfish(release: &str) -> Result<PathBuf> {
+    let asset = asset_name()?;
+    println!("Looking up {} in Stockfish release {}...", asset, release);
+
+    let api_url = format!("{}/{}", RELEASES_API, release);
+    let listing = curl_output(&api_url)?;
+    let listing: serde_json::Value = serde_json::from_slice(&listing)
+        .map_err(|e| anyhow!("Unexpected answer from {}: {}", api_url, e))?;
+    let entry = listing["assets"]
+        .as_array()
+        .and_then(|assets| assets.iter().find(|a| a["name"] == asset))
+        .ok_or_else(|| anyhow!("Release {} has no build named {}", release, asset))?;
+    let url = entry["browser_download_url"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Release {} lists no download for {}", release, asset))?;
+    let expected = entry["digest"]
+        .as_str()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .ok_or_else(|| anyhow!("No checksum published for {}, not installing it", asset))?
+        .to_lowercase();
+
+    let dir = storage::data_dir()?.join("engines").join(release);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    let archive = dir.join(&asset);
+    println!("Downloading {}...", url);
+    let status = Command::new("curl")
+        .arg("-fL")
+        .arg("-o")
+        .arg(&archive)
+        .arg(url)
+        .status()
+        .map_err(|e| anyhow!("Failed to run curl: {}", e))?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&archive);
+        return Err(anyhow!("Download of {} failed", url));
+    }
+
+    let actual = integrity::file_sha256(&archive)?;
+    if actual != expected {
+        let _ = std::fs::remove_file(&archive);
+        return Err(anyhow!(
+            "Checksum mismatch for {} (expected {}, got {}); the download was deleted",
+            asset,
+            expected,
+            actual
+        ));
+    }
+    println!("Checksum verified.");
+
+    // tar unpacks .zip archives too on Windows 10 and later
+    let status = Command::new("tar")
+        .arg("-xf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&dir)
+        .status()
+        .map_err(|e| anyhow!("Failed to run tar: {}", e))?;
+    if !status.success() {
+        return Err(anyhow!("Failed to unpack {}", archive.display()));
+    }
+    let _ = std::fs::remove_file(&archive);
+
+    let binary = find_binary(&dir, asset_stem(&asset))
+        .ok_or_else(|| anyhow!("No Stockfish binary found in {}", dir.display()))?;
+    make_executable(&binary)?;
+    println!("Installed {}", binary.display());
+    Ok(binary)
+}
+
+/// Name of the release asset for this OS and CPU, e.g. stockfish-ubuntu-x86-64-avx2.tar
+fn asset_name() -> Result<String> {
+    let (os, extension) = match std::env::consts::OS {
+        "linux" => ("ubuntu", "tar"),
+        "macos" => ("macos", "tar"),
+        "windows" => ("windows", "zip"),
+        other => return Err(anyhow!("No official Stockfish build for {}", other)),
+    };
+    let cpu = match std::env::consts::ARCH {
+        "x86_64" => x86_64_flavor(),
+        "aarch64" if os == "macos" => "m1-apple-silicon".to_string(),
+        other => {
+            return Err(anyhow!(
+                "No official Stockfish build for {} on {}; install it with your package manager",
+                other,
+                std::env::consts::OS
+            ));
+        }
+    };
+    Ok(format!("stockfish-{}-{}.{}", os, cpu, extension))
+}
+
+/// The fastest x86-64 build this CPU can run
+#[cfg(target_arch = "x86_64")]
+fn x86_64_flavor() -> String {
+    let flavor = if std::is_x86_feature_detected!("avx2") && std::is_x86_feature_detected!("bmi2") {
+        "x86-64-bmi2"
+    } else if std::is_x86_feature_detected!("avx2") {
+        "x86-64-avx2"
+    } else if std::is_x86_feature_detected!("sse4.1") && std::is_x86_feature_detected!("popcnt") {
+        "x86-64-sse41-popcnt"
+    } else {
+        "x86-64"
+    };
+    flavor.to_string()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn x86_64_flavor() -> String {
+    "x86-64".to_string()
+}
+
+fn asset_stem(asset: &str) -> &str {
+    asset.rsplit_once('.').map_or(asset, |(stem, _)| stem)
+}
+
+/// The archive unpacks to a folder holding a binary named like the asset
+fn find_binary(dir: &Path, stem: &str) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            if let Some(found) = find_binary(&path, stem) {
+                return Some(found);
+            }
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(stem))
+        {
+            return Some(path);
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| anyhow!("Failed to make {} executable: {}", path.display(), e))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn curl_output(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "-H", "Accept: application/vnd.github+json", url])
+        .output()
+        .map_err(|e| anyhow!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Request to {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,262 @@
+use crate::stats::GameRecord;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
+
+/// Starts every chain; a new layout would get a new version
+const CHAIN_VERSION: &str = "minichess-seal-1";
+
+/// The moves of a rated or ladder game and the time each took, sealed with
+/// a hash chain that also covers the record's date, result and settings.
+/// Editing the record by hand breaks the chain. The scheme is public, so
+/// it keeps honest players honest rather than stopping a determined forger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Seal {
+    /// Moves in coordinate notation, e.g. "e2e4"
+    pub moves: Vec<String>,
+    /// Milliseconds spent on each move
+    pub times_ms: Vec<u64>,
+    /// Hex SHA-256 at the end of the chain
+    pub hash: String,
+}
+
+impl Seal {
+    /// Seal `record` (whose own seal is ignored) with its game's moves and times
+    pub fn new(record: &GameRecord, moves: Vec<String>, times_ms: Vec<u64>) -> Self {
+        let hash = chain(record, &moves, &times_ms);
+        Seal {
+            moves,
+            times_ms,
+            hash,
+        }
+    }
+}
+
+/// What checking a record's seal found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Intact,
+    /// The record no longer matches its seal, and why
+    Broken(&'static str),
+    /// Casual games, and games saved before sealing existed, have no seal
+    Unsealed,
+}
+
+pub fn verify(record: &GameRecord) -> Verdict {
+    let Some(seal) = &record.seal else {
+        return Verdict::Unsealed;
+    };
+    if seal.moves.len() != seal.times_ms.len() {
+        return Verdict::Broken("the move and time lists differ in length");
+    }
+    if seal.moves.len().div_ceil(2) != record.moves {
+        return Verdict::Broken("the move count doesn't match the sealed moves");
+    }
+    if chain(record, &seal.moves, &seal.times_ms) != seal.hash {
+        return Verdict::Broken("the record was changed after the game");
+    }
+    Verdict::Intact
+}
+
+/// Records whose seal is broken, by their number in the list (from 1)
+pub fn broken(records: &[GameRecord]) -> Vec<usize> {
+    records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| matches!(verify(record), Verdict::Broken(_)))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+/// Check one record, by its number in `stats export` order (from 1), or
+/// every sealed record when `game` is None
+pub fn print_verification(records: &[GameRecord], game: Option<usize>) -> Result<()> {
+    let chosen: Vec<(usize, &GameRecord)> = match game {
+        Some(number) => {
+            let record = number
+                .checked_sub(1)
+                .and_then(|i| records.get(i))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No game {}; the profile has {} game(s)",
+                        number,
+                        records.len()
+                    )
+                })?;
+            vec![(number, record)]
+        }
+        None => records
+            .iter()
+            .enumerate()
+            .map(|(i, record)| (i + 1, record))
+            .filter(|(_, record)| record.seal.is_some())
+            .collect(),
+    };
+    if chosen.is_empty() {
+        println!("No sealed games yet; rated and ladder games are sealed when they are saved.");
+        return Ok(());
+    }
+
+    let mut broken = 0;
+    for (number, record) in &chosen {
+        let verdict = match verify(record) {
+            Verdict::Intact => "intact".to_string(),
+            Verdict::Broken(reason) => {
+                broken += 1;
+                format!("BROKEN: {}", reason)
+            }
+            Verdict::Unsealed => "not sealed (only rated and ladder games are)".to_string(),
+        };
+        println!(
+            "Game {:>4}  {}  {:<8} {:<10} {}",
+            number, record.date, record.mode, record.result, verdict
+        );
+    }
+    if game.is_none() {
+        println!(
+            "\n{} sealed game(s) checked, {} broken.",
+            chosen.len(),
+            broken
+        );
+    }
+    Ok(())
+}
+
+/// Hash the record's fields, then fold in each move and its time
+fn chain(record: &GameRecord, moves: &[String], times_ms: &[u64]) -> String {
+    let header = [
+        CHAIN_VERSION.to_string(),
+        record.date.clone(),
+        record.mode.clone(),
+        record.difficulty.to_string(),
+        record.player_color.clone(),
+        record.result.clone(),
+        record.moves.to_string(),
+        record.opening.clone(),
+        record.opening_key.clone(),
+        record.takebacks.to_string(),
+        record.started.clone(),
+        record.ended.clone().unwrap_or_default(),
+        record.engine.clone(),
+        record.engine_settings.clone(),
+    ]
+    .join("\n");
+    let mut link: [u8; 32] = Sha256::digest(header.as_bytes()).into();
+    for (chess_move, ms) in moves.iter().zip(times_ms) {
+        let mut hasher = Sha256::new();
+        hasher.update(link);
+        hasher.update(chess_move.as_bytes());
+        hasher.update(ms.to_le_bytes());
+        link = hasher.finalize().into();
+    }
+    hex(&link)
+}
+
+/// Hex SHA-256 of a file's contents
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sealed_record() -> GameRecord {
+        let mut record = GameRecord {
+            date: "2026-01-05".to_string(),
+            mode: "rated".to_string(),
+            difficulty: 20,
+            player_color: "white".to_string(),
+            result: "loss".to_string(),
+            moves: 2,
+            accuracy: None,
+            opening: "King's Pawn".to_string(),
+            opening_key: String::new(),
+            line: String::new(),
+            takebacks: 0,
+            started: "2026-01-05T10:00:00+01:00".to_string(),
+            ended: Some("2026-01-05T10:05:00+01:00".to_string()),
+            engine: "Stockfish 17.1".to_string(),
+            engine_settings: "UCI_Elo 1500, depth 10".to_string(),
+            average_loss: None,
+            seal: None,
+        };
+        let moves = ["e2e4", "e7e5", "g1f3"].map(String::from).to_vec();
+        record.seal = Some(Seal::new(&record, moves, vec![4000, 1200, 2500]));
+        record
+    }
+
+    #[test]
+    fn untouched_record_is_intact() {
+        assert_eq!(verify(&sealed_record()), Verdict::Intact);
+    }
+
+    #[test]
+    fn edited_fields_break_the_seal() {
+        let mut record = sealed_record();
+        record.result = "win".to_string();
+        assert!(matches!(verify(&record), Verdict::Broken(_)));
+
+        let mut record = sealed_record();
+        record.seal.as_mut().unwrap().times_ms[1] = 900;
+        assert!(matches!(verify(&record), Verdict::Broken(_)));
+
+        let mut record = sealed_record();
+        record.seal.as_mut().unwrap().moves[2] = "b1c3".to_string();
+        assert!(matches!(verify(&record), Verdict::Broken(_)));
+    }
+
+    #[test]
+    fn reordered_moves_break_the_seal() {
+        let mut record = sealed_record();
+        let seal = record.seal.as_mut().unwrap();
+        seal.moves.swap(0, 2);
+        seal.times_ms.swap(0, 2);
+        assert!(matches!(verify(&record), Verdict::Broken(_)));
+    }
+
+    #[test]
+    fn chain_depends_on_every_move_and_time() {
+        let record = sealed_record();
+        let moves = ["e2e4".to_string()];
+        assert_ne!(
+            chain(&record, &moves, &[1000]),
+            chain(&record, &moves, &[1001])
+        );
+        assert_ne!(chain(&record, &moves, &[1000]), chain(&record, &[], &[]));
+        assert_eq!(
+            chain(&record, &moves, &[1000]),
+            chain(&record, &moves, &[1000])
+        );
+    }
+
+    #[test]
+    fn file_hash_matches_the_standard_digest() {
+        let path = std::env::temp_dir().join(format!("minichess-sha-{}", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+        let digest = file_sha256(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            digest.unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn unsealed_records_are_not_checked() {
+        let mut record = sealed_record();
+        record.seal = None;
+        assert_eq!(verify(&record), Verdict::Unsealed);
+    }
+}
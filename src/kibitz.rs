@@ -0,0 +1,103 @@
+use crate::pgn;
+use crate::stockfish::{MATE_SCORE, Score, StockfishEngine};
+use anyhow::Result;
+use chess::{Board, ChessMove, Color};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+/// Depth of the kibitzer's look at each position
+const KIBITZ_DEPTH: u8 = 14;
+/// Centipawns a move must give away before the kibitzer says something
+const MISS_THRESHOLD: i32 = 150;
+
+/// A move to comment on: the position before it, the move, and its move number
+struct PlayedMove {
+    board: Board,
+    chess_move: ChessMove,
+    move_number: usize,
+}
+
+/// A second engine, independent of the one playing, that watches the game in
+/// the background and prints short comments when either side misses something
+pub struct Kibitzer {
+    sender: UnboundedSender<PlayedMove>,
+}
+
+impl Kibitzer {
+    pub async fn spawn(stockfish_path: &str) -> Result<Self> {
+        let mut engine = StockfishEngine::new(stockfish_path).await?;
+        engine.set_difficulty(20).await?;
+
+        let (sender, mut receiver) = unbounded_channel::<PlayedMove>();
+        tokio::spawn(async move {
+            while let Some(played) = receiver.recv().await {
+                match comment(&mut engine, &played).await {
+                    Ok(Some(text)) => println!("\n[kibitz] {}", text),
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!("\n[kibitz] Engine stopped: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Kibitzer { sender })
+    }
+
+    /// Queue a move for analysis; returns immediately
+    pub fn observe(&self, board: Board, chess_move: ChessMove, move_number: usize) {
+        // A closed channel only means the kibitzer gave up earlier
+        let _ = self.sender.send(PlayedMove {
+            board,
+            chess_move,
+            move_number,
+        });
+    }
+}
+
+async fn comment(engine: &mut StockfishEngine, played: &PlayedMove) -> Result<Option<String>> {
+    let result = engine
+        .search(&played.board, &format!("go depth {}", KIBITZ_DEPTH))
+        .await?;
+    let (Some(best_move), Some(best_score)) = (result.best_move, result.score) else {
+        return Ok(None);
+    };
+    if best_move == played.chess_move {
+        return Ok(None);
+    }
+
+    // The reply is scored for the opponent, so flip it back to the mover
+    let after = played.board.make_move_new(played.chess_move);
+    let played_score = -engine.evaluate(&after, KIBITZ_DEPTH).await?.as_centipawns();
+    let loss = best_score.as_centipawns() - played_score;
+    if loss < MISS_THRESHOLD {
+        return Ok(None);
+    }
+
+    let mover = played.board.side_to_move();
+    let best = format!(
+        "{}{}{}",
+        played.move_number,
+        if mover == Color::White { "." } else { "..." },
+        pgn::move_to_san(&played.board, best_move)
+    );
+    let name = if mover == Color::White {
+        "White"
+    } else {
+        "Black"
+    };
+
+    Ok(Some(match best_score {
+        Score::Mate(n) if n > 0 => format!("{} missed a mate in {}: {}!", name, n, best),
+        _ if played_score <= -(MATE_SCORE - 500) => {
+            format!("{} walks into a mate; {} was necessary.", name, best)
+        }
+        _ => format!(
+            "{} missed {}! ({:+.2} instead of {:+.2})",
+            name,
+            best,
+            best_score.as_centipawns() as f64 / 100.0,
+            played_score as f64 / 100.0
+        ),
+    }))
+}
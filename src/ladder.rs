@@ -0,0 +1,168 @@
+use crate::chess_game::{ChessGame, GameOptions, GameOutcome};
+use crate::stats;
+use crate::storage;
+use crate::ui::get_user_input;
+use anyhow::Result;
+use chess::{Board, Color};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const LADDER_FILE: &str = "ladder.json";
+const TOP_LEVEL: u8 = 20;
+
+/// Titles unlocked the first time the player reaches a level
+const MILESTONES: &[(u8, &str)] = &[
+    (5, "Club Player"),
+    (10, "Expert"),
+    (15, "Master"),
+    (20, "Grandmaster"),
+];
+
+/// Persistent ladder progress of the current profile
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LadderProgress {
+    pub level: u8,
+    pub best: u8,
+    /// Wins, draws and losses at each level
+    pub results: BTreeMap<u8, [u32; 3]>,
+}
+
+impl Default for LadderProgress {
+    fn default() -> Self {
+        LadderProgress {
+            level: 1,
+            best: 1,
+            results: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn load_progress() -> Result<LadderProgress> {
+    storage::load(LADDER_FILE)
+}
+
+pub fn reset() -> Result<()> {
+    storage::save(LADDER_FILE, &LadderProgress::default())?;
+    println!("Ladder progress reset to level 1.");
+    Ok(())
+}
+
+/// Campaign mode: start at level 1, a win climbs one level, a loss drops one,
+/// a draw stays. Colors alternate from game to game.
+pub async fn run(stockfish_path: &str, options: GameOptions) -> Result<()> {
+    let mut progress = load_progress()?;
+    println!("\n=== Skill Ladder ===");
+    println!(
+        "Win to climb, lose to drop back. You are on level {} of {}.",
+        progress.level, TOP_LEVEL
+    );
+
+    loop {
+        let level = progress.level;
+        let played: u32 = progress.results.values().flatten().sum();
+        let color = if played.is_multiple_of(2) {
+            Color::White
+        } else {
+            Color::Black
+        };
+        println!("\n--- Level {} ---", level);
+
+        let mut game = ChessGame::new(
+            stockfish_path,
+            level,
+            Board::default(),
+            Some(color),
+            options,
+        )
+        .await?;
+        game.set_competitive();
+        let outcome = game.run().await?;
+        stats::record_game(&mut game, "ladder", level).await?;
+
+        let tally = progress.results.entry(level).or_default();
+        match outcome {
+            GameOutcome::Win(winner) | GameOutcome::Timeout(winner) if winner == color => {
+                tally[0] += 1;
+                climb(&mut progress);
+            }
+            // Leaving a game is a loss, or any lost position could be escaped
+            GameOutcome::Win(_) | GameOutcome::Timeout(_) | GameOutcome::Unfinished => {
+                if outcome == GameOutcome::Unfinished {
+                    println!("You left the game before it was over; it counts as a loss.");
+                }
+                tally[2] += 1;
+                if level > 1 {
+                    progress.level -= 1;
+                    println!("Back down to level {}.", progress.level);
+                } else {
+                    println!("Still on level 1, try again!");
+                }
+            }
+            GameOutcome::Draw => {
+                tally[1] += 1;
+                println!("A draw keeps you on level {}.", level);
+            }
+        }
+        storage::save(LADDER_FILE, &progress)?;
+        if outcome == GameOutcome::Unfinished {
+            break;
+        }
+
+        println!("Play the next ladder game? (y/n)");
+        if get_user_input()?.trim().eq_ignore_ascii_case("n") {
+            break;
+        }
+    }
+
+    print_summary(&progress);
+    Ok(())
+}
+
+fn climb(progress: &mut LadderProgress) {
+    if progress.level == TOP_LEVEL {
+        println!("You beat the top level! Stay and defend it.");
+        return;
+    }
+
+    progress.level += 1;
+    println!("Promoted to level {}!", progress.level);
+    if progress.level > progress.best {
+        progress.best = progress.level;
+        if let Some((_, title)) = MILESTONES.iter().find(|(lvl, _)| *lvl == progress.level) {
+            println!("*** Unlocked: {} ***", title);
+        }
+    }
+}
+
+/// Title earned by the best level so far
+fn title(best: u8) -> &'static str {
+    MILESTONES
+        .iter()
+        .rev()
+        .find(|(level, _)| *level <= best)
+        .map_or("Novice", |(_, title)| title)
+}
+
+pub fn print_summary(progress: &LadderProgress) {
+    println!("\n=== Ladder for {} ===", storage::profile_name());
+    println!("Current level: {} of {}", progress.level, TOP_LEVEL);
+    println!(
+        "Best level:    {} ({})",
+        progress.best,
+        title(progress.best)
+    );
+
+    if progress.results.is_empty() {
+        println!("No ladder games yet.");
+    } else {
+        println!("\n Level   Won  Drawn  Lost");
+        for (level, [won, drawn, lost]) in &progress.results {
+            println!("{:>6} {:>5} {:>6} {:>5}", level, won, drawn, lost);
+        }
+    }
+
+    if let Some((level, title)) = MILESTONES.iter().find(|(level, _)| *level > progress.best) {
+        println!("\nNext title: {} at level {}", title, level);
+    }
+    println!();
+}
@@ -0,0 +1,488 @@
+use crate::stockfish::StockfishEngine;
+use crate::ui;
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus, ChessMove, Color};
+use chrono::Local;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::IsTerminal;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+
+const API: &str = "https://lichess.org/api";
+/// Environment variable holding the bot account's API token
+pub const TOKEN_VAR: &str = "LICHESS_BOT_TOKEN";
+/// Lines of the event log kept under the dashboard
+const LOG_LINES: usize = 8;
+
+/// Which challenges the bot takes and how it plays
+pub struct BotOptions {
+    /// Skill Level of the engine, 1 to 20
+    pub skill: u8,
+    /// Games played at once; more challenges are declined with "later"
+    pub max_games: usize,
+    /// Accept rated challenges as well as casual ones
+    pub rated: bool,
+}
+
+/// A game as shown on the dashboard
+struct GameView {
+    opponent: String,
+    color: Color,
+    speed: String,
+    moves: usize,
+    /// White's and Black's clocks
+    clocks: Option<(Duration, Duration)>,
+    last_move: String,
+    status: String,
+}
+
+/// Games in progress and recent events, redrawn whenever either changes
+struct Dashboard {
+    bot: String,
+    games: Mutex<BTreeMap<String, GameView>>,
+    log: Mutex<VecDeque<String>>,
+}
+
+impl Dashboard {
+    fn active(&self) -> usize {
+        self.games.lock().map(|games| games.len()).unwrap_or(0)
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut GameView)) {
+        if let Ok(mut games) = self.games.lock()
+            && let Some(game) = games.get_mut(id)
+        {
+            change(game);
+        }
+        self.draw();
+    }
+
+    /// Add a line to the event log. Without a terminal to redraw, the log
+    /// lines are all that is printed.
+    fn note(&self, text: String) {
+        let line = format!("{}  {}", Local::now().format("%H:%M:%S"), text);
+        if !std::io::stdout().is_terminal() {
+            println!("{}", line);
+        }
+        if let Ok(mut log) = self.log.lock() {
+            if log.len() == LOG_LINES {
+                log.pop_front();
+            }
+            log.push_back(line);
+        }
+        self.draw();
+    }
+
+    fn draw(&self) {
+        if !std::io::stdout().is_terminal() {
+            return;
+        }
+        let (Ok(games), Ok(log)) = (self.games.lock(), self.log.lock()) else {
+            return;
+        };
+        let mut screen = String::from("\x1b[2J\x1b[H");
+        screen.push_str(&format!(
+            "=== Lichess BOT {}: {} game(s) in progress ===\n\n",
+            self.bot,
+            games.len()
+        ));
+        if games.is_empty() {
+            screen.push_str("Waiting for challenges...\n");
+        } else {
+            screen.push_str(&format!(
+                "{:<9} {:<24} {:<6} {:<14} {:>5} {:>15} {:<8} {}\n",
+                "Game", "Opponent", "Color", "Speed", "Moves", "Clock (W / B)", "Last", "Status"
+            ));
+            for (id, game) in games.iter() {
+                let clocks = game
+                    .clocks
+                    .map(|(white, black)| {
+                        format!("{} / {}", format_clock(white), format_clock(black))
+                    })
+                    .unwrap_or_default();
+                screen.push_str(&format!(
+                    "{:<9} {:<24} {:<6} {:<14} {:>5} {:>15} {:<8} {}\n",
+                    id,
+                    game.opponent,
+                    if game.color == Color::White {
+                        "White"
+                    } else {
+                        "Black"
+                    },
+                    game.speed,
+                    game.moves.div_ceil(2),
+                    clocks,
+                    game.last_move,
+                    game.status
+                ));
+            }
+        }
+        screen.push_str("\nRecent events:\n");
+        for line in log.iter() {
+            screen.push_str(&format!("  {}\n", line));
+        }
+        screen.push_str("\n'q' and Enter: resign the games in progress and quit\n");
+        print!("{}", screen);
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    }
+}
+
+/// Run a Lichess BOT account: accept challenges that fit `options`, play
+/// each game with its own engine and show a dashboard of the games in
+/// progress. Talks to the Lichess API through `curl`.
+pub async fn run(stockfish_path: &str, token: &str, options: BotOptions) -> Result<()> {
+    let account = request(token, "GET", "/account", &[]).await?;
+    let name = account["username"].as_str().unwrap_or("?").to_string();
+    if account["title"].as_str() != Some("BOT") {
+        return Err(anyhow!(
+            "{} is not a BOT account. Only an account that has never played a game can be \
+             upgraded, see https://lichess.org/api#tag/Bot/operation/botAccountUpgrade",
+            name
+        ));
+    }
+    // Fail now rather than in the first game if the engine doesn't start
+    StockfishEngine::new(stockfish_path).await?;
+
+    let dashboard = Arc::new(Dashboard {
+        bot: name.clone(),
+        games: Mutex::new(BTreeMap::new()),
+        log: Mutex::new(VecDeque::new()),
+    });
+    dashboard.note(format!(
+        "Logged in as {}, accepting {} standard challenges, up to {} at a time",
+        name,
+        if options.rated {
+            "rated and casual"
+        } else {
+            "casual"
+        },
+        options.max_games
+    ));
+
+    let (mut events_process, mut events) = stream(token, "/stream/event").await?;
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            line = events.next_line() => {
+                let Some(line) = line? else {
+                    return Err(anyhow!("Lichess closed the event stream"));
+                };
+                // Empty lines keep the connection alive
+                let Ok(event) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                match event["type"].as_str() {
+                    Some("challenge") => {
+                        if let Err(e) =
+                            answer_challenge(token, &event["challenge"], &options, &dashboard).await
+                        {
+                            dashboard.note(format!("Could not answer a challenge: {}", e));
+                        }
+                    }
+                    Some("gameStart") => {
+                        let Some(id) = event["game"]["gameId"].as_str() else {
+                            continue;
+                        };
+                        let (path, token, id, dashboard) = (
+                            stockfish_path.to_string(),
+                            token.to_string(),
+                            id.to_string(),
+                            Arc::clone(&dashboard),
+                        );
+                        let skill = options.skill;
+                        tokio::spawn(async move {
+                            if let Err(e) = play_game(&path, &token, &id, skill, &dashboard).await {
+                                dashboard.note(format!("Game {} stopped: {}", id, e));
+                            }
+                            if let Ok(mut games) = dashboard.games.lock() {
+                                games.remove(&id);
+                            }
+                            dashboard.draw();
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            input = ui::next_input(&mut stdin) => {
+                match input? {
+                    Some(text) if !matches!(text.trim(), "q" | "quit") => dashboard.draw(),
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    let _ = events_process.kill().await;
+    let ids: Vec<String> = dashboard
+        .games
+        .lock()
+        .map(|games| games.keys().cloned().collect())
+        .unwrap_or_default();
+    for id in ids {
+        match request(token, "POST", &format!("/bot/game/{}/resign", id), &[]).await {
+            Ok(_) => println!("Resigned game {}", id),
+            Err(e) => println!("Could not resign game {}: {}", id, e),
+        }
+    }
+    Ok(())
+}
+
+/// Accept a challenge, or decline it with the reason Lichess shows the
+/// challenger
+async fn answer_challenge(
+    token: &str,
+    challenge: &Value,
+    options: &BotOptions,
+    dashboard: &Dashboard,
+) -> Result<()> {
+    let id = challenge["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("challenge without an id"))?;
+    let challenger = challenge["challenger"]["name"].as_str().unwrap_or("?");
+    if challenger.eq_ignore_ascii_case(&dashboard.bot) {
+        // Our own outgoing challenge
+        return Ok(());
+    }
+    let speed = challenge["speed"].as_str().unwrap_or("?");
+    let rated = challenge["rated"].as_bool().unwrap_or(false);
+    let decline = if challenge["variant"]["key"].as_str() != Some("standard") {
+        Some("standard")
+    } else if speed == "correspondence" {
+        Some("timeControl")
+    } else if rated && !options.rated {
+        Some("casual")
+    } else if dashboard.active() >= options.max_games {
+        Some("later")
+    } else {
+        None
+    };
+    let kind = format!("{}, {}", speed, if rated { "rated" } else { "casual" });
+    match decline {
+        Some(reason) => {
+            request(
+                token,
+                "POST",
+                &format!("/challenge/{}/decline", id),
+                &[("reason", reason)],
+            )
+            .await?;
+            dashboard.note(format!(
+                "Declined {} from {} ({})",
+                kind, challenger, reason
+            ));
+        }
+        None => {
+            request(token, "POST", &format!("/challenge/{}/accept", id), &[]).await?;
+            dashboard.note(format!("Accepted {} from {}", kind, challenger));
+        }
+    }
+    Ok(())
+}
+
+/// Follow one game's stream and answer with the engine's move whenever it
+/// is the bot's turn
+async fn play_game(
+    stockfish_path: &str,
+    token: &str,
+    id: &str,
+    skill: u8,
+    dashboard: &Dashboard,
+) -> Result<()> {
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    engine.set_difficulty(skill).await?;
+    engine.new_game().await?;
+
+    let (mut process, mut lines) = stream(token, &format!("/bot/game/stream/{}", id)).await?;
+    let mut color = Color::White;
+    let mut start = Board::default();
+    while let Some(line) = lines.next_line().await? {
+        let Ok(event) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let state = match event["type"].as_str() {
+            Some("gameFull") => {
+                let white_id = event["white"]["id"].as_str().unwrap_or_default();
+                color = if white_id.eq_ignore_ascii_case(&dashboard.bot) {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                start = match event["initialFen"].as_str() {
+                    None | Some("startpos") => Board::default(),
+                    Some(fen) => Board::from_str(fen)
+                        .map_err(|e| anyhow!("Unreadable starting position {}: {}", fen, e))?,
+                };
+                let opponent = &event[if color == Color::White {
+                    "black"
+                } else {
+                    "white"
+                }];
+                let view = GameView {
+                    opponent: match opponent["rating"].as_u64() {
+                        Some(rating) => {
+                            format!("{} ({})", opponent["name"].as_str().unwrap_or("?"), rating)
+                        }
+                        None => opponent["name"].as_str().unwrap_or("?").to_string(),
+                    },
+                    color,
+                    speed: event["speed"].as_str().unwrap_or("?").to_string(),
+                    moves: 0,
+                    clocks: None,
+                    last_move: String::new(),
+                    status: String::new(),
+                };
+                if let Ok(mut games) = dashboard.games.lock() {
+                    games.insert(id.to_string(), view);
+                }
+                event["state"].clone()
+            }
+            Some("gameState") => event,
+            _ => continue,
+        };
+
+        let mut board = start;
+        let mut last_move = String::new();
+        for text in state["moves"]
+            .as_str()
+            .unwrap_or_default()
+            .split_whitespace()
+        {
+            let chess_move = ChessMove::from_str(text)
+                .ok()
+                .filter(|&m| board.legal(m))
+                .ok_or_else(|| anyhow!("Lichess sent an illegal move {}", text))?;
+            board = board.make_move_new(chess_move);
+            last_move = text.to_string();
+        }
+        let moves = state["moves"]
+            .as_str()
+            .unwrap_or_default()
+            .split_whitespace()
+            .count();
+        let clocks = match (state["wtime"].as_u64(), state["btime"].as_u64()) {
+            (Some(white), Some(black)) => {
+                Some((Duration::from_millis(white), Duration::from_millis(black)))
+            }
+            _ => None,
+        };
+        let status = state["status"].as_str().unwrap_or("started");
+        let finished = !matches!(status, "created" | "started");
+        let our_turn =
+            !finished && board.side_to_move() == color && board.status() == BoardStatus::Ongoing;
+        dashboard.update(id, |game| {
+            game.moves = moves;
+            game.clocks = clocks;
+            game.last_move = last_move;
+            game.status = if finished {
+                status.to_string()
+            } else if our_turn {
+                "thinking".to_string()
+            } else {
+                "their move".to_string()
+            };
+        });
+
+        if finished {
+            let result = match state["winner"].as_str() {
+                Some(winner) if (winner == "white") == (color == Color::White) => "won",
+                Some(_) => "lost",
+                None => "drew",
+            };
+            dashboard.note(format!("Game {} over: {} ({})", id, result, status));
+            break;
+        }
+        if our_turn {
+            if let Some((white, black)) = clocks {
+                engine.set_clock(white, black);
+            }
+            let chess_move = engine.get_best_move(&board).await?;
+            request(
+                token,
+                "POST",
+                &format!("/bot/game/{}/move/{}", id, chess_move),
+                &[],
+            )
+            .await?;
+        }
+    }
+    let _ = process.kill().await;
+    Ok(())
+}
+
+/// `curl` for an API path. The token goes in through a config file on
+/// standard input rather than the command line, where other users of the
+/// machine could read it.
+fn curl(path: &str, extra: &[&str]) -> Command {
+    let mut command = Command::new("curl");
+    command
+        .args(["-fsS", "-K", "-"])
+        .args(extra)
+        .arg(format!("{}{}", API, path))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
+
+async fn send_token(child: &mut Child, token: &str) -> Result<()> {
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to pass the token to curl"))?;
+    stdin
+        .write_all(format!("header = \"Authorization: Bearer {}\"\n", token).as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// A single API call, with `form` sent as URL-encoded fields
+async fn request(token: &str, method: &str, path: &str, form: &[(&str, &str)]) -> Result<Value> {
+    let mut args = vec!["-X".to_string(), method.to_string()];
+    for (name, value) in form {
+        args.push("--data-urlencode".to_string());
+        args.push(format!("{}={}", name, value));
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut child = curl(path, &args)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run curl: {}", e))?;
+    send_token(&mut child, token).await?;
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Lichess request {} failed: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Value::Null);
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Unexpected answer from Lichess for {}: {}", path, e))
+}
+
+/// A streaming API path, read as newline-delimited JSON. The process is
+/// returned so the stream can be closed.
+async fn stream(token: &str, path: &str) -> Result<(Child, Lines<BufReader<ChildStdout>>)> {
+    let mut child = curl(path, &["-N"])
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run curl: {}", e))?;
+    send_token(&mut child, token).await?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("Failed to read from curl"))?;
+    Ok((child, BufReader::new(stdout).lines()))
+}
+
+fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -0,0 +1,115 @@
+use crate::integrity;
+use crate::storage;
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Ratings the Maia networks were trained to imitate
+const MAIA_LEVELS: [u32; 9] = [1100, 1200, 1300, 1400, 1500, 1600, 1700, 1800, 1900];
+const MAIA_RELEASE_URL: &str = "https://github.com/CSSLab/maia-chess/releases/download/v1.0";
+/// First bytes of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Parse an opponent preset such as "maia-1500" into its rating
+pub fn parse_preset(name: &str) -> Result<u32> {
+    let rating = name
+        .to_lowercase()
+        .strip_prefix("maia-")
+        .and_then(|r| r.parse().ok())
+        .filter(|r| MAIA_LEVELS.contains(r));
+    rating.ok_or_else(|| {
+        anyhow!(
+            "Unknown opponent '{}', expected one of: {}",
+            name,
+            MAIA_LEVELS
+                .iter()
+                .map(|r| format!("maia-{}", r))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Path of the weights for a Maia level, downloading them into the data
+/// directory on first use. The SHA-256 of a download is pinned beside it
+/// and checked every time the weights are loaded.
+pub fn weights_file(rating: u32) -> Result<PathBuf> {
+    let file_name = format!("maia-{}.pb.gz", rating);
+    let dir = storage::data_dir()?.join("weights");
+    let path = dir.join(&file_name);
+    let pin = dir.join(format!("{}.sha256", file_name));
+    if path.exists() {
+        verify(&path, &pin)?;
+        return Ok(path);
+    }
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    let url = format!("{}/{}", MAIA_RELEASE_URL, file_name);
+    println!("Downloading {}...", url);
+
+    let partial = dir.join(format!("{}.part", file_name));
+    let status = Command::new("curl")
+        .arg("-fL")
+        .arg("-o")
+        .arg(&partial)
+        .arg(&url)
+        .status()
+        .map_err(|e| {
+            anyhow!(
+                "Failed to run curl ({}). Download {} manually into {}",
+                e,
+                url,
+                dir.display()
+            )
+        })?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&partial);
+        return Err(anyhow!(
+            "Download failed. Fetch {} manually into {}",
+            url,
+            dir.display()
+        ));
+    }
+
+    // A failed download can leave an HTML error page instead of the network
+    let mut magic = [0u8; 2];
+    let is_gzip = std::fs::File::open(&partial)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic))
+        .is_ok()
+        && magic == GZIP_MAGIC;
+    if !is_gzip {
+        let _ = std::fs::remove_file(&partial);
+        return Err(anyhow!(
+            "{} is not a gzipped network; the download was deleted",
+            url
+        ));
+    }
+
+    let digest = integrity::file_sha256(&partial)?;
+    std::fs::rename(&partial, &path)?;
+    std::fs::write(&pin, format!("{}\n", digest))
+        .map_err(|e| anyhow!("Failed to write {}: {}", pin.display(), e))?;
+    println!("SHA-256 {} pinned in {}", digest, pin.display());
+    Ok(path)
+}
+
+/// Check downloaded weights against their pinned SHA-256. Weights without
+/// a pin, fetched by hand or by an older version, are pinned as they are.
+fn verify(path: &Path, pin: &Path) -> Result<()> {
+    let actual = integrity::file_sha256(path)?;
+    match std::fs::read_to_string(pin) {
+        Ok(expected) if expected.trim().eq_ignore_ascii_case(&actual) => Ok(()),
+        Ok(expected) => Err(anyhow!(
+            "{} has changed since it was downloaded (SHA-256 {}, pinned {}). Delete it to download it again",
+            path.display(),
+            actual,
+            expected.trim()
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            std::fs::write(pin, format!("{}\n", actual))
+                .map_err(|e| anyhow!("Failed to write {}: {}", pin.display(), e))
+        }
+        Err(e) => Err(anyhow!("Failed to read {}: {}", pin.display(), e)),
+    }
+}
@@ -1,13 +1,100 @@
+mod analysis;
+mod analysis_board;
+mod annotate;
+mod bench;
+mod book;
 mod chess_game;
+mod coach;
+mod commands;
+mod config;
+mod consult;
+mod control;
+mod crash;
+mod crosstable;
+mod diff;
+mod doctor;
+mod drills;
+mod duck;
+mod eco;
+mod editor;
+mod endgame;
+mod engine_match;
+mod eval;
+mod evalfile;
+mod event;
+mod exchange;
+mod fen;
+mod find;
+mod guess;
+mod house_rules;
+mod html;
+mod ics;
+mod install;
+mod integrity;
+mod kibitz;
+mod ladder;
+mod lichess;
+mod maia;
+mod motifs;
+mod mouse;
+mod movelist;
+mod network;
+mod overlay;
+mod pgn;
+mod piece_set;
+mod plan;
+mod polyglot;
+mod position_cache;
+mod quiz;
+mod racing_kings;
+mod rating;
+mod recurring;
+mod replay;
+mod report;
+mod review;
+mod scoresheet;
+mod scramble;
+mod scripting;
+mod sessions;
+mod simul;
+mod sound;
+mod stats;
+mod status;
 mod stockfish;
+mod storage;
+mod terminal;
+mod transcript;
 mod ui;
+mod variant;
 
-use anyhow::Result;
-use chess_game::ChessGame;
-use clap::{Arg, Command};
+use anyhow::{Result, anyhow};
+use chess::Color;
+use chess_game::{ChessGame, GameOutcome};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, Command};
+use std::io::IsTerminal;
+use std::time::Duration;
+
+/// Clocks of an Armageddon tiebreak: White gets more time, Black draw odds
+const ARMAGEDDON_WHITE: Duration = Duration::from_secs(5 * 60);
+const ARMAGEDDON_BLACK: Duration = Duration::from_secs(4 * 60);
+/// Most boards in a simul, each running its own engine
+const MAX_SIMUL_BOARDS: usize = 20;
+/// Difficulty levels at which the computer varies its openings by default
+const VARIED_LEVELS: u8 = 10;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    terminal::install_panic_hook();
+    terminal::setup_console();
+    let result = run().await;
+    if let Err(e) = &result {
+        crash::offer_report(e);
+    }
+    result
+}
+
+async fn run() -> Result<()> {
     let matches = Command::new("Chess CLI")
         .version("1.0")
         .author("Your Name")
@@ -16,8 +103,216 @@ async fn main() -> Result<()> {
             Arg::new("stockfish-path")
                 .long("stockfish-path")
                 .value_name("PATH")
-                .help("Path to Stockfish executable")
-                .default_value("stockfish"), // Adjust this path as needed to the stockfish binary
+                .help("Stockfish executable, or ssh:user@host:/path/to/stockfish, or tcp:host:port")
+                .default_value("stockfish") // Adjust this path as needed to the stockfish binary
+                .global(true),
+        )
+        .arg(
+            Arg::new("opponent")
+                .long("opponent")
+                .value_name("PRESET")
+                .help("Human-like opponent preset: maia-1100 to maia-1900 (runs lc0)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("weights")
+                .long("weights")
+                .value_name("FILE")
+                .help("Network weights file for lc0 and other neural-network engines")
+                .global(true),
+        )
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .value_name("NAME")
+                .help("lc0 backend (cuda, opencl, blas, ...)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("nodes")
+                .long("nodes")
+                .value_name("N")
+                .help("Limit engine moves by node count instead of depth")
+                .global(true),
+        )
+        .arg(
+            Arg::new("engine-movetime")
+                .long("engine-movetime")
+                .value_name("MS")
+                .help("Handicap by thinking time: full skill, but only MS milliseconds per move")
+                .conflicts_with("nodes")
+                .global(true),
+        )
+        .arg(
+            Arg::new("analysis-hash")
+                .long("analysis-hash")
+                .value_name("MB")
+                .help("Raise the engine's hash table to MB megabytes for post-game analysis")
+                .global(true),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .help("Minimal output: no banners, board redraws or engine progress")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .global(true),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Also show the UCI conversation with engines (on stderr)")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-takebacks")
+                .long("no-takebacks")
+                .help("Allow no takebacks in games against the computer")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("mouse")
+                .long("mouse")
+                .help("Select pieces and destinations by clicking the board")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("piece-width")
+                .long("piece-width")
+                .value_name("COLUMNS")
+                .help("Columns the terminal draws a chess symbol in: 1, 2 or auto (default: auto)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("coords")
+                .long("coords")
+                .value_name("WHERE")
+                .help("Where to label ranks and files: all, edge, inside or off (default: all)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("sound")
+                .long("sound")
+                .help("Play sounds for moves, captures, checks and the end of the game (needs the sound feature)")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("eval-bar")
+                .long("eval-bar")
+                .help("Show an evaluation bar beside the board")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-undo")
+                .long("no-undo")
+                .help("Strict mode: no undo or redo in any game (takebacks still follow --no-takebacks)")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("house-rules")
+                .long("house-rules")
+                .value_name("RULES")
+                .help("Casual-game rule tweaks, comma-separated: no-castling, queen-promotion, capture-the-king (or none)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("move-diff")
+                .long("move-diff")
+                .help("After each move, describe what changed: material, checks, new threats and defenders")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("auto-recapture")
+                .long("auto-recapture")
+                .help("Offer to play your only recapture for you when the engine agrees it is best")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("confirm-moves")
+                .long("confirm-moves")
+                .help("Show each of your moves on the board and ask before playing it")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("status-file")
+                .long("status-file")
+                .value_name("PATH")
+                .help("Keep a one-line game summary in this file (for tmux/polybar status bars)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("player")
+                .long("player")
+                .value_name("NAME")
+                .help("Player profile used for stats, reviews and saved data")
+                .default_value(storage::DEFAULT_PROFILE)
+                .global(true),
+        )
+        .arg(
+            Arg::new("spectate")
+                .long("spectate")
+                .value_name("HOST:PORT")
+                .help("Watch a hosted network game in read-only mode"),
+        )
+        .arg(
+            Arg::new("fen")
+                .long("fen")
+                .value_name("FEN")
+                .help("Start the game from this position instead of the initial one"),
+        )
+        .arg(
+            Arg::new("load-pgn")
+                .long("load-pgn")
+                .value_name("FILE")
+                .help("Resume the first game of a PGN file, such as one written by 'save'")
+                .conflicts_with("fen"),
+        )
+        .arg(
+            Arg::new("consult")
+                .long("consult")
+                .value_name("ENGINE")
+                .help("Second engine the computer consults; moves are chosen by agreement or vote"),
+        )
+        .arg(
+            Arg::new("control-socket")
+                .long("control-socket")
+                .value_name("PATH")
+                .help("Answer queries and take commands for the game on this Unix socket")
+                .conflicts_with("mouse"),
+        )
+        .arg(
+            Arg::new("kibitz")
+                .long("kibitz")
+                .help("Run a second engine that comments on both sides' moves")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("coach")
+                .long("coach")
+                .value_name("URL")
+                .help("HTTP endpoint that explains your mistakes (needs the coach feature)"),
+        )
+        .arg(
+            Arg::new("book")
+                .long("book")
+                .value_name("FILE")
+                .help("Opening book (built with 'book build') the computer plays from"),
+        )
+        .arg(
+            Arg::new("script").long("script").value_name("FILE").help(
+                "Rhai script with game hooks and custom commands (needs the scripting feature)",
+            ),
         )
         .arg(
             Arg::new("difficulty")
@@ -26,23 +321,1931 @@ async fn main() -> Result<()> {
                 .help("Stockfish difficulty level (1-20)")
                 .default_value("5"),
         )
+        .arg(
+            Arg::new("opening-variety")
+                .long("opening-variety")
+                .value_name("N:K")
+                .help("Choose among the computer's top N moves for the first K moves (0 for off; default 3:6 at levels up to 10)"),
+        )
+        .arg(
+            Arg::new("resign-threshold")
+                .long("resign-threshold")
+                .value_name("CP:N")
+                .help("Let the computer resign after N moves in a row at least CP centipawns behind (0 for off)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("min-move-delay")
+                .long("min-move-delay")
+                .value_name("SECS")
+                .help("Make the computer wait at least this many seconds before replying")
+                .value_parser(parse_min_move_delay)
+                .global(true),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .value_name("N")
+                .help("Play a match of N games against the computer, alternating colors")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("armageddon")
+                .long("armageddon")
+                .help("Break a tied match with an Armageddon game (White has more time, Black wins on a draw)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("time-odds")
+                .long("time-odds")
+                .value_name("YOU:COMPUTER")
+                .help("Play with clocks, in minutes for you and the computer, e.g. 10:3"),
+        )
+        .subcommand(
+            Command::new("guess")
+                .about("Guess the winner's moves from a master game")
+                .arg(
+                    Arg::new("pgn")
+                        .value_name("FILE")
+                        .help("PGN file containing the game")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("game")
+                        .long("game")
+                        .value_name("N")
+                        .help("Which game of the PGN file to use")
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("side")
+                        .long("side")
+                        .value_name("COLOR")
+                        .help("Side whose moves to guess (white or black), defaults to the winner"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("DEPTH")
+                        .help("Engine depth used to score guesses")
+                        .default_value("12"),
+                ),
+        )
+        .subcommand(
+            Command::new("annotate")
+                .about("Annotate PGN games with engine evaluations and move marks")
+                .arg(
+                    Arg::new("pgn")
+                        .value_name("FILE")
+                        .help("PGN file to annotate")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("PATH")
+                        .help("Where to write the annotated PGN (standard output by default)"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("DEPTH")
+                        .help("Search depth for every position")
+                        .default_value("16"),
+                )
+                .arg(
+                    Arg::new("engines")
+                        .long("engines")
+                        .value_name("N")
+                        .help("Engine processes to run in parallel (one per core by default)"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Measure the engine's speed on a fixed set of positions")
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("DEPTH")
+                        .help("Search depth for every position")
+                        .default_value("16"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .value_name("N")
+                        .help("Engine threads (the engine's default when not given)"),
+                )
+                .arg(
+                    Arg::new("hash")
+                        .long("hash")
+                        .value_name("MB")
+                        .help("Engine hash table size (the engine's default when not given)"),
+                ),
+        )
+        .subcommand(
+            Command::new("board")
+                .about("Analysis board: free moves for both sides, variations and engine analysis")
+                .arg(
+                    Arg::new("fen")
+                        .long("fen")
+                        .value_name("FEN")
+                        .help("Start from this position")
+                        .conflicts_with("pgn"),
+                )
+                .arg(
+                    Arg::new("pgn")
+                        .long("pgn")
+                        .value_name("FILE")
+                        .help("Start with the moves of a game from a PGN file"),
+                )
+                .arg(
+                    Arg::new("game")
+                        .long("game")
+                        .value_name("N")
+                        .help("Which game of the PGN file to load")
+                        .default_value("1"),
+                ),
+        )
+        .subcommand(
+            Command::new("variant")
+                .about("Play a chess variant")
+                .arg(
+                    Arg::new("name")
+                        .value_name("VARIANT")
+                        .help("Which variant: duck or racingkings")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("color")
+                        .long("color")
+                        .value_name("COLOR")
+                        .help("Your color: white or black")
+                        .default_value("white"),
+                )
+                .arg(
+                    Arg::new("difficulty")
+                        .long("difficulty")
+                        .value_name("LEVEL")
+                        .help("Engine difficulty level (1-20) for variants the engine plays")
+                        .default_value("5"),
+                )
+                .arg(
+                    Arg::new("two-player")
+                        .long("two-player")
+                        .help("Both sides move at the keyboard")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("evalfile")
+                .about("Evaluate every position of a FEN or EPD file")
+                .arg(
+                    Arg::new("positions")
+                        .value_name("FILE")
+                        .help("File with one FEN or EPD position per line")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("PATH")
+                        .help("Where to write the results (standard output by default)"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("DEPTH")
+                        .help("Search depth for every position")
+                        .default_value("16"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format: csv or json")
+                        .default_value("csv"),
+                )
+                .arg(
+                    Arg::new("engines")
+                        .long("engines")
+                        .value_name("N")
+                        .help("Engine processes to run in parallel (one per core by default)"),
+                ),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Step through a game from a PGN file, or let it play itself")
+                .arg(
+                    Arg::new("pgn")
+                        .value_name("FILE")
+                        .help("PGN file containing the game")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("game")
+                        .long("game")
+                        .value_name("N")
+                        .help("Which game of the PGN file to replay")
+                        .default_value("1"),
+                ),
+        )
+        .subcommand(
+            Command::new("endgame")
+                .about("Practice a random endgame position against full-strength Stockfish")
+                .arg(
+                    Arg::new("type")
+                        .long("type")
+                        .value_name("TYPE")
+                        .help("Endgame class: KPvK, KRvK or KQvKR")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("defend")
+                        .long("defend")
+                        .help("Play the defending side instead of the stronger side")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("scramble")
+                .about("Play a balanced middlegame with seconds on the clock to train fast play")
+                .arg(
+                    Arg::new("seconds")
+                        .long("seconds")
+                        .value_name("SECONDS")
+                        .help("Time on each clock")
+                        .default_value("45"),
+                )
+                .arg(
+                    Arg::new("difficulty")
+                        .long("difficulty")
+                        .value_name("LEVEL")
+                        .help("Stockfish difficulty level (1-20)")
+                        .default_value("8"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("SOURCE")
+                        .help("Where the position comes from: games (your own), engine, or auto")
+                        .default_value("auto"),
+                ),
+        )
+        .subcommand(
+            Command::new("drill")
+                .about("Checkmate pattern drills (mate in 1, 2 and 3)")
+                .arg(
+                    Arg::new("pattern")
+                        .long("pattern")
+                        .value_name("PATTERN")
+                        .help("Only drill one pattern (back-rank, smothered, arabian, ...)"),
+                )
+                .arg(
+                    Arg::new("stats")
+                        .long("stats")
+                        .help("Show per-pattern success rates and exit")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("quiz")
+                .about("Board-vision quizzes for beginners")
+                .arg(
+                    Arg::new("stats")
+                        .long("stats")
+                        .help("Show quiz accuracy and times and exit")
+                        .action(ArgAction::SetTrue),
+                )
+                .subcommand(
+                    Command::new("squares")
+                        .about("Name highlighted squares, or say whether a square is light or dark")
+                        .arg(
+                            Arg::new("mode")
+                                .long("mode")
+                                .value_name("MODE")
+                                .help("name (highlighted square) or color (light or dark)")
+                                .default_value("name"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .long("count")
+                                .value_name("N")
+                                .help("Number of questions")
+                                .default_value("20"),
+                        )
+                        .arg(
+                            Arg::new("color")
+                                .long("color")
+                                .value_name("COLOR")
+                                .help("See the board from white's or black's side")
+                                .default_value("white"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("knight")
+                        .about("Find the shortest knight path between two squares")
+                        .arg(Arg::new("from").value_name("FROM").help("Start square, e.g. a1"))
+                        .arg(
+                            Arg::new("to")
+                                .value_name("TO")
+                                .help("Target square, e.g. h8")
+                                .requires("from"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .long("count")
+                                .value_name("N")
+                                .help("Number of random paths when no squares are given")
+                                .default_value("10"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("moves")
+                        .about("Name every square a piece on an empty board can move to")
+                        .arg(
+                            Arg::new("piece")
+                                .long("piece")
+                                .value_name("PIECE")
+                                .help("knight, bishop, rook, queen, king or pawn (knight, bishop and king by default)"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .long("count")
+                                .value_name("N")
+                                .help("Number of questions")
+                                .default_value("10"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("review").about("Review missed drills on a spaced-repetition schedule"),
+        )
+        .subcommand(
+            Command::new("engine-match")
+                .about("Play two engines against each other")
+                .arg(
+                    Arg::new("engine-a")
+                        .value_name("ENGINE_A")
+                        .help("First engine (path, ssh: or tcp: address)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("engine-b")
+                        .value_name("ENGINE_B")
+                        .help("Second engine")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("games")
+                        .long("games")
+                        .value_name("N")
+                        .help("Number of games; colors alternate")
+                        .default_value("2"),
+                )
+                .arg(
+                    Arg::new("movetime")
+                        .long("movetime")
+                        .value_name("MS")
+                        .help("Thinking time per move")
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("DEPTH")
+                        .help("Search to a fixed depth instead of a fixed time")
+                        .conflicts_with("movetime"),
+                )
+                .arg(
+                    Arg::new("openings")
+                        .long("openings")
+                        .value_name("FILE")
+                        .help("EPD or PGN opening suite; each opening is played with both colors"),
+                )
+                .arg(
+                    Arg::new("pgn")
+                        .long("pgn")
+                        .value_name("PATH")
+                        .help("Save the games to this PGN file"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .value_name("PATH")
+                        .help("Export the crosstable and Elo estimate as JSON"),
+                )
+                .arg(
+                    Arg::new("pentanomial")
+                        .long("pentanomial")
+                        .help("Compute error bars over game pairs sharing an opening")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("ladder")
+                .about("Climb the skill ladder: win to advance a level, lose to drop back")
+                .arg(
+                    Arg::new("summary")
+                        .long("summary")
+                        .help("Show ladder progress and exit")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("reset")
+                        .long("reset")
+                        .help("Start the ladder over from level 1")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("simul")
+                .about("Play several games against the engine at once")
+                .arg(
+                    Arg::new("boards")
+                        .long("boards")
+                        .value_name("N")
+                        .help("Number of boards")
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new("difficulty")
+                        .long("difficulty")
+                        .value_name("LEVEL")
+                        .help("Stockfish difficulty level (1-20) on every board")
+                        .default_value("3"),
+                ),
+        )
+        .subcommand(
+            Command::new("rated")
+                .about("Play a rated game against an Elo-limited engine")
+                .arg(
+                    Arg::new("elo")
+                        .long("elo")
+                        .value_name("ELO")
+                        .help("Strength of the engine (Stockfish accepts 1320 to 3190)")
+                        .default_value("1500"),
+                ),
+        )
+        .subcommand(
+            Command::new("session")
+                .about("Keep several games going between runs")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List saved sessions"))
+                .subcommand(
+                    Command::new("new")
+                        .about("Start a new session and play it")
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("difficulty")
+                                .long("difficulty")
+                                .value_name("LEVEL")
+                                .help("Stockfish difficulty level (1-20)")
+                                .default_value("5"),
+                        )
+                        .arg(
+                            Arg::new("color")
+                                .long("color")
+                                .value_name("COLOR")
+                                .help("Your color: white or black")
+                                .default_value("white"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("switch")
+                        .about("Continue a saved session")
+                        .arg(Arg::new("name").required(true)),
+                )
+                .subcommand(
+                    Command::new("close")
+                        .about("Delete a saved session")
+                        .arg(Arg::new("name").required(true)),
+                ),
+        )
+        .subcommand(Command::new("profiles").about("List player profiles"))
+        .subcommand(
+            Command::new("setup").about("Choose engine, strength, color, notation and board style"),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the engine, terminal, configuration and data directory"),
+        )
+        .subcommand(
+            Command::new("engine")
+                .about("Manage the chess engine")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Download the official Stockfish build for this computer")
+                        .arg(
+                            Arg::new("release")
+                                .long("release")
+                                .value_name("TAG")
+                                .help("Stockfish release to install")
+                                .default_value(install::DEFAULT_RELEASE),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("host")
+                .about("Host a game against another player over the network")
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .help("Port to listen on")
+                        .default_value("7878"),
+                )
+                .arg(
+                    Arg::new("color")
+                        .long("color")
+                        .value_name("COLOR")
+                        .help("Your color (white or black), random by default"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .long("time")
+                        .value_name("MINUTES")
+                        .help("Starting time on each clock")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("increment")
+                        .long("increment")
+                        .value_name("SECONDS")
+                        .help("Time added after each move")
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            Command::new("join").about("Join a network game").arg(
+                Arg::new("address")
+                    .value_name("HOST:PORT")
+                    .help("Address of the hosting player")
+                    .required(true),
+            ),
+        )
+        .subcommand(
+            Command::new("event")
+                .about("Run a Swiss or round-robin club event between human players")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List events"))
+                .subcommand(
+                    Command::new("new")
+                        .about("Start an event and pair its first round")
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("players")
+                                .long("players")
+                                .value_name("NAMES")
+                                .help("Comma-separated player names, strongest first")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("swiss or round-robin")
+                                .default_value("swiss"),
+                        )
+                        .arg(
+                            Arg::new("rounds")
+                                .long("rounds")
+                                .value_name("N")
+                                .help("Number of rounds (default: enough for a clear winner, or a full cycle)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("play")
+                        .about("Play the next board of the round at this terminal, or host it")
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("board")
+                                .long("board")
+                                .value_name("N")
+                                .help("Board to play (default: the first without a result)"),
+                        )
+                        .arg(
+                            Arg::new("host")
+                                .long("host")
+                                .value_name("PORT")
+                                .help("Host the game for the opponent to join over the network"),
+                        )
+                        .arg(
+                            Arg::new("as")
+                                .long("as")
+                                .value_name("PLAYER")
+                                .help("Player at this computer when hosting")
+                                .requires("host"),
+                        )
+                        .arg(
+                            Arg::new("time")
+                                .long("time")
+                                .value_name("MINUTES")
+                                .help("Starting time on each clock of a hosted game")
+                                .default_value("10"),
+                        )
+                        .arg(
+                            Arg::new("increment")
+                                .long("increment")
+                                .value_name("SECONDS")
+                                .help("Time added after each move of a hosted game")
+                                .default_value("0"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("result")
+                        .about("Record the result of a game played elsewhere")
+                        .arg(Arg::new("name").required(true))
+                        .arg(Arg::new("board").value_name("BOARD").required(true))
+                        .arg(
+                            Arg::new("result")
+                                .value_name("RESULT")
+                                .help("1-0, 0-1 or 1/2-1/2")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("pgn")
+                                .long("pgn")
+                                .value_name("FILE")
+                                .help("PGN file holding the game's moves"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("standings")
+                        .about("Show standings with tiebreaks and the crosstable")
+                        .arg(Arg::new("name").required(true)),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Write every finished game of the event as PGN")
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .value_name("FILE")
+                                .help("File to write instead of printing"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("lichess-bot")
+                .about("Accept challenges on a Lichess BOT account and play them with the engine")
+                .arg(
+                    Arg::new("max-games")
+                        .long("max-games")
+                        .value_name("N")
+                        .help("Games to play at once; later challenges are declined")
+                        .default_value("2"),
+                )
+                .arg(
+                    Arg::new("skill")
+                        .long("skill")
+                        .value_name("LEVEL")
+                        .help("Engine skill level from 1 to 20")
+                        .default_value("20"),
+                )
+                .arg(
+                    Arg::new("rated")
+                        .long("rated")
+                        .help("Accept rated challenges too, not only casual ones")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("ics")
+                .about("Play on the Free Internet Chess Server (or another ICS)")
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .value_name("HOST")
+                        .help("ICS server address")
+                        .default_value(ics::DEFAULT_HOST),
+                )
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .help("ICS server port")
+                        .default_value("5000"),
+                )
+                .arg(
+                    Arg::new("handle")
+                        .long("handle")
+                        .value_name("NAME")
+                        .help("Login handle, 'guest' for an anonymous account")
+                        .default_value("guest"),
+                )
+                .arg(
+                    Arg::new("password")
+                        .long("password")
+                        .value_name("PASSWORD")
+                        .help("Password for a registered handle"),
+                ),
+        )
+        .subcommand(
+            Command::new("book")
+                .about("Opening book tools")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("build")
+                        .about("Build a weighted Polyglot book from PGN games")
+                        .arg(
+                            Arg::new("pgn")
+                                .long("pgn")
+                                .value_name("PATH")
+                                .help("PGN file or folder of PGN files")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .value_name("FILE")
+                                .help("Book file to write")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("max-ply")
+                                .long("max-ply")
+                                .value_name("N")
+                                .help("Only use the first N plies of each game")
+                                .default_value("20"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("Summarize your recent training: games, accuracy, openings, motifs, time")
+                .arg(
+                    Arg::new("week")
+                        .long("week")
+                        .help("Cover the last 7 days (the default)")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("month"),
+                )
+                .arg(
+                    Arg::new("month")
+                        .long("month")
+                        .help("Cover the last 30 days")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("markdown")
+                        .long("markdown")
+                        .help("Write Markdown instead of plain text")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write to a file instead of standard output"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that rated and ladder game records haven't been edited")
+                .arg(
+                    Arg::new("game")
+                        .value_name("GAME")
+                        .help("Number of the game in `stats export` order (every sealed game by default)"),
+                ),
+        )
+        .subcommand(
+            Command::new("find")
+                .about("Find the stored games that reach a position or piece pattern")
+                .arg(
+                    Arg::new("fen")
+                        .long("fen")
+                        .value_name("FEN")
+                        .help("Position to look for; just the placement field matches either side to move")
+                        .conflicts_with("pattern")
+                        .required_unless_present("pattern"),
+                )
+                .arg(
+                    Arg::new("pattern")
+                        .long("pattern")
+                        .value_name("PATTERN")
+                        .help("Piece placement, e.g. \"white queen on h-file and black king on g8\""),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show game statistics for the current player")
+                .subcommand(
+                    Command::new("openings")
+                        .about("Results, accuracy and common deviations per ECO opening")
+                        .arg(
+                            Arg::new("color")
+                                .long("color")
+                                .value_name("COLOR")
+                                .help("Only games played as white or black"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("positions")
+                        .about("Positions you keep reaching, your score from them and the engine's move")
+                        .arg(
+                            Arg::new("min-games")
+                                .long("min-games")
+                                .value_name("N")
+                                .help("Games a position must come up in")
+                                .default_value("3"),
+                        )
+                        .arg(
+                            Arg::new("depth")
+                                .long("depth")
+                                .value_name("DEPTH")
+                                .help("Engine search depth for each position")
+                                .default_value("18"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Export per-game records for spreadsheets or dashboards")
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("Output format: csv or json")
+                                .default_value("csv"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .long("output")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Write to a file instead of standard output"),
+                        ),
+                ),
+        )
         .get_matches();
 
-    let stockfish_path = matches.get_one::<String>("stockfish-path").unwrap();
-    let difficulty: u8 = matches
-        .get_one::<String>("difficulty")
+    ui::set_verbosity(if matches.get_flag("quiet") {
+        ui::Verbosity::Quiet
+    } else if matches.get_flag("verbose") {
+        ui::Verbosity::Verbose
+    } else {
+        ui::Verbosity::Normal
+    });
+
+    if let Some(path) = matches.get_one::<String>("status-file") {
+        status::configure(path.into());
+    }
+    let mut game_options = chess_game::GameOptions::default();
+    if matches.get_flag("no-takebacks") {
+        game_options.takebacks = 0;
+    }
+    game_options.confirm_moves = matches.get_flag("confirm-moves");
+    game_options.auto_recapture = matches.get_flag("auto-recapture");
+    game_options.eval_bar = matches.get_flag("eval-bar");
+    game_options.move_diff = matches.get_flag("move-diff");
+    game_options.no_undo = matches.get_flag("no-undo");
+    if matches.get_flag("sound") {
+        sound::enable()?;
+    }
+    mouse::set_enabled(matches.get_flag("mouse"));
+    if let Some(delay) = matches.get_one::<Duration>("min-move-delay") {
+        game_options.min_move_delay = *delay;
+    }
+
+    if let Some(("setup", _)) = matches.subcommand() {
+        config::wizard()?;
+        return Ok(());
+    }
+    if let Some(("engine", sub)) = matches.subcommand()
+        && let Some(("install", install_args)) = sub.subcommand()
+    {
+        return install::run(install_args.get_one::<String>("release").unwrap());
+    }
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    let config = match config::load()? {
+        Some(config) => config,
+        // First run: ask instead of silently using defaults
+        None if interactive && matches.subcommand_name().is_none() => config::wizard()?,
+        None => config::Config::default(),
+    };
+    let console = terminal::console();
+    let board_style = if console.unicode {
+        config.board_style
+    } else {
+        if config.board_style != ui::BoardStyle::Ascii {
+            println!("This console can't show chess symbols, so pieces are shown as letters.");
+        }
+        ui::BoardStyle::Ascii
+    };
+    if !console.ansi {
+        println!(
+            "This console doesn't understand color codes; run minichess in Windows Terminal for a readable board."
+        );
+    }
+    ui::set_display_options(
+        piece_set::PieceSet::from_config(board_style, config.custom_pieces.as_deref())?,
+        config.notation,
+    );
+    ui::set_piece_width(
+        match matches.get_one::<String>("piece-width").map(String::as_str) {
+            Some("1") => 1,
+            Some("2") => 2,
+            Some("auto") => terminal::detect_piece_width(),
+            Some(_) => return Err(anyhow!("--piece-width must be 1, 2 or auto")),
+            None => config
+                .piece_width
+                .map(usize::from)
+                .unwrap_or_else(terminal::detect_piece_width),
+        },
+    );
+    ui::set_coordinates(match matches.get_one::<String>("coords") {
+        Some(text) => text.parse()?,
+        None => config.coordinates,
+    });
+    terminal::set_show_title(config.terminal_title);
+    game_options.undo_limit = config.undo_states;
+    ui::set_macros(&config.macros);
+    if let Some(text) = matches
+        .get_one::<String>("resign-threshold")
+        .or(config.resign_threshold.as_ref())
+    {
+        game_options.resign_threshold = chess_game::ResignThreshold::parse(text)?;
+    }
+    house_rules::set(match matches.get_one::<String>("house-rules") {
+        Some(text) => house_rules::HouseRules::parse(text)?,
+        None => config.house_rules,
+    });
+
+    let is_default = |id: &str| matches.value_source(id) == Some(ValueSource::DefaultValue);
+    let stockfish_path = match &config.stockfish_path {
+        Some(path) if is_default("stockfish-path") => path.clone(),
+        _ => matches.get_one::<String>("stockfish-path").unwrap().clone(),
+    };
+    storage::select_profile(matches.get_one::<String>("player").unwrap())?;
+
+    let engine_settings = stockfish::EngineSettings {
+        weights: matches.get_one::<String>("weights").cloned(),
+        backend: matches.get_one::<String>("backend").cloned(),
+        nodes: matches
+            .get_one::<String>("nodes")
+            .map(|n| n.parse())
+            .transpose()
+            .map_err(|_| anyhow!("--nodes must be a number"))?,
+        movetime: matches
+            .get_one::<String>("engine-movetime")
+            .map(|ms| ms.parse())
+            .transpose()
+            .map_err(|_| anyhow!("--engine-movetime must be a number of milliseconds"))?,
+        analysis_hash: matches
+            .get_one::<String>("analysis-hash")
+            .map(|mb| mb.parse())
+            .transpose()
+            .map_err(|_| anyhow!("--analysis-hash must be a number of megabytes"))?,
+    };
+    // The opponent gets its own engine and settings; hints, analysis and
+    // the kibitzer keep using the configured engine
+    let opponent = match matches.get_one::<String>("opponent") {
+        Some(preset) => {
+            // Maia plays like a human of its rating only when it searches a single node
+            let rating = maia::parse_preset(preset)?;
+            let weights = maia::weights_file(rating)?;
+            let settings = stockfish::EngineSettings {
+                weights: Some(weights.to_string_lossy().into_owned()),
+                backend: engine_settings.backend.clone(),
+                nodes: Some(1),
+                movetime: None,
+                analysis_hash: None,
+            };
+            let path = if is_default("stockfish-path") {
+                "lc0".to_string()
+            } else {
+                stockfish_path.clone()
+            };
+            Some((path, settings))
+        }
+        None => None,
+    };
+    stockfish::configure(engine_settings);
+    let stockfish_path = stockfish_path.as_str();
+
+    if let Some(("profiles", _)) = matches.subcommand() {
+        for name in storage::list_profiles()? {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    if let Some(("doctor", _)) = matches.subcommand() {
+        return doctor::run(
+            stockfish_path,
+            matches.get_one::<String>("weights").map(|s| s.as_str()),
+            matches.get_one::<String>("book").map(|s| s.as_str()),
+        )
+        .await;
+    }
+
+    if let Some(address) = matches.get_one::<String>("spectate") {
+        return network::spectate(address).await;
+    }
+
+    if let Some(("host", sub)) = matches.subcommand() {
+        let port: u16 = sub
+            .get_one::<String>("port")
+            .unwrap()
+            .parse()
+            .unwrap_or(network::DEFAULT_PORT);
+        let color = sub
+            .get_one::<String>("color")
+            .map(|s| parse_color(s))
+            .transpose()?;
+        let time: u32 = sub.get_one::<String>("time").unwrap().parse().unwrap_or(10);
+        let increment: u32 = sub
+            .get_one::<String>("increment")
+            .unwrap()
+            .parse()
+            .unwrap_or(0);
+        let (game, link) = network::host(port, color, time, increment).await?;
+        return network::play_rematches(game, link).await;
+    }
+
+    if let Some(("join", sub)) = matches.subcommand() {
+        let (game, link) = network::join(sub.get_one::<String>("address").unwrap()).await?;
+        return network::play_rematches(game, link).await;
+    }
+
+    if let Some(("event", sub)) = matches.subcommand() {
+        return match sub.subcommand() {
+            Some(("new", args)) => {
+                let players = args
+                    .get_one::<String>("players")
+                    .unwrap()
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                let format: event::Format = args.get_one::<String>("format").unwrap().parse()?;
+                let rounds = args
+                    .get_one::<String>("rounds")
+                    .map(|r| r.parse::<usize>())
+                    .transpose()
+                    .map_err(|_| anyhow!("--rounds must be a number"))?;
+                event::create(
+                    args.get_one::<String>("name").unwrap(),
+                    players,
+                    format,
+                    rounds,
+                )
+            }
+            Some(("play", args)) => {
+                let board = args
+                    .get_one::<String>("board")
+                    .map(|b| b.parse::<usize>())
+                    .transpose()
+                    .map_err(|_| anyhow!("--board must be a number"))?;
+                let host = match args.get_one::<String>("host") {
+                    Some(port) => Some(event::HostOptions {
+                        port: port
+                            .parse()
+                            .map_err(|_| anyhow!("--host must be a port number"))?,
+                        player: args
+                            .get_one::<String>("as")
+                            .cloned()
+                            .ok_or_else(|| anyhow!("--host needs --as PLAYER"))?,
+                        base_minutes: args
+                            .get_one::<String>("time")
+                            .unwrap()
+                            .parse()
+                            .map_err(|_| anyhow!("--time must be a number of minutes"))?,
+                        increment_seconds: args
+                            .get_one::<String>("increment")
+                            .unwrap()
+                            .parse()
+                            .map_err(|_| anyhow!("--increment must be a number of seconds"))?,
+                    }),
+                    None => None,
+                };
+                event::play(args.get_one::<String>("name").unwrap(), board, host).await
+            }
+            Some(("result", args)) => {
+                let board: usize = args
+                    .get_one::<String>("board")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| anyhow!("BOARD must be a number"))?;
+                let result = event::parse_result(args.get_one::<String>("result").unwrap())?;
+                event::record_result(
+                    args.get_one::<String>("name").unwrap(),
+                    board,
+                    result,
+                    args.get_one::<String>("pgn").map(|s| s.as_str()),
+                )
+            }
+            Some(("standings", args)) => event::standings(args.get_one::<String>("name").unwrap()),
+            Some(("export", args)) => event::export(
+                args.get_one::<String>("name").unwrap(),
+                args.get_one::<String>("out").map(|s| s.as_str()),
+            ),
+            _ => event::list(),
+        };
+    }
+
+    if let Some(("lichess-bot", sub)) = matches.subcommand() {
+        let token = std::env::var(lichess::TOKEN_VAR)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .or_else(|| config.lichess_token.clone())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Set {} (or \"lichess_token\" in the config file) to the BOT account's API token",
+                    lichess::TOKEN_VAR
+                )
+            })?;
+        let max_games = sub
+            .get_one::<String>("max-games")
+            .unwrap()
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| anyhow!("--max-games must be a positive number"))?;
+        let skill = sub
+            .get_one::<String>("skill")
+            .unwrap()
+            .parse()
+            .ok()
+            .filter(|level| (1..=20).contains(level))
+            .ok_or_else(|| anyhow!("--skill must be a level from 1 to 20"))?;
+        return lichess::run(
+            stockfish_path,
+            &token,
+            lichess::BotOptions {
+                skill,
+                max_games,
+                rated: sub.get_flag("rated"),
+            },
+        )
+        .await;
+    }
+
+    if let Some(("ics", sub)) = matches.subcommand() {
+        let host = sub.get_one::<String>("host").unwrap();
+        let port: u16 = sub
+            .get_one::<String>("port")
+            .unwrap()
+            .parse()
+            .unwrap_or(ics::DEFAULT_PORT);
+        let handle = sub.get_one::<String>("handle").unwrap();
+        let password = sub.get_one::<String>("password").map(|s| s.as_str());
+        return ics::run(host, port, handle, password).await;
+    }
+
+    if let Some(("guess", sub)) = matches.subcommand() {
+        let pgn_path = sub.get_one::<String>("pgn").unwrap();
+        let game_number: usize = sub.get_one::<String>("game").unwrap().parse().unwrap_or(1);
+        let depth: u8 = sub
+            .get_one::<String>("depth")
+            .unwrap()
+            .parse()
+            .unwrap_or(12);
+        let side = sub
+            .get_one::<String>("side")
+            .map(|s| parse_color(s))
+            .transpose()?;
+        return guess::run(stockfish_path, pgn_path, game_number, side, depth).await;
+    }
+
+    if let Some(("annotate", sub)) = matches.subcommand() {
+        let depth: u8 = sub
+            .get_one::<String>("depth")
+            .unwrap()
+            .parse()
+            .map_err(|_| anyhow!("--depth must be a number"))?;
+        let engines = match sub.get_one::<String>("engines") {
+            Some(n) => n
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| anyhow!("--engines must be a positive number"))?,
+            None => annotate::default_engine_count(),
+        };
+        return annotate::run(
+            stockfish_path,
+            sub.get_one::<String>("pgn").unwrap(),
+            sub.get_one::<String>("out").map(|s| s.as_str()),
+            depth,
+            engines,
+        )
+        .await;
+    }
+
+    if let Some(("bench", sub)) = matches.subcommand() {
+        let depth: u8 = sub
+            .get_one::<String>("depth")
+            .unwrap()
+            .parse()
+            .map_err(|_| anyhow!("--depth must be a number"))?;
+        let threads = sub
+            .get_one::<String>("threads")
+            .map(|n| n.parse().ok().filter(|&n| n > 0))
+            .map(|n| n.ok_or_else(|| anyhow!("--threads must be a positive number")))
+            .transpose()?;
+        let hash = sub
+            .get_one::<String>("hash")
+            .map(|mb| mb.parse().ok().filter(|&mb| mb > 0))
+            .map(|mb| mb.ok_or_else(|| anyhow!("--hash must be a number of megabytes")))
+            .transpose()?;
+        return bench::run(
+            stockfish_path,
+            bench::BenchOptions {
+                depth,
+                threads,
+                hash,
+            },
+        )
+        .await;
+    }
+
+    if let Some(("board", sub)) = matches.subcommand() {
+        let game_number: usize = sub.get_one::<String>("game").unwrap().parse().unwrap_or(1);
+        let (start, moves) = analysis_board::load_start(
+            sub.get_one::<String>("fen").map(|s| s.as_str()),
+            sub.get_one::<String>("pgn").map(|s| s.as_str()),
+            game_number,
+        )?;
+        return analysis_board::run(stockfish_path, start, &moves).await;
+    }
+
+    if let Some(("variant", sub)) = matches.subcommand() {
+        let player = if sub.get_flag("two-player") {
+            None
+        } else {
+            Some(parse_color(sub.get_one::<String>("color").unwrap())?)
+        };
+        return match sub
+            .get_one::<String>("name")
+            .unwrap()
+            .to_lowercase()
+            .as_str()
+        {
+            "duck" => variant::run(duck::DuckChess, player).await,
+            "racingkings" | "racing-kings" => {
+                let racing_kings = if player.is_some() {
+                    let difficulty: u8 = sub
+                        .get_one::<String>("difficulty")
+                        .unwrap()
+                        .parse()
+                        .map_err(|_| anyhow!("--difficulty must be a number from 1 to 20"))?;
+                    racing_kings::RacingKings::with_engine(stockfish_path, difficulty).await?
+                } else {
+                    racing_kings::RacingKings::two_player()
+                };
+                variant::run(racing_kings, player).await
+            }
+            other => Err(anyhow!(
+                "Unknown variant '{}'. Available: duck, racingkings",
+                other
+            )),
+        };
+    }
+
+    if let Some(("evalfile", sub)) = matches.subcommand() {
+        let depth: u8 = sub
+            .get_one::<String>("depth")
+            .unwrap()
+            .parse()
+            .map_err(|_| anyhow!("--depth must be a number"))?;
+        let engines = match sub.get_one::<String>("engines") {
+            Some(n) => n
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| anyhow!("--engines must be a positive number"))?,
+            None => annotate::default_engine_count(),
+        };
+        return evalfile::run(
+            stockfish_path,
+            sub.get_one::<String>("positions").unwrap(),
+            sub.get_one::<String>("out").map(|s| s.as_str()),
+            depth,
+            engines,
+            sub.get_one::<String>("format").unwrap(),
+        )
+        .await;
+    }
+
+    if let Some(("replay", sub)) = matches.subcommand() {
+        let pgn_path = sub.get_one::<String>("pgn").unwrap();
+        let game_number: usize = sub.get_one::<String>("game").unwrap().parse().unwrap_or(1);
+        return replay::run(pgn_path, game_number).await;
+    }
+
+    if let Some(("endgame", sub)) = matches.subcommand() {
+        let endgame_type = sub.get_one::<String>("type").unwrap().parse()?;
+        return endgame::run(
+            stockfish_path,
+            endgame_type,
+            sub.get_flag("defend"),
+            game_options,
+        )
+        .await;
+    }
+
+    if let Some(("scramble", sub)) = matches.subcommand() {
+        let options = scramble::ScrambleOptions {
+            seconds: sub
+                .get_one::<String>("seconds")
+                .unwrap()
+                .parse()
+                .ok()
+                .filter(|s| (10..=300).contains(s))
+                .ok_or_else(|| anyhow!("--seconds must be a number from 10 to 300"))?,
+            difficulty: sub
+                .get_one::<String>("difficulty")
+                .unwrap()
+                .parse()
+                .map_err(|_| anyhow!("--difficulty must be a number from 1 to 20"))?,
+            source: sub.get_one::<String>("from").unwrap().parse()?,
+        };
+        return scramble::run(stockfish_path, options, game_options).await;
+    }
+
+    if let Some(("drill", sub)) = matches.subcommand() {
+        if sub.get_flag("stats") {
+            drills::print_stats(&drills::load_stats()?);
+            return Ok(());
+        }
+        let pattern = sub.get_one::<String>("pattern").map(|s| s.as_str());
+        return drills::run(stockfish_path, pattern).await;
+    }
+
+    if let Some(("quiz", sub)) = matches.subcommand() {
+        return match sub.subcommand() {
+            Some(("squares", args)) => {
+                let count: u32 = args
+                    .get_one::<String>("count")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| anyhow!("--count must be a number"))?;
+                quiz::run_squares(
+                    quiz::SquareMode::parse(args.get_one::<String>("mode").unwrap())?,
+                    count,
+                    parse_color(args.get_one::<String>("color").unwrap())?,
+                )
+            }
+            Some(("knight", args)) => {
+                let count: u32 = args
+                    .get_one::<String>("count")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| anyhow!("--count must be a number"))?;
+                let square = |id: &str| {
+                    args.get_one::<String>(id)
+                        .map(|text| quiz::parse_square(text))
+                        .transpose()
+                };
+                let route = match (square("from")?, square("to")?) {
+                    (Some(from), Some(to)) if from == to => {
+                        return Err(anyhow!("The start and target squares are the same"));
+                    }
+                    (Some(from), Some(to)) => Some((from, to)),
+                    (Some(_), None) => return Err(anyhow!("Give the target square too")),
+                    _ => None,
+                };
+                quiz::run_knight(route, count)
+            }
+            Some(("moves", args)) => {
+                let count: u32 = args
+                    .get_one::<String>("count")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| anyhow!("--count must be a number"))?;
+                let piece = args
+                    .get_one::<String>("piece")
+                    .map(|text| quiz::parse_piece(text))
+                    .transpose()?;
+                quiz::run_moves(piece, count)
+            }
+            _ if sub.get_flag("stats") => {
+                quiz::print_stats(&quiz::load_stats()?);
+                Ok(())
+            }
+            _ => Err(anyhow!(
+                "Choose a quiz: squares, knight or moves, or --stats for results"
+            )),
+        };
+    }
+
+    if let Some(("review", _)) = matches.subcommand() {
+        return review::run(stockfish_path).await;
+    }
+
+    if let Some(("engine-match", sub)) = matches.subcommand() {
+        let number = |id: &str| -> Result<u64> {
+            sub.get_one::<String>(id)
+                .unwrap()
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| anyhow!("--{} must be a positive number", id))
+        };
+        let go_command = match sub.get_one::<String>("depth") {
+            Some(_) => format!("go depth {}", number("depth")?),
+            None => format!("go movetime {}", number("movetime")?),
+        };
+        let options = engine_match::MatchOptions {
+            games: number("games")? as usize,
+            go_command,
+            openings: sub.get_one::<String>("openings").cloned(),
+            pgn_out: sub.get_one::<String>("pgn").cloned(),
+            json_out: sub.get_one::<String>("json").cloned(),
+            pentanomial: sub.get_flag("pentanomial"),
+        };
+        return engine_match::run(
+            sub.get_one::<String>("engine-a").unwrap(),
+            sub.get_one::<String>("engine-b").unwrap(),
+            options,
+        )
+        .await;
+    }
+
+    if let Some(("ladder", sub)) = matches.subcommand() {
+        if sub.get_flag("reset") {
+            return ladder::reset();
+        }
+        if sub.get_flag("summary") {
+            ladder::print_summary(&ladder::load_progress()?);
+            return Ok(());
+        }
+        return ladder::run(stockfish_path, game_options).await;
+    }
+
+    if let Some(("session", sub)) = matches.subcommand() {
+        return match sub.subcommand() {
+            Some(("new", args)) => {
+                let name = args.get_one::<String>("name").unwrap();
+                let difficulty: u8 = args
+                    .get_one::<String>("difficulty")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| anyhow!("--difficulty must be a number from 1 to 20"))?;
+                let color = parse_color(args.get_one::<String>("color").unwrap())?;
+                sessions::create(name, difficulty, color)?;
+                sessions::play(stockfish_path, name, game_options).await
+            }
+            Some(("switch", args)) => {
+                sessions::play(
+                    stockfish_path,
+                    args.get_one::<String>("name").unwrap(),
+                    game_options,
+                )
+                .await
+            }
+            Some(("close", args)) => {
+                let name = args.get_one::<String>("name").unwrap();
+                sessions::close(name)?;
+                println!("Session '{}' closed.", name);
+                Ok(())
+            }
+            _ => sessions::list(),
+        };
+    }
+
+    if let Some(("simul", sub)) = matches.subcommand() {
+        let boards: usize = sub
+            .get_one::<String>("boards")
+            .unwrap()
+            .parse()
+            .ok()
+            .filter(|n| (1..=MAX_SIMUL_BOARDS).contains(n))
+            .ok_or_else(|| anyhow!("--boards must be a number from 1 to {}", MAX_SIMUL_BOARDS))?;
+        let difficulty: u8 = sub
+            .get_one::<String>("difficulty")
+            .unwrap()
+            .parse()
+            .map_err(|_| anyhow!("--difficulty must be a number from 1 to 20"))?;
+        return simul::run(stockfish_path, difficulty, boards, game_options).await;
+    }
+
+    if let Some(("rated", sub)) = matches.subcommand() {
+        let elo: u32 = sub
+            .get_one::<String>("elo")
+            .unwrap()
+            .parse()
+            .map_err(|_| anyhow!("--elo must be a number"))?;
+        return rating::run(stockfish_path, elo, game_options).await;
+    }
+
+    if let Some(("book", sub)) = matches.subcommand() {
+        if let Some(("build", build)) = sub.subcommand() {
+            let pgn_path = build.get_one::<String>("pgn").unwrap();
+            let out = build.get_one::<String>("out").unwrap();
+            let max_ply: usize = build
+                .get_one::<String>("max-ply")
+                .unwrap()
+                .parse()
+                .map_err(|_| anyhow!("--max-ply must be a number"))?;
+            let report = book::build(pgn_path.as_ref(), out.as_ref(), max_ply)?;
+            println!(
+                "Read {} game(s) from {} file(s) ({} skipped), wrote {} entries to {}",
+                report.games, report.files, report.skipped_files, report.entries, out
+            );
+            println!(
+                "{} position(s) are reached by more than one move order",
+                report.transpositions
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(("report", sub)) = matches.subcommand() {
+        let days = if sub.get_flag("month") { 30 } else { 7 };
+        let format = if sub.get_flag("markdown") {
+            scoresheet::Format::Markdown
+        } else {
+            scoresheet::Format::Text
+        };
+        let text = report::render(
+            &stats::load_records()?,
+            &drills::load_stats()?,
+            &review::load_items()?,
+            &motifs::load_counts()?,
+            days,
+            format,
+        );
+        match sub.get_one::<String>("output") {
+            Some(path) => {
+                std::fs::write(path, text)
+                    .map_err(|e| anyhow!("Failed to write {}: {}", path, e))?;
+                println!("Report written to {}", path);
+            }
+            None => print!("{}", text),
+        }
+        return Ok(());
+    }
+
+    if let Some(("verify", sub)) = matches.subcommand() {
+        let game = sub
+            .get_one::<String>("game")
+            .map(|n| n.parse().map_err(|_| anyhow!("GAME must be a number")))
+            .transpose()?;
+        return integrity::print_verification(&stats::load_records()?, game);
+    }
+
+    if let Some(("find", sub)) = matches.subcommand() {
+        let query = match sub.get_one::<String>("fen") {
+            Some(text) => find::Query::fen(text)?,
+            None => find::Query::pattern(sub.get_one::<String>("pattern").unwrap())?,
+        };
+        find::run(&stats::load_records()?, &query);
+        return Ok(());
+    }
+
+    if let Some(("stats", sub)) = matches.subcommand() {
+        let records = stats::load_records()?;
+        match sub.subcommand() {
+            Some(("openings", openings)) => {
+                let color = openings
+                    .get_one::<String>("color")
+                    .map(|c| parse_color(c))
+                    .transpose()?
+                    .map(|c| ui::color_name(c).to_lowercase());
+                stats::print_opening_stats(&records, color.as_deref());
+            }
+            Some(("positions", positions)) => {
+                let options = recurring::RecurringOptions {
+                    min_games: positions
+                        .get_one::<String>("min-games")
+                        .unwrap()
+                        .parse()
+                        .ok()
+                        .filter(|&n| n >= 2)
+                        .ok_or_else(|| anyhow!("--min-games must be a number of 2 or more"))?,
+                    depth: positions
+                        .get_one::<String>("depth")
+                        .unwrap()
+                        .parse()
+                        .map_err(|_| anyhow!("--depth must be a number"))?,
+                };
+                recurring::run(stockfish_path, &records, options).await?;
+            }
+            Some(("export", export)) => {
+                let format = export.get_one::<String>("format").unwrap();
+                match export.get_one::<String>("output") {
+                    Some(path) => {
+                        let mut file = std::fs::File::create(path)
+                            .map_err(|e| anyhow!("Failed to create {}: {}", path, e))?;
+                        stats::export(&records, format, &mut file)?;
+                        println!("Exported {} game(s) to {}", records.len(), path);
+                    }
+                    None => stats::export(&records, format, &mut std::io::stdout())?,
+                }
+            }
+            _ => {
+                stats::print_summary(&records);
+                motifs::print_counts(&motifs::load_counts()?);
+                rating::print_history(&rating::load()?);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = matches.get_one::<String>("control-socket") {
+        control::start(path, stockfish_path)?;
+    }
+
+    // A game to resume keeps its side, difficulty and moves
+    let loaded = match matches.get_one::<String>("load-pgn") {
+        Some(path) => pgn::load_pgn_file(path)?.into_iter().next(),
+        None => None,
+    };
+    let saved_difficulty = loaded
+        .as_ref()
+        .and_then(|game| game.header("Difficulty"))
+        .and_then(|level| level.parse().ok());
+    let saved_color = loaded.as_ref().and_then(|game| {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| game.header(&format!("{:?}", color)) == Some("Player"))
+    });
+
+    let difficulty: u8 = match saved_difficulty.or(config.difficulty) {
+        Some(level) if is_default("difficulty") => level,
+        _ => matches
+            .get_one::<String>("difficulty")
+            .unwrap()
+            .parse()
+            .unwrap_or(5),
+    };
+    game_options.opening_variety = match matches.get_one::<String>("opening-variety") {
+        Some(text) => chess_game::OpeningVariety::parse(text)?,
+        None => (difficulty <= VARIED_LEVELS).then_some(chess_game::OpeningVariety {
+            choices: 3,
+            moves: 6,
+        }),
+    };
+    let color = match saved_color {
+        Some(color) => Some(color),
+        None => config.color.as_deref().map(parse_color).transpose()?,
+    };
+
+    let board = match (matches.get_one::<String>("fen"), &loaded) {
+        (Some(text), _) => fen::parse_fen(text).map_err(|e| anyhow!("Invalid --fen: {}", e))?,
+        (None, Some(game)) => game.start,
+        (None, None) => chess::Board::default(),
+    };
+
+    if ui::verbosity() > ui::Verbosity::Quiet {
+        println!("Starting chess game...");
+        println!("Stockfish path: {}", stockfish_path);
+        println!("Player: {}", storage::profile_name());
+        match matches.get_one::<String>("engine-movetime") {
+            Some(ms) => println!("Difficulty: full strength, {} ms per move", ms),
+            None => println!("Difficulty: {}", difficulty),
+        }
+        println!("Press 'q' to quit, 'h' for help");
+        println!();
+    }
+
+    let games: usize = matches
+        .get_one::<String>("games")
         .unwrap()
         .parse()
-        .unwrap_or(5);
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| anyhow!("--games must be a positive number"))?;
+    let armageddon = matches.get_flag("armageddon");
+    let time_odds = matches
+        .get_one::<String>("time-odds")
+        .map(|odds| parse_time_odds(odds))
+        .transpose()?;
+    let is_match = games > 1 || armageddon;
+    let mut color = color;
+    let mut engine = None;
+    // Points for the player and the computer
+    let mut score = (0.0, 0.0);
+    let mut match_pgn = String::new();
+    let mut table = crosstable::Crosstable::new(vec!["You".to_string(), "Computer".to_string()]);
+
+    let mut round = 0;
+    loop {
+        round += 1;
+        let tiebreak = is_match && round > games;
+        if tiebreak {
+            println!(
+                "\n=== Armageddon: White has {} minutes, Black {} and wins on a draw ===",
+                ARMAGEDDON_WHITE.as_secs() / 60,
+                ARMAGEDDON_BLACK.as_secs() / 60
+            );
+        } else if games > 1 {
+            println!("\n=== Game {} of {} ===", round, games);
+        } else if round > 1 {
+            println!("\n=== Rematch, game {} ===", round);
+        }
+        // The engine carries over between the games of a match, keeping
+        // its loaded network and hash table
+        let mut game = match engine.take() {
+            Some(engine) => ChessGame::with_engine(engine, board, color, game_options)?,
+            None => match &opponent {
+                Some((path, settings)) => {
+                    let engine =
+                        stockfish::StockfishEngine::with_settings(path, settings.clone()).await?;
+                    ChessGame::with_engine(engine, board, color, game_options)?
+                }
+                None => {
+                    ChessGame::new(stockfish_path, difficulty, board, color, game_options).await?
+                }
+            },
+        };
+        game.set_analysis_engine(stockfish_path);
+        if round == 1
+            && let Some(saved) = &loaded
+        {
+            game.replay_moves(&saved.moves)?;
+        }
+        if tiebreak {
+            game.set_clock(ARMAGEDDON_WHITE, ARMAGEDDON_BLACK);
+        } else if let Some((mine, computer)) = time_odds {
+            if game.player_color() == Color::White {
+                game.set_clock(mine, computer);
+            } else {
+                game.set_clock(computer, mine);
+            }
+        }
+        if let Some(path) = matches.get_one::<String>("consult") {
+            let mut consultant = stockfish::StockfishEngine::new(path).await?;
+            consultant.set_difficulty(difficulty).await?;
+            game.set_consultant(consultant);
+        }
+        if let Some(path) = matches.get_one::<String>("book") {
+            game.set_book(book::Book::load(path)?);
+        }
+        if let Some(path) = matches.get_one::<String>("script") {
+            game.set_scripts(scripting::Scripts::load(path)?);
+        }
+        if matches.get_flag("kibitz") {
+            game.set_kibitzer(kibitz::Kibitzer::spawn(stockfish_path).await?);
+        }
+        if let Some(url) = matches
+            .get_one::<String>("coach")
+            .or(config.coach_url.as_ref())
+        {
+            let timeout = config
+                .coach_timeout
+                .map_or(coach::DEFAULT_TIMEOUT, Duration::from_secs);
+            game.set_coach(coach::Coach::new(url, timeout)?);
+        }
+        let outcome = game.run().await?;
+        stats::record_game(
+            &mut game,
+            if is_match { "match" } else { "casual" },
+            difficulty,
+        )
+        .await?;
+        if !is_match {
+            let player = game.player_color();
+            match outcome {
+                GameOutcome::Win(winner) | GameOutcome::Timeout(winner) if winner == player => {
+                    score.0 += 1.0
+                }
+                GameOutcome::Win(_) | GameOutcome::Timeout(_) => score.1 += 1.0,
+                GameOutcome::Draw => {
+                    score.0 += 0.5;
+                    score.1 += 0.5;
+                }
+                GameOutcome::Unfinished => break,
+            }
+            if round > 1 {
+                println!("\nScore: You {} - {} Computer", score.0, score.1);
+            }
+            if !std::io::stdin().is_terminal() || !ask_rematch()? {
+                break;
+            }
+            color = Some(!player);
+            engine = Some(game.into_engine());
+            continue;
+        }
+
+        let event = if tiebreak {
+            "Match vs computer, Armageddon"
+        } else {
+            "Match vs computer"
+        };
+        match_pgn.push_str(&game.to_pgn(event, Some(round)));
+        match_pgn.push('\n');
+        let player = game.player_color();
+        if player == Color::White {
+            table.add(0, 1, outcome);
+        } else {
+            table.add(1, 0, outcome);
+        }
+        if tiebreak {
+            // Black has draw odds
+            let winner = match outcome {
+                GameOutcome::Win(winner) | GameOutcome::Timeout(winner) => winner,
+                GameOutcome::Draw => Color::Black,
+                GameOutcome::Unfinished => {
+                    println!("Armageddon game abandoned, the match stays tied.");
+                    break;
+                }
+            };
+            if winner == player {
+                score.0 += 1.0;
+                println!("\nYou win the Armageddon game and the match!");
+            } else {
+                score.1 += 1.0;
+                println!("\nThe computer wins the Armageddon game and the match.");
+            }
+            break;
+        }
 
-    println!("Starting chess game...");
-    println!("Stockfish path: {}", stockfish_path);
-    println!("Difficulty: {}", difficulty);
-    println!("Press 'q' to quit, 'h' for help");
-    println!();
+        match outcome {
+            GameOutcome::Win(winner) | GameOutcome::Timeout(winner) if winner == player => {
+                score.0 += 1.0
+            }
+            GameOutcome::Win(_) | GameOutcome::Timeout(_) => score.1 += 1.0,
+            GameOutcome::Draw => {
+                score.0 += 0.5;
+                score.1 += 0.5;
+            }
+            GameOutcome::Unfinished => {
+                println!("Match stopped after {} game(s).", round);
+                break;
+            }
+        }
+        println!("\nMatch score: You {} - {} Computer", score.0, score.1);
+        color = Some(!player);
+        engine = Some(game.into_engine());
+        if round == games && !(armageddon && score.0 == score.1) {
+            break;
+        }
+    }
 
-    let mut game = ChessGame::new(stockfish_path, difficulty).await?;
-    game.run().await?;
+    if !match_pgn.is_empty() {
+        let dir = storage::profile_dir()?.join("matches");
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+        let path = dir.join(format!(
+            "{}.pgn",
+            chrono::Local::now().format("%Y-%m-%d-%H%M%S")
+        ));
+        std::fs::write(&path, match_pgn)
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        table.print(false);
+        let report = path.with_extension("json");
+        table.write_json(&report.to_string_lossy(), false)?;
+        println!(
+            "Final score: You {} - {} Computer. Games saved to {}, report to {}",
+            score.0,
+            score.1,
+            path.display(),
+            report.display()
+        );
+    }
 
     Ok(())
 }
+
+/// Ask after a finished game whether to play again with colors swapped
+fn ask_rematch() -> Result<bool> {
+    println!("\nRematch with colors swapped? (y/n)");
+    let answer = ui::get_user_input()?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// `--time-odds 10:3`: minutes for the player and for the computer
+fn parse_time_odds(value: &str) -> Result<(Duration, Duration)> {
+    let minutes = |text: &str| {
+        text.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|m| *m > 0.0)
+            .and_then(|m| Duration::try_from_secs_f64(m * 60.0).ok())
+    };
+    value
+        .split_once(':')
+        .and_then(|(mine, computer)| Some((minutes(mine)?, minutes(computer)?)))
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid --time-odds '{}', expected minutes for you and the computer like 10:3",
+                value
+            )
+        })
+}
+
+/// `--min-move-delay 1.5`: seconds, zero or more
+fn parse_min_move_delay(value: &str) -> std::result::Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| "expected a number of seconds like 1.5".to_string())
+}
+
+fn parse_color(value: &str) -> Result<Color> {
+    match value.to_lowercase().as_str() {
+        "white" | "w" => Ok(Color::White),
+        "black" | "b" => Ok(Color::Black),
+        _ => Err(anyhow!(
+            "Invalid color '{}', expected white or black",
+            value
+        )),
+    }
+}
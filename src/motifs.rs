@@ -0,0 +1,182 @@
+use crate::exchange::value;
+use crate::overlay::attackers;
+use crate::storage;
+use anyhow::Result;
+use chess::{
+    BitBoard, Board, BoardStatus, ChessMove, Color, EMPTY, Piece, Square, between,
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, line,
+};
+use std::collections::BTreeMap;
+
+const MOTIFS_FILE: &str = "motifs.json";
+/// Plies of the refutation searched for a back-rank mate
+const MATE_SEARCH_PLIES: usize = 8;
+
+/// The tactic that punishes a blunder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Motif {
+    BackRank,
+    HangingPiece,
+    Fork,
+    Pin,
+    Skewer,
+    /// Anything the patterns above don't recognize
+    Other,
+}
+
+impl Motif {
+    pub fn name(self) -> &'static str {
+        match self {
+            Motif::BackRank => "back-rank mate",
+            Motif::HangingPiece => "hanging piece",
+            Motif::Fork => "fork",
+            Motif::Pin => "pin",
+            Motif::Skewer => "skewer",
+            Motif::Other => "other tactic",
+        }
+    }
+}
+
+/// Classify the refutation of a blunder. `board` is the position after the
+/// blunder and `pv` the engine's best line from there, starting with the
+/// opponent's reply.
+pub fn classify(board: &Board, pv: &[ChessMove]) -> Motif {
+    let Some(&reply) = pv.first() else {
+        return Motif::Other;
+    };
+    if !board.legal(reply) {
+        return Motif::Other;
+    }
+    if ends_in_back_rank_mate(board, pv) {
+        return Motif::BackRank;
+    }
+
+    let attacker = board.side_to_move();
+    let to = reply.get_dest();
+    if let (Some(_), Some(victim)) = (board.piece_on(to), board.color_on(to))
+        && victim != attacker
+        && attackers(board, to, victim) == EMPTY
+    {
+        return Motif::HangingPiece;
+    }
+
+    let after = board.make_move_new(reply);
+    let Some(piece) = after.piece_on(to) else {
+        return Motif::Other;
+    };
+    let enemies = *after.color_combined(!attacker);
+    let targets = attacks(&after, piece, to, attacker) & enemies;
+    let forked = targets
+        .into_iter()
+        .filter(|&square| {
+            let target = after.piece_on(square).unwrap_or(Piece::Pawn);
+            target == Piece::King
+                || value(target) > value(piece)
+                || attackers(&after, square, !attacker) == EMPTY
+        })
+        .count();
+    if forked >= 2 {
+        return Motif::Fork;
+    }
+    if matches!(piece, Piece::Bishop | Piece::Rook | Piece::Queen)
+        && let Some(motif) = line_tactic(&after, to, targets, enemies)
+    {
+        return motif;
+    }
+    Motif::Other
+}
+
+/// Squares attacked by `piece` of `color` standing on `square`
+fn attacks(board: &Board, piece: Piece, square: Square, color: Color) -> BitBoard {
+    let occupied = *board.combined();
+    match piece {
+        Piece::Pawn => get_pawn_attacks(square, color, !EMPTY),
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, occupied),
+        Piece::Rook => get_rook_moves(square, occupied),
+        Piece::Queen => get_bishop_moves(square, occupied) | get_rook_moves(square, occupied),
+        Piece::King => get_king_moves(square),
+    }
+}
+
+/// A pin when the attacked piece shields a more valuable one behind it on
+/// the slider's line, a skewer when it is the more valuable one
+fn line_tactic(board: &Board, from: Square, targets: BitBoard, enemies: BitBoard) -> Option<Motif> {
+    let occupied = *board.combined();
+    for front in targets {
+        for back in line(from, front) & enemies {
+            let behind_front = (between(from, back) & BitBoard::from_square(front)) != EMPTY;
+            if back == front || !behind_front || (between(front, back) & occupied) != EMPTY {
+                continue;
+            }
+            let front_piece = board.piece_on(front)?;
+            let back_piece = board.piece_on(back)?;
+            if front_piece == Piece::King || value(front_piece) > value(back_piece) {
+                return Some(Motif::Skewer);
+            }
+            if back_piece == Piece::King || value(back_piece) > value(front_piece) {
+                return Some(Motif::Pin);
+            }
+        }
+    }
+    None
+}
+
+/// Whether the line mates a king on its back rank with a rook or queen
+/// along that rank
+fn ends_in_back_rank_mate(board: &Board, pv: &[ChessMove]) -> bool {
+    let mut position = *board;
+    for &chess_move in pv.iter().take(MATE_SEARCH_PLIES) {
+        if !position.legal(chess_move) {
+            return false;
+        }
+        position = position.make_move_new(chess_move);
+        if position.status() == BoardStatus::Checkmate {
+            let mated = position.side_to_move();
+            let king = position.king_square(mated);
+            let back_rank = if mated == Color::White { 0 } else { 7 };
+            return king.get_rank().to_index() == back_rank
+                && position.checkers().into_iter().any(|checker| {
+                    matches!(position.piece_on(checker), Some(Piece::Rook | Piece::Queen))
+                        && checker.get_rank() == king.get_rank()
+                });
+        }
+    }
+    false
+}
+
+/// How often each motif has refuted the player's blunders
+pub type MotifCounts = BTreeMap<String, u32>;
+
+pub fn load_counts() -> Result<MotifCounts> {
+    storage::load(MOTIFS_FILE)
+}
+
+/// Add blunder motifs to the player's running totals
+pub fn record(motifs: &[Motif]) -> Result<()> {
+    if motifs.is_empty() {
+        return Ok(());
+    }
+    let mut counts = load_counts()?;
+    for motif in motifs {
+        *counts.entry(motif.name().to_string()).or_default() += 1;
+    }
+    storage::save(MOTIFS_FILE, &counts)
+}
+
+/// Motifs sorted from most to least frequent
+pub fn ranked(counts: &MotifCounts) -> Vec<(&str, u32)> {
+    let mut ranked: Vec<(&str, u32)> = counts.iter().map(|(m, n)| (m.as_str(), *n)).collect();
+    ranked.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    ranked
+}
+
+pub fn print_counts(counts: &MotifCounts) {
+    if counts.is_empty() {
+        return;
+    }
+    println!("\nBlunder motifs (what punished your blunders):");
+    for (motif, count) in ranked(counts) {
+        println!("  {:<16} {}", motif, count);
+    }
+}
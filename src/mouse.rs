@@ -0,0 +1,168 @@
+use crate::ui;
+use chess::{Color, File, Rank, Square};
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::Command;
+use std::sync::OnceLock;
+
+static MOUSE: OnceLock<bool> = OnceLock::new();
+
+/// Screen row of the first rank row when the board is drawn at the top of a
+/// cleared screen: a blank line, the file labels and the top border come first
+const FIRST_RANK_ROW: u16 = 4;
+/// Screen column of the first square's left edge, after "8 │"
+const FIRST_FILE_COLUMN: u16 = 4;
+
+/// Accept mouse clicks on the board. Called once from main.
+pub fn set_enabled(enabled: bool) {
+    let _ = MOUSE.set(enabled);
+}
+
+/// Mouse input needs a terminal on both ends to report and draw clicks
+pub fn enabled() -> bool {
+    MOUSE.get().copied().unwrap_or(false) && io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// What the player did at the prompt
+pub enum Input {
+    Line(String),
+    /// Left click at a screen column and row, both 1-based
+    Click(u16, u16),
+}
+
+/// Clear the screen so the next board is drawn at a known position
+pub fn clear_screen() {
+    print!("\x1b[H\x1b[2J");
+    let _ = io::stdout().flush();
+}
+
+/// The square under a click on a board drawn at the top of a cleared screen
+pub fn square_at(column: u16, row: u16, player_color: Color) -> Option<Square> {
+    let row = row.checked_sub(FIRST_RANK_ROW)?;
+    let column = column.checked_sub(FIRST_FILE_COLUMN)?;
+    // Odd rows are borders between ranks, every fourth column a border between files
+    if row % 2 == 1 || column % 4 == 3 || row / 2 > 7 || column / 4 > 7 {
+        return None;
+    }
+    let (row, column) = ((row / 2) as usize, (column / 4) as usize);
+    let (rank, file) = if player_color == Color::White {
+        (7 - row, column)
+    } else {
+        (row, 7 - column)
+    };
+    Some(Square::make_square(
+        Rank::from_index(rank),
+        File::from_index(file),
+    ))
+}
+
+/// Read a typed line or a left click. The terminal is switched to
+/// unbuffered input with mouse reporting for the duration of the call.
+pub fn read_input() -> io::Result<Input> {
+    print!("> ");
+    io::stdout().flush()?;
+    if let Some(command) = ui::pending_macro_command() {
+        return Ok(Input::Line(command));
+    }
+    let _mode = MouseMode::start()?;
+    let mut stdin = io::stdin().lock();
+    let mut line: Vec<u8> = Vec::new();
+
+    loop {
+        match read_byte(&mut stdin)? {
+            b'\r' | b'\n' => {
+                println!();
+                let line = String::from_utf8_lossy(&line).into_owned();
+                return Ok(Input::Line(ui::expand_macro(line)));
+            }
+            // Backspace and delete
+            0x7f | 0x08 if line.pop().is_some() => {
+                // Drop the rest of a multi-byte character
+                while line.last().is_some_and(|b| b & 0xc0 == 0x80) {
+                    line.pop();
+                }
+                print!("\x08 \x08");
+            }
+            0x1b => {
+                if let Some((column, row)) = read_escape(&mut stdin)? {
+                    println!();
+                    return Ok(Input::Click(column, row));
+                }
+                continue;
+            }
+            byte if byte >= 0x20 && byte != 0x7f => {
+                line.push(byte);
+                io::stdout().write_all(&[byte])?;
+            }
+            _ => {}
+        }
+        io::stdout().flush()?;
+    }
+}
+
+fn read_byte(stdin: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    stdin.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Consume an escape sequence; a left-button press in SGR mouse encoding
+/// (`ESC [ < 0 ; column ; row M`) gives its position, anything else
+/// (arrow keys, releases, other buttons) is ignored
+fn read_escape(stdin: &mut impl Read) -> io::Result<Option<(u16, u16)>> {
+    if read_byte(stdin)? != b'[' {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    let last = loop {
+        let byte = read_byte(stdin)?;
+        if (0x40..=0x7e).contains(&byte) {
+            break byte;
+        }
+        body.push(byte);
+    };
+    let Some(fields) = body.strip_prefix(b"<") else {
+        return Ok(None);
+    };
+    let numbers: Vec<u16> = String::from_utf8_lossy(fields)
+        .split(';')
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    match numbers.as_slice() {
+        [0, column, row] if last == b'M' => Ok(Some((*column, *row))),
+        _ => Ok(None),
+    }
+}
+
+/// Unbuffered, unechoed input with click reporting; dropping it puts the
+/// terminal back, also when reading fails
+struct MouseMode;
+
+impl MouseMode {
+    fn start() -> io::Result<Self> {
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        // Report button presses, in SGR encoding
+        print!("\x1b[?1000h\x1b[?1006h");
+        io::stdout().flush()?;
+        Ok(MouseMode)
+    }
+}
+
+impl Drop for MouseMode {
+    fn drop(&mut self) {
+        print!("\x1b[?1006l\x1b[?1000l");
+        let _ = io::stdout().flush();
+        let _ = stty(&["icanon", "echo"]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<()> {
+    let status = Command::new("stty")
+        .args(args)
+        .stdin(std::process::Stdio::inherit())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("stty failed"))
+    }
+}
@@ -0,0 +1,213 @@
+use crate::pgn;
+use crate::stockfish::Score;
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Columns that wrapped move lists and PGN movetext fit in
+pub const LINE_WIDTH: usize = 80;
+
+/// How `history` lays out the moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// One row per move number, with the evaluation and time of each move
+    Table,
+    /// A numbered paragraph, as moves are written in books
+    Inline,
+    /// The same paragraph in from-to squares, e.g. "1.e2e4 e7e5"
+    Coordinate,
+    /// Movetext as it appears in a PGN file, with thinking times and result
+    Pgn,
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Style::Table),
+            "inline" => Ok(Style::Inline),
+            "coordinate" | "coords" | "uci" => Ok(Style::Coordinate),
+            "pgn" => Ok(Style::Pgn),
+            _ => Err(anyhow!("Usage: history [table|inline|coordinate|pgn]")),
+        }
+    }
+}
+
+/// One move of a game as the move list shows it
+pub struct MoveEntry {
+    pub san: String,
+    pub coordinate: String,
+    /// Time spent on the move, when known
+    pub time: Option<Duration>,
+    /// Evaluation after the move from White's point of view, when analyzed
+    pub eval: Option<Score>,
+}
+
+/// A move's thinking time, as "4.2s" or "1:05"
+pub fn format_duration(time: Duration) -> String {
+    let seconds = time.as_secs_f64();
+    if seconds < 60.0 {
+        format!("{:.1}s", seconds)
+    } else {
+        let whole = seconds.round() as u64;
+        format!("{}:{:02}", whole / 60, whole % 60)
+    }
+}
+
+/// Lay out a game's moves in the given style. `black_first` numbers the
+/// first move as Black's for games set up with Black to move; `result` ends
+/// PGN movetext.
+pub fn render(moves: &[MoveEntry], black_first: bool, result: &str, style: Style) -> String {
+    match style {
+        Style::Table => table(moves, black_first),
+        Style::Inline => wrap(numbered_tokens(
+            moves.iter().map(|entry| entry.san.clone()),
+            black_first,
+        )),
+        Style::Coordinate => wrap(numbered_tokens(
+            moves.iter().map(|entry| entry.coordinate.clone()),
+            black_first,
+        )),
+        Style::Pgn => {
+            let mut tokens = Vec::new();
+            for (ply, entry) in moves.iter().enumerate() {
+                let index = ply + black_first as usize;
+                if index.is_multiple_of(2) {
+                    tokens.push(format!("{}.", index / 2 + 1));
+                } else if ply == 0 {
+                    tokens.push(format!("{}...", index / 2 + 1));
+                }
+                tokens.push(entry.san.clone());
+                if let Some(time) = entry.time {
+                    tokens.push(format!("{{{}}}", emt(time)));
+                }
+            }
+            tokens.push(result.to_string());
+            wrap(tokens)
+        }
+    }
+}
+
+/// Moves in SAN with move numbers, e.g. "1.e4 c5 2.Nf3"
+pub fn numbered(start: &Board, moves: &[ChessMove]) -> String {
+    let mut board = *start;
+    let mut sans = Vec::with_capacity(moves.len());
+    for &chess_move in moves {
+        sans.push(pgn::move_to_san(&board, chess_move));
+        board = board.make_move_new(chess_move);
+    }
+    numbered_tokens(sans, start.side_to_move() == chess::Color::Black).join(" ")
+}
+
+/// A thinking time as a PGN `[%emt H:MM:SS]` command
+pub fn emt(time: Duration) -> String {
+    let seconds = time.as_secs_f64().round() as u64;
+    format!(
+        "[%emt {}:{:02}:{:02}]",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Join tokens with spaces, breaking lines before they pass
+/// `LINE_WIDTH` columns
+pub fn wrap(tokens: impl IntoIterator<Item = String>) -> String {
+    let mut text = String::new();
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + token.len() + 1 > LINE_WIDTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+/// Moves with "1." before White's and "1..." before a first move by Black
+fn numbered_tokens(moves: impl IntoIterator<Item = String>, black_first: bool) -> Vec<String> {
+    moves
+        .into_iter()
+        .enumerate()
+        .map(|(ply, text)| {
+            let index = ply + black_first as usize;
+            if index.is_multiple_of(2) {
+                format!("{}.{}", index / 2 + 1, text)
+            } else if ply == 0 {
+                format!("{}...{}", index / 2 + 1, text)
+            } else {
+                text
+            }
+        })
+        .collect()
+}
+
+/// Aligned columns: move number, then White's and Black's move each with
+/// its evaluation and time
+fn table(moves: &[MoveEntry], black_first: bool) -> String {
+    let cells: Vec<[String; 3]> = std::iter::repeat_n(None, black_first as usize)
+        .chain(moves.iter().map(Some))
+        .map(|entry| match entry {
+            Some(entry) => [
+                entry.san.clone(),
+                entry
+                    .eval
+                    .map(|score| score.to_string())
+                    .unwrap_or_default(),
+                entry.time.map(format_duration).unwrap_or_default(),
+            ],
+            None => ["...".to_string(), String::new(), String::new()],
+        })
+        .collect();
+    let widths: [usize; 3] = std::array::from_fn(|column| {
+        cells
+            .iter()
+            .map(|cell| cell[column].chars().count())
+            .chain([["White", "Eval", "Time"][column].len()])
+            .max()
+            .unwrap_or(0)
+    });
+    let number_width = cells.len().div_ceil(2).to_string().len();
+
+    let half = |cell: Option<&[String; 3]>| match cell {
+        Some([san, eval, time]) => format!(
+            "{:<sw$}  {:>ew$}  {:>tw$}",
+            san,
+            eval,
+            time,
+            sw = widths[0],
+            ew = widths[1],
+            tw = widths[2]
+        ),
+        None => String::new(),
+    };
+    let heading = |side: &str| [side.to_string(), "Eval".to_string(), "Time".to_string()];
+    let mut text = format!(
+        "{:>nw$}   {}   {}\n",
+        "",
+        half(Some(&heading("White"))),
+        half(Some(&heading("Black"))),
+        nw = number_width
+    );
+    for (row, pair) in cells.chunks(2).enumerate() {
+        let line = format!(
+            "{:>nw$}.  {}   {}",
+            row + 1,
+            half(pair.first()),
+            half(pair.get(1)),
+            nw = number_width
+        );
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
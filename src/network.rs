@@ -0,0 +1,1193 @@
+use crate::chess_game::GameOutcome;
+use crate::pgn;
+use crate::status;
+use crate::ui::{self, color_name, display_board_for_player};
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus, ChessMove, Color};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// How long the joining side keeps trying to get back into a dropped game
+const RECONNECT_ATTEMPTS: u32 = 30;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// How long a new connection gets to introduce itself during a game
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Messages a spectator may fall behind by before it is dropped
+const SPECTATOR_QUEUE: usize = 64;
+/// Time a move may spend on the wire before it counts against the mover
+const LAG_ALLOWANCE_MS: i64 = 1000;
+
+/// Messages exchanged between the two players, one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// First message from a connecting side; carries the game id when resuming
+    Hello {
+        game_id: Option<String>,
+        #[serde(default)]
+        spectate: bool,
+    },
+    /// Full game state from the host, sent on connect and whenever the sides disagree
+    Welcome {
+        game_id: String,
+        you_play_white: bool,
+        increment_ms: i64,
+        moves: Vec<String>,
+        white_ms: i64,
+        black_ms: i64,
+    },
+    /// A move; `seq` is its ply number and `clock_ms` the mover's remaining time
+    Move {
+        seq: usize,
+        uci: String,
+        clock_ms: i64,
+    },
+    /// Ask the host to send the full state again
+    SyncRequest,
+    Resign,
+    /// The side to move after `seq` plies ran out of time; moves after it
+    /// crossed the claim and don't count
+    Flag {
+        seq: usize,
+    },
+    /// Ask to take the game back to the position after `plies` moves
+    TakebackOffer {
+        plies: usize,
+    },
+    /// Answer to a takeback offer
+    TakebackReply {
+        plies: usize,
+        accept: bool,
+    },
+    /// Whether this side wants a rematch once the game is over
+    Rematch {
+        accept: bool,
+    },
+    /// A line of chat
+    Chat {
+        text: String,
+    },
+    /// Final result, sent to spectators
+    GameOver {
+        result: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// One TCP connection to the other player
+struct Peer {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Peer {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        }
+    }
+
+    async fn send(&mut self, message: &Message) -> Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Next message, or None once the connection is gone
+    async fn recv(&mut self) -> Option<Message> {
+        loop {
+            match self.lines.next_line().await {
+                Ok(Some(line)) => match serde_json::from_str(&line) {
+                    Ok(message) => return Some(message),
+                    Err(e) => println!("Ignoring malformed message from peer: {}", e),
+                },
+                Ok(None) | Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// Which end of the connection we are, and how to get the connection back
+enum Role {
+    /// The host also streams the game to read-only spectators, and sets
+    /// the clocks of rematches
+    Host {
+        /// Connections that have said hello, from `accept_connections`
+        arrivals: mpsc::Receiver<Arrival>,
+        /// Every spectator's task gets what is sent here
+        spectators: broadcast::Sender<Message>,
+        base_ms: i64,
+    },
+    Guest(String),
+}
+
+/// A new connection to the host and the hello it opened with
+struct Arrival {
+    peer: Peer,
+    address: SocketAddr,
+    hello: Message,
+}
+
+/// Game state both sides keep in lockstep
+struct Session {
+    game_id: String,
+    my_color: Color,
+    board: Board,
+    moves: Vec<ChessMove>,
+    /// Remaining time of White and Black at the start of the current turn
+    white_ms: i64,
+    black_ms: i64,
+    increment_ms: i64,
+    turn_started: Instant,
+    /// Side that resigned or lost on time
+    loser: Option<Color>,
+    /// Whether `loser` lost on time
+    flagged: bool,
+    /// Takeback offer waiting for an answer
+    takeback: Option<Takeback>,
+}
+
+/// A takeback one side asked for
+#[derive(Debug, Clone, Copy)]
+struct Takeback {
+    /// Moves left on the board once it is taken back
+    plies: usize,
+    /// Whether we asked for it
+    ours: bool,
+}
+
+/// Moves and outcome of a finished network game
+pub struct NetworkGame {
+    pub moves: Vec<ChessMove>,
+    pub outcome: GameOutcome,
+}
+
+/// The connection to the opponent after a game, kept for a rematch
+pub struct Link {
+    peer: Peer,
+    role: Role,
+    /// Our color in the game just played
+    my_color: Color,
+    increment_ms: i64,
+}
+
+impl Link {
+    /// Ask whether to play again with colors swapped and, if the opponent
+    /// wants to as well, play the rematch. None once either side declines
+    /// or the connection is gone.
+    pub async fn rematch(mut self) -> Result<Option<(NetworkGame, Link)>> {
+        println!("\nRematch with colors swapped? (y/n)");
+        let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+        let answer = ui::next_input(&mut stdin).await?.unwrap_or_default();
+        let accept = matches!(answer.trim().to_lowercase().as_str(), "y" | "yes");
+        if self.peer.send(&Message::Rematch { accept }).await.is_err() || !accept {
+            return Ok(None);
+        }
+
+        println!("Waiting for your opponent...");
+        loop {
+            match self.peer.recv().await {
+                Some(Message::Rematch { accept: true }) => break,
+                Some(Message::Rematch { accept: false }) | None => {
+                    println!("Your opponent doesn't want a rematch.");
+                    return Ok(None);
+                }
+                Some(Message::Chat { text }) => println!("\n[opponent] {}", text),
+                // Leftovers from the game just finished
+                Some(_) => {}
+            }
+        }
+
+        let session = match &self.role {
+            Role::Host { base_ms, .. } => {
+                let session = Session::new(!self.my_color, *base_ms, self.increment_ms);
+                self.peer.send(&session.welcome()).await?;
+                session
+            }
+            Role::Guest(_) => loop {
+                match self.peer.recv().await {
+                    Some(message @ Message::Welcome { .. }) => {
+                        break Session::from_welcome(message)?;
+                    }
+                    Some(_) => {}
+                    None => return Err(anyhow!("The host left before the rematch began")),
+                }
+            },
+        };
+        play(session, self.peer, self.role).await.map(Some)
+    }
+}
+
+impl Session {
+    /// A new game with both clocks at `base_ms`
+    fn new(my_color: Color, base_ms: i64, increment_ms: i64) -> Self {
+        Session {
+            game_id: format!("{:016x}", rand::random::<u64>()),
+            my_color,
+            board: Board::default(),
+            moves: Vec::new(),
+            white_ms: base_ms,
+            black_ms: base_ms,
+            increment_ms,
+            turn_started: Instant::now(),
+            loser: None,
+            flagged: false,
+            takeback: None,
+        }
+    }
+
+    fn remaining(&self, color: Color) -> i64 {
+        let stored = match color {
+            Color::White => self.white_ms,
+            Color::Black => self.black_ms,
+        };
+        if self.board.side_to_move() == color {
+            stored - self.turn_started.elapsed().as_millis() as i64
+        } else {
+            stored
+        }
+    }
+
+    /// When the side to move runs out of time. The opponent gets
+    /// `LAG_ALLOWANCE_MS` more, since their move takes a while to get here.
+    fn deadline(&self) -> tokio::time::Instant {
+        let side = self.board.side_to_move();
+        let mut ms = match side {
+            Color::White => self.white_ms,
+            Color::Black => self.black_ms,
+        };
+        if side != self.my_color {
+            ms += LAG_ALLOWANCE_MS;
+        }
+        tokio::time::Instant::from_std(self.turn_started + Duration::from_millis(ms.max(0) as u64))
+    }
+
+    /// Whether the side to move is out of time by our own clock
+    fn out_of_time(&self) -> bool {
+        let side = self.board.side_to_move();
+        let allowance = if side == self.my_color {
+            0
+        } else {
+            LAG_ALLOWANCE_MS
+        };
+        self.remaining(side) + allowance <= 0
+    }
+
+    /// End the game with the side to move losing on time; the result line
+    fn flag(&mut self) -> String {
+        let loser = self.board.side_to_move();
+        if loser == self.my_color {
+            println!("\nYour time ran out. You lose on time.");
+        } else {
+            println!("\nYour opponent ran out of time. You win on time!");
+        }
+        self.loser = Some(loser);
+        self.flagged = true;
+        format!("{} loses on time", color_name(loser))
+    }
+
+    fn set_clock(&mut self, color: Color, ms: i64) {
+        match color {
+            Color::White => self.white_ms = ms,
+            Color::Black => self.black_ms = ms,
+        }
+    }
+
+    /// Play a move for the side to move, whose clock ends at `clock_ms`
+    fn apply(&mut self, chess_move: ChessMove, clock_ms: i64) {
+        self.set_clock(self.board.side_to_move(), clock_ms);
+        self.board = self.board.make_move_new(chess_move);
+        self.moves.push(chess_move);
+        self.turn_started = Instant::now();
+    }
+
+    /// Moves left on the board when our last move is taken back, along
+    /// with any reply to it; None before we have moved
+    fn takeback_target(&self) -> Option<usize> {
+        let white = self.my_color == Color::White;
+        (0..self.moves.len())
+            .rev()
+            .find(|ply| ply.is_multiple_of(2) == white)
+    }
+
+    fn position_after(&self, plies: usize) -> Board {
+        self.moves[..plies]
+            .iter()
+            .fold(Board::default(), |board, m| board.make_move_new(*m))
+    }
+
+    /// Take the game back to the position after `plies` moves. Time used
+    /// so far stays used; the clock restarts for the side now to move.
+    fn rewind(&mut self, plies: usize) {
+        let side = self.board.side_to_move();
+        let remaining = self.remaining(side);
+        self.set_clock(side, remaining);
+        self.board = self.position_after(plies);
+        self.moves.truncate(plies);
+        self.turn_started = Instant::now();
+        self.takeback = None;
+    }
+
+    /// Ask to take back our last move; the ply count to send, or None
+    /// before we have moved
+    fn offer_takeback(&mut self) -> Option<usize> {
+        let plies = self.takeback_target()?;
+        self.takeback = Some(Takeback { plies, ours: true });
+        Some(plies)
+    }
+
+    /// The opponent asks to take the game back to `plies` moves; false if
+    /// the request crossed with our own or no longer makes sense
+    fn takeback_offered(&mut self, plies: usize) -> bool {
+        if plies >= self.moves.len() || self.takeback.is_some() {
+            return false;
+        }
+        self.takeback = Some(Takeback { plies, ours: false });
+        true
+    }
+
+    /// Answer the opponent's request; the ply count to reply with, or None
+    /// if there is no request to answer
+    fn answer_takeback(&mut self, accept: bool) -> Option<usize> {
+        let takeback = self.takeback.filter(|takeback| !takeback.ours)?;
+        if accept {
+            self.rewind(takeback.plies);
+        } else {
+            self.takeback = None;
+        }
+        Some(takeback.plies)
+    }
+
+    /// The opponent answered our request. An accepted takeback goes back to
+    /// the ply count both sides agreed on, so a reply the opponent made in
+    /// the meantime goes too. False if the answer is not for our request.
+    fn takeback_answered(&mut self, plies: usize, accept: bool) -> bool {
+        match self.takeback {
+            Some(takeback) if takeback.ours && takeback.plies == plies => {
+                if accept && plies <= self.moves.len() {
+                    self.rewind(plies);
+                } else {
+                    self.takeback = None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The moves a takeback to `plies` would remove, in SAN
+    fn taken_back(&self, plies: usize) -> String {
+        pgn::line_to_san(&self.position_after(plies), &self.moves[plies..])
+    }
+
+    fn welcome(&self) -> Message {
+        Message::Welcome {
+            game_id: self.game_id.clone(),
+            you_play_white: self.my_color == Color::Black,
+            increment_ms: self.increment_ms,
+            moves: self.moves.iter().map(|m| m.to_string()).collect(),
+            white_ms: self.remaining(Color::White),
+            black_ms: self.remaining(Color::Black),
+        }
+    }
+
+    /// Rebuild the state from the host's Welcome message
+    fn from_welcome(message: Message) -> Result<Self> {
+        let Message::Welcome {
+            game_id,
+            you_play_white,
+            increment_ms,
+            moves,
+            white_ms,
+            black_ms,
+        } = message
+        else {
+            return Err(anyhow!("Expected the game state from the host"));
+        };
+
+        let mut board = Board::default();
+        let mut played = Vec::new();
+        for uci in &moves {
+            let chess_move = ChessMove::from_str(uci)
+                .ok()
+                .filter(|m| board.legal(*m))
+                .ok_or_else(|| anyhow!("Host sent an illegal move history ({})", uci))?;
+            board = board.make_move_new(chess_move);
+            played.push(chess_move);
+        }
+
+        Ok(Session {
+            game_id,
+            my_color: if you_play_white {
+                Color::White
+            } else {
+                Color::Black
+            },
+            board,
+            moves: played,
+            white_ms,
+            black_ms,
+            increment_ms,
+            turn_started: Instant::now(),
+            loser: None,
+            flagged: false,
+            takeback: None,
+        })
+    }
+
+    fn finish(self, peer: Peer, role: Role) -> (NetworkGame, Link) {
+        let outcome = match (self.loser, self.board.status()) {
+            (Some(loser), _) if self.flagged => GameOutcome::Timeout(!loser),
+            (Some(loser), _) => GameOutcome::Win(!loser),
+            (None, BoardStatus::Checkmate) => GameOutcome::Win(!self.board.side_to_move()),
+            (None, BoardStatus::Stalemate) => GameOutcome::Draw,
+            (None, BoardStatus::Ongoing) => GameOutcome::Unfinished,
+        };
+        let link = Link {
+            peer,
+            role,
+            my_color: self.my_color,
+            increment_ms: self.increment_ms,
+        };
+        (
+            NetworkGame {
+                moves: self.moves,
+                outcome,
+            },
+            link,
+        )
+    }
+
+    fn show(&self) {
+        self.show_board();
+        if self.board.side_to_move() == self.my_color {
+            println!("Your move:");
+        } else {
+            println!("Waiting for opponent...");
+        }
+    }
+
+    fn show_board(&self) {
+        display_board_for_player(&self.board, self.my_color);
+        println!(
+            "White {} | Black {}",
+            format_clock(self.remaining(Color::White)),
+            format_clock(self.remaining(Color::Black))
+        );
+        status::update(
+            &self.board,
+            self.moves.len() / 2 + 1,
+            None,
+            Some(format!(
+                "W {} B {}",
+                format_clock(self.remaining(Color::White)),
+                format_clock(self.remaining(Color::Black))
+            )),
+        );
+    }
+}
+
+enum Event {
+    Peer(Option<Message>),
+    Input(Option<String>),
+    Arrival(Result<Arrival>),
+    /// The side to move's time is up
+    Flag,
+}
+
+/// Host a game and wait for the other player to join
+pub async fn host(
+    port: u16,
+    color: Option<Color>,
+    base_minutes: u32,
+    increment_seconds: u32,
+) -> Result<(NetworkGame, Link)> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| anyhow!("Failed to listen on port {}: {}", port, e))?;
+    println!("Waiting for an opponent on port {}...", port);
+
+    let my_color = color.unwrap_or(if rand::random::<bool>() {
+        Color::White
+    } else {
+        Color::Black
+    });
+    let base_ms = base_minutes as i64 * 60_000;
+    let mut session = Session::new(my_color, base_ms, increment_seconds as i64 * 1000);
+
+    let (arrived, arrivals) = mpsc::channel(1);
+    tokio::spawn(accept_connections(listener, arrived));
+    let mut role = Role::Host {
+        arrivals,
+        spectators: broadcast::channel(SPECTATOR_QUEUE).0,
+        base_ms,
+    };
+    let peer = accept_guest(&mut role, &mut session).await?;
+    play(session, peer, role).await
+}
+
+/// Join a game hosted at host:port
+pub async fn join(address: &str) -> Result<(NetworkGame, Link)> {
+    let stream = TcpStream::connect(address)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to {}: {}", address, e))?;
+    let mut peer = Peer::new(stream);
+    peer.send(&Message::Hello {
+        game_id: None,
+        spectate: false,
+    })
+    .await?;
+
+    let session = match peer.recv().await {
+        Some(Message::Error { message }) => return Err(anyhow!("Host refused: {}", message)),
+        Some(message) => Session::from_welcome(message)?,
+        None => return Err(anyhow!("Host closed the connection")),
+    };
+
+    play(session, peer, Role::Guest(address.to_string())).await
+}
+
+/// After a game, offer rematches with colors swapped for as long as both
+/// players want them, keeping score over the games
+pub async fn play_rematches(mut game: NetworkGame, mut link: Link) -> Result<()> {
+    // Points for us and for the opponent
+    let mut score = (0.0, 0.0);
+    let mut played = 0;
+    loop {
+        match game.outcome {
+            GameOutcome::Win(winner) | GameOutcome::Timeout(winner) if winner == link.my_color => {
+                score.0 += 1.0
+            }
+            GameOutcome::Win(_) | GameOutcome::Timeout(_) => score.1 += 1.0,
+            GameOutcome::Draw => {
+                score.0 += 0.5;
+                score.1 += 0.5;
+            }
+            GameOutcome::Unfinished => return Ok(()),
+        }
+        played += 1;
+        if played > 1 {
+            println!(
+                "\nScore after {} games: You {} - {} Opponent",
+                played, score.0, score.1
+            );
+        }
+        match link.rematch().await? {
+            Some((next, next_link)) => (game, link) = (next, next_link),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Watch a game hosted at host:port without taking part
+pub async fn spectate(address: &str) -> Result<()> {
+    let stream = TcpStream::connect(address)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to {}: {}", address, e))?;
+    let mut peer = Peer::new(stream);
+    peer.send(&Message::Hello {
+        game_id: None,
+        spectate: true,
+    })
+    .await?;
+    println!("Spectating the game at {}. Press Ctrl-C to leave.", address);
+
+    loop {
+        match peer.recv().await {
+            Some(message @ Message::Welcome { .. }) => {
+                let mut session = Session::from_welcome(message)?;
+                session.my_color = Color::White;
+                session.show_board();
+                println!(
+                    "Move {}, {} to move",
+                    session.moves.len() / 2 + 1,
+                    color_name(session.board.side_to_move())
+                );
+            }
+            Some(Message::GameOver { result }) => {
+                println!("\nGame over: {}", result);
+                return Ok(());
+            }
+            Some(Message::Error { message }) => {
+                return Err(anyhow!("Host refused: {}", message));
+            }
+            Some(_) => {}
+            None => {
+                println!("\nThe host closed the connection.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn play(mut session: Session, mut peer: Peer, mut role: Role) -> Result<(NetworkGame, Link)> {
+    println!(
+        "\nGame {} started. You play {}.",
+        session.game_id,
+        color_name(session.my_color)
+    );
+    println!("Enter moves in SAN or coordinates. 'show' redraws the board, 'resign' gives up.");
+    println!("'takeback' asks to undo your last move, 'accept'/'decline' answers such a request.");
+    println!("'say <message>' chats with your opponent, 'mute'/'unmute' hides their messages.");
+    session.show();
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut muted = false;
+
+    while session.board.status() == BoardStatus::Ongoing {
+        let plies = session.moves.len();
+        let event = tokio::select! {
+            message = peer.recv() => Event::Peer(message),
+            input = ui::next_input(&mut stdin) => Event::Input(input?),
+            arrival = next_arrival(&mut role) => Event::Arrival(arrival),
+            _ = tokio::time::sleep_until(session.deadline()) => Event::Flag,
+        };
+
+        let game_over = match event {
+            Event::Peer(None) => {
+                println!("\nConnection to opponent lost.");
+                peer = reconnect(&mut role, &mut session).await?;
+                println!("Reconnected, resuming the game.");
+                session.show();
+                None
+            }
+            Event::Peer(Some(message)) => {
+                handle_message(&mut session, &mut peer, &role, message, muted).await?
+            }
+            Event::Arrival(arrival) => {
+                if let Some(returning) = admit(&role, &session, arrival?, false).await? {
+                    // The opponent came back before the old connection was seen to drop
+                    peer = returning;
+                }
+                None
+            }
+            // The clock may have been reset while this timer was set
+            Event::Flag if !session.out_of_time() => None,
+            Event::Flag => {
+                // The connection may be gone; the opponent hears on reconnecting
+                let _ = peer
+                    .send(&Message::Flag {
+                        seq: session.moves.len(),
+                    })
+                    .await;
+                Some(session.flag())
+            }
+            Event::Input(None) => Some("Game abandoned".to_string()),
+            Event::Input(Some(input)) => {
+                let input = input.trim();
+                if let Some(text) = input.strip_prefix("say ") {
+                    peer.send(&Message::Chat {
+                        text: text.trim().to_string(),
+                    })
+                    .await?;
+                    continue;
+                }
+                match input {
+                    "mute" => {
+                        muted = true;
+                        println!("Opponent chat muted.");
+                        continue;
+                    }
+                    "unmute" => {
+                        muted = false;
+                        println!("Opponent chat unmuted.");
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                handle_input(&mut session, &mut peer, input).await?
+            }
+        };
+
+        if session.moves.len() != plies {
+            broadcast(&role, &session.welcome());
+        }
+        if let Some(result) = game_over {
+            broadcast(&role, &Message::GameOver { result });
+            return Ok(session.finish(peer, role));
+        }
+    }
+
+    print_result(&session);
+    let result = match session.board.status() {
+        BoardStatus::Checkmate => format!(
+            "Checkmate, {} wins",
+            color_name(!session.board.side_to_move())
+        ),
+        _ => "Stalemate, the game is a draw".to_string(),
+    };
+    broadcast(&role, &Message::GameOver { result });
+    Ok(session.finish(peer, role))
+}
+
+/// Returns the result when the game is over
+async fn handle_message(
+    session: &mut Session,
+    peer: &mut Peer,
+    role: &Role,
+    message: Message,
+    muted: bool,
+) -> Result<Option<String>> {
+    match message {
+        Message::Move { seq, uci, clock_ms } => {
+            let chess_move = ChessMove::from_str(&uci)
+                .ok()
+                .filter(|m| session.board.legal(*m));
+            let in_sync =
+                seq == session.moves.len() + 1 && session.board.side_to_move() != session.my_color;
+
+            match chess_move {
+                // They moved after their time was up by our clock
+                Some(_) if in_sync && session.out_of_time() => {
+                    peer.send(&Message::Flag {
+                        seq: session.moves.len(),
+                    })
+                    .await?;
+                    return Ok(Some(session.flag()));
+                }
+                // Our takeback request stays open: the opponent may have moved
+                // before it reached them, and can still accept it
+                Some(chess_move) if in_sync => {
+                    println!(
+                        "\nOpponent plays {}",
+                        pgn::move_to_san(&session.board, chess_move)
+                    );
+                    // The mover reports their own clock; never believe more
+                    // time than our measurement leaves them
+                    let measured = session.remaining(session.board.side_to_move())
+                        + LAG_ALLOWANCE_MS
+                        + session.increment_ms;
+                    session.apply(chess_move, clock_ms.min(measured));
+                    session.show();
+                }
+                _ => {
+                    println!(
+                        "\nOut of sync with the opponent (move {} '{}'), resynchronizing...",
+                        seq, uci
+                    );
+                    resync(session, peer, role).await?;
+                }
+            }
+        }
+        Message::Welcome { .. } => {
+            let my_color = session.my_color;
+            *session = Session::from_welcome(message)?;
+            if session.my_color != my_color {
+                return Err(anyhow!("Host changed sides during the game"));
+            }
+            session.show();
+        }
+        Message::SyncRequest => {
+            if matches!(role, Role::Host { .. }) {
+                peer.send(&session.welcome()).await?;
+            }
+        }
+        Message::Flag { seq } if seq <= session.moves.len() => {
+            if seq < session.moves.len() {
+                session.rewind(seq);
+            }
+            return Ok(Some(session.flag()));
+        }
+        Message::Flag { .. } => resync(session, peer, role).await?,
+        Message::Resign => {
+            println!("\nYour opponent resigned. You win!");
+            session.loser = Some(!session.my_color);
+            return Ok(Some(format!("{} resigns", color_name(!session.my_color))));
+        }
+        Message::TakebackOffer { plies } => {
+            if session.takeback_offered(plies) {
+                println!(
+                    "\nYour opponent asks to take back {}. Type 'accept' or 'decline'.",
+                    session.taken_back(plies)
+                );
+            } else {
+                // Crossed with our own request, or no longer makes sense
+                peer.send(&Message::TakebackReply {
+                    plies,
+                    accept: false,
+                })
+                .await?;
+            }
+        }
+        Message::TakebackReply { plies, accept } => {
+            if session.takeback_answered(plies, accept) {
+                if accept {
+                    println!("\nYour opponent accepted the takeback.");
+                    session.show();
+                } else {
+                    println!("\nYour opponent declined the takeback.");
+                }
+            }
+        }
+        Message::Chat { text } => {
+            if !muted {
+                println!("\n[opponent] {}", text);
+            }
+        }
+        Message::Error { message } => println!("\nOpponent reported an error: {}", message),
+        Message::Hello { .. } | Message::GameOver { .. } | Message::Rematch { .. } => {}
+    }
+    Ok(None)
+}
+
+/// Returns the result when the game is over
+async fn handle_input(
+    session: &mut Session,
+    peer: &mut Peer,
+    input: &str,
+) -> Result<Option<String>> {
+    match input {
+        "" => {}
+        "resign" | "q" | "quit" => {
+            peer.send(&Message::Resign).await?;
+            println!("You resigned.");
+            session.loser = Some(session.my_color);
+            return Ok(Some(format!("{} resigns", color_name(session.my_color))));
+        }
+        "show" | "board" => session.show(),
+        "takeback" if session.takeback.is_some() => {
+            println!("A takeback request is already waiting for an answer.");
+        }
+        "takeback" => match session.offer_takeback() {
+            Some(plies) => {
+                peer.send(&Message::TakebackOffer { plies }).await?;
+                println!(
+                    "Asked to take back {}, waiting for your opponent...",
+                    session.taken_back(plies)
+                );
+            }
+            None => println!("You have no move to take back."),
+        },
+        "accept" | "decline" => {
+            let accept = input == "accept";
+            match session.answer_takeback(accept) {
+                Some(plies) => {
+                    peer.send(&Message::TakebackReply { plies, accept }).await?;
+                    if accept {
+                        session.show();
+                    } else {
+                        println!("Takeback declined.");
+                    }
+                }
+                None => println!("There is no takeback request to answer."),
+            }
+        }
+        _ if session.board.side_to_move() != session.my_color => {
+            println!("It's not your move.");
+        }
+        _ if session.takeback.is_some_and(|takeback| takeback.ours) => {
+            println!("Waiting for your opponent to answer the takeback request.");
+        }
+        _ => match pgn::parse_move(&session.board, input) {
+            Ok(chess_move) => {
+                let remaining = session.remaining(session.my_color);
+                if remaining <= 0 {
+                    peer.send(&Message::Flag {
+                        seq: session.moves.len(),
+                    })
+                    .await?;
+                    return Ok(Some(session.flag()));
+                }
+
+                // Playing on turns down a takeback the opponent asked for
+                if let Some(takeback) = session.takeback.take() {
+                    peer.send(&Message::TakebackReply {
+                        plies: takeback.plies,
+                        accept: false,
+                    })
+                    .await?;
+                }
+                let clock_ms = remaining + session.increment_ms;
+                session.apply(chess_move, clock_ms);
+                peer.send(&Message::Move {
+                    seq: session.moves.len(),
+                    uci: chess_move.to_string(),
+                    clock_ms,
+                })
+                .await?;
+                session.show();
+            }
+            Err(e) => println!("Invalid move: {}. Try again.", e),
+        },
+    }
+    Ok(None)
+}
+
+async fn resync(session: &mut Session, peer: &mut Peer, role: &Role) -> Result<()> {
+    match role {
+        // The host's state is authoritative
+        Role::Host { .. } => {
+            peer.send(&session.welcome()).await?;
+            session.show();
+        }
+        Role::Guest(_) => peer.send(&Message::SyncRequest).await?,
+    }
+    Ok(())
+}
+
+/// Get the connection back after it dropped. Clocks are paused meanwhile.
+async fn reconnect(role: &mut Role, session: &mut Session) -> Result<Peer> {
+    // Bank the time used so far so the outage is not charged to anyone
+    let side = session.board.side_to_move();
+    let remaining = session.remaining(side);
+    session.set_clock(side, remaining);
+
+    let peer = match role {
+        Role::Host { .. } => {
+            println!("Waiting for the opponent to reconnect...");
+            accept_guest(role, session).await?
+        }
+        Role::Guest(address) => {
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                if attempt > RECONNECT_ATTEMPTS {
+                    return Err(anyhow!("Could not reconnect to {}", address));
+                }
+                println!("Reconnecting to {} (attempt {})...", address, attempt);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+
+                let Ok(stream) = TcpStream::connect(address.as_str()).await else {
+                    continue;
+                };
+                let mut peer = Peer::new(stream);
+                peer.send(&Message::Hello {
+                    game_id: Some(session.game_id.clone()),
+                    spectate: false,
+                })
+                .await?;
+                match peer.recv().await {
+                    Some(Message::Error { message }) => {
+                        return Err(anyhow!("Host refused: {}", message));
+                    }
+                    Some(message) => {
+                        *session = Session::from_welcome(message)?;
+                        break peer;
+                    }
+                    None => continue,
+                }
+            }
+        }
+    };
+
+    session.turn_started = Instant::now();
+    Ok(peer)
+}
+
+/// Wait for the opponent while the clocks are stopped, letting spectators in meanwhile
+async fn accept_guest(role: &mut Role, session: &mut Session) -> Result<Peer> {
+    loop {
+        let arrival = next_arrival(role).await?;
+        let address = arrival.address;
+        session.turn_started = Instant::now();
+        if let Some(peer) = admit(role, session, arrival, true).await? {
+            println!("Opponent connected from {}", address);
+            return Ok(peer);
+        }
+    }
+}
+
+/// Sort out a new connection to the host: spectators get a task of their
+/// own that follows the game, the opponent (new, or resuming this game) is
+/// returned, and anyone else is turned away
+async fn admit(
+    role: &Role,
+    session: &Session,
+    arrival: Arrival,
+    waiting_for_opponent: bool,
+) -> Result<Option<Peer>> {
+    let Arrival {
+        mut peer,
+        address,
+        hello,
+    } = arrival;
+    match hello {
+        Message::Hello { spectate: true, .. } => {
+            if let Role::Host { spectators, .. } = role {
+                println!("\nSpectator connected from {}", address);
+                tokio::spawn(watch(peer, session.welcome(), spectators.subscribe()));
+            }
+            Ok(None)
+        }
+        Message::Hello { game_id, .. }
+            if game_id.is_none() && waiting_for_opponent && session.moves.is_empty()
+                || game_id.as_deref() == Some(session.game_id.as_str()) =>
+        {
+            peer.send(&session.welcome()).await?;
+            Ok(Some(peer))
+        }
+        _ => {
+            tokio::spawn(async move {
+                let _ = peer
+                    .send(&Message::Error {
+                        message: "A different game is in progress".to_string(),
+                    })
+                    .await;
+            });
+            Ok(None)
+        }
+    }
+}
+
+/// Accept connections for as long as the game (or its rematches) goes on.
+/// Each one gets `HELLO_TIMEOUT` to say hello in a task of its own, so a
+/// silent client never holds up the game.
+async fn accept_connections(listener: TcpListener, arrived: mpsc::Sender<Arrival>) {
+    loop {
+        let accepted = tokio::select! {
+            _ = arrived.closed() => return,
+            accepted = listener.accept() => accepted,
+        };
+        let Ok((stream, address)) = accepted else {
+            // Out of file descriptors and the like; try again shortly
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            continue;
+        };
+        let arrived = arrived.clone();
+        tokio::spawn(async move {
+            let mut peer = Peer::new(stream);
+            if let Ok(Some(hello)) = tokio::time::timeout(HELLO_TIMEOUT, peer.recv()).await {
+                let _ = arrived
+                    .send(Arrival {
+                        peer,
+                        address,
+                        hello,
+                    })
+                    .await;
+            }
+        });
+    }
+}
+
+async fn next_arrival(role: &mut Role) -> Result<Arrival> {
+    match role {
+        Role::Host { arrivals, .. } => arrivals
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("Stopped accepting connections")),
+        Role::Guest(_) => std::future::pending().await,
+    }
+}
+
+/// Stream the game to one spectator, starting from `welcome`. A spectator
+/// that falls `SPECTATOR_QUEUE` messages behind is dropped.
+async fn watch(mut peer: Peer, welcome: Message, mut updates: broadcast::Receiver<Message>) {
+    if peer.send(&welcome).await.is_err() {
+        return;
+    }
+    loop {
+        match updates.recv().await {
+            Ok(message) => {
+                let over = matches!(message, Message::GameOver { .. });
+                if peer.send(&message).await.is_err() || over {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                let _ = peer
+                    .send(&Message::Error {
+                        message: "Too far behind the game".to_string(),
+                    })
+                    .await;
+                return;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Send a message to every spectator without waiting for any of them
+fn broadcast(role: &Role, message: &Message) {
+    if let Role::Host { spectators, .. } = role {
+        // Fails only when nobody is watching
+        let _ = spectators.send(message.clone());
+    }
+}
+
+fn print_result(session: &Session) {
+    match session.board.status() {
+        BoardStatus::Checkmate => {
+            if session.board.side_to_move() == session.my_color {
+                println!("\nCheckmate! You lose.");
+            } else {
+                println!("\nCheckmate! You win!");
+            }
+        }
+        BoardStatus::Stalemate => println!("\nStalemate! The game is a draw."),
+        BoardStatus::Ongoing => {}
+    }
+}
+
+fn format_clock(ms: i64) -> String {
+    let seconds = ms.max(0) / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(sessions: &mut [&mut Session], uci: &str) {
+        let chess_move = ChessMove::from_str(uci).unwrap();
+        for session in sessions.iter_mut() {
+            session.apply(chess_move, 60_000);
+        }
+    }
+
+    #[test]
+    fn takeback_accepted_after_opponent_moved() {
+        let mut white = Session::new(Color::White, 60_000, 0);
+        let mut black = Session::new(Color::Black, 60_000, 0);
+        play(&mut [&mut white, &mut black], "e2e4");
+        play(&mut [&mut white, &mut black], "e7e5");
+        play(&mut [&mut white, &mut black], "g1f3");
+
+        // White asks to take back Nf3 while Black's reply is on its way
+        let plies = white.offer_takeback().unwrap();
+        assert_eq!(plies, 2);
+        play(&mut [&mut white, &mut black], "b8c6");
+        assert!(white.takeback.is_some());
+
+        // Black sees the request after moving and accepts it
+        assert!(black.takeback_offered(plies));
+        assert_eq!(black.answer_takeback(true), Some(plies));
+        assert!(white.takeback_answered(plies, true));
+
+        assert_eq!(white.moves, black.moves);
+        assert_eq!(white.board, black.board);
+        assert_eq!(white.moves.len(), 2);
+        assert_eq!(white.board.side_to_move(), Color::White);
+        assert!(white.takeback.is_none() && black.takeback.is_none());
+    }
+
+    #[test]
+    fn declined_takeback_leaves_the_moves() {
+        let mut white = Session::new(Color::White, 60_000, 0);
+        let mut black = Session::new(Color::Black, 60_000, 0);
+        play(&mut [&mut white, &mut black], "e2e4");
+
+        let plies = white.offer_takeback().unwrap();
+        assert!(black.takeback_offered(plies));
+        assert_eq!(black.answer_takeback(false), Some(plies));
+        assert!(white.takeback_answered(plies, false));
+
+        assert_eq!(white.moves.len(), 1);
+        assert_eq!(white.board, black.board);
+        assert!(white.takeback.is_none() && black.takeback.is_none());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::pgn::piece_char;
+use crate::ui::{display_board_with, get_piece_char};
+use chess::{
+    BitBoard, Board, ChessMove, Color, EMPTY, MoveGen, Piece, Square, between, get_bishop_moves,
+    get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+};
+
+/// Pieces of `by` that attack `square`
+pub fn attackers(board: &Board, square: Square, by: Color) -> BitBoard {
+    attackers_within(board, square, by, *board.combined())
+}
+
+/// Pieces of `by` among `occupied` that attack `square` when only the
+/// pieces in `occupied` are on the board; taking pieces out of it reveals
+/// the sliders behind them
+pub fn attackers_within(board: &Board, square: Square, by: Color, occupied: BitBoard) -> BitBoard {
+    let own = *board.color_combined(by) & occupied;
+    let queens = *board.pieces(Piece::Queen);
+
+    // A pawn of `by` attacks the square exactly where an opposite pawn on it would attack
+    (get_pawn_attacks(square, !by, *board.pieces(Piece::Pawn)) & own)
+        | (get_knight_moves(square) & *board.pieces(Piece::Knight) & own)
+        | (get_bishop_moves(square, occupied) & (*board.pieces(Piece::Bishop) | queens) & own)
+        | (get_rook_moves(square, occupied) & (*board.pieces(Piece::Rook) | queens) & own)
+        | (get_king_moves(square) & *board.pieces(Piece::King) & own)
+}
+
+/// Pieces of `color` pinned to their own king, whichever side is to move
+pub fn pinned(board: &Board, color: Color) -> BitBoard {
+    let king = board.king_square(color);
+    let enemy = *board.color_combined(!color);
+    let queens = *board.pieces(Piece::Queen);
+    let diagonal = (*board.pieces(Piece::Bishop) | queens) & enemy & get_bishop_moves(king, EMPTY);
+    let straight = (*board.pieces(Piece::Rook) | queens) & enemy & get_rook_moves(king, EMPTY);
+
+    let mut pinned = EMPTY;
+    for slider in diagonal | straight {
+        let blockers = between(king, slider) & *board.combined();
+        if blockers.popcnt() == 1 && (blockers & *board.color_combined(color)) != EMPTY {
+            pinned |= blockers;
+        }
+    }
+    pinned
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Safety {
+    /// Attacked and not defended
+    Hanging,
+    Pinned,
+    Attacked,
+    Undefended,
+}
+
+impl Safety {
+    fn marker(&self) -> char {
+        match self {
+            Safety::Hanging => '!',
+            Safety::Pinned => '~',
+            Safety::Attacked => '*',
+            Safety::Undefended => '°',
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Safety::Hanging => "Hanging (attacked, undefended)",
+            Safety::Pinned => "Pinned",
+            Safety::Attacked => "Attacked",
+            Safety::Undefended => "Undefended",
+        }
+    }
+}
+
+/// Beginner safety check: mark the player's pieces that are attacked,
+/// undefended or pinned
+pub fn scan(board: &Board, player_color: Color) {
+    let pinned = pinned(board, player_color);
+    let mut findings: Vec<(Square, Safety)> = Vec::new();
+
+    for square in *board.color_combined(player_color) {
+        let attacked = attackers(board, square, !player_color) != EMPTY;
+        // The king can't be defended; an attacked king is simply in check
+        let defended = board.piece_on(square) == Some(Piece::King)
+            || attackers(board, square, player_color) != EMPTY;
+
+        let safety = if attacked && !defended {
+            Safety::Hanging
+        } else if (pinned & BitBoard::from_square(square)) != EMPTY {
+            Safety::Pinned
+        } else if attacked {
+            Safety::Attacked
+        } else if !defended {
+            Safety::Undefended
+        } else {
+            continue;
+        };
+        findings.push((square, safety));
+    }
+
+    display_board_with(board, player_color, |square| {
+        let marker = findings
+            .iter()
+            .find(|(sq, _)| *sq == square)
+            .map_or(' ', |(_, safety)| safety.marker());
+        format!(" {}{}", get_piece_char(board, square), marker)
+    });
+
+    if findings.is_empty() {
+        println!("All your pieces are defended and none are under attack.");
+        return;
+    }
+
+    for safety in [
+        Safety::Hanging,
+        Safety::Pinned,
+        Safety::Attacked,
+        Safety::Undefended,
+    ] {
+        let pieces: Vec<String> = findings
+            .iter()
+            .filter(|(_, s)| *s == safety)
+            .filter_map(|(square, _)| {
+                board
+                    .piece_on(*square)
+                    .map(|piece| format!("{}{}", piece_char(piece), square))
+            })
+            .collect();
+        if !pieces.is_empty() {
+            println!(
+                "{} {}: {}",
+                safety.marker(),
+                safety.label(),
+                pieces.join(", ")
+            );
+        }
+    }
+}
+
+/// Color every square by how often each side attacks it: blue for White's
+/// control, red for Black's, yellow where both sides are level
+pub fn heatmap(board: &Board, player_color: Color) {
+    let control = |square: Square| {
+        let white = attackers(board, square, Color::White).popcnt() as i32;
+        let black = attackers(board, square, Color::Black).popcnt() as i32;
+        (white, black)
+    };
+
+    display_board_with(board, player_color, |square| {
+        let (white, black) = control(square);
+        let background = match white - black {
+            _ if white == 0 && black == 0 => None,
+            0 => Some(229),
+            1 => Some(117),
+            n if n > 1 => Some(33),
+            -1 => Some(217),
+            _ => Some(160),
+        };
+        let piece = get_piece_char(board, square);
+        match background {
+            // Black text keeps the pieces readable on every background
+            Some(code) => format!("\x1b[48;5;{}m\x1b[30m {} \x1b[0m", code, piece),
+            None => format!(" {} ", piece),
+        }
+    });
+
+    let (mut white_squares, mut black_squares) = (0, 0);
+    for square in chess::ALL_SQUARES {
+        let (white, black) = control(square);
+        if white > black {
+            white_squares += 1;
+        } else if black > white {
+            black_squares += 1;
+        }
+    }
+
+    println!(
+        "\x1b[48;5;33m  \x1b[0m/\x1b[48;5;117m  \x1b[0m White control   \
+         \x1b[48;5;160m  \x1b[0m/\x1b[48;5;217m  \x1b[0m Black control   \
+         \x1b[48;5;229m  \x1b[0m Contested"
+    );
+    println!(
+        "White controls {} squares, Black controls {}.",
+        white_squares, black_squares
+    );
+}
+
+/// Draw a suggested move on the board: the source square highlighted, the
+/// destination highlighted brighter, and arrow glyphs along the squares the
+/// piece passes over
+pub fn show_move(board: &Board, player_color: Color, chess_move: ChessMove) {
+    let (source, dest) = (chess_move.get_source(), chess_move.get_dest());
+    let path = between(source, dest);
+    let arrow = arrow_glyph(source, dest, player_color);
+
+    display_board_with(board, player_color, |square| {
+        let piece = get_piece_char(board, square);
+        if square == source {
+            format!("\x1b[48;5;150m\x1b[30m {} \x1b[0m", piece)
+        } else if square == dest {
+            let shown = if piece == ' ' { arrow } else { piece };
+            format!("\x1b[48;5;78m\x1b[30m {} \x1b[0m", shown)
+        } else if (path & BitBoard::from_square(square)) != EMPTY {
+            format!(" {} ", arrow)
+        } else {
+            format!(" {} ", piece)
+        }
+    });
+}
+
+/// Squares the piece on `source` can legally move to
+pub fn targets(board: &Board, source: Square) -> BitBoard {
+    MoveGen::new_legal(board)
+        .filter(|m| m.get_source() == source)
+        .fold(EMPTY, |targets, m| {
+            targets | BitBoard::from_square(m.get_dest())
+        })
+}
+
+/// Draw a selected piece highlighted, with a dot on every empty square it can
+/// move to and the pieces it can capture highlighted brighter
+pub fn show_targets(board: &Board, player_color: Color, source: Square) {
+    let targets = targets(board, source);
+
+    display_board_with(board, player_color, |square| {
+        let piece = get_piece_char(board, square);
+        if square == source {
+            format!("\x1b[48;5;150m\x1b[30m {} \x1b[0m", piece)
+        } else if (targets & BitBoard::from_square(square)) == EMPTY {
+            format!(" {} ", piece)
+        } else if piece == ' ' {
+            " · ".to_string()
+        } else {
+            format!("\x1b[48;5;78m\x1b[30m {} \x1b[0m", piece)
+        }
+    });
+}
+
+/// Arrow pointing from `source` towards `dest` as seen from the player's side
+fn arrow_glyph(source: Square, dest: Square, player_color: Color) -> char {
+    let mut files =
+        (dest.get_file().to_index() as i32 - source.get_file().to_index() as i32).signum();
+    let mut ranks =
+        (dest.get_rank().to_index() as i32 - source.get_rank().to_index() as i32).signum();
+    if player_color == Color::Black {
+        files = -files;
+        ranks = -ranks;
+    }
+    match (files, ranks) {
+        (1, 0) => '→',
+        (-1, 0) => '←',
+        (0, 1) => '↑',
+        (0, -1) => '↓',
+        (1, 1) => '↗',
+        (-1, 1) => '↖',
+        (1, -1) => '↘',
+        _ => '↙',
+    }
+}
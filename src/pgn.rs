@@ -0,0 +1,623 @@
+use crate::fen;
+use crate::movelist;
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus, ChessMove, Color, File, MoveGen, Piece, Rank, Square};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A single game read from a PGN file
+pub struct PgnGame {
+    pub headers: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<ChessMove>,
+    /// Time left on the mover's clock after each move, from `[%clk]`
+    /// comments; None where a move has none
+    pub clocks: Vec<Option<Duration>>,
+}
+
+impl PgnGame {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn winner(&self) -> Option<Color> {
+        match self.header("Result") {
+            Some("1-0") => Some(Color::White),
+            Some("0-1") => Some(Color::Black),
+            _ => None,
+        }
+    }
+}
+
+pub fn load_pgn_file(path: &str) -> Result<Vec<PgnGame>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read PGN file {}: {}", path, e))?;
+    parse_pgn(&text)
+}
+
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>> {
+    let mut games = Vec::new();
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            // A tag after movetext starts the next game
+            if !movetext.trim().is_empty() {
+                games.push(build_game(
+                    std::mem::take(&mut headers),
+                    &movetext,
+                    games.len(),
+                )?);
+                movetext.clear();
+            }
+            if let Some(tag) = parse_tag(line) {
+                headers.push(tag);
+            }
+        } else if !line.starts_with('%') {
+            // Drop rest-of-line comments
+            let content = line.split(';').next().unwrap_or("");
+            movetext.push_str(content);
+            movetext.push(' ');
+        }
+    }
+
+    if !movetext.trim().is_empty() || !headers.is_empty() {
+        games.push(build_game(headers, &movetext, games.len())?);
+    }
+
+    if games.is_empty() {
+        return Err(anyhow!("No games found in PGN"));
+    }
+
+    Ok(games)
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.trim_start_matches('[').trim_end_matches(']').trim();
+    let (key, rest) = inner.split_once(char::is_whitespace)?;
+    let value = rest.trim().trim_matches('"').replace("\\\"", "\"");
+    Some((key.to_string(), value))
+}
+
+fn build_game(headers: Vec<(String, String)>, movetext: &str, index: usize) -> Result<PgnGame> {
+    let start = match headers.iter().find(|(key, _)| key == "FEN") {
+        Some((_, fen)) => fen::parse_fen(fen)
+            .map_err(|e| anyhow!("Game {}: invalid FEN header: {}", index + 1, e))?,
+        None => Board::default(),
+    };
+
+    let mut board = start;
+    let mut moves = Vec::new();
+    let mut clocks = Vec::new();
+
+    for (token, clock) in movetext_tokens(movetext) {
+        let chess_move = parse_san(&board, &token).map_err(|e| {
+            anyhow!(
+                "Game {}: cannot play '{}' at ply {}: {}",
+                index + 1,
+                token,
+                moves.len() + 1,
+                e
+            )
+        })?;
+        board = board.make_move_new(chess_move);
+        moves.push(chess_move);
+        clocks.push(clock);
+    }
+
+    Ok(PgnGame {
+        headers,
+        start,
+        moves,
+        clocks,
+    })
+}
+
+/// Extract the SAN move tokens from PGN movetext, skipping variations,
+/// NAGs, move numbers and the result marker. Comments are dropped except
+/// for a `[%clk]` time, which goes with the move it follows.
+fn movetext_tokens(movetext: &str) -> Vec<(String, Option<Duration>)> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut comment: Option<String> = None;
+    let mut variation_depth = 0;
+
+    for c in movetext.chars() {
+        if let Some(text) = comment.as_mut() {
+            if c == '}' {
+                if variation_depth == 0
+                    && let Some(clock) = parse_clock(text)
+                    && let Some((_, last)) = tokens.last_mut()
+                {
+                    *last = Some(clock);
+                }
+                comment = None;
+            } else {
+                text.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '{' => comment = Some(String::new()),
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            c if c.is_whitespace() => {
+                push_token(&mut tokens, &current);
+                current.clear();
+            }
+            c => current.push(c),
+        }
+
+        if c == '{' || c == '(' || c == ')' {
+            push_token(&mut tokens, &current);
+            current.clear();
+        }
+    }
+    push_token(&mut tokens, &current);
+
+    tokens
+}
+
+fn push_token(tokens: &mut Vec<(String, Option<Duration>)>, raw: &str) {
+    if raw.is_empty() || raw.starts_with('$') || matches!(raw, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return;
+    }
+
+    // Strip move numbers such as "12." or "12..." (possibly glued to the move)
+    let token = if raw.contains('.') {
+        raw.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+    } else {
+        raw
+    };
+
+    if !token.is_empty() {
+        tokens.push((token.to_string(), None));
+    }
+}
+
+/// The clock time in a comment such as `[%clk 0:03:25.3]`
+fn parse_clock(comment: &str) -> Option<Duration> {
+    let (_, rest) = comment.split_once("[%clk")?;
+    let time = rest.split(']').next()?.trim();
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        let value: f64 = part.parse().ok()?;
+        seconds = seconds * 60.0 + value;
+    }
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+/// Starting time and increment from a `TimeControl` header such as
+/// "300+3" (seconds); None for untimed or multi-period controls
+pub fn time_control(game: &PgnGame) -> Option<(Duration, Duration)> {
+    let value = game.header("TimeControl")?;
+    let (base, increment) = value.split_once('+').unwrap_or((value, "0"));
+    Some((
+        Duration::from_secs(base.trim().parse().ok()?),
+        Duration::from_secs(increment.trim().parse().ok()?),
+    ))
+}
+
+/// Parse a move typed either in coordinate form (e2e4, e7e8q) or in SAN
+pub fn parse_move(board: &Board, input: &str) -> Result<ChessMove> {
+    if let Ok(chess_move) = ChessMove::from_str(input)
+        && board.legal(chess_move)
+    {
+        return Ok(chess_move);
+    }
+    parse_san(board, input)
+}
+
+/// Parse a move in Standard Algebraic Notation (Nf3, exd5, O-O, e8=Q)
+pub fn parse_san(board: &Board, san: &str) -> Result<ChessMove> {
+    let candidates = san_candidates(board, san)?;
+    match candidates.as_slice() {
+        [] => Err(anyhow!("No legal move matches '{}'", san)),
+        [chess_move] => Ok(*chess_move),
+        _ => Err(ambiguity(board, san, &candidates)),
+    }
+}
+
+/// The error for SAN that fits several legal moves, naming each of them
+/// in full SAN, e.g. "'Nd2' is ambiguous, could be Nbd2 or Nfd2"
+pub fn ambiguity(board: &Board, san: &str, candidates: &[ChessMove]) -> anyhow::Error {
+    let sans: Vec<String> = candidates
+        .iter()
+        .map(|&chess_move| move_to_san(board, chess_move))
+        .collect();
+    let listed = match sans.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        _ => sans.join(""),
+    };
+    anyhow!("'{}' is ambiguous, could be {}", san, listed)
+}
+
+/// Every legal move that SAN fits: none when it names no legal move,
+/// several when it leaves out a needed file or rank. Malformed SAN is an
+/// error.
+pub fn san_candidates(board: &Board, san: &str) -> Result<Vec<ChessMove>> {
+    let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    if text.is_empty() {
+        return Err(anyhow!("Empty move"));
+    }
+
+    let legal_moves: Vec<ChessMove> = MoveGen::new_legal(board).collect();
+
+    // Castling
+    let castle_file = match text {
+        "O-O" | "0-0" => Some(File::G),
+        "O-O-O" | "0-0-0" => Some(File::C),
+        _ => None,
+    };
+    if let Some(file) = castle_file {
+        let king = board.king_square(board.side_to_move());
+        let dest = Square::make_square(king.get_rank(), file);
+        let castling: Vec<ChessMove> = legal_moves
+            .into_iter()
+            .filter(|m| m.get_source() == king && m.get_dest() == dest)
+            .collect();
+        if castling.is_empty() {
+            return Err(anyhow!("Castling is not legal in this position"));
+        }
+        return Ok(castling);
+    }
+
+    let mut chars: Vec<char> = text
+        .chars()
+        .filter(|c| !matches!(c, 'x' | ':' | '-' | '='))
+        .collect();
+
+    // Promotion piece, written as "e8=Q" or "e8Q"
+    let mut promotion = None;
+    if chars.len() > 2 && chars[chars.len() - 2].is_ascii_digit() {
+        promotion = piece_from_char(chars[chars.len() - 1].to_ascii_uppercase());
+        if promotion.is_none() {
+            return Err(anyhow!("Invalid promotion piece in '{}'", san));
+        }
+        chars.pop();
+    }
+
+    let piece = match chars.first().and_then(|c| piece_from_char(*c)) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => Piece::Pawn,
+    };
+
+    if chars.len() < 2 {
+        return Err(anyhow!("'{}' is not a valid move", san));
+    }
+
+    let dest_str: String = chars[chars.len() - 2..].iter().collect();
+    let dest = Square::from_str(&dest_str)
+        .map_err(|_| anyhow!("Invalid destination square: {}", dest_str))?;
+
+    // Anything left over is source-square disambiguation
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in &chars[..chars.len() - 2] {
+        match c {
+            'a'..='h' => from_file = Some(File::from_index(*c as usize - 'a' as usize)),
+            '1'..='8' => from_rank = Some(Rank::from_index(*c as usize - '1' as usize)),
+            _ => return Err(anyhow!("'{}' is not a valid move", san)),
+        }
+    }
+    // A pawn move without a file is a push; captures always name the file
+    if piece == Piece::Pawn && from_file.is_none() {
+        from_file = Some(dest.get_file());
+    }
+
+    Ok(legal_moves
+        .into_iter()
+        .filter(|m| {
+            m.get_dest() == dest
+                && board.piece_on(m.get_source()) == Some(piece)
+                && m.get_promotion() == promotion
+                && from_file.is_none_or(|f| m.get_source().get_file() == f)
+                && from_rank.is_none_or(|r| m.get_source().get_rank() == r)
+        })
+        .collect())
+}
+
+/// Render a legal move in Standard Algebraic Notation
+pub fn move_to_san(board: &Board, chess_move: ChessMove) -> String {
+    let from_square = chess_move.get_source();
+    let to_square = chess_move.get_dest();
+
+    // Get the piece that's moving
+    let piece = match board.piece_on(from_square) {
+        Some(piece) => piece,
+        None => return chess_move.to_string(), // Fallback
+    };
+
+    // Check for castling first
+    if piece == Piece::King && from_square.get_file() == File::E {
+        match to_square.get_file() {
+            File::G => return format!("O-O{}", check_suffix(board, chess_move)),
+            File::C => return format!("O-O-O{}", check_suffix(board, chess_move)),
+            _ => {}
+        }
+    }
+
+    // Pawns moving diagonally always capture (including en passant)
+    let is_capture = board.piece_on(to_square).is_some()
+        || (piece == Piece::Pawn && from_square.get_file() != to_square.get_file());
+
+    let mut notation = String::new();
+
+    if piece == Piece::Pawn {
+        // For pawn captures, include the file
+        if is_capture {
+            notation.push(file_char(from_square.get_file()));
+        }
+    } else {
+        notation.push(piece_char(piece));
+
+        // Disambiguate if other pieces of the same type can reach the destination
+        let others: Vec<Square> = MoveGen::new_legal(board)
+            .filter(|m| {
+                m.get_dest() == to_square
+                    && m.get_source() != from_square
+                    && board.piece_on(m.get_source()) == Some(piece)
+            })
+            .map(|m| m.get_source())
+            .collect();
+
+        if !others.is_empty() {
+            let same_file = others
+                .iter()
+                .any(|s| s.get_file() == from_square.get_file());
+            let same_rank = others
+                .iter()
+                .any(|s| s.get_rank() == from_square.get_rank());
+
+            if !same_file {
+                notation.push(file_char(from_square.get_file()));
+            } else if !same_rank {
+                notation.push(rank_char(from_square.get_rank()));
+            } else {
+                notation.push(file_char(from_square.get_file()));
+                notation.push(rank_char(from_square.get_rank()));
+            }
+        }
+    }
+
+    // Add capture notation
+    if is_capture {
+        notation.push('x');
+    }
+
+    // Add destination square
+    notation.push_str(&to_square.to_string());
+
+    // Add promotion
+    if let Some(promotion_piece) = chess_move.get_promotion() {
+        notation.push('=');
+        notation.push(piece_char(promotion_piece));
+    }
+
+    notation.push_str(check_suffix(board, chess_move));
+    notation
+}
+
+/// Render a sequence of moves (such as an engine's principal variation) in
+/// SAN, stopping at the first move that is not legal
+pub fn line_to_san(board: &Board, moves: &[ChessMove]) -> String {
+    moves_to_san(board, moves).join(" ")
+}
+
+/// Each move of a game played from `start` in SAN, stopping at the first
+/// move that is not legal
+pub fn moves_to_san(start: &Board, moves: &[ChessMove]) -> Vec<String> {
+    let mut board = *start;
+    let mut sans = Vec::with_capacity(moves.len());
+    for &chess_move in moves {
+        if !board.legal(chess_move) {
+            break;
+        }
+        sans.push(move_to_san(&board, chess_move));
+        board = board.make_move_new(chess_move);
+    }
+    sans
+}
+
+/// Write one game as PGN. `moves` holds each move in SAN (with any mark
+/// such as `?!` already attached) and an optional comment to follow it.
+pub fn write_game(
+    headers: &[(String, String)],
+    start: &Board,
+    moves: &[(String, Option<String>)],
+    result: &str,
+) -> String {
+    let black_first = start.side_to_move() == Color::Black;
+    let mut tokens = Vec::new();
+    for (ply, (san, comment)) in moves.iter().enumerate() {
+        let number = (ply + black_first as usize) / 2 + 1;
+        if (ply + black_first as usize).is_multiple_of(2) {
+            tokens.push(format!("{}.", number));
+        } else if ply == 0 {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(san.clone());
+        if let Some(comment) = comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+    }
+    tokens.push(result.to_string());
+    write_tokens(headers, tokens)
+}
+
+/// Write headers and movetext tokens (move numbers, moves, comments,
+/// variation brackets, the result) as PGN
+pub fn write_tokens(headers: &[(String, String)], tokens: Vec<String>) -> String {
+    let mut text = String::new();
+    for (name, value) in headers {
+        text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+    }
+    text.push('\n');
+    // Wrap movetext at 80 columns like most PGN writers
+    text.push_str(&movelist::wrap(tokens));
+    text
+}
+
+fn check_suffix(board: &Board, chess_move: ChessMove) -> &'static str {
+    let after = board.make_move_new(chess_move);
+    if after.status() == BoardStatus::Checkmate {
+        "#"
+    } else if after.checkers().popcnt() > 0 {
+        "+"
+    } else {
+        ""
+    }
+}
+
+pub fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::King => 'K',
+        Piece::Queen => 'Q',
+        Piece::Rook => 'R',
+        Piece::Bishop => 'B',
+        Piece::Knight => 'N',
+        Piece::Pawn => 'P',
+    }
+}
+
+fn piece_from_char(c: char) -> Option<Piece> {
+    match c {
+        'K' => Some(Piece::King),
+        'Q' => Some(Piece::Queen),
+        'R' => Some(Piece::Rook),
+        'B' => Some(Piece::Bishop),
+        'N' => Some(Piece::Knight),
+        _ => None,
+    }
+}
+
+fn file_char(file: File) -> char {
+    (b'a' + file.to_index() as u8) as char
+}
+
+fn rank_char(rank: Rank) -> char {
+    (b'1' + rank.to_index() as u8) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen).unwrap()
+    }
+
+    fn san(fen: &str, san: &str) -> Result<String> {
+        parse_san(&board(fen), san).map(|chess_move| chess_move.to_string())
+    }
+
+    fn candidates(fen: &str, san: &str) -> Vec<String> {
+        let mut moves: Vec<String> = san_candidates(&board(fen), san)
+            .unwrap()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        moves.sort();
+        moves
+    }
+
+    /// Knights on b1 and f3 can both go to d2
+    const KNIGHTS: &str = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+    /// Rooks on a1 and a5 can both go to a3
+    const ROOKS: &str = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+    /// Queens on a1, c1 and a3 can all go to b2
+    const QUEENS: &str = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+    /// A pawn on e7 can promote on e8 or by taking the rook on d8
+    const PROMOTION: &str = "3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1";
+
+    #[test]
+    fn file_disambiguation() {
+        assert_eq!(candidates(KNIGHTS, "Nd2"), ["b1d2", "f3d2"]);
+        let error = san(KNIGHTS, "Nd2").unwrap_err().to_string();
+        assert!(
+            error.contains("Nbd2") && error.contains("Nfd2"),
+            "{}",
+            error
+        );
+        assert_eq!(san(KNIGHTS, "Nbd2").unwrap(), "b1d2");
+        assert_eq!(san(KNIGHTS, "Nfd2").unwrap(), "f3d2");
+        assert_eq!(san(KNIGHTS, "N3d2").unwrap(), "f3d2");
+    }
+
+    #[test]
+    fn rank_disambiguation() {
+        assert_eq!(candidates(ROOKS, "Ra3"), ["a1a3", "a5a3"]);
+        // Both rooks are on the a-file, so the file doesn't help
+        assert_eq!(candidates(ROOKS, "Raa3"), ["a1a3", "a5a3"]);
+        assert_eq!(san(ROOKS, "R1a3").unwrap(), "a1a3");
+        assert_eq!(san(ROOKS, "R5a3").unwrap(), "a5a3");
+    }
+
+    #[test]
+    fn square_disambiguation() {
+        assert_eq!(candidates(QUEENS, "Qb2"), ["a1b2", "a3b2", "c1b2"]);
+        assert_eq!(candidates(QUEENS, "Qab2"), ["a1b2", "a3b2"]);
+        assert_eq!(candidates(QUEENS, "Q1b2"), ["a1b2", "c1b2"]);
+        assert_eq!(san(QUEENS, "Qa1b2").unwrap(), "a1b2");
+        assert_eq!(san(QUEENS, "Qa1xb2").unwrap(), "a1b2");
+    }
+
+    #[test]
+    fn promotion() {
+        assert_eq!(san(PROMOTION, "e8=Q").unwrap(), "e7e8q");
+        assert_eq!(san(PROMOTION, "e8Q+").unwrap(), "e7e8q");
+        assert_eq!(san(PROMOTION, "e8=n").unwrap(), "e7e8n");
+        assert_eq!(san(PROMOTION, "exd8=R").unwrap(), "e7d8r");
+        // The promotion piece must be given, and be one a pawn can become
+        assert!(candidates(PROMOTION, "e8").is_empty());
+        assert!(candidates(PROMOTION, "e8=K").is_empty());
+        assert!(san_candidates(&board(PROMOTION), "e8=X").is_err());
+    }
+
+    #[test]
+    fn pawn_moves_and_captures() {
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+        assert_eq!(san(fen, "exd5").unwrap(), "e4d5");
+        assert_eq!(san(fen, "ed5").unwrap(), "e4d5");
+        assert_eq!(san(fen, "e5").unwrap(), "e4e5");
+        assert_eq!(san(fen, "d4").unwrap(), "d2d4");
+        assert!(candidates(fen, "d5").is_empty());
+
+        let en_passant = "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(san(en_passant, "exd6").unwrap(), "e5d6");
+    }
+
+    #[test]
+    fn lowercase_b_is_a_pawn_and_capital_b_a_bishop() {
+        let fen = "4k3/8/8/8/8/2n5/1P1B4/4K3 w - - 0 1";
+        assert_eq!(san(fen, "bxc3").unwrap(), "b2c3");
+        assert_eq!(san(fen, "Bxc3").unwrap(), "d2c3");
+    }
+
+    #[test]
+    fn written_san_parses_back() {
+        for fen in [KNIGHTS, ROOKS, QUEENS, PROMOTION] {
+            let board = board(fen);
+            for chess_move in MoveGen::new_legal(&board) {
+                let written = move_to_san(&board, chess_move);
+                assert_eq!(
+                    parse_san(&board, &written).unwrap(),
+                    chess_move,
+                    "{}",
+                    written
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::ui::BoardStyle;
+use anyhow::{Result, anyhow};
+use chess::{Color, Piece};
+
+/// Order of the glyphs in a custom piece set: White's pieces, then Black's
+pub const GLYPH_ORDER: &str = "KQRBNPkqrbnp";
+
+const PIECES: [Piece; 6] = [
+    Piece::King,
+    Piece::Queen,
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Knight,
+    Piece::Pawn,
+];
+
+/// The characters every board renderer draws the pieces with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceSet {
+    /// King, queen, rook, bishop, knight and pawn of White, then of Black
+    glyphs: [char; 12],
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        Self::unicode()
+    }
+}
+
+impl PieceSet {
+    /// Chess figurines: outlined for White, filled for Black
+    pub fn unicode() -> Self {
+        Self::parse("♔♕♖♗♘♙♚♛♜♝♞♟").expect("twelve glyphs")
+    }
+
+    /// Letters, uppercase for White: for fonts without chess symbols
+    pub fn letters() -> Self {
+        Self::parse(GLYPH_ORDER).expect("twelve glyphs")
+    }
+
+    /// Twelve glyphs in `GLYPH_ORDER`, e.g. "KQRBNPkqrbnp"
+    pub fn parse(text: &str) -> Result<Self> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let glyphs: [char; 12] = chars.try_into().map_err(|chars: Vec<char>| {
+            anyhow!(
+                "A piece set needs 12 glyphs in the order {}, got {}",
+                GLYPH_ORDER,
+                chars.len()
+            )
+        })?;
+        Ok(PieceSet { glyphs })
+    }
+
+    /// The piece set chosen in the configuration
+    pub fn from_config(style: BoardStyle, custom: Option<&str>) -> Result<Self> {
+        match style {
+            BoardStyle::Unicode => Ok(Self::unicode()),
+            BoardStyle::Ascii => Ok(Self::letters()),
+            BoardStyle::Custom => Self::parse(custom.ok_or_else(|| {
+                anyhow!("Board style \"custom\" needs \"custom_pieces\" in the config file")
+            })?),
+        }
+    }
+
+    pub fn glyph(&self, piece: Piece, color: Color) -> char {
+        let index = PIECES.iter().position(|&p| p == piece).unwrap_or(0);
+        let offset = if color == Color::White { 0 } else { 6 };
+        self.glyphs[offset + index]
+    }
+
+    /// Whether `c` is one of the set's glyphs
+    pub fn contains(&self, c: char) -> bool {
+        self.glyphs.contains(&c)
+    }
+
+    /// All six glyphs of one side, e.g. "♔♕♖♗♘♙"
+    pub fn side(&self, color: Color) -> String {
+        PIECES
+            .iter()
+            .map(|&piece| self.glyph(piece, color))
+            .collect()
+    }
+}
@@ -0,0 +1,292 @@
+use crate::overlay::attackers;
+use crate::pgn::piece_char;
+use chess::{Board, CastleRights, Color, EMPTY, File, Piece, Rank, Square, get_file};
+
+/// Non-pawn material (knight and bishop 3, rook 5, queen 9) at or below
+/// which the game counts as an endgame; 62 at the start
+const ENDGAME_MATERIAL: u32 = 26;
+/// Moves after which the opening is over even if development lags
+const OPENING_MOVES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Opening => "Opening",
+            Phase::Middlegame => "Middlegame",
+            Phase::Endgame => "Endgame",
+        }
+    }
+}
+
+/// Phase of the game from the material left and how far development has got
+pub fn phase(board: &Board, move_number: usize) -> Phase {
+    let material: u32 = [
+        (Piece::Knight, 3),
+        (Piece::Bishop, 3),
+        (Piece::Rook, 5),
+        (Piece::Queen, 9),
+    ]
+    .iter()
+    .map(|&(piece, value)| board.pieces(piece).popcnt() * value)
+    .sum();
+    let undeveloped = undeveloped_minors(board, Color::White).len()
+        + undeveloped_minors(board, Color::Black).len();
+
+    if material <= ENDGAME_MATERIAL {
+        Phase::Endgame
+    } else if move_number <= OPENING_MOVES || undeveloped >= 4 {
+        Phase::Opening
+    } else {
+        Phase::Middlegame
+    }
+}
+
+/// Generic guidance for `color` in this phase, tied to the actual pieces
+pub fn advice(board: &Board, color: Color, phase: Phase) -> Vec<String> {
+    let mut tips = Vec::new();
+    match phase {
+        Phase::Opening => {
+            let undeveloped = undeveloped_minors(board, color);
+            if !undeveloped.is_empty() {
+                tips.push(format!(
+                    "Develop your knights and bishops: {} still at home.",
+                    describe(board, &undeveloped)
+                ));
+            }
+            if !controls_center(board, color) {
+                let (e, d) = if color == Color::White {
+                    ("e4", "d4")
+                } else {
+                    ("e5", "d5")
+                };
+                tips.push(format!("Claim the center with a pawn on {} or {}.", e, d));
+            }
+            tips.extend(king_advice(board, color));
+            let queen_home = home_square(color, File::D);
+            let queen_out = (board.pieces(Piece::Queen) & board.color_combined(color))
+                .into_iter()
+                .any(|square| square != queen_home);
+            if queen_out && undeveloped.len() >= 2 {
+                tips.push(
+                    "Don't bring the queen out too early: developing moves will chase her around."
+                        .to_string(),
+                );
+            }
+        }
+        Phase::Middlegame => {
+            tips.extend(hanging_pieces(board, color));
+            tips.extend(king_advice(board, color));
+            tips.extend(rook_advice(board, color));
+            let undeveloped = undeveloped_minors(board, color);
+            if !undeveloped.is_empty() {
+                tips.push(format!(
+                    "Finish your development: {} still at home.",
+                    describe(board, &undeveloped)
+                ));
+            }
+        }
+        Phase::Endgame => {
+            tips.extend(hanging_pieces(board, color));
+            let king = board.king_square(color);
+            if distance_to_center(king) > 1 {
+                tips.push(format!(
+                    "Activate your king: in the endgame it is a fighting piece. Bring it from {} toward the center.",
+                    king
+                ));
+            }
+            let mine = passed_pawns(board, color);
+            if !mine.is_empty() {
+                tips.push(format!(
+                    "Push your passed pawn{} on {}, escorted by your king or a rook behind it.",
+                    if mine.len() > 1 { "s" } else { "" },
+                    squares(&mine)
+                ));
+            }
+            let theirs = passed_pawns(board, !color);
+            if !theirs.is_empty() {
+                tips.push(format!(
+                    "Stop the passed pawn{} on {}: blockade the square in front with your king or a piece.",
+                    if theirs.len() > 1 { "s" } else { "" },
+                    squares(&theirs)
+                ));
+            }
+        }
+    }
+    if tips.is_empty() {
+        tips.push(
+            "Your position is in order. Improve your worst-placed piece and look for weaknesses."
+                .to_string(),
+        );
+    }
+    tips
+}
+
+/// Knights and bishops of `color` still on their starting squares
+fn undeveloped_minors(board: &Board, color: Color) -> Vec<Square> {
+    [
+        (Piece::Knight, File::B),
+        (Piece::Bishop, File::C),
+        (Piece::Bishop, File::F),
+        (Piece::Knight, File::G),
+    ]
+    .iter()
+    .map(|&(piece, file)| (piece, home_square(color, file)))
+    .filter(|&(piece, square)| {
+        board.piece_on(square) == Some(piece) && board.color_on(square) == Some(color)
+    })
+    .map(|(_, square)| square)
+    .collect()
+}
+
+/// A pawn of `color` on one of the four center squares
+fn controls_center(board: &Board, color: Color) -> bool {
+    let pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    pawns.into_iter().any(|square| {
+        matches!(square.get_file(), File::D | File::E)
+            && matches!(square.get_rank(), Rank::Fourth | Rank::Fifth)
+    })
+}
+
+fn king_advice(board: &Board, color: Color) -> Vec<String> {
+    let king = board.king_square(color);
+    let in_center = matches!(king.get_file(), File::D | File::E | File::F);
+    let queens_on = board.pieces(Piece::Queen).popcnt() > 0;
+    if king == home_square(color, File::E) && board.castle_rights(color) != CastleRights::NoRights {
+        return vec!["Castle soon to get your king out of the center.".to_string()];
+    }
+    if in_center && queens_on {
+        return vec![
+            "Your king can no longer castle and stands in the center: keep the position closed or walk it to safety."
+                .to_string(),
+        ];
+    }
+
+    // A castled king wants pawns on the squares in front of it
+    let pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let shield = pawns
+        .into_iter()
+        .filter(|pawn| {
+            let files = pawn.get_file().to_index() as i32 - king.get_file().to_index() as i32;
+            let ranks = relative_rank(*pawn, color) as i32 - relative_rank(king, color) as i32;
+            files.abs() <= 1 && (1..=2).contains(&ranks)
+        })
+        .count();
+    if queens_on && shield < 2 {
+        vec!["The pawn cover in front of your king is thin: be careful about opening lines toward it.".to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+fn rook_advice(board: &Board, color: Color) -> Vec<String> {
+    let rooks = board.pieces(Piece::Rook) & board.color_combined(color);
+    let my_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let all_pawns = *board.pieces(Piece::Pawn);
+    let open = |file: File| (get_file(file) & all_pawns) == EMPTY;
+    let half_open = |file: File| (get_file(file) & my_pawns) == EMPTY;
+
+    if rooks == EMPTY || rooks.into_iter().any(|rook| half_open(rook.get_file())) {
+        return Vec::new();
+    }
+    let files: Vec<File> = (0..8).map(File::from_index).collect();
+    if let Some(file) = files.iter().find(|&&file| open(file)) {
+        return vec![format!(
+            "Put a rook on the open {}-file.",
+            (b'a' + file.to_index() as u8) as char
+        )];
+    }
+    if let Some(file) = files.iter().find(|&&file| half_open(file)) {
+        return vec![format!(
+            "Activate your rooks: the half-open {}-file is a good place for one.",
+            (b'a' + file.to_index() as u8) as char
+        )];
+    }
+    Vec::new()
+}
+
+/// Pieces of `color` that are attacked and have no defender
+fn hanging_pieces(board: &Board, color: Color) -> Vec<String> {
+    let mut tips = Vec::new();
+    for square in *board.color_combined(color) {
+        if board.piece_on(square) == Some(Piece::King) {
+            continue;
+        }
+        if attackers(board, square, !color) != EMPTY && attackers(board, square, color) == EMPTY {
+            tips.push(format!(
+                "Your {} is attacked and undefended: save it first.",
+                describe(board, &[square])
+            ));
+        }
+    }
+    tips
+}
+
+/// Pawns of `color` with no enemy pawn ahead of them on their own or an
+/// adjacent file
+fn passed_pawns(board: &Board, color: Color) -> Vec<Square> {
+    let mine = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let theirs = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    mine.into_iter()
+        .filter(|pawn| {
+            !theirs.into_iter().any(|enemy| {
+                let files = enemy.get_file().to_index() as i32 - pawn.get_file().to_index() as i32;
+                files.abs() <= 1 && relative_rank(enemy, color) > relative_rank(*pawn, color)
+            })
+        })
+        .collect()
+}
+
+/// Square on `color`'s back rank
+fn home_square(color: Color, file: File) -> Square {
+    let rank = if color == Color::White {
+        Rank::First
+    } else {
+        Rank::Eighth
+    };
+    Square::make_square(rank, file)
+}
+
+/// Rank counted from `color`'s side, 0 for its back rank
+fn relative_rank(square: Square, color: Color) -> usize {
+    let rank = square.get_rank().to_index();
+    if color == Color::White {
+        rank
+    } else {
+        7 - rank
+    }
+}
+
+/// King steps from `square` to the nearest of the four center squares
+fn distance_to_center(square: Square) -> usize {
+    let file = square.get_file().to_index();
+    let rank = square.get_rank().to_index();
+    let off = |n: usize| if n < 3 { 3 - n } else { n.saturating_sub(4) };
+    off(file).max(off(rank))
+}
+
+/// Pieces with their squares, e.g. "Ng1, Bf1"
+fn describe(board: &Board, pieces: &[Square]) -> String {
+    pieces
+        .iter()
+        .map(|&square| match board.piece_on(square) {
+            Some(Piece::Pawn) => format!("pawn on {}", square),
+            Some(piece) => format!("{}{}", piece_char(piece), square),
+            None => square.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn squares(list: &[Square]) -> String {
+    list.iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
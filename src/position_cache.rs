@@ -0,0 +1,58 @@
+use crate::stockfish::SearchResult;
+use crate::ui::{Verbosity, verbosity};
+use chess::Board;
+use std::collections::HashMap;
+
+/// Positions remembered before the cache starts over
+const CAPACITY: usize = 50_000;
+
+/// Depth-limited analysis already done by one engine, keyed by the
+/// position's Zobrist hash, so reviewing a game again or stepping back and
+/// forth through a line doesn't send the engine positions it has already
+/// searched as deeply
+#[derive(Default)]
+pub struct PositionCache {
+    /// The depth that was asked for, and what the search found
+    entries: HashMap<u64, (u32, SearchResult)>,
+}
+
+impl PositionCache {
+    /// A search of `position` at least `depth` plies deep, if one was made
+    pub fn get(&self, position: &Board, depth: u32) -> Option<SearchResult> {
+        let result = self
+            .entries
+            .get(&position.get_hash())
+            .filter(|(searched, _)| *searched >= depth)
+            .map(|(_, result)| result.clone());
+        if result.is_some() && verbosity() == Verbosity::Verbose {
+            eprintln!(
+                "  (depth {} analysis of {} from the position cache)",
+                depth, position
+            );
+        }
+        result
+    }
+
+    /// Remember a search asked to go `depth` plies deep. The engine may
+    /// report a lower depth when it finds a mate early, so the requested one
+    /// is what later requests are compared with.
+    pub fn insert(&mut self, position: &Board, depth: u32, result: &SearchResult) {
+        let key = position.get_hash();
+        if self
+            .entries
+            .get(&key)
+            .is_some_and(|(searched, _)| *searched >= depth)
+        {
+            return;
+        }
+        if self.entries.len() >= CAPACITY {
+            self.entries.clear();
+        }
+        self.entries.insert(key, (depth, result.clone()));
+    }
+
+    /// Forget everything, after a change that alters the engine's evaluations
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,460 @@
+use crate::exchange;
+use crate::storage;
+use crate::ui::{display_cells, get_user_input, piece_set};
+use anyhow::{Result, anyhow};
+use chess::{
+    ALL_SQUARES, BitBoard, Color, EMPTY, Piece, Square, get_bishop_moves, get_king_moves,
+    get_knight_moves, get_rook_moves,
+};
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::str::FromStr;
+use std::time::Instant;
+
+const STATS_FILE: &str = "quiz_stats.json";
+
+/// Results of one kind of quiz over all sessions
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct QuizStats {
+    pub attempts: u32,
+    pub correct: u32,
+    /// Time spent answering, for the average per question
+    pub total_ms: u64,
+    /// Best session average of a session with every answer right
+    pub best_ms: Option<u64>,
+}
+
+pub type QuizRecords = BTreeMap<String, QuizStats>;
+
+/// What the square quiz asks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquareMode {
+    /// A square is highlighted on the board; type its name
+    Name,
+    /// A square name is flashed; say whether it is light or dark
+    Color,
+}
+
+impl SquareMode {
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.to_lowercase().as_str() {
+            "name" => Ok(SquareMode::Name),
+            "color" | "colour" => Ok(SquareMode::Color),
+            other => Err(anyhow!("Unknown quiz mode '{}', use name or color", other)),
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            SquareMode::Name => "squares-name",
+            SquareMode::Color => "squares-color",
+        }
+    }
+}
+
+/// One session's tally
+#[derive(Default)]
+struct Session {
+    asked: u32,
+    correct: u32,
+    total_ms: u64,
+}
+
+/// Square quiz for board vision: `count` questions from White's or Black's
+/// side, timed, with the session saved to the quiz statistics
+pub fn run_squares(mode: SquareMode, count: u32, perspective: Color) -> Result<()> {
+    println!("\n=== Square Quiz ===");
+    match mode {
+        SquareMode::Name => println!("Name the highlighted square, e.g. 'e4'."),
+        SquareMode::Color => println!("Is the square light or dark? Answer 'l' or 'd'."),
+    }
+    println!("{} questions, 'q' to stop.", count);
+
+    let mut rng = rand::rng();
+    let mut session = Session::default();
+    for number in 1..=count {
+        let target = *ALL_SQUARES.choose(&mut rng).expect("64 squares");
+        let light = is_light(target);
+        println!("\nQuestion {}/{}", number, count);
+        match mode {
+            SquareMode::Name => {
+                display_cells(perspective, |square| {
+                    if square == target {
+                        "\x1b[48;5;78m   \x1b[0m".to_string()
+                    } else {
+                        "   ".to_string()
+                    }
+                });
+                println!("Which square is highlighted?");
+            }
+            SquareMode::Color => println!("{}: light or dark?", target),
+        }
+
+        let asked = Instant::now();
+        let input = get_user_input()?.trim().to_lowercase();
+        let elapsed = asked.elapsed().as_millis() as u64;
+        if input == "q" || input == "quit" {
+            break;
+        }
+        let right = match mode {
+            SquareMode::Name => Square::from_str(&input).ok() == Some(target),
+            SquareMode::Color => match input.as_str() {
+                "l" | "light" | "w" | "white" => light,
+                "d" | "dark" | "b" | "black" => !light,
+                _ => false,
+            },
+        };
+
+        session.asked += 1;
+        session.total_ms += elapsed;
+        let description = format!(
+            "{}, a {} square {}",
+            target,
+            if light { "light" } else { "dark" },
+            region(target)
+        );
+        if right {
+            session.correct += 1;
+            println!("Correct ({:.1}s): {}", elapsed as f64 / 1000.0, description);
+        } else {
+            println!("No, that was {}", description);
+        }
+    }
+
+    record(mode.key(), &session)
+}
+
+/// Knight-path drill: find the shortest knight route between two squares,
+/// typed as the squares it lands on. With no squares given, `count` random
+/// pairs are asked.
+pub fn run_knight(route: Option<(Square, Square)>, count: u32) -> Result<()> {
+    println!("\n=== Knight Paths ===");
+    println!("Type the squares the knight lands on, e.g. 'c2 e3 g4', 'q' to stop.");
+
+    let mut rng = rand::rng();
+    let count = if route.is_some() { 1 } else { count };
+    let mut session = Session::default();
+    for number in 1..=count {
+        let (from, to) = route.unwrap_or_else(|| {
+            loop {
+                let from = *ALL_SQUARES.choose(&mut rng).expect("64 squares");
+                let to = *ALL_SQUARES.choose(&mut rng).expect("64 squares");
+                if from != to {
+                    break (from, to);
+                }
+            }
+        });
+        let shortest = knight_path(from, to);
+        let knight = piece_set().glyph(Piece::Knight, Color::White);
+        println!("\nQuestion {}/{}", number, count);
+        display_cells(Color::White, |square| {
+            if square == from {
+                format!(" {} ", knight)
+            } else if square == to {
+                "\x1b[48;5;78m   \x1b[0m".to_string()
+            } else {
+                "   ".to_string()
+            }
+        });
+        println!("Knight from {} to {}: the shortest path?", from, to);
+
+        let asked = Instant::now();
+        let input = get_user_input()?.trim().to_lowercase();
+        let elapsed = asked.elapsed().as_millis() as u64;
+        if input == "q" || input == "quit" {
+            break;
+        }
+        session.asked += 1;
+        session.total_ms += elapsed;
+        let answer = format_path(&shortest);
+        match check_knight_path(from, to, &input) {
+            Ok(hops) if hops == shortest.len() => {
+                session.correct += 1;
+                println!(
+                    "Correct ({:.1}s): {} move{}",
+                    elapsed as f64 / 1000.0,
+                    hops,
+                    if hops == 1 { "" } else { "s" }
+                );
+            }
+            Ok(hops) => println!(
+                "That gets there in {} moves, but {} is enough: {}",
+                hops,
+                shortest.len(),
+                answer
+            ),
+            Err(e) => println!("{}. One shortest path: {}", e, answer),
+        }
+    }
+
+    record("knight-path", &session)
+}
+
+/// Check a typed knight route; the number of moves when it is a valid one
+fn check_knight_path(from: Square, to: Square, input: &str) -> Result<usize> {
+    let mut at = from;
+    let mut hops = 0;
+    for text in input.split(|c: char| c.is_whitespace() || c == ',' || c == '-') {
+        if text.is_empty() {
+            continue;
+        }
+        let square = Square::from_str(text).map_err(|_| anyhow!("'{}' is not a square", text))?;
+        // Typing the start square first is fine
+        if hops == 0 && square == from {
+            continue;
+        }
+        if get_knight_moves(at) & BitBoard::from_square(square) == EMPTY {
+            return Err(anyhow!("A knight can't jump from {} to {}", at, square));
+        }
+        at = square;
+        hops += 1;
+    }
+    if at != to {
+        return Err(anyhow!("That path ends on {}, not {}", at, to));
+    }
+    Ok(hops)
+}
+
+/// A shortest knight route by breadth-first search, as the squares landed
+/// on after `from`
+pub fn knight_path(from: Square, to: Square) -> Vec<Square> {
+    let mut came_from: [Option<Square>; 64] = [None; 64];
+    let mut queue = VecDeque::from([from]);
+    came_from[from.to_index()] = Some(from);
+    while let Some(square) = queue.pop_front() {
+        if square == to {
+            break;
+        }
+        for next in get_knight_moves(square) {
+            if came_from[next.to_index()].is_none() {
+                came_from[next.to_index()] = Some(square);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut at = to;
+    while at != from {
+        path.push(at);
+        at = came_from[at.to_index()].expect("every square is reachable by a knight");
+    }
+    path.reverse();
+    path
+}
+
+fn format_path(path: &[Square]) -> String {
+    path.iter()
+        .map(|square| square.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Piece-movement drill: a piece stands alone on the board and every
+/// square it can move to must be named. `piece` picks one kind; by default
+/// knights, bishops and kings take turns, the pieces beginners mix up.
+pub fn run_moves(piece: Option<Piece>, count: u32) -> Result<()> {
+    println!("\n=== Piece Movement ===");
+    println!("Type every square the piece can move to, in any order, 'q' to stop.");
+
+    let mut rng = rand::rng();
+    let mut session = Session::default();
+    for number in 1..=count {
+        let piece = piece.unwrap_or_else(|| {
+            *[Piece::Knight, Piece::Bishop, Piece::King]
+                .choose(&mut rng)
+                .expect("three pieces")
+        });
+        // Pawns never stand on the first or last rank
+        let squares: Vec<Square> = ALL_SQUARES
+            .iter()
+            .copied()
+            .filter(|square| {
+                piece != Piece::Pawn || (1..=6).contains(&square.get_rank().to_index())
+            })
+            .collect();
+        let from = *squares.choose(&mut rng).expect("squares to stand on");
+        let targets: BTreeSet<Square> = reachable(piece, from).into_iter().collect();
+        let glyph = piece_set().glyph(piece, Color::White);
+
+        println!("\nQuestion {}/{}", number, count);
+        display_cells(Color::White, |square| {
+            if square == from {
+                format!(" {} ", glyph)
+            } else {
+                "   ".to_string()
+            }
+        });
+        println!(
+            "Where can the {} on {} move? ({} squares)",
+            exchange::name(piece),
+            from,
+            targets.len()
+        );
+
+        let asked = Instant::now();
+        let input = get_user_input()?.trim().to_lowercase();
+        let elapsed = asked.elapsed().as_millis() as u64;
+        if input == "q" || input == "quit" {
+            break;
+        }
+        session.asked += 1;
+        session.total_ms += elapsed;
+        let answered: BTreeSet<Square> = input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter_map(|text| Square::from_str(text).ok())
+            .collect();
+        let missed: Vec<String> = targets
+            .difference(&answered)
+            .map(|square| square.to_string())
+            .collect();
+        let wrong: Vec<String> = answered
+            .difference(&targets)
+            .map(|square| square.to_string())
+            .collect();
+        if missed.is_empty() && wrong.is_empty() {
+            session.correct += 1;
+            println!("Correct ({:.1}s)", elapsed as f64 / 1000.0);
+            continue;
+        }
+        if !missed.is_empty() {
+            println!("Missed: {}", missed.join(" "));
+        }
+        if !wrong.is_empty() {
+            println!("Can't go to: {}", wrong.join(" "));
+        }
+        display_cells(Color::White, |square| {
+            if square == from {
+                format!(" {} ", glyph)
+            } else if targets.contains(&square) {
+                "\x1b[48;5;78m   \x1b[0m".to_string()
+            } else {
+                "   ".to_string()
+            }
+        });
+    }
+
+    let key = match piece {
+        Some(piece) => format!("moves-{}", exchange::name(piece)),
+        None => "moves".to_string(),
+    };
+    record(&key, &session)
+}
+
+/// Squares a piece reaches from `from` on an otherwise empty board
+fn reachable(piece: Piece, from: Square) -> BitBoard {
+    match piece {
+        Piece::Knight => get_knight_moves(from),
+        Piece::Bishop => get_bishop_moves(from, EMPTY),
+        Piece::Rook => get_rook_moves(from, EMPTY),
+        Piece::Queen => get_bishop_moves(from, EMPTY) | get_rook_moves(from, EMPTY),
+        Piece::King => get_king_moves(from),
+        // A lone pawn moves one square up, or two from its starting rank
+        Piece::Pawn => {
+            let mut targets = EMPTY;
+            if let Some(one) = from.up() {
+                targets |= BitBoard::from_square(one);
+                if from.get_rank() == chess::Rank::Second
+                    && let Some(two) = one.up()
+                {
+                    targets |= BitBoard::from_square(two);
+                }
+            }
+            targets
+        }
+    }
+}
+
+pub fn parse_square(text: &str) -> Result<Square> {
+    Square::from_str(&text.to_lowercase()).map_err(|_| anyhow!("'{}' is not a square", text))
+}
+
+/// A piece named in full or by its letter
+pub fn parse_piece(text: &str) -> Result<Piece> {
+    match text.to_lowercase().as_str() {
+        "n" | "knight" => Ok(Piece::Knight),
+        "b" | "bishop" => Ok(Piece::Bishop),
+        "r" | "rook" => Ok(Piece::Rook),
+        "q" | "queen" => Ok(Piece::Queen),
+        "k" | "king" => Ok(Piece::King),
+        "p" | "pawn" => Ok(Piece::Pawn),
+        other => Err(anyhow!("Unknown piece '{}'", other)),
+    }
+}
+
+/// Where a square lies, in the words used to talk about plans: the center,
+/// a wing, and whose half of the board
+pub fn region(square: Square) -> String {
+    let file = square.get_file().to_index();
+    let rank = square.get_rank().to_index();
+    let area = if (2..=5).contains(&file) && (2..=5).contains(&rank) {
+        if (3..=4).contains(&file) && (3..=4).contains(&rank) {
+            "in the center"
+        } else {
+            "in the extended center"
+        }
+    } else if file >= 4 {
+        "on the kingside"
+    } else {
+        "on the queenside"
+    };
+    let half = if rank < 4 { "White's" } else { "Black's" };
+    format!("{}, in {} half", area, half)
+}
+
+pub fn is_light(square: Square) -> bool {
+    !(square.get_file().to_index() + square.get_rank().to_index()).is_multiple_of(2)
+}
+
+/// Add a finished session to the statistics and print how it went
+fn record(key: &str, session: &Session) -> Result<()> {
+    if session.asked == 0 {
+        return Ok(());
+    }
+    let average = session.total_ms / session.asked as u64;
+    println!(
+        "\nSession: {}/{} correct, {:.1}s per answer",
+        session.correct,
+        session.asked,
+        average as f64 / 1000.0
+    );
+
+    let mut records = load_stats()?;
+    let stats = records.entry(key.to_string()).or_default();
+    stats.attempts += session.asked;
+    stats.correct += session.correct;
+    stats.total_ms += session.total_ms;
+    if session.correct == session.asked && stats.best_ms.is_none_or(|best| average < best) {
+        stats.best_ms = Some(average);
+        println!("New best time for a perfect session!");
+    }
+    storage::save(STATS_FILE, &records)
+}
+
+pub fn load_stats() -> Result<QuizRecords> {
+    storage::load(STATS_FILE)
+}
+
+pub fn print_stats(records: &QuizRecords) {
+    println!("\n=== Quiz Results ===");
+    if records.is_empty() {
+        println!("No quizzes taken yet.");
+    }
+    for (quiz, stats) in records {
+        let rate = if stats.attempts > 0 {
+            stats.correct as f64 * 100.0 / stats.attempts as f64
+        } else {
+            0.0
+        };
+        let average = stats.total_ms as f64 / stats.attempts.max(1) as f64 / 1000.0;
+        let best = stats.best_ms.map_or(String::new(), |ms| {
+            format!(", best {:.1}s", ms as f64 / 1000.0)
+        });
+        println!(
+            "{:<14} {:>4}/{:<4} correct ({:.0}%), {:.1}s per answer{}",
+            quiz, stats.correct, stats.attempts, rate, average, best
+        );
+    }
+    println!("====================\n");
+}
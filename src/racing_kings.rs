@@ -0,0 +1,154 @@
+use crate::stockfish::StockfishEngine;
+use crate::ui::display_board_for_player;
+use crate::variant::{Outcome, Variant};
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, Color, EMPTY, MoveGen, Rank};
+use std::str::FromStr;
+
+const START_FEN: &str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1";
+
+/// Racing Kings: no pawns, no checks, and the first king to reach the eighth
+/// rank wins. The position is a normal `chess` board; the variant only
+/// removes the moves that would give check.
+pub struct RacingKings {
+    /// None when both sides are played at the keyboard
+    engine: Option<StockfishEngine>,
+}
+
+impl RacingKings {
+    /// Start the opponent engine, which must know the variant through the
+    /// `UCI_Variant` option (Fairy-Stockfish and multi-variant Stockfish do)
+    pub async fn with_engine(stockfish_path: &str, difficulty: u8) -> Result<Self> {
+        let mut engine = StockfishEngine::new(stockfish_path).await?;
+        if !engine.supports_option("UCI_Variant") {
+            return Err(anyhow!(
+                "Engine '{}' doesn't play Racing Kings. Use a variant engine such as \
+                 Fairy-Stockfish with --stockfish-path, or play with --two-player.",
+                engine.name()
+            ));
+        }
+        engine.set_option("UCI_Variant", "racingkings").await?;
+        engine.set_difficulty(difficulty).await?;
+        Ok(RacingKings {
+            engine: Some(engine),
+        })
+    }
+
+    pub fn two_player() -> Self {
+        RacingKings { engine: None }
+    }
+}
+
+/// Whether `color`'s king stands on the eighth rank
+fn home(board: &Board, color: Color) -> bool {
+    board.king_square(color).get_rank() == Rank::Eighth
+}
+
+impl Variant for RacingKings {
+    type Position = Board;
+    type Move = ChessMove;
+
+    fn name(&self) -> &'static str {
+        "Racing Kings"
+    }
+
+    fn rules(&self) -> &'static str {
+        "Race your king to the eighth rank. Giving check is not allowed, so no king is ever \
+         in check. If White gets there first, Black still has one move to reach it \
+         too and draw."
+    }
+
+    fn input_help(&self) -> &'static str {
+        "coordinates, e.g. 'h2h3'"
+    }
+
+    fn start(&self) -> Board {
+        Board::from_str(START_FEN).expect("valid Racing Kings start position")
+    }
+
+    fn side_to_move(&self, position: &Board) -> Color {
+        position.side_to_move()
+    }
+
+    /// The standard legal moves minus those that give check
+    fn legal_moves(&self, position: &Board) -> Vec<ChessMove> {
+        MoveGen::new_legal(position)
+            .filter(|&chess_move| *position.make_move_new(chess_move).checkers() == EMPTY)
+            .collect()
+    }
+
+    fn parse_move(&self, position: &Board, text: &str) -> Result<ChessMove> {
+        let chess_move = ChessMove::from_str(&text.to_lowercase())
+            .map_err(|_| anyhow!("'{}' is not a move like h2h3", text))?;
+        if !position.legal(chess_move) {
+            return Err(anyhow!("{} is not a legal move here", chess_move));
+        }
+        if *position.make_move_new(chess_move).checkers() != EMPTY {
+            return Err(anyhow!(
+                "{} gives check, which Racing Kings forbids",
+                chess_move
+            ));
+        }
+        Ok(chess_move)
+    }
+
+    fn play(&self, position: &Board, chess_move: ChessMove) -> Board {
+        position.make_move_new(chess_move)
+    }
+
+    fn outcome(&self, position: &Board) -> Option<Outcome> {
+        let (white, black) = (home(position, Color::White), home(position, Color::Black));
+        if white && black {
+            return Some(Outcome::Draw(
+                "both kings reached the eighth rank".to_string(),
+            ));
+        }
+        if black {
+            return Some(Outcome::Win(
+                Color::Black,
+                "the black king reached the eighth rank".to_string(),
+            ));
+        }
+        if white {
+            // Black moves after White, so still gets one try to draw
+            let catches_up = position.side_to_move() == Color::Black
+                && self
+                    .legal_moves(position)
+                    .into_iter()
+                    .any(|chess_move| home(&position.make_move_new(chess_move), Color::Black));
+            if !catches_up {
+                return Some(Outcome::Win(
+                    Color::White,
+                    "the white king reached the eighth rank".to_string(),
+                ));
+            }
+        }
+        if self.legal_moves(position).is_empty() {
+            return Some(Outcome::Draw("no legal moves".to_string()));
+        }
+        None
+    }
+
+    fn move_text(&self, _position: &Board, chess_move: ChessMove) -> String {
+        chess_move.to_string()
+    }
+
+    fn display(&self, position: &Board, perspective: Color) {
+        display_board_for_player(position, perspective);
+    }
+
+    async fn computer_move(&mut self, position: &Board) -> Result<ChessMove> {
+        let engine = self
+            .engine
+            .as_mut()
+            .ok_or_else(|| anyhow!("No engine to play Racing Kings"))?;
+        let chess_move = engine.get_best_move(position).await?;
+        if !self.legal_moves(position).contains(&chess_move) {
+            return Err(anyhow!(
+                "The engine played {}, which Racing Kings forbids; is UCI_Variant working?",
+                chess_move
+            ));
+        }
+        Ok(chess_move)
+    }
+}
@@ -0,0 +1,265 @@
+use crate::chess_game::{ChessGame, GameOptions, GameOutcome};
+use crate::stats;
+use crate::stockfish::StockfishEngine;
+use crate::storage;
+use anyhow::Result;
+use chess::{Board, Color};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+const RATING_FILE: &str = "rating.json";
+/// Converts between the Glicko and Glicko-2 scales
+const GLICKO2_SCALE: f64 = 173.7178;
+/// System constant limiting how fast the volatility changes
+const TAU: f64 = 0.5;
+/// Rating deviation assumed for an Elo-limited engine, whose strength is well known
+const ENGINE_RD: f64 = 50.0;
+/// Rows and columns of the rating history graph
+const GRAPH_HEIGHT: usize = 8;
+const GRAPH_WIDTH: usize = 50;
+
+/// The profile's Glicko-2 rating against Elo-limited engines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub history: Vec<RatedGame>,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            rating: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+            history: Vec::new(),
+        }
+    }
+}
+
+/// One rated game and the rating it left the player with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatedGame {
+    pub date: String,
+    pub opponent_elo: u32,
+    /// 1 for a win, 0.5 for a draw, 0 for a loss
+    pub score: f64,
+    pub rating: f64,
+    pub deviation: f64,
+}
+
+pub fn load() -> Result<Rating> {
+    storage::load(RATING_FILE)
+}
+
+/// Play one rated game against the engine limited to `elo`, then update the
+/// player's rating. Colors alternate from game to game.
+pub async fn run(stockfish_path: &str, elo: u32, options: GameOptions) -> Result<()> {
+    let mut rating = load()?;
+    let color = if rating.history.len().is_multiple_of(2) {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    engine.set_elo(elo).await?;
+    println!(
+        "\nRated game: you ({:.0} ±{:.0}) against {} limited to {} Elo",
+        rating.rating,
+        rating.deviation,
+        engine.name(),
+        elo
+    );
+
+    let mut game = ChessGame::from_position(engine, color, Board::default(), options);
+    game.set_competitive();
+    let outcome = game.run().await?;
+    // Strength comes from UCI_Elo rather than a skill level
+    stats::record_game(&mut game, "rated", 20).await?;
+
+    let score = match outcome {
+        GameOutcome::Win(winner) | GameOutcome::Timeout(winner) if winner == color => 1.0,
+        GameOutcome::Win(_) | GameOutcome::Timeout(_) => 0.0,
+        GameOutcome::Draw => 0.5,
+        // Leaving a lost position must not save the rating
+        GameOutcome::Unfinished => {
+            println!("You left the game before it was over; it counts as a loss.");
+            0.0
+        }
+    };
+
+    let before = rating.rating;
+    rating.update(&[(elo as f64, ENGINE_RD, score)]);
+    rating.history.push(RatedGame {
+        date: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        opponent_elo: elo,
+        score,
+        rating: rating.rating,
+        deviation: rating.deviation,
+    });
+    storage::save(RATING_FILE, &rating)?;
+
+    println!(
+        "Rating: {:.0} -> {:.0} ({:+.0}), deviation ±{:.0}",
+        before,
+        rating.rating,
+        rating.rating - before,
+        rating.deviation
+    );
+    Ok(())
+}
+
+impl Rating {
+    /// Glicko-2 update for a rating period with the given games, each as
+    /// the opponent's rating and deviation and the player's score. Rated
+    /// play makes every game its own period.
+    fn update(&mut self, games: &[(f64, f64, f64)]) {
+        let mu = (self.rating - 1500.0) / GLICKO2_SCALE;
+        let phi = self.deviation / GLICKO2_SCALE;
+
+        let mut information = 0.0;
+        let mut improvement = 0.0;
+        for &(opponent_rating, opponent_deviation, score) in games {
+            let mu_j = (opponent_rating - 1500.0) / GLICKO2_SCALE;
+            let phi_j = opponent_deviation / GLICKO2_SCALE;
+            let g = 1.0 / (1.0 + 3.0 * phi_j * phi_j / (PI * PI)).sqrt();
+            let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+            information += g * g * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let variance = 1.0 / information;
+        let delta = variance * improvement;
+
+        let volatility = new_volatility(phi, variance, delta, self.volatility);
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let new_phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi * new_phi * improvement;
+
+        self.rating = new_mu * GLICKO2_SCALE + 1500.0;
+        self.deviation = new_phi * GLICKO2_SCALE;
+        self.volatility = volatility;
+    }
+}
+
+/// Solve for the new volatility with the Illinois algorithm (step 5 of Glickman's paper)
+fn new_volatility(phi: f64, variance: f64, delta: f64, sigma: f64) -> f64 {
+    const EPSILON: f64 = 0.000001;
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        let denominator = phi * phi + variance + ex;
+        ex * (delta * delta - phi * phi - variance - ex) / (2.0 * denominator * denominator)
+            - (x - a) / (TAU * TAU)
+    };
+
+    let mut low = a;
+    let mut high = if delta * delta > phi * phi + variance {
+        (delta * delta - phi * phi - variance).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * TAU) < 0.0 {
+            k += 1.0;
+        }
+        a - k * TAU
+    };
+    let mut f_low = f(low);
+    let mut f_high = f(high);
+    while (high - low).abs() > EPSILON {
+        let c = low + (low - high) * f_low / (f_high - f_low);
+        let f_c = f(c);
+        if f_c * f_high <= 0.0 {
+            low = high;
+            f_low = f_high;
+        } else {
+            f_low /= 2.0;
+        }
+        high = c;
+        f_high = f_c;
+    }
+    (low / 2.0).exp()
+}
+
+/// Current rating and a text graph of the most recent rated games
+pub fn print_history(rating: &Rating) {
+    if rating.history.is_empty() {
+        return;
+    }
+
+    println!("\n=== Rating ===");
+    println!(
+        "Glicko-2: {:.0} ±{:.0} after {} rated game(s)",
+        rating.rating,
+        rating.deviation,
+        rating.history.len()
+    );
+
+    let start = rating.history.len().saturating_sub(GRAPH_WIDTH);
+    let points: Vec<f64> = rating.history[start..].iter().map(|g| g.rating).collect();
+    let min = points.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = points.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = (max - min).max(1.0);
+    let row_of = |value: f64| ((value - min) / span * (GRAPH_HEIGHT - 1) as f64).round() as usize;
+
+    println!();
+    for row in (0..GRAPH_HEIGHT).rev() {
+        let label = min + span * row as f64 / (GRAPH_HEIGHT - 1) as f64;
+        let line: String = points
+            .iter()
+            .map(|&value| if row_of(value) == row { '*' } else { ' ' })
+            .collect();
+        println!("{:>6.0} |{}", label, line);
+    }
+    println!("       +{}", "-".repeat(points.len()));
+    if start > 0 {
+        println!("        (last {} games)", points.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The worked example in Glickman's "Example of the Glicko-2 system"
+    #[test]
+    fn glickman_example() {
+        let mut rating = Rating {
+            rating: 1500.0,
+            deviation: 200.0,
+            volatility: 0.06,
+            history: Vec::new(),
+        };
+        rating.update(&[
+            (1400.0, 30.0, 1.0),
+            (1550.0, 100.0, 0.0),
+            (1700.0, 300.0, 0.0),
+        ]);
+        assert!((rating.rating - 1464.06).abs() < 0.01, "{}", rating.rating);
+        assert!(
+            (rating.deviation - 151.52).abs() < 0.01,
+            "{}",
+            rating.deviation
+        );
+        assert!(
+            (rating.volatility - 0.05999).abs() < 0.00001,
+            "{}",
+            rating.volatility
+        );
+    }
+
+    #[test]
+    fn single_game_moves_toward_result() {
+        let mut won = Rating::default();
+        won.update(&[(1500.0, ENGINE_RD, 1.0)]);
+        let mut lost = Rating::default();
+        lost.update(&[(1500.0, ENGINE_RD, 0.0)]);
+        let mut drawn = Rating::default();
+        drawn.update(&[(1500.0, ENGINE_RD, 0.5)]);
+
+        assert!(won.rating > 1500.0 && lost.rating < 1500.0);
+        assert!((won.rating - 1500.0 - (1500.0 - lost.rating)).abs() < 1e-6);
+        assert!((drawn.rating - 1500.0).abs() < 1e-6);
+        assert!(won.deviation < 350.0);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::movelist;
+use crate::pgn;
+use crate::stats::{self, GameRecord};
+use crate::stockfish::StockfishEngine;
+use crate::storage;
+use crate::ui;
+use anyhow::Result;
+use chess::{Board, ChessMove, Color};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// Positions listed at most
+const POSITIONS_SHOWN: usize = 10;
+/// Different moves listed for each position
+const CHOICES_SHOWN: usize = 3;
+
+pub struct RecurringOptions {
+    /// Games a position must come up in to be listed
+    pub min_games: usize,
+    pub depth: u8,
+}
+
+/// A position the player had to move in, in several games
+struct Recurring {
+    board: Board,
+    /// Moves leading to it in the first game that reached it
+    path: Vec<ChessMove>,
+    /// Index of each game that reached it, and the move played there
+    games: Vec<(usize, Option<ChessMove>)>,
+}
+
+/// Find the positions the player keeps reaching with the move, by their
+/// Zobrist key so transpositions count together, and show the score from
+/// each, the moves tried there and the engine's choice
+pub async fn run(
+    stockfish_path: &str,
+    records: &[GameRecord],
+    options: RecurringOptions,
+) -> Result<()> {
+    let found = find(records, options.min_games);
+    println!(
+        "\n=== Recurring positions ({}) ===",
+        storage::profile_name()
+    );
+    if found.is_empty() {
+        println!(
+            "No position with you to move came up in {} or more finished games.",
+            options.min_games
+        );
+        return Ok(());
+    }
+
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    engine.prepare_for_analysis().await?;
+    for (rank, position) in found.iter().take(POSITIONS_SHOWN).enumerate() {
+        let games: Vec<&GameRecord> = position.games.iter().map(|&(i, _)| &records[i]).collect();
+        let player = ui::color_name(position.board.side_to_move());
+        println!(
+            "\n{}. After {} ({} to move)",
+            rank + 1,
+            movelist::numbered(&Board::default(), &position.path),
+            player
+        );
+        println!(
+            "   {} game(s): {}, score {:.0}%",
+            games.len(),
+            stats::tally(&games),
+            stats::score(&games) * 100.0
+        );
+
+        let mut choices: Vec<(ChessMove, Vec<&GameRecord>)> = Vec::new();
+        for &(i, chess_move) in &position.games {
+            let Some(chess_move) = chess_move else {
+                continue;
+            };
+            match choices.iter_mut().find(|(m, _)| *m == chess_move) {
+                Some((_, games)) => games.push(&records[i]),
+                None => choices.push((chess_move, vec![&records[i]])),
+            }
+        }
+        choices.sort_by_key(|(_, games)| Reverse(games.len()));
+        if !choices.is_empty() {
+            let shown: Vec<String> = choices
+                .iter()
+                .take(CHOICES_SHOWN)
+                .map(|(chess_move, games)| {
+                    format!(
+                        "{} ({}, {:.0}%)",
+                        pgn::move_to_san(&position.board, *chess_move),
+                        games.len(),
+                        stats::score(games) * 100.0
+                    )
+                })
+                .collect();
+            println!("   You played: {}", shown.join(", "));
+        }
+
+        let result = engine
+            .analyze(&position.board, &format!("go depth {}", options.depth))
+            .await?;
+        let Some(best) = result.best_move else {
+            continue;
+        };
+        let usual = choices.first().map(|(chess_move, _)| *chess_move);
+        println!(
+            "   Engine: {} {}{}",
+            pgn::line_to_san(&position.board, &result.pv),
+            result
+                .score
+                .map(|s| format!("({})", s.for_white(position.board.side_to_move())))
+                .unwrap_or_default(),
+            if usual.is_some_and(|usual| usual != best) {
+                " — not your usual move"
+            } else {
+                ""
+            }
+        );
+    }
+    println!();
+    Ok(())
+}
+
+/// Positions with the player to move that came up in at least `min_games`
+/// finished games, most frequent first. Of the positions a set of games
+/// shares, only the deepest is kept, where the games still agreed last.
+fn find(records: &[GameRecord], min_games: usize) -> Vec<Recurring> {
+    let mut positions: HashMap<u64, Recurring> = HashMap::new();
+    for (index, record) in records.iter().enumerate() {
+        if record.result == "unfinished" {
+            continue;
+        }
+        let color = if record.player_color == "white" {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let moves = record.known_moves();
+        let mut board = Board::default();
+        // A position repeated within one game counts once
+        let mut seen = HashSet::new();
+        for ply in 0..=moves.len() {
+            if ply > 0 && board.side_to_move() == color && seen.insert(board.get_hash()) {
+                positions
+                    .entry(board.get_hash())
+                    .or_insert_with(|| Recurring {
+                        board,
+                        path: moves[..ply].to_vec(),
+                        games: Vec::new(),
+                    })
+                    .games
+                    .push((index, moves.get(ply).copied()));
+            }
+            if let Some(&chess_move) = moves.get(ply) {
+                board = board.make_move_new(chess_move);
+            }
+        }
+    }
+
+    let mut deepest: HashMap<Vec<usize>, Recurring> = HashMap::new();
+    for position in positions
+        .into_values()
+        .filter(|p| p.games.len() >= min_games)
+    {
+        let games: Vec<usize> = position.games.iter().map(|&(i, _)| i).collect();
+        if deepest
+            .get(&games)
+            .is_none_or(|kept| kept.path.len() < position.path.len())
+        {
+            deepest.insert(games, position);
+        }
+    }
+    let mut found: Vec<Recurring> = deepest.into_values().collect();
+    found.sort_by_key(|p| (Reverse(p.games.len()), Reverse(p.path.len())));
+    found
+}
@@ -0,0 +1,358 @@
+use crate::fen;
+use crate::pgn::{self, PgnGame};
+use crate::ui::{self, display_board_for_player};
+use anyhow::{Result, anyhow};
+use chess::{Board, Color};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Pause between moves when autoplay starts, unless changed with `speed`
+const DEFAULT_SPEED_MS: u64 = 1500;
+/// Fastest autoplay pace accepted by `speed`
+const MIN_SPEED_MS: u64 = 100;
+/// Pause before a move whose thinking time the clocks don't tell, in
+/// real-time replay
+const UNKNOWN_THINK_TIME: Duration = Duration::from_secs(2);
+
+/// Step through a game from a PGN file, by hand or automatically
+pub async fn run(pgn_path: &str, game_number: usize) -> Result<()> {
+    let games = pgn::load_pgn_file(pgn_path)?;
+    let game = games.get(game_number.saturating_sub(1)).ok_or_else(|| {
+        anyhow!(
+            "PGN file contains {} game(s), cannot open game {}",
+            games.len(),
+            game_number
+        )
+    })?;
+
+    let mut replay = Replay::new(game);
+    print_header(game);
+    replay.show();
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut playing = false;
+    let mut speed = Duration::from_millis(DEFAULT_SPEED_MS);
+    // Speed-up factor while replaying at the pace the game was played
+    let mut real_time: Option<f64> = None;
+
+    loop {
+        let delay = match real_time {
+            Some(factor) => replay
+                .think_time(replay.ply + 1)
+                .unwrap_or(UNKNOWN_THINK_TIME)
+                .div_f64(factor),
+            None => speed,
+        };
+        let input = tokio::select! {
+            input = ui::next_input(&mut stdin) => input?,
+            _ = tokio::time::sleep(delay), if playing => {
+                replay.goto(replay.ply + 1);
+                replay.show();
+                if replay.at_end() {
+                    playing = false;
+                    println!("End of game.");
+                }
+                continue;
+            }
+        };
+        let Some(input) = input else {
+            return Ok(());
+        };
+
+        let typed = input.trim().to_string();
+        let input = typed.to_lowercase();
+        let (command, arg) = input.split_once(' ').unwrap_or((input.as_str(), ""));
+        match command {
+            "q" | "quit" => return Ok(()),
+            "" | "n" | "next" => replay.goto(replay.ply + 1),
+            "p" | "prev" => replay.goto(replay.ply.saturating_sub(1)),
+            "first" => replay.goto(0),
+            "last" => replay.goto(replay.positions.len() - 1),
+            "goto" => match arg.trim().parse::<usize>() {
+                // Move numbers count full moves: "goto 12" shows White's 12th move
+                Ok(number) => replay.goto(replay.ply_of_move(number)),
+                Err(_) => {
+                    println!("Usage: goto <move number>");
+                    continue;
+                }
+            },
+            "flip" => replay.flip(),
+            "play" => {
+                if replay.at_end() {
+                    replay.goto(0);
+                }
+                playing = true;
+                real_time = None;
+                println!(
+                    "Playing, one move every {} ms. 'pause' to stop.",
+                    speed.as_millis()
+                );
+                continue;
+            }
+            "realtime" => {
+                if !replay.has_clocks() {
+                    println!("This game has no [%clk] times to replay it at its real pace.");
+                    continue;
+                }
+                let factor = match arg.trim() {
+                    "" => 1.0,
+                    factor => match factor.trim_end_matches('x').parse::<f64>() {
+                        Ok(factor) if factor.is_finite() && factor > 0.0 => factor,
+                        _ => {
+                            println!("Usage: realtime [speed-up, e.g. 4 for four times as fast]");
+                            continue;
+                        }
+                    },
+                };
+                if replay.at_end() {
+                    replay.goto(0);
+                }
+                playing = true;
+                real_time = Some(factor);
+                if factor == 1.0 {
+                    println!("Playing at the game's own pace. 'pause' to stop.");
+                } else {
+                    println!(
+                        "Playing {}x faster than the game was played. 'pause' to stop.",
+                        factor
+                    );
+                }
+                continue;
+            }
+            "pause" => {
+                playing = false;
+                println!("Paused.");
+                continue;
+            }
+            "speed" => {
+                match arg.trim().parse::<u64>() {
+                    Ok(ms) if ms >= MIN_SPEED_MS => {
+                        speed = Duration::from_millis(ms);
+                        println!("Speed set to one move every {} ms.", ms);
+                    }
+                    _ => println!("Usage: speed <milliseconds, at least {}>", MIN_SPEED_MS),
+                }
+                continue;
+            }
+            "epd" => {
+                // File names and comments keep the case they were typed in
+                let arg = typed.split_once(' ').map_or("", |(_, rest)| rest.trim());
+                match arg.split_once(' ').unwrap_or((arg, "")) {
+                    ("", _) => println!("Usage: epd <file> [comment]"),
+                    (path, comment) => match replay.save_epd(path, comment.trim()) {
+                        Ok(()) => println!("Position added to {}", path),
+                        Err(e) => println!("{}", e),
+                    },
+                }
+                continue;
+            }
+            "h" | "help" => {
+                print_help();
+                continue;
+            }
+            _ => {
+                println!("Unknown command '{}', 'h' for help.", input);
+                continue;
+            }
+        }
+        // Manual navigation takes over from autoplay
+        playing = false;
+        replay.show();
+    }
+}
+
+struct Replay<'a> {
+    game: &'a PgnGame,
+    positions: Vec<Board>,
+    ply: usize,
+    perspective: Color,
+}
+
+impl<'a> Replay<'a> {
+    fn new(game: &'a PgnGame) -> Self {
+        let mut positions = vec![game.start];
+        for chess_move in &game.moves {
+            let last = *positions.last().unwrap();
+            positions.push(last.make_move_new(*chess_move));
+        }
+        Replay {
+            game,
+            positions,
+            ply: 0,
+            perspective: Color::White,
+        }
+    }
+
+    /// Whether the game recorded clock times
+    fn has_clocks(&self) -> bool {
+        self.game.clocks.iter().any(Option::is_some)
+    }
+
+    /// Each side's clock after `ply` moves: the last time recorded for it,
+    /// or the starting time from the `TimeControl` header before its first
+    /// move
+    fn clocks_at(&self, ply: usize) -> [Option<Duration>; 2] {
+        let start = pgn::time_control(self.game).map(|(base, _)| base);
+        let mut clocks = [start, start];
+        for index in 0..ply {
+            if let Some(clock) = self.game.clocks[index] {
+                clocks[self.positions[index].side_to_move().to_index()] = Some(clock);
+            }
+        }
+        clocks
+    }
+
+    /// How long the player thought over the move leading to `ply`: their
+    /// clock before it, plus the increment, minus their clock after it
+    fn think_time(&self, ply: usize) -> Option<Duration> {
+        let index = ply.checked_sub(1)?;
+        let after = (*self.game.clocks.get(index)?)?;
+        let mover = self.positions[index].side_to_move();
+        let before = self.clocks_at(index)[mover.to_index()]?;
+        let increment = pgn::time_control(self.game)
+            .map(|(_, increment)| increment)
+            .unwrap_or_default();
+        Some((before + increment).saturating_sub(after))
+    }
+
+    fn goto(&mut self, ply: usize) {
+        self.ply = ply.min(self.positions.len() - 1);
+    }
+
+    fn at_end(&self) -> bool {
+        self.ply == self.positions.len() - 1
+    }
+
+    fn flip(&mut self) {
+        self.perspective = !self.perspective;
+    }
+
+    /// Ply of move `number`: White's move, or Black's if the game starts after White's
+    fn ply_of_move(&self, number: usize) -> usize {
+        let white_ply = (number.max(1) - 1) * 2;
+        if self.game.start.side_to_move() == Color::Black {
+            white_ply.max(1)
+        } else {
+            white_ply + 1
+        }
+    }
+
+    /// Append the position on the board to an EPD file, with the players
+    /// and the move that led to it
+    fn save_epd(&self, path: &str, comment: &str) -> Result<()> {
+        let board = &self.positions[self.ply];
+        let mut operations = vec![(
+            "id",
+            format!(
+                "{} - {}, ply {}",
+                self.game.header("White").unwrap_or("?"),
+                self.game.header("Black").unwrap_or("?"),
+                self.ply
+            ),
+        )];
+        if self.ply > 0 {
+            let before = &self.positions[self.ply - 1];
+            operations.push((
+                "c1",
+                format!(
+                    "after {}",
+                    pgn::move_to_san(before, self.game.moves[self.ply - 1])
+                ),
+            ));
+        }
+        if !comment.is_empty() {
+            operations.push(("c0", comment.to_string()));
+        }
+        fen::append_epd(path, &fen::to_epd(board, &operations))
+    }
+
+    fn show(&self) {
+        display_board_for_player(&self.positions[self.ply], self.perspective);
+        if self.ply == 0 {
+            println!("Start position ({} moves)", self.game.moves.len());
+            return;
+        }
+
+        let before = &self.positions[self.ply - 1];
+        let san = pgn::move_to_san(before, self.game.moves[self.ply - 1]);
+        let starts_with_black = self.game.start.side_to_move() == Color::Black;
+        let number = (self.ply - 1 + starts_with_black as usize) / 2 + 1;
+        let dots = if before.side_to_move() == Color::White {
+            "."
+        } else {
+            "..."
+        };
+        println!(
+            "{}{} {}   (ply {}/{})",
+            number,
+            dots,
+            san,
+            self.ply,
+            self.game.moves.len()
+        );
+        if self.has_clocks()
+            && let [Some(white), Some(black)] = self.clocks_at(self.ply)
+        {
+            let spent = self
+                .think_time(self.ply)
+                .map(|time| format!("   ({} on the move)", format_clock(time)))
+                .unwrap_or_default();
+            println!(
+                "Clock: White {} | Black {}{}",
+                format_clock(white),
+                format_clock(black),
+                spent
+            );
+        }
+        if self.at_end()
+            && let Some(result) = self.game.header("Result")
+        {
+            println!("Result: {}", result);
+        }
+    }
+}
+
+fn print_header(game: &PgnGame) {
+    println!(
+        "\n=== {} vs {} ===",
+        game.header("White").unwrap_or("?"),
+        game.header("Black").unwrap_or("?")
+    );
+    if let Some(event) = game.header("Event") {
+        println!("Event: {}", event);
+    }
+    println!("Press Enter for the next move, 'play' to animate, 'h' for help, 'q' to quit.");
+}
+
+/// "1:05:09", or "4:07" under an hour
+fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn print_help() {
+    println!("\n=== Replay Help ===");
+    println!("  • Enter, 'n' or 'next' - Next move");
+    println!("  • 'p' or 'prev' - Previous move");
+    println!("  • 'first' / 'last' - Jump to the start or the end");
+    println!("  • 'goto <n>' - Jump to move n");
+    println!("  • 'play' - Play through the game automatically");
+    println!("  • 'pause' - Stop automatic play");
+    println!("  • 'speed <ms>' - Time between moves during play");
+    println!(
+        "  • 'realtime [factor]' - Play at the pace the game was played, or factor times faster"
+    );
+    println!("  • 'flip' - Turn the board around");
+    println!("  • 'epd <file> [comment]' - Add the position to an EPD collection");
+    println!("  • 'q' or 'quit' - Leave the replay");
+    println!("===================\n");
+}
@@ -0,0 +1,236 @@
+use crate::drills::{self, DrillStats};
+use crate::motifs::{self, MotifCounts};
+use crate::review::ReviewItem;
+use crate::scoresheet::Format;
+use crate::stats::{GameRecord, average_accuracy};
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+use std::collections::BTreeMap;
+
+/// Openings listed in the report
+const OPENINGS_SHOWN: usize = 3;
+/// Drill patterns solved less often than this are called out
+const WEAK_PATTERN_RATE: f64 = 0.6;
+
+/// Summary of the last `days` days of play: games and results against the
+/// period before, accuracy, day-by-day results, openings, the mate patterns
+/// that keep being missed, the tactics behind the player's blunders, and time
+/// spent
+pub fn render(
+    records: &[GameRecord],
+    drill_stats: &DrillStats,
+    review: &[ReviewItem],
+    blunder_motifs: &MotifCounts,
+    days: i64,
+    format: Format,
+) -> String {
+    let now = Local::now().naive_local();
+    let since = now - Duration::days(days);
+    let before = since - Duration::days(days);
+    let period: Vec<&GameRecord> = records
+        .iter()
+        .filter(|r| played_at(r).is_some_and(|t| t >= since))
+        .collect();
+    let previous: Vec<&GameRecord> = records
+        .iter()
+        .filter(|r| played_at(r).is_some_and(|t| t >= before && t < since))
+        .collect();
+
+    let mut out = Report::new(format);
+    out.title(&format!(
+        "Training report: {} to {}",
+        since.format("%Y-%m-%d"),
+        now.format("%Y-%m-%d")
+    ));
+
+    out.section("Games");
+    out.item(&format!(
+        "{} played ({}), score {} — previous {} days: {} played, score {}",
+        period.len(),
+        tally(&period),
+        score_percent(&period),
+        days,
+        previous.len(),
+        score_percent(&previous)
+    ));
+    let mut by_day: BTreeMap<String, Vec<&GameRecord>> = BTreeMap::new();
+    for record in &period {
+        by_day
+            .entry(record.date.chars().take(10).collect())
+            .or_default()
+            .push(record);
+    }
+    for (day, games) in &by_day {
+        out.item(&format!(
+            "{}: {} game(s), {}",
+            day,
+            games.len(),
+            tally(games)
+        ));
+    }
+
+    out.section("Accuracy");
+    match (average_accuracy(&period), average_accuracy(&previous)) {
+        (Some(now), Some(then)) => out.item(&format!(
+            "{:.1}% over analyzed games, {:+.1} points on the previous {} days",
+            now,
+            now - then,
+            days
+        )),
+        (Some(now), None) => out.item(&format!("{:.1}% over analyzed games", now)),
+        (None, _) => out.item("No analyzed games in this period."),
+    }
+
+    out.section("Openings");
+    let mut openings: BTreeMap<&str, Vec<&GameRecord>> = BTreeMap::new();
+    for record in period.iter().filter(|r| !r.opening.is_empty()) {
+        openings.entry(&record.opening).or_default().push(record);
+    }
+    let mut openings: Vec<(&str, Vec<&GameRecord>)> = openings.into_iter().collect();
+    openings.sort_by_key(|(_, games)| std::cmp::Reverse(games.len()));
+    if openings.is_empty() {
+        out.item("No games played.");
+    }
+    for (opening, games) in openings.iter().take(OPENINGS_SHOWN) {
+        out.item(&format!(
+            "{} — {} game(s), {}",
+            opening,
+            games.len(),
+            tally(games)
+        ));
+    }
+
+    out.section("Recurring motifs");
+    let mut missed: BTreeMap<&str, usize> = BTreeMap::new();
+    for item in review {
+        *missed.entry(&item.pattern).or_default() += 1;
+    }
+    let mut missed: Vec<(&str, usize)> = missed.into_iter().collect();
+    missed.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    for (pattern, count) in &missed {
+        out.item(&format!(
+            "{} mate: {} missed puzzle(s) in the review queue",
+            drills::pattern_name(pattern),
+            count
+        ));
+    }
+    for (pattern, entry) in drill_stats {
+        let rate = entry.solved as f64 / entry.attempts.max(1) as f64;
+        if entry.attempts > 0 && rate < WEAK_PATTERN_RATE {
+            out.item(&format!(
+                "{} mate: solved {} of {} drills ({:.0}%)",
+                drills::pattern_name(pattern),
+                entry.solved,
+                entry.attempts,
+                rate * 100.0
+            ));
+        }
+    }
+    for (motif, count) in motifs::ranked(blunder_motifs) {
+        out.item(&format!(
+            "{}: punished {} of your blunders (all time)",
+            motif, count
+        ));
+    }
+    if !out.last_section_has_items() {
+        out.item("Nothing recurring: no missed puzzles, weak drill patterns or blunders.");
+    }
+
+    out.section("Time spent");
+    let timed: Vec<i64> = period.iter().filter_map(|r| game_minutes(r)).collect();
+    if timed.is_empty() {
+        out.item("No game times recorded in this period.");
+    } else {
+        let total: i64 = timed.iter().sum();
+        out.item(&format!(
+            "{}h{:02} over {} timed game(s), {} min per game",
+            total / 60,
+            total % 60,
+            timed.len(),
+            total / timed.len() as i64
+        ));
+    }
+    out.finish()
+}
+
+/// When a game was played, from its start time or its date
+fn played_at(record: &GameRecord) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(&record.started)
+        .map(|t| t.with_timezone(&Local).naive_local())
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(&record.date, "%Y-%m-%d %H:%M").ok())
+}
+
+fn game_minutes(record: &GameRecord) -> Option<i64> {
+    let started = DateTime::parse_from_rfc3339(&record.started).ok()?;
+    let ended = DateTime::parse_from_rfc3339(record.ended.as_deref()?).ok()?;
+    Some((ended - started).num_minutes().max(0))
+}
+
+fn tally(games: &[&GameRecord]) -> String {
+    let count = |result: &str| games.iter().filter(|g| g.result == result).count();
+    format!("+{} ={} -{}", count("win"), count("draw"), count("loss"))
+}
+
+fn score_percent(games: &[&GameRecord]) -> String {
+    let decided: Vec<&&GameRecord> = games.iter().filter(|g| g.result != "unfinished").collect();
+    if decided.is_empty() {
+        return "-".to_string();
+    }
+    let points: f64 = decided
+        .iter()
+        .map(|g| match g.result.as_str() {
+            "win" => 1.0,
+            "draw" => 0.5,
+            _ => 0.0,
+        })
+        .sum();
+    format!("{:.0}%", points * 100.0 / decided.len() as f64)
+}
+
+/// Text or Markdown output, one heading per section and a bullet per fact
+struct Report {
+    format: Format,
+    text: String,
+    items_in_section: usize,
+}
+
+impl Report {
+    fn new(format: Format) -> Self {
+        Report {
+            format,
+            text: String::new(),
+            items_in_section: 0,
+        }
+    }
+
+    fn title(&mut self, title: &str) {
+        match self.format {
+            Format::Text => self.text.push_str(&format!("=== {} ===\n", title)),
+            Format::Markdown => self.text.push_str(&format!("# {}\n", title)),
+        }
+    }
+
+    fn section(&mut self, name: &str) {
+        self.items_in_section = 0;
+        match self.format {
+            Format::Text => self.text.push_str(&format!("\n{}:\n", name)),
+            Format::Markdown => self.text.push_str(&format!("\n## {}\n\n", name)),
+        }
+    }
+
+    fn item(&mut self, line: &str) {
+        self.items_in_section += 1;
+        match self.format {
+            Format::Text => self.text.push_str(&format!("  • {}\n", line)),
+            Format::Markdown => self.text.push_str(&format!("- {}\n", line)),
+        }
+    }
+
+    fn last_section_has_items(&self) -> bool {
+        self.items_in_section > 0
+    }
+
+    fn finish(self) -> String {
+        self.text
+    }
+}
@@ -0,0 +1,136 @@
+use crate::drills::{self, MatePuzzle};
+use crate::stockfish::StockfishEngine;
+use crate::storage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REVIEW_FILE: &str = "review.json";
+const INITIAL_EASE: f64 = 2.5;
+const MINIMUM_EASE: f64 = 1.3;
+
+/// A missed training item scheduled with the SM-2 algorithm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub pattern: String,
+    pub fen: String,
+    pub mate_in: u8,
+    pub solution: String,
+    pub repetitions: u32,
+    pub interval_days: u32,
+    pub ease: f64,
+    /// Due date, in days since the Unix epoch
+    pub due: u64,
+}
+
+impl ReviewItem {
+    fn puzzle(&self) -> MatePuzzle<'_> {
+        MatePuzzle {
+            pattern: &self.pattern,
+            fen: &self.fen,
+            mate_in: self.mate_in,
+            solution: &self.solution,
+        }
+    }
+
+    /// Update the schedule after a review. Quality is graded 0-5 as in SM-2.
+    fn schedule(&mut self, quality: u8) {
+        let q = quality.min(5) as f64;
+
+        if quality < 3 {
+            // Start over: the item comes back tomorrow
+            self.repetitions = 0;
+            self.interval_days = 1;
+        } else {
+            self.repetitions += 1;
+            self.interval_days = match self.repetitions {
+                1 => 1,
+                2 => 6,
+                _ => (self.interval_days as f64 * self.ease).round() as u32,
+            };
+        }
+
+        self.ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(MINIMUM_EASE);
+        self.due = today() + self.interval_days as u64;
+    }
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0)
+}
+
+/// Every item in the review queue, due or not
+pub fn load_items() -> Result<Vec<ReviewItem>> {
+    storage::load(REVIEW_FILE)
+}
+
+/// Queue a failed puzzle for review tomorrow, or reset it if it's already queued
+pub fn add_failed_puzzle(puzzle: &MatePuzzle<'_>) -> Result<()> {
+    let mut items: Vec<ReviewItem> = storage::load(REVIEW_FILE)?;
+
+    match items.iter_mut().find(|item| item.fen == puzzle.fen) {
+        Some(item) => item.schedule(0),
+        None => {
+            let mut item = ReviewItem {
+                pattern: puzzle.pattern.to_string(),
+                fen: puzzle.fen.to_string(),
+                mate_in: puzzle.mate_in,
+                solution: puzzle.solution.to_string(),
+                repetitions: 0,
+                interval_days: 0,
+                ease: INITIAL_EASE,
+                due: 0,
+            };
+            item.schedule(0);
+            items.push(item);
+        }
+    }
+
+    storage::save(REVIEW_FILE, &items)
+}
+
+/// Re-present every training item that is due today
+pub async fn run(stockfish_path: &str) -> Result<()> {
+    let mut items: Vec<ReviewItem> = storage::load(REVIEW_FILE)?;
+    let now = today();
+    let due: Vec<usize> = (0..items.len()).filter(|&i| items[i].due <= now).collect();
+
+    if due.is_empty() {
+        match items.iter().map(|item| item.due).min() {
+            Some(next) => println!(
+                "Nothing to review today. Next review in {} day(s).",
+                next - now
+            ),
+            None => println!("Your review queue is empty. Missed drills will show up here."),
+        }
+        return Ok(());
+    }
+
+    println!("\n=== Review: {} item(s) due ===", due.len());
+
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    engine.set_difficulty(20).await?;
+
+    let mut reviewed = 0;
+    for index in due {
+        let solved = match drills::play_puzzle(&mut engine, &items[index].puzzle()).await? {
+            Some(solved) => solved,
+            None => break,
+        };
+
+        let item = &mut items[index];
+        item.schedule(if solved { 4 } else { 1 });
+        if solved {
+            println!("Next review in {} day(s).", item.interval_days);
+        }
+        reviewed += 1;
+
+        storage::save(REVIEW_FILE, &items)?;
+    }
+
+    println!("\nReviewed {} item(s).", reviewed);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+/// Layout of exported text such as score sheets and reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Aligned plain-text columns, for printing
+    Text,
+    /// A Markdown table, for pasting into notes
+    Markdown,
+}
+
+/// Names, date and result printed above the moves
+pub struct SheetHeader<'a> {
+    pub white: &'a str,
+    pub black: &'a str,
+    pub date: &'a str,
+    pub result: &'a str,
+}
+
+/// Lay out SAN moves as a score sheet with one row per move number and
+/// columns for White and Black. `black_first` leaves White's first cell
+/// empty for games set up with Black to move.
+pub fn render(header: &SheetHeader, moves: &[String], black_first: bool, format: Format) -> String {
+    let mut cells: Vec<&str> = Vec::with_capacity(moves.len() + 1);
+    if black_first {
+        cells.push("...");
+    }
+    cells.extend(moves.iter().map(String::as_str));
+    let rows: Vec<(usize, &str, &str)> = cells
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| (i + 1, pair[0], pair.get(1).copied().unwrap_or("")))
+        .collect();
+
+    let mut text = String::new();
+    match format {
+        Format::Text => {
+            let number_width = rows.len().to_string().len();
+            let white_width = rows
+                .iter()
+                .map(|(_, white, _)| white.chars().count())
+                .chain([header.white.chars().count(), 5])
+                .max()
+                .unwrap_or(5);
+            text.push_str(&format!("{} - {}\n", header.white, header.black));
+            text.push_str(&format!("{}    {}\n\n", header.date, header.result));
+            text.push_str(&format!(
+                "{:>nw$}   {:<ww$}   {}\n",
+                "",
+                "White",
+                "Black",
+                nw = number_width,
+                ww = white_width
+            ));
+            for (number, white, black) in &rows {
+                text.push_str(&format!(
+                    "{:>nw$}.  {:<ww$}   {}\n",
+                    number,
+                    white,
+                    black,
+                    nw = number_width,
+                    ww = white_width
+                ));
+            }
+            text.push_str(&format!("\n{}\n", header.result));
+        }
+        Format::Markdown => {
+            text.push_str(&format!("**{}** – **{}**  \n", header.white, header.black));
+            text.push_str(&format!("{}, {}\n\n", header.date, header.result));
+            text.push_str("| # | White | Black |\n|--:|:------|:------|\n");
+            for (number, white, black) in &rows {
+                text.push_str(&format!("| {} | {} | {} |\n", number, white, black));
+            }
+        }
+    }
+    text
+}
@@ -0,0 +1,182 @@
+use crate::chess_game::{ChessGame, GameOptions, GameOutcome};
+use crate::stats::{self, GameRecord};
+use crate::stockfish::StockfishEngine;
+use anyhow::{Result, anyhow};
+use chess::{Board, BoardStatus, Color};
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Plies into a game where the middlegame is taken to start, and where it
+/// is taken to be over
+const MIDDLEGAME_PLIES: std::ops::RangeInclusive<usize> = 20..=40;
+/// Engine scores within this many centipawns count as roughly equal
+const EQUAL_MARGIN: i32 = 80;
+/// Depth of the search judging whether a position is equal
+const ASSESSMENT_DEPTH: u8 = 14;
+/// Lines the engine considers while playing out a generated opening
+const GENERATION_LINES: usize = 4;
+/// Positions tried before giving up on finding an equal one
+const ATTEMPTS: usize = 20;
+
+/// Where scramble positions come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// A middlegame from the player's own games, falling back to the engine
+    Auto,
+    Games,
+    Engine,
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Source::Auto),
+            "games" => Ok(Source::Games),
+            "engine" => Ok(Source::Engine),
+            _ => Err(anyhow!("--from must be auto, games or engine")),
+        }
+    }
+}
+
+pub struct ScrambleOptions {
+    pub seconds: u64,
+    pub difficulty: u8,
+    pub source: Source,
+}
+
+/// Play a roughly equal middlegame with only seconds on both clocks, then
+/// measure how much the player's moves lost under the pressure
+pub async fn run(
+    stockfish_path: &str,
+    options: ScrambleOptions,
+    game_options: GameOptions,
+) -> Result<()> {
+    let mut engine = StockfishEngine::new(stockfish_path).await?;
+    let player_color = if rand::rng().random_bool(0.5) {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    println!("Looking for a balanced middlegame...");
+    let records = stats::load_records()?;
+    let board = match options.source {
+        Source::Games => from_games(&mut engine, &records, player_color)
+            .await?
+            .ok_or_else(|| anyhow!("None of your recorded games has a balanced middlegame"))?,
+        Source::Engine => from_engine(&mut engine, player_color).await?,
+        Source::Auto => match from_games(&mut engine, &records, player_color).await? {
+            Some(board) => board,
+            None => from_engine(&mut engine, player_color).await?,
+        },
+    };
+
+    engine.set_difficulty(options.difficulty).await?;
+    println!("\n=== Time Scramble: {} seconds each ===", options.seconds);
+    println!(
+        "You are {}. FEN: {}",
+        if player_color == Color::White {
+            "White"
+        } else {
+            "Black"
+        },
+        board
+    );
+    let mut game = ChessGame::from_position(engine, player_color, board, game_options);
+    let time = Duration::from_secs(options.seconds);
+    game.set_clock(time, time);
+    let outcome = game.run().await?;
+
+    let mut record: GameRecord = stats::new_record(&game, "scramble", options.difficulty);
+    // The game began in the middlegame, so it has no opening to classify
+    record.opening.clear();
+    record.line.clear();
+    if outcome != GameOutcome::Unfinished {
+        stats::analyze(&mut game, &mut record).await?;
+        if let Some(loss) = record.average_loss {
+            println!(
+                "Average loss under time pressure: {:.0} centipawns per move",
+                loss
+            );
+        }
+    }
+    stats::save_record(record)
+}
+
+/// A middlegame position with the player to move from one of their own
+/// recorded games
+async fn from_games(
+    engine: &mut StockfishEngine,
+    records: &[GameRecord],
+    player_color: Color,
+) -> Result<Option<Board>> {
+    let mut candidates = Vec::new();
+    for record in records {
+        let moves = record.known_moves();
+        let mut board = Board::default();
+        for (ply, chess_move) in moves.iter().enumerate() {
+            if MIDDLEGAME_PLIES.contains(&ply) && board.side_to_move() == player_color {
+                candidates.push(board);
+            }
+            board = board.make_move_new(*chess_move);
+        }
+    }
+
+    let mut rng = rand::rng();
+    for _ in 0..ATTEMPTS {
+        let Some(&board) = candidates.choose(&mut rng) else {
+            return Ok(None);
+        };
+        if is_balanced(engine, &board).await? {
+            return Ok(Some(board));
+        }
+    }
+    Ok(None)
+}
+
+/// Let the engine play both sides through the opening, picking among its
+/// top moves at random, until a balanced middlegame comes up with the
+/// player to move
+async fn from_engine(engine: &mut StockfishEngine, player_color: Color) -> Result<Board> {
+    let mut rng = rand::rng();
+    for _ in 0..ATTEMPTS {
+        // An even number of plies leaves White to move
+        let plies = rng.random_range(MIDDLEGAME_PLIES) / 2 * 2 + player_color.to_index();
+        let mut board = Board::default();
+        for _ in 0..plies {
+            if board.status() != BoardStatus::Ongoing {
+                break;
+            }
+            let candidates = engine.candidate_moves(&board, GENERATION_LINES).await?;
+            let best = candidates[0].1.as_centipawns();
+            let chess_move = candidates
+                .iter()
+                .filter(|(_, score)| best - score.as_centipawns() <= EQUAL_MARGIN / 2)
+                .map(|(chess_move, _)| *chess_move)
+                .collect::<Vec<_>>()
+                .choose(&mut rng)
+                .copied()
+                .unwrap_or(candidates[0].0);
+            board = board.make_move_new(chess_move);
+        }
+        if board.side_to_move() == player_color && is_balanced(engine, &board).await? {
+            return Ok(board);
+        }
+    }
+    Err(anyhow!(
+        "Could not generate a balanced middlegame in {} tries",
+        ATTEMPTS
+    ))
+}
+
+async fn is_balanced(engine: &mut StockfishEngine, board: &Board) -> Result<bool> {
+    if board.status() != BoardStatus::Ongoing {
+        return Ok(false);
+    }
+    let score = engine.evaluate(board, ASSESSMENT_DEPTH).await?;
+    Ok(score.as_centipawns().abs() <= EQUAL_MARGIN)
+}
@@ -0,0 +1,121 @@
+use anyhow::{Result, anyhow};
+
+/// Prefix of script functions that become in-game commands: `fn cmd_drill(args)`
+/// is run when the player types `drill ...`
+#[cfg(feature = "scripting")]
+const COMMAND_PREFIX: &str = "cmd_";
+
+/// User scripts (Rhai) with hooks into the game:
+///
+/// - `fn on_move(san, fen, by_player)` after every move
+/// - `fn on_game_end(result, moves)` when a game finishes or is abandoned
+/// - `fn cmd_<name>(args)` for a custom in-game command `<name>`
+///
+/// Besides Rhai's standard library, scripts can call `http_post(url, body)`
+/// (through curl) to send results elsewhere.
+#[cfg(feature = "scripting")]
+pub struct Scripts {
+    engine: rhai::Engine,
+    ast: rhai::AST,
+    scope: rhai::Scope<'static>,
+}
+
+#[cfg(feature = "scripting")]
+impl Scripts {
+    pub fn load(path: &str) -> Result<Self> {
+        let mut engine = rhai::Engine::new();
+        engine.register_fn("http_post", http_post);
+
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| anyhow!("Failed to load script {}: {}", path, e))?;
+        // Top-level statements run once, for setup
+        let mut scope = rhai::Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow!("Script {} failed: {}", path, e))?;
+
+        Ok(Scripts { engine, ast, scope })
+    }
+
+    pub fn on_move(&mut self, san: &str, fen: &str, by_player: bool) {
+        self.call_hook("on_move", (san.to_string(), fen.to_string(), by_player));
+    }
+
+    pub fn on_game_end(&mut self, result: &str, moves: &str) {
+        self.call_hook("on_game_end", (result.to_string(), moves.to_string()));
+    }
+
+    /// Names of the custom commands the scripts define
+    pub fn commands(&self) -> Vec<String> {
+        self.ast
+            .iter_functions()
+            .filter_map(|f| f.name.strip_prefix(COMMAND_PREFIX).map(str::to_string))
+            .collect()
+    }
+
+    /// Run a custom command if a script defines it; false if there is none
+    pub fn run_command(&mut self, input: &str) -> bool {
+        let (name, args) = input.split_once(' ').unwrap_or((input, ""));
+        if !self.commands().iter().any(|c| c == name) {
+            return false;
+        }
+        self.call_hook(
+            &format!("{}{}", COMMAND_PREFIX, name),
+            (args.trim().to_string(),),
+        );
+        true
+    }
+
+    /// Call a script function if it is defined; script errors are reported
+    /// but never end the game
+    fn call_hook(&mut self, name: &str, args: impl rhai::FuncArgs) {
+        if !self.ast.iter_functions().any(|f| f.name == name) {
+            return;
+        }
+        let options = rhai::CallFnOptions::new().eval_ast(false);
+        if let Err(e) = self.engine.call_fn_with_options::<rhai::Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        ) {
+            println!("[script] {} failed: {}", name, e);
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn http_post(url: &str, body: &str) -> bool {
+    std::process::Command::new("curl")
+        .args(["-fsS", "-X", "POST", "-H", "Content-Type: application/json"])
+        .args(["--data", body, url])
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Stand-in when minichess is built without the `scripting` feature
+#[cfg(not(feature = "scripting"))]
+pub struct Scripts;
+
+#[cfg(not(feature = "scripting"))]
+impl Scripts {
+    pub fn load(_path: &str) -> Result<Self> {
+        Err(anyhow!(
+            "This build has no scripting support; rebuild with `cargo build --features scripting`"
+        ))
+    }
+
+    pub fn on_move(&mut self, _san: &str, _fen: &str, _by_player: bool) {}
+
+    pub fn on_game_end(&mut self, _result: &str, _moves: &str) {}
+
+    pub fn commands(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    pub fn run_command(&mut self, _input: &str) -> bool {
+        false
+    }
+}
@@ -0,0 +1,182 @@
+use crate::chess_game::{ChessGame, GameOptions, GameOutcome};
+use crate::{fen, stats, storage};
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, Color};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+const SESSIONS_FILE: &str = "sessions.json";
+
+/// An in-progress game kept between runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub created: String,
+    pub updated: String,
+    pub difficulty: u8,
+    pub player_color: String,
+    /// FEN of the position the game started from
+    pub start: String,
+    /// Moves played so far, in coordinate notation
+    pub moves: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionStore {
+    /// Session played last
+    current: Option<String>,
+    sessions: BTreeMap<String, Session>,
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M").to_string()
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid session name '{}': use letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Start a new session from the initial position; fails if the name is taken
+pub fn create(name: &str, difficulty: u8, player_color: Color) -> Result<()> {
+    check_name(name)?;
+    let mut store: SessionStore = storage::load(SESSIONS_FILE)?;
+    if store.sessions.contains_key(name) {
+        return Err(anyhow!("Session '{}' already exists", name));
+    }
+    store.sessions.insert(
+        name.to_string(),
+        Session {
+            created: now(),
+            updated: now(),
+            difficulty,
+            player_color: if player_color == Color::White {
+                "white".to_string()
+            } else {
+                "black".to_string()
+            },
+            start: Board::default().to_string(),
+            moves: Vec::new(),
+        },
+    );
+    storage::save(SESSIONS_FILE, &store)
+}
+
+pub fn get(name: &str) -> Result<Session> {
+    let store: SessionStore = storage::load(SESSIONS_FILE)?;
+    store
+        .sessions
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("No session named '{}' (see 'session list')", name))
+}
+
+/// Store the moves of a session's game after each move
+pub fn update(name: &str, moves: &[ChessMove]) -> Result<()> {
+    let mut store: SessionStore = storage::load(SESSIONS_FILE)?;
+    let session = store
+        .sessions
+        .get_mut(name)
+        .ok_or_else(|| anyhow!("Session '{}' was closed", name))?;
+    session.moves = moves.iter().map(|m| m.to_string()).collect();
+    session.updated = now();
+    storage::save(SESSIONS_FILE, &store)
+}
+
+pub fn close(name: &str) -> Result<()> {
+    let mut store: SessionStore = storage::load(SESSIONS_FILE)?;
+    if store.sessions.remove(name).is_none() {
+        return Err(anyhow!("No session named '{}'", name));
+    }
+    if store.current.as_deref() == Some(name) {
+        store.current = None;
+    }
+    storage::save(SESSIONS_FILE, &store)
+}
+
+pub fn list() -> Result<()> {
+    let store: SessionStore = storage::load(SESSIONS_FILE)?;
+    if store.sessions.is_empty() {
+        println!("No sessions. Start one with 'session new <name>'.");
+        return Ok(());
+    }
+    println!(
+        "  {:<16} {:<6} {:>5} {:>6}  Last played",
+        "Session", "Color", "Level", "Moves"
+    );
+    for (name, session) in &store.sessions {
+        let marker = if store.current.as_deref() == Some(name) {
+            '*'
+        } else {
+            ' '
+        };
+        println!(
+            "{} {:<16} {:<6} {:>5} {:>6}  {}",
+            marker,
+            name,
+            session.player_color,
+            session.difficulty,
+            session.moves.len().div_ceil(2),
+            session.updated
+        );
+    }
+    Ok(())
+}
+
+/// Play sessions, starting with `name`, until the player quits. Switching
+/// sessions from the move prompt saves the game and loads the other one;
+/// a finished game is recorded in the statistics and its session closed.
+pub async fn play(stockfish_path: &str, name: &str, options: GameOptions) -> Result<()> {
+    let mut name = name.to_string();
+    loop {
+        let session = get(&name)?;
+        let mut store: SessionStore = storage::load(SESSIONS_FILE)?;
+        store.current = Some(name.clone());
+        storage::save(SESSIONS_FILE, &store)?;
+
+        let start =
+            fen::parse_fen(&session.start).map_err(|e| anyhow!("Corrupt session: {}", e))?;
+        let color = if session.player_color == "black" {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let moves = session
+            .moves
+            .iter()
+            .map(|m| ChessMove::from_str(m).map_err(|_| anyhow!("Corrupt session move {}", m)))
+            .collect::<Result<Vec<_>>>()?;
+
+        println!("\n=== Session '{}' ===", name);
+        let mut game = ChessGame::new(
+            stockfish_path,
+            session.difficulty,
+            start,
+            Some(color),
+            options,
+        )
+        .await?;
+        game.replay_moves(&moves)?;
+        game.set_session(&name)?;
+        let outcome = game.run().await?;
+
+        if outcome != GameOutcome::Unfinished {
+            stats::record_game(&mut game, "session", session.difficulty).await?;
+            close(&name)?;
+            println!("Session '{}' is over and has been closed.", name);
+        }
+        match game.take_session_switch() {
+            Some(next) => name = next,
+            None => return Ok(()),
+        }
+    }
+}
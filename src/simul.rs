@@ -0,0 +1,106 @@
+use crate::chess_game::{ChessGame, GameOptions, GameOutcome, SimulTurn};
+use crate::stats;
+use anyhow::Result;
+use chess::{Board, Color};
+
+/// Play `boards` games against the engine at once, as White on every board
+/// like the exhibitor of a simultaneous exhibition. After each move play
+/// goes on to the next board that is still running.
+pub async fn run(
+    stockfish_path: &str,
+    difficulty: u8,
+    boards: usize,
+    options: GameOptions,
+) -> Result<()> {
+    let mut games = Vec::with_capacity(boards);
+    for _ in 0..boards {
+        let mut game = ChessGame::new(
+            stockfish_path,
+            difficulty,
+            Board::default(),
+            Some(Color::White),
+            options,
+        )
+        .await?;
+        game.set_simul()?;
+        games.push(game);
+    }
+
+    println!("\n=== Simul: {} boards at level {} ===", boards, difficulty);
+    println!("You play White everywhere. After each move you go on to the next board.");
+    println!("'board <n>' switches boards, 'overview' shows all of them.");
+
+    let mut current = 0;
+    while let Some(next) = next_running(&games, current) {
+        current = next;
+        println!("\n=== Board {} of {} ===", current + 1, boards);
+        match games[current].play_simul_turn().await? {
+            SimulTurn::Moved => {
+                if games[current].outcome() != GameOutcome::Unfinished {
+                    stats::record_game(&mut games[current], "simul", difficulty).await?;
+                }
+                current = (current + 1) % boards;
+            }
+            SimulTurn::Board(number) if (1..=boards).contains(&number) => {
+                if games[number - 1].outcome() == GameOutcome::Unfinished {
+                    current = number - 1;
+                } else {
+                    println!("Board {} is already finished.", number);
+                }
+            }
+            SimulTurn::Board(_) => println!("There is no such board; pick 1 to {}.", boards),
+            SimulTurn::Overview => print_overview(&games, current),
+            SimulTurn::Quit => break,
+        }
+    }
+
+    print_overview(&games, current);
+    print_score(&games);
+    Ok(())
+}
+
+/// The first unfinished board from `from` on, wrapping around
+fn next_running(games: &[ChessGame], from: usize) -> Option<usize> {
+    (0..games.len())
+        .map(|offset| (from + offset) % games.len())
+        .find(|&i| games[i].outcome() == GameOutcome::Unfinished)
+}
+
+fn print_overview(games: &[ChessGame], current: usize) {
+    println!("\n=== Simul overview ===");
+    for (i, game) in games.iter().enumerate() {
+        let marker = if i == current { '>' } else { ' ' };
+        println!("{} Board {:>2}: {}", marker, i + 1, game.summary());
+    }
+}
+
+/// The exhibitor's score over the finished boards
+fn print_score(games: &[ChessGame]) {
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    for game in games {
+        match game.outcome() {
+            GameOutcome::Win(winner) | GameOutcome::Timeout(winner)
+                if winner == game.player_color() =>
+            {
+                wins += 1
+            }
+            GameOutcome::Win(_) | GameOutcome::Timeout(_) => losses += 1,
+            GameOutcome::Draw => draws += 1,
+            GameOutcome::Unfinished => {}
+        }
+    }
+    let finished = wins + draws + losses;
+    println!(
+        "\nYou scored {}/{} (+{} ={} -{}){}",
+        wins as f64 + draws as f64 / 2.0,
+        finished,
+        wins,
+        draws,
+        losses,
+        if finished < games.len() {
+            format!(", {} board(s) unfinished", games.len() - finished)
+        } else {
+            String::new()
+        }
+    );
+}
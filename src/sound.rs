@@ -0,0 +1,115 @@
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, Piece};
+use std::sync::OnceLock;
+use std::sync::mpsc::Sender;
+
+/// Something worth hearing during a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Move,
+    Capture,
+    Check,
+    GameEnd,
+}
+
+impl Sound {
+    /// The sound of `chess_move` played from `before`; a check drowns out a
+    /// capture
+    pub fn for_move(before: &Board, chess_move: ChessMove) -> Sound {
+        let (source, dest) = (chess_move.get_source(), chess_move.get_dest());
+        // A pawn changing files onto an empty square takes en passant
+        let capture = before.piece_on(dest).is_some()
+            || (before.piece_on(source) == Some(Piece::Pawn)
+                && source.get_file() != dest.get_file());
+        if before.make_move_new(chess_move).checkers().popcnt() > 0 {
+            Sound::Check
+        } else if capture {
+            Sound::Capture
+        } else {
+            Sound::Move
+        }
+    }
+}
+
+/// Queue of the thread that plays the sounds, once `--sound` started it
+static PLAYER: OnceLock<Sender<Sound>> = OnceLock::new();
+
+/// Play a sound without waiting for it; does nothing without `--sound`
+pub fn play(sound: Sound) {
+    if let Some(player) = PLAYER.get() {
+        let _ = player.send(sound);
+    }
+}
+
+/// Start playing sounds, on a thread of their own since the audio output
+/// can't be shared. Fails when there is no audio device.
+#[cfg(feature = "sound")]
+pub fn enable() -> Result<()> {
+    use rodio::source::{SineWave, Source};
+    use rodio::{OutputStream, Sink};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let (sender, receiver) = mpsc::channel::<Sound>();
+    let (ready_sender, ready) = mpsc::channel();
+    std::thread::spawn(move || {
+        let output = OutputStream::try_default()
+            .map_err(|e| e.to_string())
+            .and_then(|(stream, handle)| {
+                Sink::try_new(&handle)
+                    .map(|sink| (stream, sink))
+                    .map_err(|e| e.to_string())
+            });
+        // The stream has to stay alive for the sink to be heard
+        let (_stream, sink) = match output {
+            Ok(output) => {
+                let _ = ready_sender.send(Ok(()));
+                output
+            }
+            Err(e) => {
+                let _ = ready_sender.send(Err(e));
+                return;
+            }
+        };
+        for sound in receiver {
+            for &(frequency, millis) in sound.notes() {
+                sink.append(
+                    SineWave::new(frequency)
+                        .take_duration(Duration::from_millis(millis))
+                        .amplify(VOLUME),
+                );
+            }
+        }
+    });
+    ready
+        .recv()
+        .map_err(|_| anyhow!("The sound thread failed to start"))?
+        .map_err(|e| anyhow!("No audio output for --sound: {}", e))?;
+    let _ = PLAYER.set(sender);
+    Ok(())
+}
+
+/// Loudness of the tones, from 0 to 1
+#[cfg(feature = "sound")]
+const VOLUME: f32 = 0.2;
+
+#[cfg(feature = "sound")]
+impl Sound {
+    /// Frequency in Hz and length in milliseconds of each tone
+    fn notes(self) -> &'static [(f32, u64)] {
+        match self {
+            Sound::Move => &[(660.0, 50)],
+            Sound::Capture => &[(440.0, 60), (330.0, 90)],
+            Sound::Check => &[(880.0, 70), (1175.0, 110)],
+            Sound::GameEnd => &[(523.0, 120), (659.0, 120), (784.0, 240)],
+        }
+    }
+}
+
+/// Stand-in when minichess is built without the `sound` feature
+#[cfg(not(feature = "sound"))]
+pub fn enable() -> Result<()> {
+    Err(anyhow!(
+        "This build has no sound support; rebuild with `cargo build --features sound`"
+    ))
+}
@@ -0,0 +1,471 @@
+use crate::chess_game::{ChessGame, GameOutcome};
+use crate::eco::{self, Classification};
+use crate::integrity::{self, Seal};
+use crate::pgn;
+use crate::storage;
+use anyhow::{Result, anyhow};
+use chess::{Board, ChessMove, Color};
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+const GAMES_FILE: &str = "games.json";
+/// Number of plies used to describe the opening of a game
+const OPENING_PLIES: usize = 6;
+/// Number of plies kept to classify the opening by ECO code
+pub const LINE_PLIES: usize = 24;
+/// Number of openings listed in the summary
+const OPENINGS_SHOWN: usize = 5;
+/// Continuations listed for each opening in `stats openings`
+const DEVIATIONS_SHOWN: usize = 3;
+
+/// One finished (or abandoned) game, as stored in the profile's statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub date: String,
+    pub mode: String,
+    pub difficulty: u8,
+    pub player_color: String,
+    pub result: String,
+    pub moves: usize,
+    /// Percentage worked out from `average_loss`, for analyzed games
+    pub accuracy: Option<f64>,
+    pub opening: String,
+    /// Zobrist key (hex) of the position after the opening moves, so the
+    /// same opening reached by different move orders is counted together
+    #[serde(default)]
+    pub opening_key: String,
+    /// First moves of the game in SAN, deeper than `opening`, for naming
+    /// the opening by its ECO code
+    #[serde(default)]
+    pub line: String,
+    #[serde(default)]
+    pub takebacks: u32,
+    /// Start and end of the game in RFC 3339, with the local UTC offset
+    #[serde(default)]
+    pub started: String,
+    #[serde(default)]
+    pub ended: Option<String>,
+    /// Engine name and version, as it identified itself
+    #[serde(default)]
+    pub engine: String,
+    #[serde(default)]
+    pub engine_settings: String,
+    /// Average centipawns lost per move, for modes that analyze the game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub average_loss: Option<f64>,
+    /// Moves, times and hash chain of a rated or ladder game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seal: Option<Seal>,
+}
+
+/// Modes whose records are sealed against editing
+const SEALED_MODES: [&str; 2] = ["rated", "ladder"];
+
+/// The profile's game records. Sealed records that no longer match their
+/// seal are reported, so an edited history doesn't pass unnoticed.
+pub fn load_records() -> Result<Vec<GameRecord>> {
+    let records: Vec<GameRecord> = storage::load(GAMES_FILE)?;
+    let broken = integrity::broken(&records);
+    if !broken.is_empty() {
+        eprintln!(
+            "Warning: game record(s) {} fail their integrity check; run `verify` for details.",
+            broken
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(records)
+}
+
+/// Append a game to the profile's statistics, analyzing the player's
+/// moves first if the game was played to the end
+pub async fn record_game(game: &mut ChessGame, mode: &str, difficulty: u8) -> Result<()> {
+    let mut record = new_record(game, mode, difficulty);
+    if game.outcome() != GameOutcome::Unfinished {
+        analyze(game, &mut record).await?;
+    }
+    save_record(record)
+}
+
+/// Measure the player's moves against the engine's best and keep the
+/// average loss and accuracy on the record
+pub async fn analyze(game: &mut ChessGame, record: &mut GameRecord) -> Result<()> {
+    println!("\nChecking your moves...");
+    record.average_loss = game.average_loss().await?;
+    record.accuracy = record.average_loss.map(accuracy);
+    Ok(())
+}
+
+/// Mean accuracy of the analyzed games among `records`, None if none was
+/// analyzed
+pub fn average_accuracy(records: &[&GameRecord]) -> Option<f64> {
+    let values: Vec<f64> = records.iter().filter_map(|r| r.accuracy()).collect();
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Accuracy in percent for an average loss in centipawns per move: 100 for
+/// the engine's own moves, halving with about every 70 centipawns lost
+pub fn accuracy(average_loss: f64) -> f64 {
+    100.0 * (-average_loss.max(0.0) / 100.0).exp()
+}
+
+/// The statistics record of a game, sealed for the modes that need it
+pub fn new_record(game: &ChessGame, mode: &str, difficulty: u8) -> GameRecord {
+    let san_moves = game.san_moves();
+    let player_color = game.player_color();
+    let (started, ended) = game.times();
+
+    let result = match game.outcome() {
+        GameOutcome::Win(winner) | GameOutcome::Timeout(winner) if winner == player_color => "win",
+        GameOutcome::Win(_) | GameOutcome::Timeout(_) => "loss",
+        GameOutcome::Draw => "draw",
+        GameOutcome::Unfinished => "unfinished",
+    };
+
+    let mut record = GameRecord {
+        date: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        mode: mode.to_string(),
+        difficulty,
+        player_color: if player_color == Color::White {
+            "white".to_string()
+        } else {
+            "black".to_string()
+        },
+        result: result.to_string(),
+        moves: san_moves.len().div_ceil(2),
+        accuracy: None,
+        opening: san_moves
+            .iter()
+            .take(OPENING_PLIES)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" "),
+        opening_key: format!("{:016x}", game.position_after(OPENING_PLIES).get_hash()),
+        line: san_moves
+            .iter()
+            .take(LINE_PLIES)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" "),
+        takebacks: game.takebacks_used(),
+        started: started.to_rfc3339_opts(SecondsFormat::Secs, false),
+        ended: ended.map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, false)),
+        engine: game.engine_name().to_string(),
+        engine_settings: game.engine_settings(),
+        average_loss: None,
+        seal: None,
+    };
+    if SEALED_MODES.contains(&mode) {
+        let times = game
+            .move_times()
+            .iter()
+            .map(|time| time.as_millis() as u64)
+            .collect();
+        record.seal = Some(Seal::new(&record, game.coordinate_moves(), times));
+    }
+    record
+}
+
+pub fn save_record(record: GameRecord) -> Result<()> {
+    let mut records = load_records()?;
+    records.push(record);
+    storage::save(GAMES_FILE, &records)
+}
+
+pub fn print_summary(records: &[GameRecord]) {
+    let count = |result: &str| records.iter().filter(|r| r.result == result).count();
+
+    println!("\n=== Statistics for {} ===", storage::profile_name());
+    println!("Games played: {}", records.len());
+    println!("Wins:         {}", count("win"));
+    println!("Draws:        {}", count("draw"));
+    println!("Losses:       {}", count("loss"));
+    println!("Unfinished:   {}", count("unfinished"));
+    println!(
+        "Takebacks:    {}",
+        records.iter().map(|r| r.takebacks).sum::<u32>()
+    );
+    print_openings(records);
+    print_scrambles(records);
+    println!("==========================\n");
+}
+
+/// Time-scramble games on their own, since results and accuracy with
+/// seconds on the clock say little about normal play and vice versa
+fn print_scrambles(records: &[GameRecord]) {
+    let games: Vec<&GameRecord> = records
+        .iter()
+        .filter(|r| r.mode == "scramble" && r.result != "unfinished")
+        .collect();
+    if games.is_empty() {
+        return;
+    }
+    let losses: Vec<f64> = games.iter().filter_map(|r| r.average_loss).collect();
+    println!(
+        "\nTime scrambles: {} game(s), {}, score {:.0}%{}",
+        games.len(),
+        tally(&games),
+        score(&games) * 100.0,
+        if losses.is_empty() {
+            String::new()
+        } else {
+            format!(
+                ", average loss {:.0} cp/move",
+                losses.iter().sum::<f64>() / losses.len() as f64
+            )
+        }
+    );
+}
+
+/// Most played openings, grouped by the position they lead to rather than
+/// the move order
+fn print_openings(records: &[GameRecord]) {
+    let mut groups: Vec<(&str, Vec<&GameRecord>)> = Vec::new();
+    for record in records.iter().filter(|r| !r.opening_key.is_empty()) {
+        match groups
+            .iter_mut()
+            .find(|(key, _)| *key == record.opening_key)
+        {
+            Some((_, games)) => games.push(record),
+            None => groups.push((&record.opening_key, vec![record])),
+        }
+    }
+    if groups.is_empty() {
+        return;
+    }
+    groups.sort_by_key(|(_, games)| std::cmp::Reverse(games.len()));
+
+    println!("\nMost played openings:");
+    for (_, games) in groups.iter().take(OPENINGS_SHOWN) {
+        let mut orders: Vec<&str> = games.iter().map(|g| g.opening.as_str()).collect();
+        orders.sort();
+        orders.dedup();
+        // Label the group with its most common move order
+        let label = orders
+            .iter()
+            .max_by_key(|order| games.iter().filter(|g| g.opening == **order).count())
+            .copied()
+            .unwrap_or_default();
+        let count = |result: &str| games.iter().filter(|g| g.result == result).count();
+        let transposed = if orders.len() > 1 {
+            format!(", {} move orders", orders.len())
+        } else {
+            String::new()
+        };
+        println!(
+            "  {} — {} game(s): +{} ={} -{}{}",
+            label,
+            games.len(),
+            count("win"),
+            count("draw"),
+            count("loss"),
+            transposed
+        );
+    }
+}
+
+impl GameRecord {
+    /// The opening moves in SAN; records from before `line` was kept only
+    /// have their first few
+    fn line(&self) -> &str {
+        if self.line.is_empty() {
+            &self.opening
+        } else {
+            &self.line
+        }
+    }
+
+    /// Accuracy in percent, also for records from before it was kept that
+    /// have an average loss
+    pub fn accuracy(&self) -> Option<f64> {
+        self.accuracy.or(self.average_loss.map(accuracy))
+    }
+
+    /// The opening by ECO code
+    pub fn classify_opening(&self) -> Option<Classification> {
+        let moves: Vec<String> = self.line().split_whitespace().map(String::from).collect();
+        eco::classify(&moves)
+    }
+
+    /// Moves known from the record, from the initial position: the whole
+    /// game when it is sealed, the opening line otherwise
+    pub fn known_moves(&self) -> Vec<ChessMove> {
+        let tokens: Vec<&str> = match &self.seal {
+            Some(seal) => seal.moves.iter().map(|m| m.as_str()).collect(),
+            None => self.line().split_whitespace().collect(),
+        };
+        let mut board = Board::default();
+        let mut moves = Vec::new();
+        // Sealed moves are in coordinates and the line in SAN; parse_move reads both
+        for token in tokens {
+            let Ok(chess_move) = pgn::parse_move(&board, token) else {
+                break;
+            };
+            board = board.make_move_new(chess_move);
+            moves.push(chess_move);
+        }
+        moves
+    }
+}
+
+/// Results, accuracy and the moves that left the book line for each
+/// opening the player has reached, by ECO code and the player's color
+pub fn print_opening_stats(records: &[GameRecord], color: Option<&str>) {
+    type Key = (Option<(&'static str, &'static str)>, String);
+    type Game<'a> = (&'a GameRecord, Option<Classification>);
+    let mut groups: Vec<(Key, Vec<Game>)> = Vec::new();
+    for record in records
+        .iter()
+        .filter(|r| r.result != "unfinished" && !r.line().is_empty())
+        .filter(|r| color.is_none_or(|c| r.player_color == c))
+    {
+        let classification = record.classify_opening();
+        let key = (
+            classification.map(|c| (c.code, c.name)),
+            record.player_color.clone(),
+        );
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, games)) => games.push((record, classification)),
+            None => groups.push((key, vec![(record, classification)])),
+        }
+    }
+
+    println!(
+        "\n=== Openings by ECO code ({}) ===",
+        storage::profile_name()
+    );
+    if groups.is_empty() {
+        println!("No finished games yet.");
+        return;
+    }
+    // Most played first, games no known line covers last
+    groups.sort_by_key(|((opening, _), games)| (opening.is_none(), std::cmp::Reverse(games.len())));
+
+    for ((opening, player_color), games) in &groups {
+        let label = match opening {
+            Some((code, name)) => format!("{} {}", code, name),
+            None => "Unclassified".to_string(),
+        };
+        let records: Vec<&GameRecord> = games.iter().map(|(record, _)| *record).collect();
+        println!(
+            "\n{} — as {}, {} game(s): {}, score {:.0}%{}",
+            label,
+            player_color,
+            records.len(),
+            tally(&records),
+            score(&records) * 100.0,
+            average_accuracy(&records)
+                .map(|accuracy| format!(", accuracy {:.1}%", accuracy))
+                .unwrap_or_default()
+        );
+
+        // The move each game continued with once the named line ended
+        let mut deviations: Vec<(String, Vec<&GameRecord>)> = Vec::new();
+        for (record, classification) in games {
+            let Some(classification) = classification else {
+                continue;
+            };
+            let Some(san) = record.line().split_whitespace().nth(classification.ply) else {
+                continue;
+            };
+            let ply = classification.ply;
+            let text = format!(
+                "{}{}{}",
+                ply / 2 + 1,
+                if ply % 2 == 0 { "." } else { "..." },
+                san
+            );
+            match deviations.iter_mut().find(|(m, _)| *m == text) {
+                Some((_, games)) => games.push(record),
+                None => deviations.push((text, vec![record])),
+            }
+        }
+        if deviations.is_empty() {
+            continue;
+        }
+        deviations.sort_by_key(|(_, games)| std::cmp::Reverse(games.len()));
+        let shown: Vec<String> = deviations
+            .iter()
+            .take(DEVIATIONS_SHOWN)
+            .map(|(text, games)| {
+                format!("{} ({}, {:.0}%)", text, games.len(), score(games) * 100.0)
+            })
+            .collect();
+        println!("    continued with: {}", shown.join(", "));
+    }
+    println!();
+}
+
+pub fn tally(games: &[&GameRecord]) -> String {
+    let count = |result: &str| games.iter().filter(|g| g.result == result).count();
+    format!("+{} ={} -{}", count("win"), count("draw"), count("loss"))
+}
+
+/// The player's points per game, from 0 to 1
+pub fn score(games: &[&GameRecord]) -> f64 {
+    let points: f64 = games
+        .iter()
+        .map(|g| match g.result.as_str() {
+            "win" => 1.0,
+            "draw" => 0.5,
+            _ => 0.0,
+        })
+        .sum();
+    points / games.len().max(1) as f64
+}
+
+/// Write every game record as CSV or JSON to the given writer
+pub fn export(records: &[GameRecord], format: &str, out: &mut dyn Write) -> Result<()> {
+    match format {
+        "json" => {
+            serde_json::to_writer_pretty(&mut *out, records)?;
+            writeln!(out)?;
+        }
+        "csv" => {
+            writeln!(
+                out,
+                "date,mode,difficulty,player_color,result,moves,accuracy,opening,opening_key,takebacks,started,ended,engine,engine_settings"
+            )?;
+            for r in records {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    csv_field(&r.date),
+                    csv_field(&r.mode),
+                    r.difficulty,
+                    csv_field(&r.player_color),
+                    csv_field(&r.result),
+                    r.moves,
+                    r.accuracy()
+                        .map(|a| format!("{:.1}", a))
+                        .unwrap_or_default(),
+                    csv_field(&r.opening),
+                    r.opening_key,
+                    r.takebacks,
+                    csv_field(&r.started),
+                    csv_field(r.ended.as_deref().unwrap_or_default()),
+                    csv_field(&r.engine),
+                    csv_field(&r.engine_settings)
+                )?;
+            }
+        }
+        _ => {
+            return Err(anyhow!(
+                "Unknown export format '{}', expected csv or json",
+                format
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
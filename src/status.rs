@@ -0,0 +1,48 @@
+use crate::stockfish::Score;
+use chess::{Board, BoardStatus, Color};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static STATUS_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keep a one-line game summary in this file. Called once from main.
+pub fn configure(path: PathBuf) {
+    let _ = STATUS_FILE.set(path);
+}
+
+pub fn enabled() -> bool {
+    STATUS_FILE.get().is_some()
+}
+
+/// Replace the status file with a summary of the position, e.g.
+/// `Black to move | +0.35 | move 12 | W 4:31 B 3:58`. `eval` is from
+/// White's point of view.
+pub fn update(board: &Board, move_number: usize, eval: Option<Score>, clock: Option<String>) {
+    let Some(path) = STATUS_FILE.get() else {
+        return;
+    };
+
+    let side = if board.side_to_move() == Color::White {
+        "White"
+    } else {
+        "Black"
+    };
+    let mut fields = vec![match board.status() {
+        BoardStatus::Checkmate => format!("{} is checkmated", side),
+        BoardStatus::Stalemate => "Stalemate".to_string(),
+        BoardStatus::Ongoing => format!("{} to move", side),
+    }];
+    if let Some(eval) = eval {
+        fields.push(eval.to_string());
+    }
+    fields.push(format!("move {}", move_number));
+    fields.extend(clock);
+
+    // Write a temporary file and rename it so readers never see half a line
+    let partial = path.with_extension("part");
+    let written = std::fs::write(&partial, format!("{}\n", fields.join(" | ")))
+        .and_then(|_| std::fs::rename(&partial, path));
+    if let Err(e) = written {
+        eprintln!("Failed to update status file {}: {}", path.display(), e);
+    }
+}
@@ -1,92 +1,868 @@
+use crate::crash;
+use crate::pgn;
+use crate::position_cache::PositionCache;
+use crate::terminal::StatusLine;
+use crate::ui::{Verbosity, verbosity};
 use anyhow::{Result, anyhow};
-use chess::{Board, ChessMove};
+use chess::{Board, BoardStatus, ChessMove, Color};
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::str::FromStr;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
 use tokio::process::{Child, Command};
 
+/// Engine evaluation, always relative to the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl Score {
+    /// Collapse the score into centipawns so it can be compared and averaged
+    pub fn as_centipawns(&self) -> i32 {
+        match *self {
+            Score::Centipawns(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE_SCORE - moves,
+            Score::Mate(moves) => -MATE_SCORE - moves,
+        }
+    }
+
+    /// The same score from White's point of view
+    pub fn for_white(self, side_to_move: Color) -> Score {
+        match (self, side_to_move) {
+            (score, Color::White) => score,
+            (Score::Centipawns(cp), Color::Black) => Score::Centipawns(-cp),
+            (Score::Mate(moves), Color::Black) => Score::Mate(-moves),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Score::Centipawns(cp) => write!(f, "{:+.2}", cp as f64 / 100.0),
+            Score::Mate(moves) => write!(f, "#{}", moves),
+        }
+    }
+}
+
+pub const MATE_SCORE: i32 = 10_000;
+
+/// A score from `Score::as_centipawns` in pawns, e.g. "+0.35", or as a mate
+/// in so many moves, e.g. "+M3"
+pub fn format_centipawns(score: i32) -> String {
+    if score.abs() >= MATE_SCORE - 500 {
+        let moves = MATE_SCORE - score.abs();
+        format!("{}M{}", if score > 0 { "+" } else { "-" }, moves.max(0))
+    } else {
+        format!("{:+.2}", score as f64 / 100.0)
+    }
+}
+
+/// Ways an engine can fail to produce a usable move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    /// The position is already checkmate or stalemate
+    GameOver,
+    /// The engine answered "bestmove (none)" in a position that has legal moves
+    NoMove,
+    /// The engine returned a move that is not legal in the position
+    IllegalMove(String),
+    /// The engine's output could not be understood
+    Garbage(String),
+    /// The engine process went away
+    Terminated,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::GameOver => write!(f, "the game is already over"),
+            EngineError::NoMove => write!(f, "the engine returned no move"),
+            EngineError::IllegalMove(m) => write!(f, "the engine played an illegal move: {}", m),
+            EngineError::Garbage(line) => write!(f, "unexpected engine output: {}", line),
+            EngineError::Terminated => write!(f, "the engine exited unexpectedly"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// Default search used to pick a move
+const DEFAULT_DEPTH: u8 = 10;
+
+/// How far the engine searches when asked for a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchLimit {
+    Depth(u8),
+    /// Neural-network engines such as lc0 are limited by nodes rather than depth
+    Nodes(u64),
+    /// Fixed thinking time per move in milliseconds
+    MoveTime(u64),
+}
+
+impl SearchLimit {
+    fn go_command(&self) -> String {
+        match self {
+            SearchLimit::Depth(depth) => format!("go depth {}", depth),
+            SearchLimit::Nodes(nodes) => format!("go nodes {}", nodes),
+            SearchLimit::MoveTime(ms) => format!("go movetime {}", ms),
+        }
+    }
+}
+
+/// Engine configuration from the command line, applied to every engine
+/// started with `StockfishEngine::new`
+#[derive(Debug, Clone, Default)]
+pub struct EngineSettings {
+    /// Network file for lc0 (`WeightsFile` option)
+    pub weights: Option<String>,
+    /// lc0 computation backend (cuda, opencl, blas, ...)
+    pub backend: Option<String>,
+    /// Search by node count instead of depth
+    pub nodes: Option<u64>,
+    /// Play at full skill with this much time per move, in milliseconds
+    pub movetime: Option<u64>,
+    /// Hash table size in MB to switch to before analyzing a finished game
+    pub analysis_hash: Option<u32>,
+}
+
+static SETTINGS: OnceLock<EngineSettings> = OnceLock::new();
+
+/// Set the engine settings for this run. Must be called before any engine is started.
+pub fn configure(settings: EngineSettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+fn settings() -> &'static EngineSettings {
+    SETTINGS.get_or_init(EngineSettings::default)
+}
+
+/// Options that change how the engine plays or searches but not what it
+/// finds at a given depth; setting them keeps the position cache
+const CACHE_NEUTRAL_OPTIONS: [&str; 8] = [
+    "MultiPV",
+    "Skill Level",
+    "UCI_LimitStrength",
+    "UCI_Elo",
+    "Threads",
+    "Hash",
+    "Ponder",
+    "Move Overhead",
+];
+
+/// Outcome of a single `go` command
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    pub score: Option<Score>,
+    pub depth: u32,
+    /// Nodes searched, as last reported
+    pub nodes: u64,
+    pub pv: Vec<ChessMove>,
+}
+
 pub struct StockfishEngine {
-    process: Child,
-    reader: BufReader<tokio::process::ChildStdout>,
+    process: Option<Child>, // None when talking to an engine over TCP
+    reader: Box<dyn AsyncBufRead + Unpin + Send>,
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    path: String,         // How the engine was started, to spawn more instances
+    name: String,         // From "id name"
+    options: Vec<String>, // Names of the UCI options the engine supports
+    limit: SearchLimit,
+    skill: Option<u8>, // Skill Level set by set_difficulty, if any
+    elo: Option<u32>,  // UCI_Elo set by set_elo, if any
+    clock: Option<(Duration, Duration)>, // White's and Black's remaining time, in timed games
+    progress: bool,    // Show a live status line while searching
+    cache: PositionCache, // Full-strength depth searches already done
+    analysis_hash: bool, // Hash already raised by prepare_for_analysis
+    last_score: Option<Score>, // Score of the last game move searched for
+    settings: EngineSettings, // Settings the engine was started with
+}
+
+/// The program to run for a local engine path. On Windows a path may leave
+/// out ".exe", as the same path would on other systems.
+fn engine_program(path: &str) -> PathBuf {
+    let program = PathBuf::from(path);
+    if cfg!(windows) && !program.is_file() {
+        let exe = PathBuf::from(format!("{}.exe", path));
+        if exe.is_file() {
+            return exe;
+        }
+    }
+    program
 }
 
 impl StockfishEngine {
+    /// Start an engine. Besides a local path, the engine can be given as
+    /// `ssh:user@host:/path/to/stockfish` (run remotely through ssh) or
+    /// `tcp:host:port` (a UCI engine exposed on a socket).
     pub async fn new(stockfish_path: &str) -> Result<Self> {
-        let mut process = Command::new(stockfish_path)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| {
+        Self::with_settings(stockfish_path, settings().clone()).await
+    }
+
+    /// Start an engine with its own settings instead of the run's, such as
+    /// the `--opponent` network
+    pub async fn with_settings(stockfish_path: &str, settings: EngineSettings) -> Result<Self> {
+        let mut engine = if let Some(address) = stockfish_path.strip_prefix("tcp:") {
+            let stream = TcpStream::connect(address)
+                .await
+                .map_err(|e| anyhow!("Failed to connect to engine at {}: {}", address, e))?;
+            let (read_half, write_half) = stream.into_split();
+            StockfishEngine {
+                process: None,
+                reader: Box::new(BufReader::new(read_half)),
+                writer: Box::new(write_half),
+                path: String::new(),
+                name: String::new(),
+                options: Vec::new(),
+                limit: SearchLimit::Depth(DEFAULT_DEPTH),
+                skill: None,
+                elo: None,
+                clock: None,
+                progress: false,
+                cache: PositionCache::default(),
+                analysis_hash: false,
+                last_score: None,
+                settings: EngineSettings::default(),
+            }
+        } else if let Some(remote) = stockfish_path.strip_prefix("ssh:") {
+            let (host, path) = remote.split_once(':').ok_or_else(|| {
+                anyhow!(
+                    "Invalid ssh engine '{}', expected ssh:user@host:/path/to/engine",
+                    stockfish_path
+                )
+            })?;
+            let mut command = Command::new("ssh");
+            command.arg("-T").arg(host).arg(path);
+            Self::spawn(command).map_err(|e| {
+                anyhow!(
+                    "Failed to start remote engine through ssh on {}: {}",
+                    host,
+                    e
+                )
+            })?
+        } else {
+            Self::spawn(Command::new(engine_program(stockfish_path))).map_err(|e| {
                 anyhow!(
                     "Failed to start Stockfish: {}. Make sure Stockfish is installed and in PATH",
                     e
                 )
-            })?;
+            })?
+        };
 
-        let stdout = process
-            .stdout
-            .take()
-            .ok_or_else(|| anyhow!("Failed to get stdout from Stockfish"))?;
-
-        let reader = BufReader::new(stdout);
-        let mut engine = StockfishEngine { process, reader };
+        engine.path = stockfish_path.to_string();
+        engine.settings = settings;
 
         // Initialize UCI
         engine.send_command("uci").await?;
-        engine.wait_for_response("uciok").await?;
+        engine.read_identity().await?;
 
-        // Set up the engine
+        if let Some(weights) = engine.settings.weights.clone() {
+            engine.set_option("WeightsFile", &weights).await?;
+        }
+        if let Some(backend) = engine.settings.backend.clone() {
+            engine.set_option("Backend", &backend).await?;
+        }
+        if let Some(ms) = engine.settings.movetime {
+            engine.limit = SearchLimit::MoveTime(ms);
+        } else if let Some(nodes) = engine.settings.nodes {
+            engine.limit = SearchLimit::Nodes(nodes);
+        } else if engine.is_lc0() {
+            engine.limit = SearchLimit::Nodes(800);
+        }
+
+        // lc0 loads its network here, which can take a long time on a slow backend
+        if engine.is_lc0() {
+            println!("Loading {} network, this may take a while...", engine.name);
+        }
         engine.send_command("isready").await?;
         engine.wait_for_response("readyok").await?;
 
         Ok(engine)
     }
 
+    /// Read the engine's name and option list up to "uciok"
+    async fn read_identity(&mut self) -> Result<()> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line).await? == 0 {
+                return Err(anyhow!(
+                    "Engine closed its output while waiting for 'uciok'"
+                ));
+            }
+
+            let line = line.trim();
+            if line == "uciok" {
+                return Ok(());
+            } else if let Some(name) = line.strip_prefix("id name ") {
+                self.name = name.to_string();
+            } else if let Some(option) = line.strip_prefix("option name ") {
+                // The name runs up to the " type " keyword and may contain spaces
+                let name = option.split(" type ").next().unwrap_or(option);
+                self.options.push(name.to_string());
+            }
+        }
+    }
+
+    /// The engine's name as reported by `id name`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The difficulty level set by `set_difficulty`, if the engine took it
+    pub fn skill(&self) -> Option<u8> {
+        self.skill
+    }
+
+    /// How the engine is limited, for game records, e.g. "Skill Level 5, depth 10"
+    pub fn settings_summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(skill) = self.skill {
+            parts.push(format!("Skill Level {}", skill));
+        }
+        if let Some(elo) = self.elo {
+            parts.push(format!("UCI_Elo {}", elo));
+        }
+        parts.push(match self.limit {
+            SearchLimit::Depth(depth) => format!("depth {}", depth),
+            SearchLimit::Nodes(nodes) => format!("{} nodes", nodes),
+            SearchLimit::MoveTime(ms) => format!("{} ms per move", ms),
+        });
+        parts.join(", ")
+    }
+
+    /// Tell the engine a new game starts, so it forgets the last one's
+    /// search history, and wait until it is ready. Sent only at game
+    /// boundaries: between the moves of a game the hash table carries over.
+    pub async fn new_game(&mut self) -> Result<()> {
+        self.send_command("ucinewgame").await?;
+        self.send_command("isready").await?;
+        self.wait_for_response("readyok").await
+    }
+
+    /// Switch to the `--analysis-hash` table size before analyzing a
+    /// finished game. Resizing clears the table, so it is done only once.
+    pub async fn prepare_for_analysis(&mut self) -> Result<()> {
+        let Some(mb) = self.settings.analysis_hash else {
+            return Ok(());
+        };
+        if self.analysis_hash || !self.supports_option("Hash") {
+            return Ok(());
+        }
+        self.set_option("Hash", &mb.to_string()).await?;
+        self.send_command("isready").await?;
+        self.wait_for_response("readyok").await?;
+        self.analysis_hash = true;
+        Ok(())
+    }
+
+    /// Round trip of an `isready`/`readyok` exchange
+    pub async fn ping(&mut self) -> Result<Duration> {
+        let started = Instant::now();
+        self.send_command("isready").await?;
+        self.wait_for_response("readyok").await?;
+        Ok(started.elapsed())
+    }
+
+    /// The path (or ssh:/tcp: address) the engine was started from
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Leela Chess Zero and derived engines (Maia) need nodes-based searches
+    pub fn is_lc0(&self) -> bool {
+        let name = self.name.to_lowercase();
+        name.contains("lc0") || name.contains("leela")
+    }
+
+    pub fn supports_option(&self, name: &str) -> bool {
+        self.options.iter().any(|o| o.eq_ignore_ascii_case(name))
+    }
+
+    pub async fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        if !self.options.is_empty() && !self.supports_option(name) {
+            return Err(anyhow!("Engine '{}' has no option '{}'", self.name, name));
+        }
+        if !CACHE_NEUTRAL_OPTIONS
+            .iter()
+            .any(|option| option.eq_ignore_ascii_case(name))
+        {
+            self.cache.clear();
+        }
+        self.send_command(&format!("setoption name {} value {}", name, value))
+            .await
+    }
+
+    fn spawn(mut command: Command) -> Result<Self> {
+        let mut process = command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+
+        let stdout = process
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to get stdout from Stockfish"))?;
+        let stdin = process
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to get stdin from Stockfish"))?;
+
+        Ok(StockfishEngine {
+            process: Some(process),
+            reader: Box::new(BufReader::new(stdout)),
+            writer: Box::new(stdin),
+            path: String::new(),
+            name: String::new(),
+            options: Vec::new(),
+            limit: SearchLimit::Depth(DEFAULT_DEPTH),
+            skill: None,
+            elo: None,
+            clock: None,
+            progress: false,
+            cache: PositionCache::default(),
+            analysis_hash: false,
+            last_score: None,
+            settings: EngineSettings::default(),
+        })
+    }
+
     pub async fn set_difficulty(&mut self, level: u8) -> Result<()> {
         let level = level.clamp(1, 20);
-        self.send_command(&format!("setoption name Skill Level value {}", level))
-            .await?;
+        if self.settings.movetime.is_some() {
+            // The time handicap replaces the skill setting
+            return Ok(());
+        }
+        if self.supports_option("Skill Level") || self.options.is_empty() {
+            self.send_command(&format!("setoption name Skill Level value {}", level))
+                .await?;
+            self.skill = Some(level);
+        } else if self.settings.nodes.is_none() {
+            // Engines without a skill setting (lc0) are weakened by searching fewer nodes
+            self.limit = SearchLimit::Nodes(1 << (level - 1));
+        }
         Ok(())
     }
 
+    /// Search at least `depth` plies per move. Engines limited by nodes or
+    /// move time keep their limit.
+    pub fn set_min_depth(&mut self, depth: u8) {
+        if let SearchLimit::Depth(current) = self.limit {
+            self.limit = SearchLimit::Depth(current.max(depth));
+        }
+    }
+
+    /// Limit the engine to a playing strength in Elo through UCI_LimitStrength
+    pub async fn set_elo(&mut self, elo: u32) -> Result<()> {
+        if !self.supports_option("UCI_Elo") {
+            return Err(anyhow!(
+                "Engine '{}' cannot be limited to an Elo rating",
+                self.name
+            ));
+        }
+        self.set_option("UCI_LimitStrength", "true").await?;
+        self.set_option("UCI_Elo", &elo.to_string()).await?;
+        self.elo = Some(elo);
+        Ok(())
+    }
+
+    /// Remaining time on both clocks, passed along with every `get_best_move`
+    /// so the engine budgets its time in timed games
+    pub fn set_clock(&mut self, white: Duration, black: Duration) {
+        self.clock = Some((white, black));
+    }
+
+    /// Show elapsed time, depth and best move so far while searching. Only
+    /// takes effect when standard output is a terminal.
+    pub fn show_progress(&mut self, enabled: bool) {
+        self.progress = enabled;
+    }
+
+    /// Search at full strength, even if the engine has been weakened to play
+    /// the game; used for analysis features like threats and hints. A
+    /// `go depth N` search of a position already searched at least that deep
+    /// is answered from the position cache.
+    pub async fn analyze(&mut self, position: &Board, go_command: &str) -> Result<SearchResult> {
+        let depth = go_command
+            .strip_prefix("go depth ")
+            .and_then(|depth| depth.trim().parse::<u32>().ok());
+        if let Some(depth) = depth
+            && let Some(result) = self.cache.get(position, depth)
+        {
+            return Ok(result);
+        }
+        let result = self.analyze_uncached(position, go_command).await?;
+        if let Some(depth) = depth {
+            self.cache.insert(position, depth, &result);
+        }
+        Ok(result)
+    }
+
+    async fn analyze_uncached(
+        &mut self,
+        position: &Board,
+        go_command: &str,
+    ) -> Result<SearchResult> {
+        let skill = self.skill.filter(|level| *level < 20);
+        if skill.is_none() && self.elo.is_none() {
+            return self.search(position, go_command).await;
+        }
+
+        if skill.is_some() {
+            self.send_command("setoption name Skill Level value 20")
+                .await?;
+        }
+        if self.elo.is_some() {
+            self.send_command("setoption name UCI_LimitStrength value false")
+                .await?;
+        }
+        let result = self.search(position, go_command).await;
+        if let Some(skill) = skill {
+            self.send_command(&format!("setoption name Skill Level value {}", skill))
+                .await?;
+        }
+        if self.elo.is_some() {
+            self.send_command("setoption name UCI_LimitStrength value true")
+                .await?;
+        }
+        result
+    }
+
+    /// Ask for a move in the current position. Failures come back as an
+    /// `EngineError` so callers can turn them into a game result.
     pub async fn get_best_move(&mut self, position: &Board) -> Result<ChessMove> {
+        if position.status() != BoardStatus::Ongoing {
+            return Err(EngineError::GameOver.into());
+        }
+
+        let go = self.move_go_command();
+        let result = self.search(position, &go).await?;
+        self.last_score = result.score;
+        match result.best_move {
+            Some(chess_move) if position.legal(chess_move) => Ok(chess_move),
+            Some(chess_move) => Err(EngineError::IllegalMove(chess_move.to_string()).into()),
+            None => Err(EngineError::NoMove.into()),
+        }
+    }
+
+    /// Like `get_best_move`, but the engine may only choose among `moves`,
+    /// passed to it as `searchmoves`
+    pub async fn get_best_move_among(
+        &mut self,
+        position: &Board,
+        moves: &[ChessMove],
+    ) -> Result<ChessMove> {
+        let searchmoves: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        let go = format!(
+            "{} searchmoves {}",
+            self.move_go_command(),
+            searchmoves.join(" ")
+        );
+        let result = self.search(position, &go).await?;
+        self.last_score = result.score;
+        match result.best_move {
+            Some(chess_move) if moves.contains(&chess_move) => Ok(chess_move),
+            Some(chess_move) => Err(EngineError::IllegalMove(chess_move.to_string()).into()),
+            None => Err(EngineError::NoMove.into()),
+        }
+    }
+
+    /// The engine's score for the move it last chose in a game, relative to
+    /// the side it moved for. None once taken, or when the move came from
+    /// somewhere else, like a book.
+    pub fn take_last_score(&mut self) -> Option<Score> {
+        self.last_score.take()
+    }
+
+    /// The `go` command for a game move: the engine stops at its depth or
+    /// node limit, or earlier if the clock demands it
+    fn move_go_command(&self) -> String {
+        let mut go = self.limit.go_command();
+        if let Some((white, black)) = self.clock {
+            go.push_str(&format!(
+                " wtime {} btime {}",
+                white.as_millis(),
+                black.as_millis()
+            ));
+        }
+        go
+    }
+
+    /// Search for a game move with `MultiPV` set to `lines`, returning the
+    /// engine's top moves with their scores, best first. Engines without the
+    /// option give just their best move.
+    pub async fn candidate_moves(
+        &mut self,
+        position: &Board,
+        lines: usize,
+    ) -> Result<Vec<(ChessMove, Score)>> {
+        if position.status() != BoardStatus::Ongoing {
+            return Err(EngineError::GameOver.into());
+        }
+        if lines <= 1 || !self.supports_option("MultiPV") {
+            let best = self.get_best_move(position).await?;
+            return Ok(vec![(best, Score::Centipawns(0))]);
+        }
+
+        self.set_option("MultiPV", &lines.to_string()).await?;
+        self.send_command(&format!("position fen {}", position))
+            .await?;
+        let go = self.move_go_command();
+        self.send_command(&go).await?;
+
+        // Latest report of each line, indexed by `multipv` number
+        let mut reports: Vec<Option<SearchResult>> = (0..lines).map(|_| None).collect();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line).await? == 0 {
+                return Err(EngineError::Terminated.into());
+            }
+            if line.starts_with("info") {
+                let index = multipv_index(&line);
+                let mut result = SearchResult {
+                    best_move: None,
+                    score: None,
+                    depth: 0,
+                    nodes: 0,
+                    pv: Vec::new(),
+                };
+                parse_info_line(&line, &mut result);
+                if result.score.is_some()
+                    && !result.pv.is_empty()
+                    && let Some(report) = reports.get_mut(index)
+                {
+                    *report = Some(result);
+                }
+            } else if line.starts_with("bestmove") {
+                break;
+            }
+        }
+        self.set_option("MultiPV", "1").await?;
+
+        let candidates: Vec<(ChessMove, Score)> = reports
+            .into_iter()
+            .flatten()
+            .filter_map(|report| Some((*report.pv.first()?, report.score?)))
+            .filter(|(chess_move, _)| position.legal(*chess_move))
+            .collect();
+        if candidates.is_empty() {
+            return Err(EngineError::NoMove.into());
+        }
+        Ok(candidates)
+    }
+
+    /// Evaluate a position at the given depth, from the side to move's point
+    /// of view. The score doesn't depend on the skill setting, so cached
+    /// analysis answers too.
+    pub async fn evaluate(&mut self, position: &Board, depth: u8) -> Result<Score> {
+        let result = match self.cache.get(position, depth.into()) {
+            Some(result) => result,
+            None => {
+                let result = self
+                    .search(position, &format!("go depth {}", depth))
+                    .await?;
+                self.cache.insert(position, depth.into(), &result);
+                result
+            }
+        };
+        result
+            .score
+            .ok_or_else(|| anyhow!("Stockfish did not report a score"))
+    }
+
+    /// Run a search and collect the final `info` line along with `bestmove`
+    pub async fn search(&mut self, position: &Board, go_command: &str) -> Result<SearchResult> {
         // Set up position
         let fen = position.to_string();
         self.send_command(&format!("position fen {}", fen)).await?;
 
-        // Request best move
-        self.send_command("go depth 10").await?;
+        // Start the search
+        self.send_command(go_command).await?;
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: None,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+
+        let status_line =
+            (self.progress && std::io::stdout().is_terminal()).then(StatusLine::start);
+        let started = Instant::now();
 
         // Wait for bestmove response
         let mut line = String::new();
         loop {
             line.clear();
-            self.reader.read_line(&mut line).await?;
+            if self.read_line(&mut line).await? == 0 {
+                return Err(EngineError::Terminated.into());
+            }
 
-            if line.starts_with("bestmove") {
+            if line.starts_with("info") {
+                parse_info_line(&line, &mut result);
+                if let Some(status_line) = &status_line
+                    && result.depth > 0
+                {
+                    status_line.update(&format_progress(
+                        position,
+                        &result,
+                        started.elapsed().as_secs_f64(),
+                    ));
+                }
+            } else if line.starts_with("bestmove") {
+                // Clears the status line
+                drop(status_line);
                 let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    let move_str = parts[1];
-                    return ChessMove::from_str(move_str)
-                        .map_err(|_| anyhow!("Invalid move from Stockfish: {}", move_str));
+                match parts.get(1) {
+                    Some(&"(none)") => {}
+                    Some(move_str) => {
+                        result.best_move = Some(
+                            ChessMove::from_str(move_str)
+                                .map_err(|_| EngineError::Garbage(line.trim().to_string()))?,
+                        );
+                    }
+                    None => return Err(EngineError::Garbage(line.trim().to_string()).into()),
                 }
+                return Ok(result);
             }
         }
     }
 
+    /// Analyze with `go infinite` until `stop` completes, calling `on_update`
+    /// each time the engine reports a new score. Returns the value `stop`
+    /// produced once the engine has acknowledged with `bestmove`.
+    pub async fn analyze_until<T>(
+        &mut self,
+        position: &Board,
+        stop: impl Future<Output = T>,
+        mut on_update: impl FnMut(&SearchResult),
+    ) -> Result<T> {
+        self.send_command(&format!("position fen {}", position))
+            .await?;
+        self.send_command("go infinite").await?;
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: None,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        tokio::pin!(stop);
+
+        // `next_line` is cancel safe, so no output is lost when `stop` wins the race
+        let mut lines = (&mut self.reader).lines();
+        let mut finished = false;
+        let value = loop {
+            tokio::select! {
+                value = &mut stop => break value,
+                line = lines.next_line(), if !finished => {
+                    let Some(line) = line? else {
+                        return Err(EngineError::Terminated.into());
+                    };
+                    if verbosity() == Verbosity::Verbose {
+                        eprintln!("< {}", line);
+                    }
+                    if line.starts_with("info") {
+                        parse_info_line(&line, &mut result);
+                        if line.contains(" score ") && line.contains(" pv ") {
+                            on_update(&result);
+                        }
+                    } else if line.starts_with("bestmove") {
+                        // Nothing left to search (mate or stalemate on the board)
+                        finished = true;
+                    }
+                }
+            }
+        };
+
+        if !finished {
+            self.send_command("stop").await?;
+            self.wait_for_prefix("bestmove").await?;
+        }
+        Ok(value)
+    }
+
+    /// Output of Stockfish's (non-UCI) `eval` command for a position. The
+    /// command has no end marker, so `isready` is sent after it and
+    /// everything up to `readyok` is collected.
+    pub async fn static_eval(&mut self, position: &Board) -> Result<Vec<String>> {
+        self.send_command(&format!("position fen {}", position))
+            .await?;
+        self.send_command("eval").await?;
+        self.send_command("isready").await?;
+
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line).await? == 0 {
+                return Err(EngineError::Terminated.into());
+            }
+            if line.trim() == "readyok" {
+                return Ok(lines);
+            }
+            lines.push(line.trim_end().to_string());
+        }
+    }
+
     async fn send_command(&mut self, command: &str) -> Result<()> {
-        if let Some(stdin) = self.process.stdin.as_mut() {
-            stdin.write_all(format!("{}\n", command).as_bytes()).await?;
-            stdin.flush().await?;
+        if verbosity() == Verbosity::Verbose {
+            eprintln!("> {}", command);
         }
+        crash::log_uci('>', command);
+        self.writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .await?;
+        self.writer.flush().await?;
         Ok(())
     }
 
+    /// Read one line of engine output, returning 0 at the end of the output
+    async fn read_line(&mut self, line: &mut String) -> Result<usize> {
+        let read = self.reader.read_line(line).await?;
+        if read > 0 && verbosity() == Verbosity::Verbose {
+            eprintln!("< {}", line.trim_end());
+        }
+        if read > 0 {
+            crash::log_uci('<', line);
+        }
+        Ok(read)
+    }
+
+    async fn wait_for_prefix(&mut self, prefix: &str) -> Result<()> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.read_line(&mut line).await? == 0 {
+                return Err(EngineError::Terminated.into());
+            }
+            if line.starts_with(prefix) {
+                return Ok(());
+            }
+        }
+    }
+
     async fn wait_for_response(&mut self, expected: &str) -> Result<()> {
         let mut line = String::new();
         loop {
             line.clear();
-            self.reader.read_line(&mut line).await?;
+            if self.read_line(&mut line).await? == 0 {
+                return Err(anyhow!(
+                    "Engine closed its output while waiting for '{}'",
+                    expected
+                ));
+            }
 
             if line.trim() == expected {
                 break;
@@ -96,9 +872,79 @@ impl StockfishEngine {
     }
 }
 
+fn format_progress(position: &Board, result: &SearchResult, elapsed: f64) -> String {
+    let best = result
+        .pv
+        .first()
+        .filter(|m| position.legal(**m))
+        .map(|m| pgn::move_to_san(position, *m))
+        .unwrap_or_default();
+    let score = result.score.map(|s| s.to_string()).unwrap_or_default();
+    format!(
+        "  Thinking... {:.1}s  depth {}  {} {}",
+        elapsed, result.depth, best, score
+    )
+}
+
+/// 0-based line number of an `info` line from a `MultiPV` search
+fn multipv_index(line: &str) -> usize {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    tokens
+        .iter()
+        .position(|t| *t == "multipv")
+        .and_then(|i| tokens.get(i + 1))
+        .and_then(|t| t.parse::<usize>().ok())
+        .map_or(0, |n| n.saturating_sub(1))
+}
+
+fn parse_info_line(line: &str, result: &mut SearchResult) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if !tokens.contains(&"score") {
+        // Skip "info string" and currmove updates
+        return;
+    }
+
+    let mut i = 1;
+    while i < tokens.len() {
+        match tokens[i] {
+            "depth" => {
+                if let Some(depth) = tokens.get(i + 1).and_then(|t| t.parse().ok()) {
+                    result.depth = depth;
+                }
+                i += 2;
+            }
+            "score" => {
+                let value = tokens.get(i + 2).and_then(|t| t.parse().ok());
+                result.score = match (tokens.get(i + 1), value) {
+                    (Some(&"cp"), Some(cp)) => Some(Score::Centipawns(cp)),
+                    (Some(&"mate"), Some(moves)) => Some(Score::Mate(moves)),
+                    _ => result.score,
+                };
+                i += 3;
+            }
+            "nodes" => {
+                if let Some(nodes) = tokens.get(i + 1).and_then(|t| t.parse().ok()) {
+                    result.nodes = nodes;
+                }
+                i += 2;
+            }
+            "pv" => {
+                result.pv = tokens[i + 1..]
+                    .iter()
+                    .map_while(|t| ChessMove::from_str(t).ok())
+                    .collect();
+                break;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
 impl Drop for StockfishEngine {
     fn drop(&mut self) {
         // Kill the process when the engine is dropped
-        let _ = self.process.start_kill();
+        if let Some(process) = self.process.as_mut() {
+            let _ = process.start_kill();
+        }
     }
 }
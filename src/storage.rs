@@ -0,0 +1,116 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Directory holding all persistent data. Defaults to `~/.minichess`,
+/// overridable with the MINICHESS_HOME environment variable.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Ok(dir) = std::env::var("MINICHESS_HOME") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .map_err(|_| anyhow!("Cannot locate home directory, set MINICHESS_HOME"))?;
+    Ok(PathBuf::from(home).join(".minichess"))
+}
+
+/// Choose the player profile used for every later load and save.
+/// Must be called before any data is accessed; defaults to "default".
+pub fn select_profile(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid player name '{}': use letters, digits, '-' or '_'",
+            name
+        ));
+    }
+
+    PROFILE
+        .set(name.to_string())
+        .map_err(|_| anyhow!("Player profile already selected"))
+}
+
+pub fn profile_name() -> &'static str {
+    PROFILE.get().map(|s| s.as_str()).unwrap_or(DEFAULT_PROFILE)
+}
+
+/// Directory holding the data of the selected player profile
+pub fn profile_dir() -> Result<PathBuf> {
+    let dir = data_dir()?.join("profiles").join(profile_name());
+    if profile_name() == DEFAULT_PROFILE && !dir.exists() {
+        migrate_legacy_files(&dir)?;
+    }
+    Ok(dir)
+}
+
+/// Data written before profiles existed lives directly in the data directory;
+/// it becomes the default profile
+fn migrate_legacy_files(profile_dir: &Path) -> Result<()> {
+    let root = data_dir()?;
+    let Ok(entries) = fs::read_dir(&root) else {
+        return Ok(());
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            fs::create_dir_all(profile_dir)?;
+            fs::rename(&path, profile_dir.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Names of all profiles that have stored data
+pub fn list_profiles() -> Result<Vec<String>> {
+    let Ok(entries) = fs::read_dir(data_dir()?.join("profiles")) else {
+        return Ok(Vec::new());
+    };
+
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Load a JSON data file, returning the default value if it doesn't exist yet
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let path = profile_dir()?.join(name);
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let text = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| anyhow!("Corrupt data file {}: {}", path.display(), e))
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let dir = profile_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| anyhow!("Failed to create data directory {}: {}", dir.display(), e))?;
+
+    // Write to a temporary file first so a crash never leaves a truncated file behind
+    let path = dir.join(name);
+    let tmp_path = dir.join(format!("{}.tmp", name));
+    let text = serde_json::to_string_pretty(value)?;
+    fs::write(&tmp_path, text)
+        .map_err(|e| anyhow!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, &path)
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::io::{IsTerminal, Write};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Terminal state changed by the program, so it can be undone on a panic
+static CURSOR_HIDDEN: AtomicBool = AtomicBool::new(false);
+static LINE_IN_USE: AtomicBool = AtomicBool::new(false);
+static TITLE_SAVED: AtomicBool = AtomicBool::new(false);
+
+static SHOW_TITLE: OnceLock<bool> = OnceLock::new();
+static CONSOLE: OnceLock<ConsoleSupport> = OnceLock::new();
+
+/// What the console can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleSupport {
+    /// Escape codes for colors and cursor movement are interpreted
+    pub ansi: bool,
+    /// Chess symbols can be shown
+    pub unicode: bool,
+}
+
+/// Prepare the console for colors and Unicode. Unix terminals need
+/// nothing; the Windows console is switched to virtual-terminal processing
+/// and the UTF-8 code page. Called once from main.
+pub fn setup_console() -> ConsoleSupport {
+    *CONSOLE.get_or_init(|| {
+        #[cfg(windows)]
+        {
+            windows::setup()
+        }
+        #[cfg(not(windows))]
+        {
+            ConsoleSupport {
+                ansi: true,
+                unicode: true,
+            }
+        }
+    })
+}
+
+/// What `setup_console` found
+pub fn console() -> ConsoleSupport {
+    setup_console()
+}
+
+/// Columns a chess symbol takes when the board is drawn, guessed from the
+/// environment: Windows Terminal and CJK locales render the symbols
+/// double-width. `--piece-width` overrides the guess.
+pub fn detect_piece_width() -> usize {
+    if std::env::var_os("WT_SESSION").is_some() {
+        return 2;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    if ["zh", "ja", "ko"]
+        .iter()
+        .any(|lang| locale.starts_with(lang))
+    {
+        2
+    } else {
+        1
+    }
+}
+
+/// Show the game state in the terminal window title. Called once from main.
+pub fn set_show_title(enabled: bool) {
+    let _ = SHOW_TITLE.set(enabled);
+}
+
+pub fn show_title() -> bool {
+    SHOW_TITLE.get().copied().unwrap_or(false) && std::io::stdout().is_terminal()
+}
+
+/// Restore the terminal before the default panic message is printed, so a
+/// crash mid-redraw doesn't leave a hidden cursor or colored text behind
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        restore_title();
+        default_hook(info);
+    }));
+}
+
+/// Undo everything the program may have changed: clear an in-place status
+/// line, reset colors and show the cursor
+pub fn restore() {
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return;
+    }
+    if LINE_IN_USE.swap(false, Ordering::SeqCst) {
+        let _ = write!(stdout, "\r\x1b[K");
+    }
+    let _ = write!(stdout, "\x1b[0m");
+    if CURSOR_HIDDEN.swap(false, Ordering::SeqCst) {
+        let _ = write!(stdout, "\x1b[?25h");
+    }
+    let _ = stdout.flush();
+}
+
+/// A line redrawn in place (like the engine's thinking indicator) with the
+/// cursor hidden. Dropping the guard clears the line and shows the cursor
+/// again, also when the owner returns early with an error.
+pub struct StatusLine;
+
+impl StatusLine {
+    pub fn start() -> Self {
+        CURSOR_HIDDEN.store(true, Ordering::SeqCst);
+        LINE_IN_USE.store(true, Ordering::SeqCst);
+        print!("\x1b[?25l");
+        let _ = std::io::stdout().flush();
+        StatusLine
+    }
+
+    pub fn update(&self, text: &str) {
+        print!("\r\x1b[K{}", text);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+impl Drop for StatusLine {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Set the window title. The first call saves the current title on the
+/// terminal's title stack so `restore_title` can put it back.
+pub fn set_title(text: &str) {
+    if !show_title() {
+        return;
+    }
+    let mut stdout = std::io::stdout();
+    if !TITLE_SAVED.swap(true, Ordering::SeqCst) {
+        let _ = write!(stdout, "\x1b[22;0t");
+    }
+    let _ = write!(stdout, "\x1b]0;{}\x07", text);
+    let _ = stdout.flush();
+}
+
+/// Put back the title that was there before the first `set_title`
+pub fn restore_title() {
+    if TITLE_SAVED.swap(false, Ordering::SeqCst) {
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "\x1b[23;0t");
+        let _ = stdout.flush();
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::ConsoleSupport;
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const CP_UTF8: u32 = 65001;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+        fn GetConsoleOutputCP() -> u32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+
+    /// Turn on escape-code processing and the UTF-8 code page. Consoles that
+    /// refuse escape codes are the legacy ones (before Windows 10), whose
+    /// fonts lack chess symbols too, so they get letters instead.
+    pub fn setup() -> ConsoleSupport {
+        // SAFETY: plain Win32 calls on this process's own standard output
+        // handle, with a valid pointer for the mode
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            let mut mode = 0;
+            if handle.is_null() || GetConsoleMode(handle, &mut mode) == 0 {
+                // Not a console (a file, a pipe or a mintty pty): the bytes
+                // pass through and the other end decides
+                return ConsoleSupport {
+                    ansi: true,
+                    unicode: true,
+                };
+            }
+            let ansi = mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0;
+            let utf8 = GetConsoleOutputCP() == CP_UTF8 || SetConsoleOutputCP(CP_UTF8) != 0;
+            ConsoleSupport {
+                ansi,
+                unicode: ansi && utf8,
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{DateTime, Local};
+
+/// One thing that happened during a game
+pub enum Entry {
+    /// A line typed at the move prompt
+    Command(String),
+    /// A move in SAN with its number ("12." or "12...") and who played it
+    Move {
+        number: String,
+        san: String,
+        by: &'static str,
+    },
+    /// The board as it was shown, in plain text
+    Board(String),
+    /// Engine output worth keeping: hints, threats, analyses
+    Engine(String),
+    /// Takebacks, the result and other events
+    Event(String),
+}
+
+/// Log of a game as it was played, for `export transcript`
+#[derive(Default)]
+pub struct Transcript {
+    entries: Vec<(DateTime<Local>, Entry)>,
+}
+
+impl Transcript {
+    pub fn record(&mut self, entry: Entry) {
+        self.entries.push((Local::now(), entry));
+    }
+
+    /// The log under `title` and `header` lines, as Markdown with boards in
+    /// code blocks or as plain text
+    pub fn render(&self, title: &str, header: &[String], markdown: bool) -> String {
+        let mut out = Vec::new();
+        if markdown {
+            out.push(format!("# {}\n", title));
+            out.extend(header.iter().map(|line| format!("- {}", line)));
+        } else {
+            out.push(title.to_string());
+            out.push("=".repeat(title.chars().count()));
+            out.extend(header.iter().cloned());
+        }
+        out.push(String::new());
+
+        for (time, entry) in &self.entries {
+            let time = time.format("%H:%M:%S");
+            out.push(match (entry, markdown) {
+                (Entry::Command(text), true) => format!("**{}** `> {}`", time, text),
+                (Entry::Command(text), false) => format!("[{}] > {}", time, text),
+                (Entry::Move { number, san, by }, true) => {
+                    format!("**{}** {} **{}** ({})", time, number, san, by)
+                }
+                (Entry::Move { number, san, by }, false) => {
+                    format!("[{}] {} {} ({})", time, number, san, by)
+                }
+                (Entry::Board(board), true) => format!("```text\n{}\n```", board),
+                (Entry::Board(board), false) => board.clone(),
+                (Entry::Engine(text), true) => text
+                    .lines()
+                    .map(|line| format!("> {}", line))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                (Entry::Engine(text), false) => text
+                    .lines()
+                    .map(|line| format!("[{}] engine: {}", time, line))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                (Entry::Event(text), true) => format!("**{}** *{}*", time, text),
+                (Entry::Event(text), false) => format!("[{}] -- {}", time, text),
+            });
+            out.push(String::new());
+        }
+        out.join("\n")
+    }
+}
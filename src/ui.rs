@@ -1,82 +1,417 @@
-use chess::{Board, Color, Piece, Square};
+use crate::piece_set::PieceSet;
+use crate::stockfish::Score;
+use chess::{BitBoard, Board, BoardStatus, Color, Square};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{AsyncBufRead, Lines};
 
-pub fn display_board_for_player(board: &Board, player_color: Color) {
-    display_board_oriented(board, player_color);
+/// How much the CLI prints besides the essentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// No banners, no board redraws after moves, no engine progress
+    Quiet,
+    Normal,
+    /// Also echo the UCI conversation with engines to stderr
+    Verbose,
 }
 
-fn display_board_oriented(board: &Board, player_color: Color) {
-    if player_color == Color::White {
-        display_board_white_perspective(board);
-    } else {
-        display_board_black_perspective(board);
-    }
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+/// Set the output level for this run. Called once from main.
+pub fn set_verbosity(verbosity: Verbosity) {
+    let _ = VERBOSITY.set(verbosity);
 }
 
-fn display_board_white_perspective(board: &Board) {
-    println!("\n    a   b   c   d   e   f   g   h");
-    println!("  ┌───┬───┬───┬───┬───┬───┬───┬───┐");
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or(Verbosity::Normal)
+}
 
-    for rank in (0..8).rev() {
-        print!("{} │", rank + 1);
+/// How pieces are drawn on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardStyle {
+    #[default]
+    Unicode,
+    /// Letters, uppercase for White: for fonts without chess symbols
+    Ascii,
+    /// Glyphs of the config's `custom_pieces`
+    Custom,
+}
 
-        for file in 0..8 {
-            let square =
-                Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
+/// How the computer's moves are announced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notation {
+    #[default]
+    San,
+    Coordinate,
+}
 
-            let piece_char = get_piece_char(board, square);
-            print!(" {} │", piece_char);
-        }
+/// Where the board's rank and file labels go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Coordinates {
+    /// Files above and below the board, ranks on both sides
+    #[default]
+    All,
+    /// Files below and ranks on the left, as in printed diagrams
+    Edge,
+    /// In the corners of the edge squares themselves
+    Inside,
+    /// No labels, for board vision practice
+    Off,
+}
 
-        print!(" {}", rank + 1);
+impl FromStr for Coordinates {
+    type Err = anyhow::Error;
 
-        if rank > 0 {
-            println!("\n  ├───┼───┼───┼───┼───┼───┼───┼───┤");
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "all" => Ok(Coordinates::All),
+            "edge" => Ok(Coordinates::Edge),
+            "inside" => Ok(Coordinates::Inside),
+            "off" => Ok(Coordinates::Off),
+            _ => Err(anyhow::anyhow!("--coords must be all, edge, inside or off")),
         }
     }
+}
+
+static PIECE_SET: OnceLock<PieceSet> = OnceLock::new();
+static NOTATION: OnceLock<Notation> = OnceLock::new();
+static PIECE_WIDTH: OnceLock<usize> = OnceLock::new();
+static COORDINATES: OnceLock<Coordinates> = OnceLock::new();
+
+/// Columns between two of the board's vertical lines
+const CELL_WIDTH: usize = 3;
+
+/// Set the piece set and notation from the configuration. Called once from main.
+pub fn set_display_options(pieces: PieceSet, notation: Notation) {
+    let _ = PIECE_SET.set(pieces);
+    let _ = NOTATION.set(notation);
+}
 
-    println!("\n  └───┴───┴───┴───┴───┴───┴───┴───┘");
-    println!("    a   b   c   d   e   f   g   h\n");
+pub fn notation() -> Notation {
+    NOTATION.get().copied().unwrap_or_default()
+}
+
+/// Set how many columns the terminal uses for a piece glyph, 1 or 2.
+/// Called once from main.
+pub fn set_piece_width(width: usize) {
+    let _ = PIECE_WIDTH.set(width.clamp(1, 2));
+}
+
+pub fn piece_width() -> usize {
+    PIECE_WIDTH.get().copied().unwrap_or(1)
+}
+
+/// Set where the board's coordinates are drawn. Called once from main.
+pub fn set_coordinates(coordinates: Coordinates) {
+    let _ = COORDINATES.set(coordinates);
+}
 
+pub fn coordinates() -> Coordinates {
+    COORDINATES.get().copied().unwrap_or_default()
+}
+
+/// "White" or "Black"
+pub fn color_name(color: Color) -> &'static str {
+    if color == Color::White {
+        "White"
+    } else {
+        "Black"
+    }
+}
+
+/// Draw the board from the player's side. A king in check is shown on a red
+/// square and the pieces giving check on orange ones.
+pub fn display_board_for_player(board: &Board, player_color: Color) {
+    display_board_with_eval(board, player_color, None);
+}
+
+/// Like `display_board_for_player`, with an evaluation bar beside the board
+/// when `eval` (from White's point of view) is given
+pub fn display_board_with_eval(board: &Board, player_color: Color, eval: Option<Score>) {
+    let checkers = board.checkers();
+    let king_in_check = (checkers.popcnt() > 0).then(|| board.king_square(board.side_to_move()));
+    let cell = |square| {
+        let piece = get_piece_char(board, square);
+        if Some(square) == king_in_check {
+            format!("\x1b[48;5;196m\x1b[30m {} \x1b[0m", piece)
+        } else if (checkers & BitBoard::from_square(square)).popcnt() > 0 {
+            format!("\x1b[48;5;208m\x1b[30m {} \x1b[0m", piece)
+        } else {
+            format!(" {} ", piece)
+        }
+    };
+    draw_board(board, player_color, cell, eval);
+}
+
+/// End-of-game banner for a checkmate, with the final position and result
+pub fn display_checkmate(board: &Board, player_color: Color, headline: &str, result: &str) {
+    const WIDTH: usize = 36;
+    println!("\n╔{}╗", "═".repeat(WIDTH));
+    println!("║{:^width$}║", "CHECKMATE", width = WIDTH);
+    println!("║{:^width$}║", result, width = WIDTH);
+    println!("╚{}╝", "═".repeat(WIDTH));
+    display_board_for_player(board, player_color);
+    println!("{}", headline);
+}
+
+/// Draw the board from the player's side, letting `cell` render the three
+/// characters inside each square (piece, markers, colors for overlays)
+pub fn display_board_with(board: &Board, player_color: Color, cell: impl Fn(Square) -> String) {
+    draw_board(board, player_color, cell, None);
+}
+
+/// Draw a position the `chess` crate can't hold, such as a variant's, with
+/// `cell` rendering each square; no turn or check status is printed
+pub fn display_cells(player_color: Color, cell: impl Fn(Square) -> String) {
+    draw_grid(player_color, cell, None);
+}
+
+fn draw_board(
+    board: &Board,
+    player_color: Color,
+    cell: impl Fn(Square) -> String,
+    eval: Option<Score>,
+) {
+    draw_grid(player_color, cell, eval);
     display_game_status(board);
 }
 
-fn display_board_black_perspective(board: &Board) {
-    println!("\n    h   g   f   e   d   c   b   a");
-    println!("  ┌───┬───┬───┬───┬───┬───┬───┬───┐");
+fn draw_grid(player_color: Color, cell: impl Fn(Square) -> String, eval: Option<Score>) {
+    print!("{}", render_grid(player_color, cell, eval));
+}
 
-    for rank in 0..8 {
-        print!("{} │", rank + 1);
+/// The board's lines as `draw_grid` prints them
+fn render_grid(
+    player_color: Color,
+    cell: impl Fn(Square) -> String,
+    eval: Option<Score>,
+) -> String {
+    use std::fmt::Write as _;
+    let mut out = String::new();
+    let coordinates = coordinates();
+    let top_and_right = coordinates == Coordinates::All;
+    let left = matches!(coordinates, Coordinates::All | Coordinates::Edge);
+    let bottom = left;
+    // The bar runs beside the board's 17 lines, from the top border to the
+    // bottom one; lines without a rank label are padded to line up
+    let bar = |line: usize| match eval {
+        Some(eval) => format!(
+            "{}  {}",
+            if top_and_right && line.is_multiple_of(2) {
+                "  "
+            } else {
+                ""
+            },
+            eval_bar_cell(eval, player_color, line)
+        ),
+        None => String::new(),
+    };
+    let (ranks, files): (Vec<usize>, Vec<usize>) = if player_color == Color::White {
+        ((0..8).rev().collect(), (0..8).collect())
+    } else {
+        ((0..8).collect(), (0..8).rev().collect())
+    };
+    let pieces = piece_set();
+    let labels = files
+        .iter()
+        .map(|file| ((b'a' + *file as u8) as char).to_string())
+        .collect::<Vec<_>>()
+        .join("   ");
 
-        for file in (0..8).rev() {
-            let square =
-                Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file));
+    // Hidden labels leave their lines and columns blank, so the board
+    // stays where mouse clicks expect it
+    let _ = writeln!(
+        out,
+        "\n    {}",
+        if top_and_right { labels.as_str() } else { "" }
+    );
+    let _ = writeln!(out, "  ┌───┬───┬───┬───┬───┬───┬───┬───┐{}", bar(0));
 
-            let piece_char = get_piece_char(board, square);
-            print!(" {} │", piece_char);
+    for (row, rank) in ranks.iter().enumerate() {
+        if left {
+            let _ = write!(out, "{} │", rank + 1);
+        } else {
+            let _ = write!(out, "  │");
         }
 
-        print!(" {}", rank + 1);
+        for (column, file) in files.iter().enumerate() {
+            let square = Square::make_square(
+                chess::Rank::from_index(*rank),
+                chess::File::from_index(*file),
+            );
+            let mut text = fit_cell(cell(square), &pieces);
+            if coordinates == Coordinates::Inside {
+                text = label_cell(
+                    text,
+                    (column == 0).then(|| char::from(b'1' + *rank as u8)),
+                    (row == 7).then(|| char::from(b'a' + *file as u8)),
+                );
+            }
+            let _ = write!(out, "{}│", text);
+        }
+
+        if top_and_right {
+            let _ = write!(out, " {}", rank + 1);
+        }
+        let _ = write!(out, "{}", bar(1 + 2 * row));
 
-        if rank < 7 {
-            println!("\n  ├───┼───┼───┼───┼───┼───┼───┼───┤");
+        if row < 7 {
+            let _ = writeln!(
+                out,
+                "\n  ├───┼───┼───┼───┼───┼───┼───┼───┤{}",
+                bar(2 + 2 * row)
+            );
         }
     }
 
-    println!("\n  └───┴───┴───┴───┴───┴───┴───┴───┘");
-    println!("    h   g   f   e   d   c   b   a\n");
+    let _ = writeln!(out, "\n  └───┴───┴───┴───┴───┴───┴───┴───┘{}", bar(16));
+    let labels = if bottom {
+        labels
+    } else {
+        " ".repeat(labels.len())
+    };
+    let _ = match eval {
+        Some(eval) => writeln!(out, "    {}      {}\n", labels, eval),
+        None => writeln!(out, "    {}\n", labels.trim_end()),
+    };
+    out
+}
 
-    display_game_status(board);
+/// The position as plain text for logs: the board as `draw_grid` draws it
+/// from `player_color`'s side, without colors or highlights
+pub fn board_text(board: &Board, player_color: Color) -> String {
+    let grid = render_grid(
+        player_color,
+        |square| format!(" {} ", get_piece_char(board, square)),
+        None,
+    );
+    strip_escapes(&grid).trim_matches('\n').to_string()
+}
+
+/// Text with its terminal escape codes removed
+fn strip_escapes(text: &str) -> String {
+    let mut plain = String::new();
+    let mut in_escape = false;
+    for c in text.chars() {
+        if in_escape {
+            in_escape = !c.is_ascii_alphabetic();
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Write coordinates into a square's padding, `left` over its first column
+/// and `right` over its last where those are blank, dimmed so they don't
+/// read as pieces
+fn label_cell(text: String, left: Option<char>, right: Option<char>) -> String {
+    // Byte offsets of the visible characters, skipping escape codes
+    let mut visible = Vec::new();
+    let mut in_escape = false;
+    for (i, c) in text.char_indices() {
+        if in_escape {
+            in_escape = !c.is_ascii_alphabetic();
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            visible.push((i, c));
+        }
+    }
+    let dim = |label: char| format!("\x1b[2m{}\x1b[22m", label);
+    let mut text = text;
+    // Replacing from the back keeps the earlier offsets valid
+    if let (Some(label), [_, .., (i, ' ')]) = (right, visible.as_slice()) {
+        text.replace_range(*i..*i + 1, &dim(label));
+    }
+    if let (Some(label), [(i, ' '), ..]) = (left, visible.as_slice()) {
+        text.replace_range(*i..*i + 1, &dim(label));
+    }
+    text
 }
 
-fn get_piece_char(board: &Board, square: Square) -> char {
-    match board.piece_on(square) {
-        Some(piece) => {
-            let color = board.color_on(square).unwrap();
-            piece_to_unicode(piece, color)
+/// Drop padding spaces from a rendered square until it is `CELL_WIDTH`
+/// columns wide again, for terminals that draw the piece glyphs
+/// double-width. Escape codes take no columns; trailing spaces go first so
+/// the glyph stays next to the left edge, where it reads as centered.
+fn fit_cell(text: String, pieces: &PieceSet) -> String {
+    let glyph_width = piece_width();
+    if glyph_width == 1 {
+        return text;
+    }
+    let mut columns = 0;
+    let mut spaces = Vec::new();
+    let mut in_escape = false;
+    for (i, c) in text.char_indices() {
+        if in_escape {
+            in_escape = !c.is_ascii_alphabetic();
+            continue;
+        }
+        match c {
+            '\x1b' => in_escape = true,
+            ' ' => {
+                spaces.push(i);
+                columns += 1;
+            }
+            c if !c.is_ascii() && pieces.contains(c) => columns += glyph_width,
+            _ => columns += 1,
         }
-        None => ' ',
     }
+    let mut text = text;
+    // Removing from the back keeps the earlier offsets valid
+    while columns > CELL_WIDTH
+        && let Some(i) = spaces.pop()
+    {
+        text.remove(i);
+        columns -= 1;
+    }
+    text
+}
+
+/// One line of the evaluation bar: White's share is light and Black's dark,
+/// with the player's side at the bottom. A mate fills the whole bar.
+fn eval_bar_cell(eval: Score, player_color: Color, line: usize) -> &'static str {
+    const LINES: f64 = 17.0;
+    let white_share = match eval {
+        Score::Mate(moves) if moves > 0 => 1.0,
+        Score::Mate(_) => 0.0,
+        // Expected score from the centipawn advantage, as in the Elo formula
+        Score::Centipawns(cp) => 1.0 / (1.0 + 10f64.powf(-cp as f64 / 400.0)),
+    };
+    let bottom_share = if player_color == Color::White {
+        white_share
+    } else {
+        1.0 - white_share
+    };
+    let filled_from = LINES - (bottom_share * LINES).round();
+    let bottom_is_white = player_color == Color::White;
+    if ((line as f64) >= filled_from) == bottom_is_white {
+        "\x1b[47m  \x1b[0m"
+    } else {
+        "\x1b[100m  \x1b[0m"
+    }
+}
+
+pub fn get_piece_char(board: &Board, square: Square) -> char {
+    match (board.piece_on(square), board.color_on(square)) {
+        (Some(piece), Some(color)) => piece_set().glyph(piece, color),
+        _ => ' ',
+    }
+}
+
+/// The configured piece set, Unicode figurines by default
+pub fn piece_set() -> PieceSet {
+    PIECE_SET.get().cloned().unwrap_or_default()
 }
 
 fn display_game_status(board: &Board) {
@@ -89,58 +424,207 @@ fn display_game_status(board: &Board) {
     println!("{}'s turn to move", turn);
 
     // Show check status
-    if board.checkers().popcnt() > 0 {
+    if board.status() == BoardStatus::Checkmate {
+        println!("⚠️  {} is checkmated!", turn);
+    } else if board.checkers().popcnt() > 0 {
         println!("⚠️  {} is in check!", turn);
     }
 }
 
-fn piece_to_unicode(piece: Piece, color: Color) -> char {
-    match (piece, color) {
-        (Piece::King, Color::White) => '♔',
-        (Piece::Queen, Color::White) => '♕',
-        (Piece::Rook, Color::White) => '♖',
-        (Piece::Bishop, Color::White) => '♗',
-        (Piece::Knight, Color::White) => '♘',
-        (Piece::Pawn, Color::White) => '♙',
-        (Piece::King, Color::Black) => '♚',
-        (Piece::Queen, Color::Black) => '♛',
-        (Piece::Rook, Color::Black) => '♜',
-        (Piece::Bishop, Color::Black) => '♝',
-        (Piece::Knight, Color::Black) => '♞',
-        (Piece::Pawn, Color::Black) => '♟',
-    }
-}
-
 pub fn get_user_input() -> io::Result<String> {
     print!("> ");
     io::stdout().flush()?;
+    if let Some(command) = pending_macro_command() {
+        return Ok(command);
+    }
+    if let Some((_, lines)) = SHARED_INPUT.get() {
+        let lines = lines.lock().unwrap_or_else(|e| e.into_inner());
+        let (line, typed_elsewhere) = match lines.try_recv() {
+            Ok(line) => line,
+            Err(_) if STDIN_CLOSED.load(Ordering::SeqCst) => (String::new(), false),
+            Err(_) => lines.recv().unwrap_or_default(),
+        };
+        if typed_elsewhere {
+            println!("{}", line.trim_end());
+        }
+        return Ok(expand_macro(line));
+    }
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
-    Ok(input)
+    Ok(expand_macro(input))
+}
+
+/// Lines for the prompt from the keyboard and from elsewhere, such as the
+/// control socket, once input is shared; true marks a line not typed at the
+/// keyboard
+type InputLine = (String, bool);
+static SHARED_INPUT: OnceLock<(Sender<InputLine>, Mutex<Receiver<InputLine>>)> = OnceLock::new();
+static STDIN_CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Let other sources type at the prompt alongside the keyboard: a thread
+/// reads the keyboard from now on, so a prompt waiting for it also takes
+/// lines from `type_line`
+pub fn share_input() {
+    SHARED_INPUT.get_or_init(|| {
+        let (sender, receiver) = channel();
+        let keyboard = sender.clone();
+        std::thread::spawn(move || {
+            loop {
+                let mut line = String::new();
+                match io::stdin().read_line(&mut line) {
+                    Ok(0) | Err(_) => {
+                        // Wake a waiting prompt, which then sees end of input
+                        STDIN_CLOSED.store(true, Ordering::SeqCst);
+                        let _ = keyboard.send((String::new(), false));
+                        break;
+                    }
+                    Ok(_) if keyboard.send((line, false)).is_err() => break,
+                    Ok(_) => {}
+                }
+            }
+        });
+        (sender, Mutex::new(receiver))
+    });
+}
+
+/// Answer the next prompt with this line, as if it had been typed there.
+/// Ignored unless input is shared.
+pub fn type_line(line: &str) {
+    if let Some((sender, _)) = SHARED_INPUT.get() {
+        let _ = sender.send((line.to_string(), true));
+    }
+}
+
+static MACROS: OnceLock<HashMap<String, Vec<String>>> = OnceLock::new();
+/// Commands of the macro being run, still to be answered at the next prompts
+static PENDING_COMMANDS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Set the input macros from the configuration: each name stands for
+/// commands separated by `;`. Called once from main.
+pub fn set_macros(macros: &BTreeMap<String, String>) {
+    let macros = macros
+        .iter()
+        .map(|(name, commands)| {
+            let commands = commands
+                .split(';')
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty())
+                .collect();
+            (name.trim().to_string(), commands)
+        })
+        .collect();
+    let _ = MACROS.set(macros);
 }
 
-pub fn print_help() {
+/// Expand a typed line that is a macro's name: its first command is
+/// returned and the rest are queued for the following prompts. Queued
+/// commands are not expanded again, so macros can't loop.
+pub fn expand_macro(line: String) -> String {
+    let Some(commands) = MACROS.get().and_then(|macros| macros.get(line.trim())) else {
+        return line;
+    };
+    let mut pending = PENDING_COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+    pending.extend(commands.iter().skip(1).cloned());
+    let first = commands.first().cloned().unwrap_or_default();
+    if verbosity() > Verbosity::Quiet {
+        println!("({})", commands.join("; "));
+    }
+    first
+}
+
+/// Next line for a prompt that reads stdin asynchronously, such as the
+/// network, replay and analysis board prompts: a command left over from a
+/// macro, or else the next line typed, with macros expanded. None at the
+/// end of input.
+pub async fn next_input<R: AsyncBufRead + Unpin>(
+    lines: &mut Lines<R>,
+) -> io::Result<Option<String>> {
+    if let Some(command) = pending_macro_command() {
+        return Ok(Some(command));
+    }
+    Ok(lines.next_line().await?.map(expand_macro))
+}
+
+/// The next command left over from a macro, echoed after the prompt as if
+/// it had been typed
+pub fn pending_macro_command() -> Option<String> {
+    let command = PENDING_COMMANDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .pop_front()?;
+    println!("{}", command);
+    Some(command)
+}
+
+/// Numbered menu at the prompt. Enter picks the first option and 'c'
+/// cancels; returns the 0-based choice, or None when cancelled.
+pub fn select(question: &str, options: &[String]) -> io::Result<Option<usize>> {
+    println!("{}", question);
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}. {}", i + 1, option);
+    }
+    loop {
+        println!("Choose 1-{} (Enter for 1, 'c' to cancel):", options.len());
+        let answer = get_user_input()?;
+        match answer.trim() {
+            "" => return Ok(Some(0)),
+            "c" | "cancel" => return Ok(None),
+            text => match text.parse::<usize>() {
+                Ok(n) if (1..=options.len()).contains(&n) => return Ok(Some(n - 1)),
+                _ => println!("Please enter a number from 1 to {}.", options.len()),
+            },
+        }
+    }
+}
+
+/// The move prompt's help: how to enter moves, then the commands that apply
+/// right now. `hidden` counts the commands left out.
+pub fn print_help(commands: &[(String, String)], hidden: usize) {
     println!("\n=== Chess CLI Help ===");
     println!("Commands:");
     println!("  • Enter moves in coordinate notation: g1f3, e2e4, etc.");
-    println!("  • For promotions, add the piece: e7e8q (queen), e7e8r (rook), etc.");
-    println!("  • 'moves' - Show all legal moves");
-    println!("  • 'history' - Show move history");
-    println!("  • 'show' or 'board' - Redisplay the current board");
-    println!("  • 'fen' - Show FEN notation of current position");
-    println!("  • 'undo' or 'u' - Undo last move(s)");
-    println!("  • 'redo' or 're' - Redo undone move(s)");
+    println!("  • For promotions, add the piece: e7e8q (queen), e7e8r (rook), etc.,");
+    println!("    or leave it out to pick it from a menu");
+    println!("  • Or use standard algebraic notation: Nf3, Nbd2, exd5, e8=Q, O-O");
+    println!("    (a menu asks which piece when several can go there)");
+    for (usage, help) in commands {
+        println!("  • {} - {}", usage, help);
+    }
+    println!("  • '?' - Search the commands and run one from a menu");
     println!("  • 'h' or 'help' - Show this help");
     println!("  • 'q' or 'quit' - Quit the game");
+    if hidden > 0 {
+        println!(
+            "  ({} more {} at other points of the game; type them any time)",
+            hidden,
+            if hidden == 1 {
+                "command applies"
+            } else {
+                "commands apply"
+            }
+        );
+    }
     println!("\nMove format examples:");
     println!("  • e2e4    - Move pawn from e2 to e4");
-    println!("  • g1f3    - Move knight from g1 to f3 (NOT Ng1f3)");
-    println!("  • e7e8q   - Promote pawn to queen");
+    println!("  • g1f3    - Move knight from g1 to f3");
+    println!("  • Nf3     - Move the knight that can reach f3 (nf3 works too)");
+    println!("  • Nbd2    - The knight on the b-file goes to d2");
+    println!("  • R1a3    - The rook on the first rank goes to a3");
+    println!("  • Qa1b2   - The queen on a1 goes to b2");
+    println!("  • e2      - Show where the piece on e2 can go, then enter e.g. e4");
+    println!("  • e7e8q   - Promote pawn to queen (or e8=Q)");
     println!("  • e1g1    - Castle kingside");
-    println!("  • e1c1    - Castle queenside");
-    println!("\nIMPORTANT: Use coordinate notation (from-square + to-square)");
-    println!("NOT standard algebraic notation (no piece letters like N, B, R, Q, K)");
+    println!("  • e1c1    - Castle queenside (O-O and O-O-O work too)");
+    println!("  • exd5    - Pawn on the e-file takes on d5");
+    println!("\nUse coordinate notation (from-square + to-square) or SAN, with the");
+    println!("piece's file, rank or square when several can move there. Spaces,");
+    println!("dashes and '=' are ignored in coordinates (e2 e4, e2-e4, e7e8=q).");
     println!("\nSquares are labeled from a1 (bottom-left) to h8 (top-right)");
-    println!("White pieces: ♔♕♖♗♘♙  Black pieces: ♚♛♜♝♞♟");
+    let pieces = piece_set();
+    println!(
+        "White pieces: {}  Black pieces: {}",
+        pieces.side(Color::White),
+        pieces.side(Color::Black)
+    );
     println!("====================\n");
 }
@@ -0,0 +1,114 @@
+use crate::ui::get_user_input;
+use anyhow::Result;
+use chess::Color;
+
+/// How a variant game ended
+pub enum Outcome {
+    Win(Color, String),
+    Draw(String),
+}
+
+/// The rules of a chess variant. Variants are played in their own loop
+/// because the `chess` crate, and with it the standard game, only knows
+/// standard chess; each variant brings its own move generation and input.
+pub trait Variant {
+    type Position: Clone;
+    type Move: Copy;
+
+    fn name(&self) -> &'static str;
+    /// One or two sentences on how the variant differs from chess
+    fn rules(&self) -> &'static str;
+    /// How to type a move, e.g. "e2e4 @d5"
+    fn input_help(&self) -> &'static str;
+    fn start(&self) -> Self::Position;
+    fn side_to_move(&self, position: &Self::Position) -> Color;
+    fn legal_moves(&self, position: &Self::Position) -> Vec<Self::Move>;
+    /// Moves as shown by the `moves` command
+    fn list_moves(&self, position: &Self::Position) -> Vec<String> {
+        self.legal_moves(position)
+            .into_iter()
+            .map(|chess_move| self.move_text(position, chess_move))
+            .collect()
+    }
+    fn parse_move(&self, position: &Self::Position, text: &str) -> Result<Self::Move>;
+    fn play(&self, position: &Self::Position, chess_move: Self::Move) -> Self::Position;
+    fn outcome(&self, position: &Self::Position) -> Option<Outcome>;
+    fn move_text(&self, position: &Self::Position, chess_move: Self::Move) -> String;
+    fn display(&self, position: &Self::Position, perspective: Color);
+    async fn computer_move(&mut self, position: &Self::Position) -> Result<Self::Move>;
+}
+
+/// Play a variant against the computer, or with both sides at the keyboard
+/// when `player` is None
+pub async fn run<V: Variant>(mut variant: V, player: Option<Color>) -> Result<()> {
+    let mut position = variant.start();
+    let mut moves: Vec<String> = Vec::new();
+
+    println!("\n=== {} ===", variant.name());
+    println!("{}", variant.rules());
+    println!(
+        "Moves: {}. Type 'moves' to list them, 'history', 'help' or 'quit'.",
+        variant.input_help()
+    );
+
+    loop {
+        let side = variant.side_to_move(&position);
+        variant.display(&position, player.unwrap_or(side));
+        if let Some(outcome) = variant.outcome(&position) {
+            match outcome {
+                Outcome::Win(color, reason) => println!("\n{:?} wins: {}", color, reason),
+                Outcome::Draw(reason) => println!("\nDraw: {}", reason),
+            }
+            return Ok(());
+        }
+
+        let chess_move = if player.is_none_or(|color| color == side) {
+            println!("{:?} to move.", side);
+            let Some(chess_move) = read_move(&variant, &position, &moves)? else {
+                println!("Game abandoned.");
+                return Ok(());
+            };
+            chess_move
+        } else {
+            println!("Computer is thinking...");
+            variant.computer_move(&position).await?
+        };
+        let text = variant.move_text(&position, chess_move);
+        println!("{:?} plays {}", side, text);
+        moves.push(text);
+        position = variant.play(&position, chess_move);
+    }
+}
+
+/// Ask until a legal move is typed; None when the player quits
+fn read_move<V: Variant>(
+    variant: &V,
+    position: &V::Position,
+    moves: &[String],
+) -> Result<Option<V::Move>> {
+    loop {
+        let input = get_user_input()?;
+        match input.trim().to_lowercase().as_str() {
+            "quit" | "q" => return Ok(None),
+            "help" | "h" => {
+                println!("{}", variant.rules());
+                println!("Moves: {}.", variant.input_help());
+            }
+            "moves" => {
+                let mut legal = variant.list_moves(position);
+                legal.sort();
+                legal.dedup();
+                println!("{}", legal.join(" "));
+            }
+            "history" => {
+                for (i, pair) in moves.chunks(2).enumerate() {
+                    println!("{}. {}", i + 1, pair.join(" "));
+                }
+            }
+            _ => match variant.parse_move(position, input.trim()) {
+                Ok(chess_move) => return Ok(Some(chess_move)),
+                Err(e) => println!("{}", e),
+            },
+        }
+    }
+}